@@ -0,0 +1,113 @@
+//! Host side of the configuration protocol, see [`controller_core::config`]
+use std::thread;
+use std::time::Duration;
+
+use controller_core::config::{
+    Command, Request, Response, Status, VersionInfo, MAX_CHUNK_SIZE, REPORT_SIZE,
+};
+use controller_core::settings::{Settings, MAX_SETTINGS_SIZE};
+
+use crate::Error;
+
+/// Times a response is read while the controller is still handling the request
+const MAX_POLLS: u32 = 200;
+
+/// The controller handles requests in its idle loop, usually within a millisecond
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Exchanges the configuration feature report with a controller
+pub trait Transport {
+    /// Sends a request, as a SET_REPORT
+    fn set_feature(&mut self, report: &[u8; REPORT_SIZE]) -> std::io::Result<()>;
+
+    /// Reads the response, as a GET_REPORT
+    fn get_feature(&mut self) -> std::io::Result<[u8; REPORT_SIZE]>;
+}
+
+pub struct ConfigClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> ConfigClient<T> {
+    pub fn new(transport: T) -> Self {
+        ConfigClient { transport }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn version(&mut self) -> Result<VersionInfo, Error> {
+        let report = self.exchange(Request::GetVersion)?;
+        match Response::decode(&report, Command::GetVersion) {
+            Ok(Response::Version(version)) => Ok(version),
+            _ => Err(Error::Protocol(Status::InvalidRequest)),
+        }
+    }
+
+    /// Encoded settings record, as written to flash
+    pub fn read_record(&mut self) -> Result<Vec<u8>, Error> {
+        let mut record = Vec::new();
+        loop {
+            let offset = record.len() as u16;
+            let report = self.exchange(Request::ReadSettings { offset })?;
+            let Ok(Response::Settings { length, data, .. }) =
+                Response::decode(&report, Command::ReadSettings)
+            else {
+                return Err(Error::Protocol(Status::InvalidRequest));
+            };
+            record.extend_from_slice(data);
+            if record.len() >= length as usize {
+                return Ok(record);
+            }
+            if data.is_empty() {
+                return Err(Error::Protocol(Status::InvalidRequest));
+            }
+        }
+    }
+
+    pub fn read_settings(&mut self) -> Result<Settings, Error> {
+        Ok(Settings::decode(&self.read_record()?)?)
+    }
+
+    /// Replaces the settings with an encoded record, written to flash if `save`
+    pub fn write_record(&mut self, record: &[u8], save: bool) -> Result<(), Error> {
+        if record.len() > MAX_SETTINGS_SIZE {
+            return Err(Error::Protocol(Status::InvalidRequest));
+        }
+        for (index, data) in record.chunks(MAX_CHUNK_SIZE).enumerate() {
+            let offset = (index * MAX_CHUNK_SIZE) as u16;
+            self.exchange(Request::WriteSettings { offset, data })?;
+        }
+        self.exchange(Request::CommitSettings {
+            length: record.len() as u16,
+            save,
+        })?;
+        Ok(())
+    }
+
+    pub fn write_settings(&mut self, settings: &Settings, save: bool) -> Result<(), Error> {
+        let mut record = [0; MAX_SETTINGS_SIZE];
+        let length = settings.encode(&mut record)?;
+        self.write_record(&record[..length], save)
+    }
+
+    pub fn start_calibration(&mut self) -> Result<(), Error> {
+        self.exchange(Request::StartCalibration).map(|_| ())
+    }
+
+    /// Sends `request` and waits for its successful response
+    fn exchange(&mut self, request: Request) -> Result<[u8; REPORT_SIZE], Error> {
+        let command = request.command();
+        self.transport.set_feature(&request.encode()?)?;
+        for _ in 0..MAX_POLLS {
+            let report = self.transport.get_feature()?;
+            match Response::decode(&report, command) {
+                Ok(_) => return Ok(report),
+                Err(Status::Pending) => thread::sleep(POLL_INTERVAL),
+                Err(status) => return Err(Error::Protocol(status)),
+            }
+        }
+        Err(Error::Timeout)
+    }
+}
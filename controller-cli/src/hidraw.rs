@@ -0,0 +1,95 @@
+//! Linux hidraw access to the HID joystick, through the stock HID driver
+//!
+//! Feature reports go through the `HIDIOCSFEATURE`/`HIDIOCGFEATURE` ioctls, the input
+//! reports are read from the device file. The joystick has no report id, so the
+//! feature buffers start with the report number 0.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use controller_core::config::REPORT_SIZE;
+use controller_core::report::{XboxJoystickReport, HID_PID, HID_VID};
+use packed_struct::PackedStructSlice;
+
+use crate::client::Transport;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// Feature report with its report number in front
+const FEATURE_BUFFER_SIZE: usize = REPORT_SIZE + 1;
+
+/// `_IOC(_IOC_READ | _IOC_WRITE, 'H', nr, size)` of linux/hidraw.h
+const fn hid_ioctl(nr: c_ulong, size: usize) -> c_ulong {
+    (3 << 30) | ((size as c_ulong) << 16) | ((b'H' as c_ulong) << 8) | nr
+}
+
+const HIDIOCSFEATURE: c_ulong = hid_ioctl(0x06, FEATURE_BUFFER_SIZE);
+const HIDIOCGFEATURE: c_ulong = hid_ioctl(0x07, FEATURE_BUFFER_SIZE);
+
+pub struct HidDevice {
+    file: File,
+}
+
+impl HidDevice {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(HidDevice { file })
+    }
+
+    /// First hidraw device of the controller in HID mode
+    pub fn find() -> io::Result<PathBuf> {
+        let id = format!("HID_ID=0003:{:08X}:{:08X}", HID_VID, HID_PID);
+        for entry in fs::read_dir("/sys/class/hidraw")? {
+            let entry = entry?;
+            let uevent = fs::read_to_string(entry.path().join("device/uevent")).unwrap_or_default();
+            if uevent.lines().any(|line| line.eq_ignore_ascii_case(&id)) {
+                return Ok(Path::new("/dev").join(entry.file_name()));
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no controller found, is it plugged in HID mode?",
+        ))
+    }
+
+    /// Waits for the next input report
+    pub fn read_input(&mut self) -> io::Result<XboxJoystickReport> {
+        let mut buffer = [0; 64];
+        let length = self.file.read(&mut buffer)?;
+        XboxJoystickReport::unpack_from_slice(&buffer[..length])
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "unexpected input report"))
+    }
+
+    fn feature_ioctl(
+        &mut self,
+        request: c_ulong,
+        buffer: &mut [u8; FEATURE_BUFFER_SIZE],
+    ) -> io::Result<()> {
+        // The buffer outlives the call and matches the size encoded in the request
+        let result = unsafe { ioctl(self.file.as_raw_fd(), request, buffer.as_mut_ptr()) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Transport for HidDevice {
+    fn set_feature(&mut self, report: &[u8; REPORT_SIZE]) -> io::Result<()> {
+        let mut buffer = [0; FEATURE_BUFFER_SIZE];
+        buffer[1..].copy_from_slice(report);
+        self.feature_ioctl(HIDIOCSFEATURE, &mut buffer)
+    }
+
+    fn get_feature(&mut self) -> io::Result<[u8; REPORT_SIZE]> {
+        let mut buffer = [0; FEATURE_BUFFER_SIZE];
+        self.feature_ioctl(HIDIOCGFEATURE, &mut buffer)?;
+        let mut report = [0; REPORT_SIZE];
+        report.copy_from_slice(&buffer[1..]);
+        Ok(report)
+    }
+}
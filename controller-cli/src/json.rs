@@ -0,0 +1,133 @@
+//! JSON profile files, without `null` as the profiles have no use for it
+use crate::value::{format_number, format_string, Cursor, Value};
+use crate::Error;
+
+/// Indented document, one entry per line
+pub fn to_string(value: &Value) -> String {
+    let mut text = String::new();
+    write_value(&mut text, value, 0);
+    text.push('\n');
+    text
+}
+
+fn write_value(text: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Bool(value) => text.push_str(if *value { "true" } else { "false" }),
+        Value::Number(number) => text.push_str(&format_number(*number)),
+        Value::String(string) => text.push_str(&format_string(string)),
+        // Short arrays of names or numbers stay on one line
+        Value::Array(values)
+            if values
+                .iter()
+                .all(|value| !matches!(value, Value::Array(_) | Value::Table(_))) =>
+        {
+            text.push('[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    text.push_str(", ");
+                }
+                write_value(text, value, indent);
+            }
+            text.push(']');
+        }
+        Value::Array(values) => {
+            text.push('[');
+            for (index, value) in values.iter().enumerate() {
+                text.push_str(if index > 0 { ",\n" } else { "\n" });
+                push_indent(text, indent + 1);
+                write_value(text, value, indent + 1);
+            }
+            text.push('\n');
+            push_indent(text, indent);
+            text.push(']');
+        }
+        Value::Table(entries) if entries.is_empty() => text.push_str("{}"),
+        Value::Table(entries) => {
+            text.push('{');
+            for (index, (key, value)) in entries.iter().enumerate() {
+                text.push_str(if index > 0 { ",\n" } else { "\n" });
+                push_indent(text, indent + 1);
+                text.push_str(&format_string(key));
+                text.push_str(": ");
+                write_value(text, value, indent + 1);
+            }
+            text.push('\n');
+            push_indent(text, indent);
+            text.push('}');
+        }
+    }
+}
+
+fn push_indent(text: &mut String, indent: usize) {
+    for _ in 0..indent {
+        text.push_str("  ");
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, Error> {
+    let mut cursor = Cursor::new(text);
+    let value = parse_value(&mut cursor)?;
+    cursor.skip_spaces(true);
+    match cursor.peek() {
+        None => Ok(value),
+        Some(_) => Err(cursor.error("unexpected text after the document")),
+    }
+}
+
+fn parse_value(cursor: &mut Cursor) -> Result<Value, Error> {
+    cursor.skip_spaces(true);
+    match cursor.peek() {
+        Some('"') => {
+            cursor.advance();
+            cursor.string().map(Value::String)
+        }
+        Some('[') => {
+            cursor.advance();
+            let mut values = Vec::new();
+            cursor.skip_spaces(true);
+            if cursor.peek() == Some(']') {
+                cursor.advance();
+                return Ok(Value::Array(values));
+            }
+            loop {
+                values.push(parse_value(cursor)?);
+                cursor.skip_spaces(true);
+                match cursor.advance() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return Err(cursor.error("expected ',' or ']'")),
+                }
+            }
+        }
+        Some('{') => {
+            cursor.advance();
+            let mut entries = Vec::new();
+            cursor.skip_spaces(true);
+            if cursor.peek() == Some('}') {
+                cursor.advance();
+                return Ok(Value::Table(entries));
+            }
+            loop {
+                cursor.skip_spaces(true);
+                cursor.expect('"')?;
+                let key = cursor.string()?;
+                cursor.skip_spaces(true);
+                cursor.expect(':')?;
+                entries.push((key, parse_value(cursor)?));
+                cursor.skip_spaces(true);
+                match cursor.advance() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Value::Table(entries)),
+                    _ => return Err(cursor.error("expected ',' or '}'")),
+                }
+            }
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => cursor.number().map(Value::Number),
+        Some(_) => match cursor.word().as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(cursor.error("expected a value")),
+        },
+        None => Err(cursor.error("unexpected end of file")),
+    }
+}
@@ -0,0 +1,68 @@
+//! Host side of the controller configuration, see the `controller-cli` binary
+//!
+//! The settings and the configuration protocol come from `controller-core`, the same
+//! code as the firmware, so both sides always agree on the encoding.
+use std::fmt;
+use std::io;
+
+use controller_core::codec::CodecError;
+use controller_core::config::Status;
+use controller_core::settings::SettingsError;
+
+pub mod client;
+#[cfg(target_os = "linux")]
+pub mod hidraw;
+pub mod json;
+pub mod monitor;
+pub mod profiles;
+#[cfg(unix)]
+pub mod serial;
+pub mod sim;
+pub mod toml;
+pub mod value;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The controller answered a request with an error status
+    Protocol(Status),
+    /// The controller did not answer in time
+    Timeout,
+    Codec(CodecError),
+    Settings(SettingsError),
+    /// Invalid profile file, or a file in an unknown format
+    Format(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Protocol(status) => write!(f, "the controller answered {:?}", status),
+            Error::Timeout => f.write_str("no answer from the controller"),
+            Error::Codec(error) => write!(f, "encoding failed: {:?}", error),
+            Error::Settings(error) => write!(f, "invalid settings record: {:?}", error),
+            Error::Format(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<CodecError> for Error {
+    fn from(error: CodecError) -> Self {
+        Error::Codec(error)
+    }
+}
+
+impl From<SettingsError> for Error {
+    fn from(error: SettingsError) -> Self {
+        Error::Settings(error)
+    }
+}
@@ -0,0 +1,224 @@
+//! Configuration and live monitoring of the controller from the host
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+use controller_cli::client::ConfigClient;
+#[cfg(target_os = "linux")]
+use controller_cli::hidraw::HidDevice;
+use controller_cli::monitor::{self, CLEAR_SCREEN};
+use controller_cli::profiles::{Bindings, Format};
+#[cfg(unix)]
+use controller_cli::serial::SerialConsole;
+use controller_cli::Error;
+
+const USAGE: &str = "\
+usage: controller-cli [--device HIDRAW] [--console TTY] COMMAND
+
+Through the HID joystick (found automatically unless --device is given):
+  info                   firmware and settings versions
+  monitor                live view of the controls, over the console if --console is given
+  calibrate              start the calibration, then follow the steps on the controller
+  export FILE            write the profiles, turbo and macros to a .json or .toml file
+  import FILE [--save]   replace the profiles, turbo and macros from a .json or .toml file
+  backup FILE            write the settings record, as stored in flash
+  restore FILE [--save]  replace the settings from a backup
+
+Through the serial console (--console required):
+  run LINE...            run a console command, e.g. `run deadzone left 0.1`
+
+Without --save the changes are lost at the next reboot.";
+
+/// Delay between two `state` commands when monitoring over the console
+#[cfg(unix)]
+const CONSOLE_MONITOR_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Options {
+    device: Option<PathBuf>,
+    console: Option<PathBuf>,
+    save: bool,
+    command: Vec<String>,
+}
+
+fn parse_options(args: impl Iterator<Item = String>) -> Result<Options, Error> {
+    let mut options = Options {
+        device: None,
+        console: None,
+        save: false,
+        command: Vec::new(),
+    };
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let mut path = || {
+            args.next()
+                .map(PathBuf::from)
+                .ok_or_else(|| usage_error(&format!("{} needs a path", arg)))
+        };
+        match arg.as_str() {
+            "--device" => options.device = Some(path()?),
+            "--console" => options.console = Some(path()?),
+            "--save" => options.save = true,
+            "-h" | "--help" => options.command = vec!["help".into()],
+            _ => options.command.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn usage_error(message: &str) -> Error {
+    Error::Format(format!("{}\n\n{}", message, USAGE))
+}
+
+fn main() -> ExitCode {
+    let result = parse_options(std::env::args().skip(1)).and_then(|options| run(&options));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), Error> {
+    let command: Vec<&str> = options.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        [] | ["help"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        ["monitor"] => match &options.console {
+            Some(console) => monitor_console(console),
+            None => monitor_hid(options),
+        },
+        ["run", line @ ..] if !line.is_empty() => {
+            let console = options
+                .console
+                .as_ref()
+                .ok_or_else(|| usage_error("run needs --console"))?;
+            print!("{}", run_console(console, &line.join(" "))?);
+            Ok(())
+        }
+        ["info"] => {
+            let version = hid_client(options)?.version()?;
+            let [major, minor, patch] = version.firmware;
+            println!("firmware {}.{}.{}", major, minor, patch);
+            println!(
+                "settings version {}, up to {} bytes",
+                version.settings_version, version.max_settings_size
+            );
+            Ok(())
+        }
+        ["calibrate"] => {
+            hid_client(options)?.start_calibration()?;
+            println!("Calibration started:");
+            println!("1. Leave the sticks and triggers at rest and press A.");
+            println!("2. Move every axis to both ends of its travel and press A.");
+            println!("Press B to cancel and keep the previous calibration.");
+            Ok(())
+        }
+        ["export", file] => {
+            let path = Path::new(file);
+            let format = Format::from_path(path)?;
+            let settings = hid_client(options)?.read_settings()?;
+            fs::write(path, format.write(&Bindings::from_settings(&settings))?)?;
+            Ok(())
+        }
+        ["import", file] => {
+            let path = Path::new(file);
+            let bindings = Format::from_path(path)?.read(&fs::read_to_string(path)?)?;
+            let mut client = hid_client(options)?;
+            let mut settings = client.read_settings()?;
+            bindings.apply(&mut settings);
+            client.write_settings(&settings, options.save)
+        }
+        ["backup", file] => {
+            let record = hid_client(options)?.read_record()?;
+            fs::write(file, record)?;
+            Ok(())
+        }
+        ["restore", file] => {
+            let record = fs::read(file)?;
+            hid_client(options)?.write_record(&record, options.save)
+        }
+        _ => Err(usage_error("unknown command")),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_hid(options: &Options) -> Result<HidDevice, Error> {
+    let path = match &options.device {
+        Some(path) => path.clone(),
+        None => HidDevice::find()?,
+    };
+    Ok(HidDevice::open(&path)?)
+}
+
+#[cfg(target_os = "linux")]
+fn hid_client(options: &Options) -> Result<ConfigClient<HidDevice>, Error> {
+    Ok(ConfigClient::new(open_hid(options)?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hid_client(
+    _options: &Options,
+) -> Result<ConfigClient<controller_cli::sim::SimulatedDevice>, Error> {
+    Err(Error::Format(
+        "the HID commands are only supported on Linux, use --console".into(),
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn monitor_hid(options: &Options) -> Result<(), Error> {
+    let mut device = open_hid(options)?;
+    loop {
+        let report = device.read_input()?;
+        print!("{}{}", CLEAR_SCREEN, monitor::render(&report));
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn monitor_hid(_options: &Options) -> Result<(), Error> {
+    Err(Error::Format(
+        "monitoring the HID joystick is only supported on Linux, use --console".into(),
+    ))
+}
+
+#[cfg(unix)]
+fn open_console(path: &Path) -> Result<SerialConsole, Error> {
+    Ok(SerialConsole::open(path)?)
+}
+
+#[cfg(unix)]
+fn run_console(path: &Path, line: &str) -> Result<String, Error> {
+    Ok(open_console(path)?.command(line)?)
+}
+
+#[cfg(not(unix))]
+fn run_console(_path: &Path, _line: &str) -> Result<String, Error> {
+    Err(Error::Format(
+        "the serial console is only supported on Unix".into(),
+    ))
+}
+
+/// Runs `state` over and over
+#[cfg(unix)]
+fn monitor_console(path: &Path) -> Result<(), Error> {
+    let mut console = open_console(path)?;
+    loop {
+        let state = console.command("state")?;
+        print!("{}{}", CLEAR_SCREEN, state);
+        thread::sleep(CONSOLE_MONITOR_INTERVAL);
+    }
+}
+
+#[cfg(not(unix))]
+fn monitor_console(_path: &Path) -> Result<(), Error> {
+    Err(Error::Format(
+        "monitoring over the serial console is only supported on Unix".into(),
+    ))
+}
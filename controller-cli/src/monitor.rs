@@ -0,0 +1,91 @@
+//! Live view of the reported controls, redrawn in the terminal with ANSI escapes
+use controller_core::dpad::HAT_NEUTRAL;
+use controller_core::report::{
+    XboxJoystickReport, JOYSTICK_MAX_VALUE, REPORT_BUTTONS, TRIGGER_MAX_VALUE,
+};
+
+/// Moves the cursor home and clears the screen
+pub const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// Characters of a bar at full deflection on one side
+const BAR_WIDTH: usize = 20;
+
+const HAT_DIRECTIONS: [&str; 8] = [
+    "up",
+    "up right",
+    "right",
+    "down right",
+    "down",
+    "down left",
+    "left",
+    "up left",
+];
+
+/// One line per axis with its value and a bar, then the held buttons and the d-pad
+pub fn render(report: &XboxJoystickReport) -> String {
+    // Shown pointing up, as in the console
+    let sticks = [
+        ("lx", report.x),
+        ("ly", report.y.saturating_neg()),
+        ("rx", report.z),
+        ("ry", report.rx.saturating_neg()),
+    ];
+    let triggers = [("lt", report.ry), ("rt", report.rz)];
+
+    let mut text = String::new();
+    for (name, value) in sticks {
+        let value = value as f32 / JOYSTICK_MAX_VALUE as f32;
+        text.push_str(&format!("{:<3}{:>7.3} {}\n", name, value, stick_bar(value)));
+    }
+    for (name, value) in triggers {
+        let value = value as f32 / TRIGGER_MAX_VALUE as f32;
+        text.push_str(&format!(
+            "{:<3}{:>7.3} {}\n",
+            name,
+            value,
+            trigger_bar(value)
+        ));
+    }
+
+    let held: Vec<&str> = REPORT_BUTTONS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| report.buttons & 1 << bit != 0)
+        .map(|(_, button)| button.name())
+        .collect();
+    text.push_str(&format!("buttons: {}\n", held.join(" ")));
+
+    let hat = report.hat & 0x0F;
+    let dpad = match hat {
+        HAT_NEUTRAL => "",
+        hat => HAT_DIRECTIONS.get(hat as usize).copied().unwrap_or("?"),
+    };
+    text.push_str(&format!("d-pad: {}\n", dpad));
+    text
+}
+
+/// `[` then the deflection on either side of the center `|`, then `]`
+fn stick_bar(value: f32) -> String {
+    let filled = (value.abs().min(1.0) * BAR_WIDTH as f32).round() as usize;
+    let (left, right) = if value < 0.0 {
+        (filled, 0)
+    } else {
+        (0, filled)
+    };
+    format!(
+        "[{}{}|{}{}]",
+        " ".repeat(BAR_WIDTH - left),
+        "=".repeat(left),
+        "=".repeat(right),
+        " ".repeat(BAR_WIDTH - right)
+    )
+}
+
+fn trigger_bar(value: f32) -> String {
+    let filled = (value.clamp(0.0, 1.0) * 2.0 * BAR_WIDTH as f32).round() as usize;
+    format!(
+        "[{}{}]",
+        "=".repeat(filled),
+        " ".repeat(2 * BAR_WIDTH + 1 - filled)
+    )
+}
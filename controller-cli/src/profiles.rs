@@ -0,0 +1,458 @@
+//! Profiles, turbo and macros as an editable document, exported and imported as JSON or TOML
+//!
+//! Controls are named as in the console, see [`Button::name`] and [`AnalogInput::name`]:
+//!
+//! ```toml
+//! active = 0
+//! switch_combo = ["rb", "back"]
+//!
+//! [[profiles]]
+//!
+//! [profiles.buttons]
+//! a = "b"
+//! x = { axis = "lt", threshold = 0.5 }
+//! guide = "none"
+//!
+//! [profiles.axes]
+//! lx = "lx"
+//! ly = { axis = "ly", inverted = true }
+//! lt = { negative = "none", positive = "lb" }
+//!
+//! [turbo]
+//! a = 10
+//!
+//! [[macros]]
+//! slot = 0
+//! trigger = "guide"
+//!
+//! [[macros.steps]]
+//! buttons = ["a"]
+//! ms = 50
+//!
+//! [[macros.steps]]
+//! stick = [0, -1]
+//! ms = 80
+//! ```
+//!
+//! A control missing from a profile keeps its identity binding, a button missing from `turbo`
+//! does not repeat and a slot missing from `macros` is empty.
+use std::path::Path;
+
+use controller_core::input::{AnalogInput, Button};
+use controller_core::macros::{Macro, MacroStep, Macros, TurboRates, MAX_MACROS, MAX_TURBO_HZ};
+use controller_core::remap::{
+    AxisBinding, ButtonBinding, ButtonSet, Profile, Profiles, MAX_PROFILES,
+};
+use controller_core::settings::Settings;
+
+use crate::value::Value;
+use crate::{json, toml, Error};
+
+/// Name of an unbound control, or of a missing button of an axis binding
+const NONE: &str = "none";
+
+/// Everything bound to the controls, the part of the settings in the exported files
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bindings {
+    pub profiles: Profiles,
+    pub turbo: TurboRates,
+    pub macros: Macros,
+}
+
+impl Bindings {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Bindings {
+            profiles: settings.profiles,
+            turbo: settings.turbo,
+            macros: settings.macros,
+        }
+    }
+
+    /// Replaces the bindings of `settings`, keeps the rest
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.profiles = self.profiles;
+        settings.turbo = self.turbo;
+        settings.macros = self.macros;
+    }
+}
+
+/// File formats of the profiles, picked from the file extension
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("toml") => Ok(Format::Toml),
+            _ => Err(Error::Format(format!(
+                "{}: expected a .json or .toml file",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn write(self, bindings: &Bindings) -> Result<String, Error> {
+        let document = to_value(bindings);
+        match self {
+            Format::Json => Ok(json::to_string(&document)),
+            Format::Toml => toml::to_string(&document),
+        }
+    }
+
+    pub fn read(self, text: &str) -> Result<Bindings, Error> {
+        let document = match self {
+            Format::Json => json::parse(text)?,
+            Format::Toml => toml::parse(text)?,
+        };
+        from_value(&document)
+    }
+}
+
+pub fn to_value(bindings: &Bindings) -> Value {
+    let profiles = &bindings.profiles;
+    let profile_values = profiles.profiles[..profiles.count as usize]
+        .iter()
+        .map(profile_to_value)
+        .collect();
+    Value::Table(vec![
+        ("active".into(), Value::Number(profiles.active as f64)),
+        (
+            "switch_combo".into(),
+            Value::Array(
+                profiles
+                    .switch_combo
+                    .iter()
+                    .map(|button| name(button.name()))
+                    .collect(),
+            ),
+        ),
+        ("profiles".into(), Value::Array(profile_values)),
+        ("turbo".into(), turbo_to_value(&bindings.turbo)),
+        ("macros".into(), macros_to_value(&bindings.macros)),
+    ])
+}
+
+fn profile_to_value(profile: &Profile) -> Value {
+    let buttons = Button::ALL
+        .into_iter()
+        .map(|button| {
+            let value = match *profile.button(button) {
+                ButtonBinding::Unbound => name(NONE),
+                ButtonBinding::Button(source) => name(source.name()),
+                ButtonBinding::Axis { input, threshold } => Value::Table(vec![
+                    ("axis".into(), name(input.name())),
+                    ("threshold".into(), number(threshold)),
+                ]),
+            };
+            (button.name().into(), value)
+        })
+        .collect();
+
+    let axes = AnalogInput::ALL
+        .into_iter()
+        .map(|axis| {
+            let value = match *profile.axis(axis) {
+                AxisBinding::Unbound => name(NONE),
+                AxisBinding::Axis {
+                    input,
+                    inverted: false,
+                } => name(input.name()),
+                AxisBinding::Axis {
+                    input,
+                    inverted: true,
+                } => Value::Table(vec![
+                    ("axis".into(), name(input.name())),
+                    ("inverted".into(), Value::Bool(true)),
+                ]),
+                AxisBinding::Buttons { negative, positive } => {
+                    let button_name =
+                        |button: Option<Button>| name(button.map_or(NONE, Button::name));
+                    Value::Table(vec![
+                        ("negative".into(), button_name(negative)),
+                        ("positive".into(), button_name(positive)),
+                    ])
+                }
+            };
+            (axis.name().into(), value)
+        })
+        .collect();
+
+    Value::Table(vec![
+        ("buttons".into(), Value::Table(buttons)),
+        ("axes".into(), Value::Table(axes)),
+    ])
+}
+
+/// Rates of the repeating buttons only
+fn turbo_to_value(turbo: &TurboRates) -> Value {
+    Value::Table(
+        Button::ALL
+            .into_iter()
+            .filter(|button| turbo.button(*button) > 0)
+            .map(|button| {
+                let rate = Value::Number(turbo.button(button) as f64);
+                (button.name().into(), rate)
+            })
+            .collect(),
+    )
+}
+
+/// Used slots only, each with its number
+fn macros_to_value(macros: &Macros) -> Value {
+    let slots = macros.slots.iter().enumerate();
+    Value::Array(
+        slots
+            .filter_map(|(slot, macro_)| macro_.as_ref().map(|macro_| macro_to_value(slot, macro_)))
+            .collect(),
+    )
+}
+
+fn macro_to_value(slot: usize, macro_: &Macro) -> Value {
+    let steps = macro_
+        .steps()
+        .iter()
+        .map(|step| {
+            let mut entries = Vec::new();
+            if !step.buttons.is_empty() {
+                let buttons = step.buttons.iter().map(|button| name(button.name()));
+                entries.push(("buttons".into(), Value::Array(buttons.collect())));
+            }
+            if let Some((x, y)) = step.left_stick {
+                entries.push(("stick".into(), Value::Array(vec![number(x), number(y)])));
+            }
+            entries.push(("ms".into(), Value::Number(step.duration_ms as f64)));
+            Value::Table(entries)
+        })
+        .collect();
+    Value::Table(vec![
+        ("slot".into(), Value::Number(slot as f64)),
+        ("trigger".into(), name(macro_.trigger.name())),
+        ("steps".into(), Value::Array(steps)),
+    ])
+}
+
+fn name(name: &str) -> Value {
+    Value::String(name.into())
+}
+
+/// Shortest decimal of the `f32`, so that 0.3 is not written 0.30000001192092896
+fn number(value: f32) -> Value {
+    Value::Number(value.to_string().parse().unwrap_or(value as f64))
+}
+
+pub fn from_value(document: &Value) -> Result<Bindings, Error> {
+    let profile_values = document
+        .get("profiles")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("missing profiles array"))?;
+    if profile_values.is_empty() || profile_values.len() > MAX_PROFILES {
+        return Err(invalid(&format!("expected 1 to {} profiles", MAX_PROFILES)));
+    }
+
+    let mut profiles = Profiles::new();
+    profiles.count = profile_values.len() as u8;
+    for (profile, value) in profiles.profiles.iter_mut().zip(profile_values) {
+        *profile = profile_from_value(value)?;
+    }
+
+    if let Some(active) = document.get("active") {
+        profiles.active = active
+            .as_f64()
+            .filter(|active| active.fract() == 0.0 && (0.0..profiles.count as f64).contains(active))
+            .ok_or_else(|| invalid("active is not the index of a profile"))?
+            as u8;
+    }
+
+    if let Some(combo) = document.get("switch_combo") {
+        let names = combo
+            .as_array()
+            .ok_or_else(|| invalid("switch_combo is not an array"))?;
+        profiles.switch_combo = ButtonSet::EMPTY;
+        for value in names {
+            profiles.switch_combo = profiles.switch_combo.with(button_from_value(value)?);
+        }
+    }
+
+    if !profiles.is_valid() {
+        return Err(invalid("invalid profiles"));
+    }
+
+    let mut turbo = TurboRates::new();
+    for (key, value) in table(document.get("turbo"))? {
+        let button = Button::from_name(key).ok_or_else(|| unknown("button", key))?;
+        *turbo.button_mut(button) = integer(value, MAX_TURBO_HZ as u32)
+            .ok_or_else(|| invalid(&format!("turbo {}: expected 0 to {}", key, MAX_TURBO_HZ)))?
+            as u8;
+    }
+
+    let mut macros = Macros::new();
+    if let Some(values) = document.get("macros") {
+        let values = values
+            .as_array()
+            .ok_or_else(|| invalid("macros is not an array"))?;
+        for value in values {
+            let slot = value
+                .get("slot")
+                .and_then(|slot| integer(slot, MAX_MACROS as u32 - 1))
+                .ok_or_else(|| invalid(&format!("macro slot must be 0 to {}", MAX_MACROS - 1)))?
+                as usize;
+            if macros.slots[slot].is_some() {
+                return Err(invalid(&format!("macro slot {} used twice", slot)));
+            }
+            macros.slots[slot] = Some(macro_from_value(value)?);
+        }
+    }
+
+    Ok(Bindings {
+        profiles,
+        turbo,
+        macros,
+    })
+}
+
+fn macro_from_value(value: &Value) -> Result<Macro, Error> {
+    let trigger = button_from_value(
+        value
+            .get("trigger")
+            .ok_or_else(|| invalid("macro: missing trigger"))?,
+    )?;
+    let step_values = value
+        .get("steps")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("macro: missing steps array"))?;
+
+    let mut steps = Vec::new();
+    for value in step_values {
+        let duration_ms = value
+            .get("ms")
+            .and_then(|ms| integer(ms, u16::MAX as u32))
+            .ok_or_else(|| invalid("macro step: ms must be 0 to 65535"))?;
+        let mut step = MacroStep::wait(duration_ms as u16);
+        if let Some(buttons) = value.get("buttons") {
+            let buttons = buttons
+                .as_array()
+                .ok_or_else(|| invalid("macro step: buttons is not an array"))?;
+            for button in buttons {
+                step.buttons = step.buttons.with(button_from_value(button)?);
+            }
+        }
+        if let Some(stick) = value.get("stick") {
+            let (x, y) = match stick.as_array() {
+                Some([x, y]) => x.as_f64().zip(y.as_f64()),
+                _ => None,
+            }
+            .ok_or_else(|| invalid("macro step: stick must be [x, y]"))?;
+            step.left_stick = Some((x as f32, y as f32));
+        }
+        steps.push(step);
+    }
+    Macro::new(trigger, &steps).ok_or_else(|| invalid("invalid macro"))
+}
+
+/// Whole number in `0..=max`
+fn integer(value: &Value, max: u32) -> Option<u32> {
+    value
+        .as_f64()
+        .filter(|number| number.fract() == 0.0 && (0.0..=max as f64).contains(number))
+        .map(|number| number as u32)
+}
+
+fn profile_from_value(value: &Value) -> Result<Profile, Error> {
+    let mut profile = Profile::IDENTITY;
+
+    for (key, value) in table(value.get("buttons"))? {
+        let button = Button::from_name(key).ok_or_else(|| unknown("button", key))?;
+        *profile.button_mut(button) = match value {
+            Value::String(source) if source == NONE => ButtonBinding::Unbound,
+            Value::String(_) => ButtonBinding::Button(button_from_value(value)?),
+            Value::Table(_) => {
+                let threshold = value
+                    .get("threshold")
+                    .and_then(Value::as_f64)
+                    .ok_or_else(|| invalid(&format!("{}: missing threshold", key)))?;
+                let binding = ButtonBinding::Axis {
+                    input: axis_from_value(value.get("axis"))?,
+                    threshold: threshold as f32,
+                };
+                if !binding.is_valid() {
+                    return Err(invalid(&format!("{}: threshold out of range", key)));
+                }
+                binding
+            }
+            _ => return Err(invalid(&format!("{}: invalid binding", key))),
+        };
+    }
+
+    for (key, value) in table(value.get("axes"))? {
+        let axis = AnalogInput::from_name(key).ok_or_else(|| unknown("axis", key))?;
+        *profile.axis_mut(axis) = match value {
+            Value::String(source) if source == NONE => AxisBinding::Unbound,
+            Value::String(_) => AxisBinding::Axis {
+                input: axis_from_value(Some(value))?,
+                inverted: false,
+            },
+            Value::Table(_) if value.get("axis").is_some() => AxisBinding::Axis {
+                input: axis_from_value(value.get("axis"))?,
+                inverted: value
+                    .get("inverted")
+                    .map(|inverted| {
+                        inverted
+                            .as_bool()
+                            .ok_or_else(|| invalid("inverted is not a boolean"))
+                    })
+                    .transpose()?
+                    .unwrap_or(false),
+            },
+            Value::Table(_) => {
+                let button = |side: &str| match value.get(side) {
+                    None => Ok(None),
+                    Some(Value::String(name)) if name == NONE => Ok(None),
+                    Some(value) => button_from_value(value).map(Some),
+                };
+                AxisBinding::Buttons {
+                    negative: button("negative")?,
+                    positive: button("positive")?,
+                }
+            }
+            _ => return Err(invalid(&format!("{}: invalid binding", key))),
+        };
+    }
+
+    Ok(profile)
+}
+
+/// Entries of an optional table
+fn table(value: Option<&Value>) -> Result<&[(String, Value)], Error> {
+    match value {
+        None => Ok(&[]),
+        Some(value) => value
+            .as_table()
+            .ok_or_else(|| invalid("buttons, axes and turbo must be tables")),
+    }
+}
+
+fn button_from_value(value: &Value) -> Result<Button, Error> {
+    let name = value
+        .as_str()
+        .ok_or_else(|| invalid("expected a button name"))?;
+    Button::from_name(name).ok_or_else(|| unknown("button", name))
+}
+
+fn axis_from_value(value: Option<&Value>) -> Result<AnalogInput, Error> {
+    let name = value
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("expected an axis name"))?;
+    AnalogInput::from_name(name).ok_or_else(|| unknown("axis", name))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Format(message.into())
+}
+
+fn unknown(kind: &str, name: &str) -> Error {
+    Error::Format(format!("unknown {} {:?}", kind, name))
+}
@@ -0,0 +1,112 @@
+//! Serial console of the controller, see `controller_core::console`
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::raw::{c_int, c_short};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Printed by the console once a command is done
+const PROMPT: &str = "> ";
+
+/// Longest wait for the prompt, saving to flash included
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Large enough for the `termios` of every unix, only handled by the C library
+#[repr(C, align(8))]
+struct Termios([u8; 256]);
+
+extern "C" {
+    fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+    fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const Termios) -> c_int;
+    fn cfmakeraw(termios: *mut Termios);
+    fn poll(fds: *mut PollFd, nfds: NfdsT, timeout: c_int) -> c_int;
+}
+
+const TCSANOW: c_int = 0;
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+#[cfg(target_os = "linux")]
+type NfdsT = std::os::raw::c_ulong;
+#[cfg(not(target_os = "linux"))]
+type NfdsT = std::os::raw::c_uint;
+
+const POLLIN: c_short = 1;
+
+pub struct SerialConsole {
+    file: File,
+}
+
+impl SerialConsole {
+    /// Opens the port in raw mode: without it the tty would echo the controller
+    /// output back to the controller
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let fd = file.as_raw_fd();
+        let mut termios = Termios([0; 256]);
+        // The C library only writes the termios through these pointers
+        unsafe {
+            if tcgetattr(fd, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            cfmakeraw(&mut termios);
+            if tcsetattr(fd, TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(SerialConsole { file })
+    }
+
+    /// Runs a command line, returns its output without the echoed line and the prompt.
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] when the prompt does not come within [`COMMAND_TIMEOUT`].
+    pub fn command(&mut self, line: &str) -> io::Result<String> {
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\r")?;
+
+        let deadline = Instant::now() + COMMAND_TIMEOUT;
+        let mut output = Vec::new();
+        let mut buffer = [0; 256];
+        while !output.ends_with(PROMPT.as_bytes()) {
+            self.wait_readable(deadline)?;
+            let length = self.file.read(&mut buffer)?;
+            if length == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            output.extend_from_slice(&buffer[..length]);
+        }
+
+        let output = String::from_utf8_lossy(&output[..output.len() - PROMPT.len()]).into_owned();
+        Ok(strip_echo(&output).to_owned())
+    }
+
+    /// Waits until there is something to read, at most until `deadline`
+    fn wait_readable(&self, deadline: Instant) -> io::Result<()> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut fd = PollFd {
+            fd: self.file.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        };
+        // `poll` only writes `revents` of the single entry
+        match unsafe { poll(&mut fd, 1, remaining.as_millis() as c_int) } {
+            0 => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no prompt from the console",
+            )),
+            ready if ready > 0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+/// Output after the line echoed by the console
+fn strip_echo(output: &str) -> &str {
+    output.split_once("\r\n").map_or("", |(_, rest)| rest)
+}
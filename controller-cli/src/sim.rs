@@ -0,0 +1,65 @@
+//! Simulated controller, answering the configuration protocol like the firmware
+//!
+//! Runs the same [`ConfigServer`] as the firmware. The firmware answers from its idle
+//! loop, so the first read after every request is still [`Status::Pending`].
+use std::io;
+
+use controller_core::config::{Action, ConfigServer, Response, Status, REPORT_SIZE};
+use controller_core::settings::Settings;
+
+use crate::client::Transport;
+
+pub struct SimulatedDevice {
+    server: ConfigServer,
+    pub firmware: [u8; 3],
+    /// Settings in use
+    pub settings: Settings,
+    /// Settings written to flash
+    pub saved: Settings,
+    pub calibration_started: bool,
+    request: Option<[u8; REPORT_SIZE]>,
+    response: [u8; REPORT_SIZE],
+}
+
+impl SimulatedDevice {
+    pub fn new(settings: Settings) -> Self {
+        SimulatedDevice {
+            server: ConfigServer::new(),
+            firmware: [0, 1, 0],
+            settings,
+            saved: settings,
+            calibration_started: false,
+            request: None,
+            response: pending_response(0),
+        }
+    }
+}
+
+fn pending_response(command: u8) -> [u8; REPORT_SIZE] {
+    Response::encode(command, Err(Status::Pending)).unwrap_or([0; REPORT_SIZE])
+}
+
+impl Transport for SimulatedDevice {
+    fn set_feature(&mut self, report: &[u8; REPORT_SIZE]) -> io::Result<()> {
+        self.response = pending_response(report[1]);
+        self.request = Some(*report);
+        Ok(())
+    }
+
+    fn get_feature(&mut self) -> io::Result<[u8; REPORT_SIZE]> {
+        let response = self.response;
+        // Handled after the first read, as the idle loop runs after the USB interrupt
+        if let Some(request) = self.request.take() {
+            let (report, action) = self
+                .server
+                .handle(&request, &mut self.settings, self.firmware);
+            self.response = report;
+            match action {
+                Action::Apply { save: true } => self.saved = self.settings,
+                Action::StartCalibration => self.calibration_started = true,
+                Action::Apply { save: false } | Action::None => {}
+            }
+        }
+        Ok(response)
+    }
+}
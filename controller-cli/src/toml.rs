@@ -0,0 +1,316 @@
+//! TOML profile files
+//!
+//! Only the subset written by [`to_string`] is parsed back: `[table]` and `[[array]]`
+//! headers, `key = value` lines with bare or quoted keys, and single line values
+//! including arrays and inline tables. Dates and multi-line strings are not supported.
+use crate::value::{format_number, format_string, parse_error, Cursor, Value};
+use crate::Error;
+
+/// Largest table written inline as `{ key = value }` rather than under a header
+const MAX_INLINE_ENTRIES: usize = 4;
+
+/// Document of the `root` table
+pub fn to_string(root: &Value) -> Result<String, Error> {
+    let entries = root
+        .as_table()
+        .ok_or_else(|| Error::Format("a TOML document must be a table".into()))?;
+    let mut text = String::new();
+    write_table(&mut text, &[], entries);
+    Ok(text)
+}
+
+/// Tables under their own header, the others inline
+fn is_section(value: &Value) -> bool {
+    match value {
+        Value::Table(entries) => {
+            entries.len() > MAX_INLINE_ENTRIES
+                || entries.iter().any(|(_, value)| !value.is_scalar())
+        }
+        _ => false,
+    }
+}
+
+/// Non-empty array of tables, written as `[[path]]` sections
+fn is_table_array(value: &Value) -> bool {
+    match value {
+        Value::Array(values) => {
+            !values.is_empty() && values.iter().all(|value| matches!(value, Value::Table(_)))
+        }
+        _ => false,
+    }
+}
+
+fn write_table(text: &mut String, path: &[&str], entries: &[(String, Value)]) {
+    for (key, value) in entries {
+        if !is_section(value) && !is_table_array(value) {
+            text.push_str(&format_key(key));
+            text.push_str(" = ");
+            write_inline(text, value);
+            text.push('\n');
+        }
+    }
+
+    for (key, value) in entries {
+        let mut path = path.to_vec();
+        path.push(key);
+        let header = path
+            .iter()
+            .map(|key| format_key(key))
+            .collect::<Vec<_>>()
+            .join(".");
+        if is_section(value) {
+            text.push_str(&format!("\n[{}]\n", header));
+            write_table(text, &path, value.as_table().unwrap_or_default());
+        } else if is_table_array(value) {
+            for value in value.as_array().unwrap_or_default() {
+                text.push_str(&format!("\n[[{}]]\n", header));
+                write_table(text, &path, value.as_table().unwrap_or_default());
+            }
+        }
+    }
+}
+
+fn write_inline(text: &mut String, value: &Value) {
+    match value {
+        Value::Bool(value) => text.push_str(if *value { "true" } else { "false" }),
+        Value::Number(number) => text.push_str(&format_number(*number)),
+        Value::String(string) => text.push_str(&format_string(string)),
+        Value::Array(values) => {
+            text.push('[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    text.push_str(", ");
+                }
+                write_inline(text, value);
+            }
+            text.push(']');
+        }
+        Value::Table(entries) => {
+            text.push('{');
+            for (index, (key, value)) in entries.iter().enumerate() {
+                text.push_str(if index > 0 { ", " } else { " " });
+                text.push_str(&format_key(key));
+                text.push_str(" = ");
+                write_inline(text, value);
+            }
+            text.push_str(" }");
+        }
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.into()
+    } else {
+        format_string(key)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, Error> {
+    let mut root = Vec::new();
+    // Table the next keys go to, `[[array]]` headers open the last element
+    let mut path: Vec<String> = Vec::new();
+    let mut cursor = Cursor::new(text);
+
+    loop {
+        cursor.skip_spaces(true);
+        match cursor.peek() {
+            None => return Ok(Value::Table(root)),
+            Some('#') => skip_comment(&mut cursor),
+            Some('[') => {
+                cursor.advance();
+                let is_array = cursor.peek() == Some('[');
+                if is_array {
+                    cursor.advance();
+                }
+                let line = cursor.line;
+                path = parse_header(&mut cursor)?;
+                if is_array {
+                    cursor.expect(']')?;
+                }
+                end_line(&mut cursor)?;
+
+                let (last, parents) = path
+                    .split_last()
+                    .ok_or_else(|| parse_error(line, "empty header"))?;
+                let parent = table_at(&mut root, parents, line)?;
+                if is_array {
+                    let array = entry(parent, last, || Value::Array(Vec::new()));
+                    match array {
+                        Value::Array(values) => values.push(Value::Table(Vec::new())),
+                        _ => return Err(parse_error(line, "not an array of tables")),
+                    }
+                } else {
+                    if parent.iter().any(|(key, _)| key == last) {
+                        return Err(parse_error(line, "table defined twice"));
+                    }
+                    parent.push((last.clone(), Value::Table(Vec::new())));
+                }
+            }
+            Some(_) => {
+                let line = cursor.line;
+                let key = parse_key(&mut cursor)?;
+                cursor.skip_spaces(false);
+                cursor.expect('=')?;
+                let value = parse_value(&mut cursor)?;
+                end_line(&mut cursor)?;
+
+                let table = table_at(&mut root, &path, line)?;
+                if table.iter().any(|(name, _)| *name == key) {
+                    return Err(parse_error(line, "key defined twice"));
+                }
+                table.push((key, value));
+            }
+        }
+    }
+}
+
+/// Table at `path` from `root`, creating the missing ones.
+/// An array of tables stands for its last element.
+fn table_at<'a>(
+    mut table: &'a mut Vec<(String, Value)>,
+    path: &[String],
+    line: usize,
+) -> Result<&'a mut Vec<(String, Value)>, Error> {
+    for key in path {
+        let value = entry(table, key, || Value::Table(Vec::new()));
+        let value = match value {
+            Value::Array(values) => values
+                .last_mut()
+                .ok_or_else(|| parse_error(line, "not a table"))?,
+            value => value,
+        };
+        table = match value {
+            Value::Table(entries) => entries,
+            _ => return Err(parse_error(line, "not a table")),
+        };
+    }
+    Ok(table)
+}
+
+/// Value of `key` in `table`, added with `default` if missing
+fn entry<'a>(
+    table: &'a mut Vec<(String, Value)>,
+    key: &str,
+    default: impl FnOnce() -> Value,
+) -> &'a mut Value {
+    let index = match table.iter().position(|(name, _)| name == key) {
+        Some(index) => index,
+        None => {
+            table.push((key.into(), default()));
+            table.len() - 1
+        }
+    };
+    &mut table[index].1
+}
+
+fn parse_key(cursor: &mut Cursor) -> Result<String, Error> {
+    cursor.skip_spaces(false);
+    if cursor.peek() == Some('"') {
+        cursor.advance();
+        return cursor.string();
+    }
+    let key = cursor.word();
+    if key.is_empty() {
+        return Err(cursor.error("expected a key"));
+    }
+    Ok(key)
+}
+
+/// Dotted keys of a header, up to its closing bracket
+fn parse_header(cursor: &mut Cursor) -> Result<Vec<String>, Error> {
+    let mut path = vec![parse_key(cursor)?];
+    loop {
+        cursor.skip_spaces(false);
+        match cursor.advance() {
+            Some('.') => path.push(parse_key(cursor)?),
+            Some(']') => return Ok(path),
+            _ => return Err(cursor.error("expected '.' or ']'")),
+        }
+    }
+}
+
+fn parse_value(cursor: &mut Cursor) -> Result<Value, Error> {
+    cursor.skip_spaces(false);
+    match cursor.peek() {
+        Some('"') => {
+            cursor.advance();
+            cursor.string().map(Value::String)
+        }
+        Some('[') => {
+            cursor.advance();
+            let mut values = Vec::new();
+            loop {
+                cursor.skip_spaces(false);
+                if cursor.peek() == Some(']') {
+                    cursor.advance();
+                    return Ok(Value::Array(values));
+                }
+                values.push(parse_value(cursor)?);
+                cursor.skip_spaces(false);
+                match cursor.peek() {
+                    Some(',') => {
+                        cursor.advance();
+                    }
+                    Some(']') => {}
+                    _ => return Err(cursor.error("expected ',' or ']'")),
+                }
+            }
+        }
+        Some('{') => {
+            cursor.advance();
+            let mut entries: Vec<(String, Value)> = Vec::new();
+            cursor.skip_spaces(false);
+            if cursor.peek() == Some('}') {
+                cursor.advance();
+                return Ok(Value::Table(entries));
+            }
+            loop {
+                let key = parse_key(cursor)?;
+                cursor.skip_spaces(false);
+                cursor.expect('=')?;
+                let value = parse_value(cursor)?;
+                if entries.iter().any(|(name, _)| *name == key) {
+                    return Err(cursor.error("key defined twice"));
+                }
+                entries.push((key, value));
+                cursor.skip_spaces(false);
+                match cursor.advance() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Value::Table(entries)),
+                    _ => return Err(cursor.error("expected ',' or '}'")),
+                }
+            }
+        }
+        Some(c) if c == '-' || c == '+' || c.is_ascii_digit() => cursor.number().map(Value::Number),
+        Some(_) => match cursor.word().as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(cursor.error("expected a value")),
+        },
+        None => Err(cursor.error("unexpected end of file")),
+    }
+}
+
+fn skip_comment(cursor: &mut Cursor) {
+    while !matches!(cursor.peek(), None | Some('\n')) {
+        cursor.advance();
+    }
+}
+
+/// Only a comment may follow a value or a header on its line
+fn end_line(cursor: &mut Cursor) -> Result<(), Error> {
+    cursor.skip_spaces(false);
+    match cursor.peek() {
+        None | Some('\n') => Ok(()),
+        Some('#') => {
+            skip_comment(cursor);
+            Ok(())
+        }
+        Some(_) => Err(cursor.error("expected the end of the line")),
+    }
+}
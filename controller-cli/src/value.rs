@@ -0,0 +1,214 @@
+//! Document tree of the profile files, written and parsed as JSON or TOML
+use crate::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Entries in file order
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Entry `key` of a table
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Table(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Neither a table nor an array of tables
+    pub fn is_scalar(&self) -> bool {
+        match self {
+            Value::Table(_) => false,
+            Value::Array(values) => values.iter().all(Value::is_scalar),
+            _ => true,
+        }
+    }
+}
+
+/// Error at `line` of a parsed file, counted from 1
+pub fn parse_error(line: usize, message: &str) -> Error {
+    Error::Format(format!("line {}: {}", line, message))
+}
+
+/// Number as written in both formats, integers without a fraction
+pub fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+/// Quoted string with the escapes common to JSON and TOML
+pub fn format_string(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reads the characters of a document, counting the lines for the errors
+pub struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    pub line: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Cursor {
+            chars: text.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    pub fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    pub fn error(&self, message: &str) -> Error {
+        parse_error(self.line, message)
+    }
+
+    /// Consumes `expected` or fails
+    pub fn expect(&mut self, expected: char) -> Result<(), Error> {
+        match self.advance() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    /// Skips spaces and tabs, and line ends if `newlines`
+    pub fn skip_spaces(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => {}
+                _ => return,
+            }
+            self.advance();
+        }
+    }
+
+    /// Quoted string, after its opening quote
+    pub fn string(&mut self) -> Result<String, Error> {
+        let mut string = String::new();
+        loop {
+            match self.advance() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let c = match self.advance() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => {
+                            let code: String = (0..4).filter_map(|_| self.advance()).collect();
+                            u32::from_str_radix(&code, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    string.push(c);
+                }
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// Number, sign and exponent included
+    pub fn number(&mut self) -> Result<f64, Error> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E' | '_')) {
+                break;
+            }
+            if c != '_' {
+                text.push(c);
+            }
+            self.advance();
+        }
+        text.parse().map_err(|_| self.error("invalid number"))
+    }
+
+    /// Letters, digits, `_` and `-`: keywords and bare keys
+    pub fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                break;
+            }
+            word.push(c);
+            self.advance();
+        }
+        word
+    }
+}
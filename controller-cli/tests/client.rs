@@ -0,0 +1,78 @@
+use controller_cli::client::ConfigClient;
+use controller_cli::sim::SimulatedDevice;
+use controller_cli::Error;
+use controller_core::config::Status;
+use controller_core::deadzone::DeadzoneMode;
+use controller_core::input::Button;
+use controller_core::remap::ButtonBinding;
+use controller_core::settings::{Settings, MAX_SETTINGS_SIZE, SETTINGS_VERSION};
+
+fn client(settings: Settings) -> ConfigClient<SimulatedDevice> {
+    ConfigClient::new(SimulatedDevice::new(settings))
+}
+
+#[test]
+fn reads_the_versions() {
+    let version = client(Settings::new()).version().unwrap();
+    assert_eq!(version.firmware, [0, 1, 0]);
+    assert_eq!(version.settings_version, SETTINGS_VERSION);
+    assert_eq!(version.max_settings_size as usize, MAX_SETTINGS_SIZE);
+}
+
+#[test]
+fn reads_the_settings() {
+    let mut settings = Settings::new();
+    settings.deadzones.left_stick.mode = DeadzoneMode::Axial;
+    *settings.profiles.profiles[0].button_mut(Button::A) = ButtonBinding::Button(Button::B);
+    assert_eq!(client(settings).read_settings().unwrap(), settings);
+}
+
+#[test]
+fn writes_the_settings_without_saving() {
+    let mut client = client(Settings::new());
+    let mut settings = Settings::new();
+    settings.deadzones.right_stick.inner = 0.25;
+    client.write_settings(&settings, false).unwrap();
+
+    let device = client.transport();
+    assert_eq!(device.settings, settings);
+    assert_eq!(device.saved, Settings::new());
+}
+
+#[test]
+fn writes_and_saves_the_settings() {
+    let mut client = client(Settings::new());
+    let mut settings = Settings::new();
+    settings.profiles.count = 2;
+    client.write_settings(&settings, true).unwrap();
+    assert_eq!(client.transport().saved, settings);
+}
+
+#[test]
+fn backups_restore_the_same_settings() {
+    let mut settings = Settings::new();
+    settings.deadzones.left_stick.outer = 0.8;
+    let record = client(settings).read_record().unwrap();
+
+    let mut client = client(Settings::new());
+    client.write_record(&record, true).unwrap();
+    assert_eq!(client.transport().saved, settings);
+}
+
+#[test]
+fn invalid_records_are_refused() {
+    let mut client = client(Settings::new());
+    let result = client.write_record(&[0xFF; 10], true);
+    assert!(matches!(
+        result,
+        Err(Error::Protocol(Status::InvalidSettings))
+    ));
+    assert_eq!(client.transport().settings, Settings::new());
+}
+
+#[test]
+fn starts_the_calibration() {
+    let mut client = client(Settings::new());
+    client.start_calibration().unwrap();
+    assert!(client.transport().calibration_started);
+}
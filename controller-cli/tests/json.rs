@@ -0,0 +1,52 @@
+use controller_cli::json::{parse, to_string};
+use controller_cli::value::Value;
+
+fn document() -> Value {
+    Value::Table(vec![
+        ("name".into(), Value::String("quote \" and \\ slash".into())),
+        ("count".into(), Value::Number(3.0)),
+        ("ratio".into(), Value::Number(-0.25)),
+        ("on".into(), Value::Bool(false)),
+        (
+            "list".into(),
+            Value::Array(vec![Value::String("a".into()), Value::Number(1e-3)]),
+        ),
+        (
+            "tables".into(),
+            Value::Array(vec![
+                Value::Table(vec![]),
+                Value::Table(vec![("k".into(), Value::Bool(true))]),
+            ]),
+        ),
+    ])
+}
+
+#[test]
+fn documents_round_trip() {
+    let text = to_string(&document());
+    assert_eq!(parse(&text).unwrap(), document());
+}
+
+#[test]
+fn integers_are_written_without_a_fraction() {
+    assert_eq!(to_string(&Value::Number(3.0)), "3\n");
+}
+
+#[test]
+fn parses_escapes_and_whitespace() {
+    let value = parse(" { \"a\" : [ \"\\u0041\\n\" ] , \"b\":true}\n").unwrap();
+    assert_eq!(
+        value.get("a").unwrap().as_array().unwrap()[0],
+        Value::String("A\n".into())
+    );
+    assert_eq!(value.get("b"), Some(&Value::Bool(true)));
+}
+
+#[test]
+fn errors_give_the_line() {
+    let error = parse("{\n\"a\": 1,\n\"b\" 2\n}").unwrap_err();
+    assert!(error.to_string().starts_with("line 3"), "{}", error);
+    assert!(parse("[1, 2").is_err());
+    assert!(parse("{} {}").is_err());
+    assert!(parse("null").is_err());
+}
@@ -0,0 +1,30 @@
+use controller_cli::monitor::render;
+use controller_core::controller::ControllerState;
+use controller_core::report::get_report;
+
+#[test]
+fn shows_axes_buttons_and_dpad() {
+    let mut state = ControllerState::new();
+    state.left_thumb_x = -0.5;
+    state.left_thumb_y = 0.25;
+    state.right_trigger = 1.0;
+    state.a = true;
+    state.start = true;
+    state.up = true;
+    state.right = true;
+
+    let text = render(&get_report(&state));
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].starts_with("lx  -0.500 ["), "{}", lines[0]);
+    assert!(lines[0].contains("==========|"), "{}", lines[0]);
+    assert!(lines[1].starts_with("ly   0.250 ["), "{}", lines[1]);
+    assert!(lines[5].starts_with("rt   1.000 [="), "{}", lines[5]);
+    assert_eq!(lines[6], "buttons: a start");
+    assert_eq!(lines[7], "d-pad: up right");
+}
+
+#[test]
+fn released_dpad_shows_nothing() {
+    let text = render(&get_report(&ControllerState::new()));
+    assert!(text.ends_with("buttons: \nd-pad: \n"), "{}", text);
+}
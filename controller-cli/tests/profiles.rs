@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use controller_cli::profiles::{from_value, to_value, Bindings, Format};
+use controller_core::input::{AnalogInput, Button};
+use controller_core::macros::{Macro, MacroStep};
+use controller_core::remap::{AxisBinding, ButtonBinding, ButtonSet, Profile, Profiles};
+use controller_core::schedule::PollRate;
+use controller_core::settings::Settings;
+
+fn edited_profiles() -> Profiles {
+    let mut profiles = Profiles::new();
+    profiles.count = 2;
+    profiles.active = 1;
+    profiles.switch_combo = ButtonSet::EMPTY.with(Button::Guide);
+
+    let profile = &mut profiles.profiles[1];
+    *profile.button_mut(Button::A) = ButtonBinding::Button(Button::B);
+    *profile.button_mut(Button::Guide) = ButtonBinding::Unbound;
+    *profile.button_mut(Button::X) = ButtonBinding::Axis {
+        input: AnalogInput::LeftTrigger,
+        threshold: 0.3,
+    };
+    *profile.axis_mut(AnalogInput::LeftThumbY) = AxisBinding::Axis {
+        input: AnalogInput::LeftThumbY,
+        inverted: true,
+    };
+    *profile.axis_mut(AnalogInput::RightThumbX) = AxisBinding::Unbound;
+    *profile.axis_mut(AnalogInput::LeftTrigger) = AxisBinding::Buttons {
+        negative: None,
+        positive: Some(Button::LeftShoulder),
+    };
+    profiles
+}
+
+fn edited_bindings() -> Bindings {
+    let mut bindings = Bindings {
+        profiles: edited_profiles(),
+        ..Bindings::default()
+    };
+    *bindings.turbo.button_mut(Button::A) = 10;
+    *bindings.turbo.button_mut(Button::RightShoulder) = 30;
+    let jump = ButtonSet::EMPTY.with(Button::A);
+    let half_flip = [
+        MacroStep::press(jump, 50),
+        MacroStep::wait(80),
+        MacroStep {
+            buttons: jump.with(Button::B),
+            left_stick: Some((0.0, -1.0)),
+            duration_ms: 50,
+        },
+        MacroStep {
+            buttons: ButtonSet::EMPTY,
+            left_stick: Some((0.3, 0.5)),
+            duration_ms: 20,
+        },
+    ];
+    bindings.macros.slots[2] = Macro::new(Button::Guide, &half_flip);
+    bindings
+}
+
+#[test]
+fn profiles_round_trip_through_json() {
+    let bindings = edited_bindings();
+    let text = Format::Json.write(&bindings).unwrap();
+    assert_eq!(Format::Json.read(&text).unwrap(), bindings);
+}
+
+#[test]
+fn profiles_round_trip_through_toml() {
+    let bindings = edited_bindings();
+    let text = Format::Toml.write(&bindings).unwrap();
+    assert!(
+        text.contains("x = { axis = \"lt\", threshold = 0.3 }"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("\n[[macros.steps]]\nbuttons = [\"a\", \"b\"]\nstick = [0, -1]\nms = 50\n")
+    );
+    assert_eq!(Format::Toml.read(&text).unwrap(), bindings);
+
+    // Without turbo nor macros
+    let bindings = Bindings::default();
+    let text = Format::Toml.write(&bindings).unwrap();
+    assert_eq!(Format::Toml.read(&text).unwrap(), bindings);
+}
+
+#[test]
+fn only_profiles_in_use_are_exported() {
+    let document = to_value(&edited_bindings());
+    let profiles = document.get("profiles").unwrap().as_array().unwrap();
+    assert_eq!(profiles.len(), 2);
+}
+
+#[test]
+fn missing_controls_keep_their_identity_binding() {
+    let text = r#"
+        [[profiles]]
+        [profiles.buttons]
+        a = "b"
+    "#;
+    let Bindings {
+        profiles,
+        turbo,
+        macros,
+    } = Format::Toml.read(text).unwrap();
+    let mut expected = Profile::IDENTITY;
+    *expected.button_mut(Button::A) = ButtonBinding::Button(Button::B);
+    assert_eq!(profiles.count, 1);
+    assert_eq!(profiles.profiles[0], expected);
+    assert_eq!(profiles.switch_combo, Profiles::new().switch_combo);
+    assert_eq!(turbo, Default::default());
+    assert_eq!(macros, Default::default());
+}
+
+#[test]
+fn import_replaces_only_the_bindings() {
+    let mut settings = Settings::new();
+    settings.profiles.count = 3;
+    *settings.turbo.button_mut(Button::X) = 5;
+    settings.poll_rate = PollRate::Hz250;
+
+    let bindings = edited_bindings();
+    bindings.apply(&mut settings);
+    assert_eq!(Bindings::from_settings(&settings), bindings);
+    assert_eq!(settings.poll_rate, PollRate::Hz250);
+}
+
+#[test]
+fn invalid_profiles_are_refused() {
+    let documents = [
+        r#"{"profiles": []}"#,
+        r#"{"profiles": [{}, {}, {}, {}, {}]}"#,
+        r#"{"active": 1, "profiles": [{}]}"#,
+        r#"{"profiles": [{"buttons": {"z": "a"}}]}"#,
+        r#"{"profiles": [{"buttons": {"a": "z"}}]}"#,
+        r#"{"profiles": [{"buttons": {"a": {"axis": "lt", "threshold": 0}}}]}"#,
+        r#"{"profiles": [{"axes": {"lx": {"axis": "lx", "inverted": 1}}}]}"#,
+        r#"{"profiles": [{}], "turbo": {"a": 31}}"#,
+        r#"{"profiles": [{}], "turbo": {"a": 2.5}}"#,
+        r#"{"profiles": [{}], "turbo": {"lx": 10}}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 4, "trigger": "a", "steps": [{"ms": 5}]}]}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 0, "steps": [{"ms": 5}]}]}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 0, "trigger": "a", "steps": []}]}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 0, "trigger": "a", "steps": [{}]}]}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 0, "trigger": "a", "steps": [{"ms": 5, "stick": [0]}]}]}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 0, "trigger": "a", "steps": [{"ms": 5, "stick": [0, 2]}]}]}"#,
+        r#"{"profiles": [{}], "macros": [{"slot": 0, "trigger": "a", "steps": [{"ms": 5}]},
+                                         {"slot": 0, "trigger": "b", "steps": [{"ms": 5}]}]}"#,
+    ];
+    for document in documents {
+        assert!(Format::Json.read(document).is_err(), "{}", document);
+    }
+    assert!(from_value(&controller_cli::value::Value::Bool(true)).is_err());
+}
+
+#[test]
+fn format_follows_the_extension() {
+    assert_eq!(
+        Format::from_path(Path::new("a.json")).unwrap(),
+        Format::Json
+    );
+    assert_eq!(
+        Format::from_path(Path::new("dir/a.toml")).unwrap(),
+        Format::Toml
+    );
+    assert!(Format::from_path(Path::new("a.txt")).is_err());
+}
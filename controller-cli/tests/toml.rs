@@ -0,0 +1,81 @@
+use controller_cli::toml::{parse, to_string};
+use controller_cli::value::Value;
+
+fn table(entries: &[(&str, Value)]) -> Value {
+    Value::Table(
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+    )
+}
+
+#[test]
+fn documents_round_trip() {
+    let small = table(&[
+        ("axis", Value::String("lt".into())),
+        ("threshold", Value::Number(0.5)),
+    ]);
+    let large = table(&[
+        ("a", Value::String("a".into())),
+        ("b", small.clone()),
+        ("c", Value::Bool(true)),
+        ("d", Value::Number(-2.0)),
+        (
+            "e",
+            Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]),
+        ),
+    ]);
+    // Inline entries come before the sections, as they are written
+    let document = table(&[
+        ("active", Value::Number(1.0)),
+        ("odd key", small),
+        (
+            "profiles",
+            Value::Array(vec![
+                table(&[("buttons", large.clone())]),
+                table(&[("buttons", large)]),
+            ]),
+        ),
+    ]);
+
+    let text = to_string(&document).unwrap();
+    assert!(
+        text.contains("[[profiles]]\n\n[profiles.buttons]\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("b = { axis = \"lt\", threshold = 0.5 }"),
+        "{}",
+        text
+    );
+    assert!(text.contains("\"odd key\" = {"), "{}", text);
+    assert_eq!(parse(&text).unwrap(), document);
+}
+
+#[test]
+fn parses_comments_and_quoted_keys() {
+    let value = parse("# settings\n\"a b\" = 1 # one\n[t]\nx = [ \"y\", ]\n").unwrap();
+    assert_eq!(value.get("a b"), Some(&Value::Number(1.0)));
+    let t = value.get("t").unwrap();
+    assert_eq!(
+        t.get("x"),
+        Some(&Value::Array(vec![Value::String("y".into())]))
+    );
+}
+
+#[test]
+fn only_tables_can_be_documents() {
+    assert!(to_string(&Value::Bool(true)).is_err());
+}
+
+#[test]
+fn errors_give_the_line() {
+    let error = parse("a = 1\nb = \n").unwrap_err();
+    assert!(error.to_string().starts_with("line 2"), "{}", error);
+    assert!(parse("a = 1\na = 2").is_err());
+    assert!(parse("[t]\n[t]").is_err());
+    assert!(parse("a = 1 2").is_err());
+    assert!(parse("a = 1\n[a.b]").is_err());
+}
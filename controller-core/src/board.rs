@@ -0,0 +1,195 @@
+//! Declarative description of the pins of a board
+//!
+//! A board is a table of the physical inputs and the control each one drives.
+//! The firmware configures the GPIOs and ADC scans from it, so rewiring a control
+//! or supporting a new PCB revision only changes the table.
+use crate::input::{AnalogInput, Button};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl Port {
+    /// Position of the port, `A` is 0
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// GPIO pin, `pin` in `0..=15`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PinId {
+    pub port: Port,
+    pub pin: u8,
+}
+
+impl PinId {
+    pub const fn new(port: Port, pin: u8) -> Self {
+        PinId { port, pin }
+    }
+}
+
+/// Internal resistor of an input pin
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Pull {
+    /// Floating, the board has its own resistor
+    None,
+    Up,
+    Down,
+}
+
+/// Level of the pin while its button is pressed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ActiveLevel {
+    Low,
+    High,
+}
+
+impl ActiveLevel {
+    pub fn is_active(self, pin_high: bool) -> bool {
+        match self {
+            ActiveLevel::Low => !pin_high,
+            ActiveLevel::High => pin_high,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Adc {
+    Adc1,
+    Adc2,
+    Adc3,
+    Adc4,
+}
+
+/// Button wired to a GPIO
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DigitalPin {
+    pub pin: PinId,
+    pub pull: Pull,
+    pub active: ActiveLevel,
+    pub button: Button,
+}
+
+/// Analog input wired to an ADC channel, converted in table order
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AnalogPin {
+    pub pin: PinId,
+    pub adc: Adc,
+    /// Channel of `pin` on `adc`, in `1..=18`
+    pub channel: u8,
+    pub input: AnalogInput,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BoardError {
+    /// Pin number out of `0..=15`
+    InvalidPin(PinId),
+    /// Pin used by two entries, or reserved by the firmware
+    PinConflict(PinId),
+    /// ADC channel out of `1..=18`
+    InvalidChannel { adc: Adc, channel: u8 },
+    /// ADC the firmware does not scan
+    UnsupportedAdc(Adc),
+    /// Channel converted twice by the same ADC
+    ChannelConflict { adc: Adc, channel: u8 },
+    /// Button driven by two pins
+    DuplicateButton(Button),
+    /// Analog input driven by two channels
+    DuplicateInput(AnalogInput),
+}
+
+/// Buttons and analog inputs of a board.
+///
+/// Controls missing from the table read as released buttons and centered axes.
+#[derive(Clone, Copy, Debug)]
+pub struct Board {
+    pub digital: &'static [DigitalPin],
+    pub analog: &'static [AnalogPin],
+}
+
+impl Board {
+    pub fn digital_pin(&self, button: Button) -> Option<&DigitalPin> {
+        self.digital.iter().find(|digital| digital.button == button)
+    }
+
+    pub fn analog_pin(&self, input: AnalogInput) -> Option<&AnalogPin> {
+        self.analog.iter().find(|analog| analog.input == input)
+    }
+
+    /// Number of channels converted by `adc`, usable to size its scan
+    pub const fn adc_channel_count(&self, adc: Adc) -> usize {
+        let mut count = 0;
+        let mut index = 0;
+        while index < self.analog.len() {
+            if self.analog[index].adc as u8 == adc as u8 {
+                count += 1;
+            }
+            index += 1;
+        }
+        count
+    }
+
+    /// Inputs converted by `adc` with their channel, in scan order.
+    ///
+    /// # Panics
+    /// If `N` is not [`Self::adc_channel_count`]
+    pub fn adc_channels<const N: usize>(&self, adc: Adc) -> [(AnalogInput, u8); N] {
+        assert_eq!(N, self.adc_channel_count(adc), "ADC scan length mismatch");
+        let mut channels = [(AnalogInput::LeftThumbX, 0); N];
+        let pins = self.analog.iter().filter(|analog| analog.adc == adc);
+        for (channel, analog) in channels.iter_mut().zip(pins) {
+            *channel = (analog.input, analog.channel);
+        }
+        channels
+    }
+
+    /// Pins used by the table, digital ones first
+    pub fn pins(&self) -> impl Iterator<Item = PinId> + '_ {
+        let digital = self.digital.iter().map(|digital| digital.pin);
+        digital.chain(self.analog.iter().map(|analog| analog.pin))
+    }
+
+    /// Checks the table is consistent and does not use any of the `reserved` pins
+    pub fn validate(&self, reserved: &[PinId]) -> Result<(), BoardError> {
+        for (index, pin) in self.pins().enumerate() {
+            if pin.pin > 15 {
+                return Err(BoardError::InvalidPin(pin));
+            }
+            if reserved.contains(&pin) || self.pins().skip(index + 1).any(|other| other == pin) {
+                return Err(BoardError::PinConflict(pin));
+            }
+        }
+
+        for (index, digital) in self.digital.iter().enumerate() {
+            let later = &self.digital[index + 1..];
+            if later.iter().any(|other| other.button == digital.button) {
+                return Err(BoardError::DuplicateButton(digital.button));
+            }
+        }
+
+        for (index, analog) in self.analog.iter().enumerate() {
+            let (adc, channel) = (analog.adc, analog.channel);
+            if !(1..=18).contains(&channel) {
+                return Err(BoardError::InvalidChannel { adc, channel });
+            }
+            let later = &self.analog[index + 1..];
+            if later
+                .iter()
+                .any(|other| other.adc == adc && other.channel == channel)
+            {
+                return Err(BoardError::ChannelConflict { adc, channel });
+            }
+            if later.iter().any(|other| other.input == analog.input) {
+                return Err(BoardError::DuplicateInput(analog.input));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! Per-axis calibration and the guided routine recording it
+use crate::controller::ControllerState;
+use crate::input::AnalogInput;
+use crate::pipeline::ADC_MAX_VALUE;
+
+/// Raw ADC values at both ends of an axis travel and at rest
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AxisCalibration {
+    pub min: u16,
+    pub center: u16,
+    pub max: u16,
+}
+
+impl AxisCalibration {
+    /// Full ADC range centered in the middle, matches an ideal potentiometer
+    pub const UNCALIBRATED: AxisCalibration = AxisCalibration {
+        min: 0,
+        center: ADC_MAX_VALUE / 2 + 1,
+        max: ADC_MAX_VALUE,
+    };
+
+    /// Full ADC range resting at its minimum, values only go from `0.0` to `1.0`
+    pub const UNCALIBRATED_TRIGGER: AxisCalibration = AxisCalibration {
+        min: 0,
+        center: 0,
+        max: ADC_MAX_VALUE,
+    };
+
+    /// Uncalibrated default of the given input
+    pub const fn uncalibrated(input: AnalogInput) -> Self {
+        if input.is_trigger() {
+            Self::UNCALIBRATED_TRIGGER
+        } else {
+            Self::UNCALIBRATED
+        }
+    }
+
+    pub const fn new(min: u16, center: u16, max: u16) -> Self {
+        AxisCalibration { min, center, max }
+    }
+
+    /// Maps a raw value to `-1.0..=1.0`, `center` mapping to `0.0`.
+    ///
+    /// Each half of the travel is scaled on its own, so an off-center rest position
+    /// still reaches full deflection on both sides. A half with no travel
+    /// (e.g. a trigger resting at `min`) maps to `0.0`.
+    pub fn apply(&self, raw: u16) -> f32 {
+        let raw = raw as f32;
+        let center = self.center as f32;
+        let value = if raw >= center {
+            if self.max > self.center {
+                (raw - center) / (self.max as f32 - center)
+            } else {
+                0f32
+            }
+        } else if self.center > self.min {
+            (raw - center) / (center - self.min as f32)
+        } else {
+            0f32
+        };
+        value.clamp(-1f32, 1f32)
+    }
+}
+
+impl Default for AxisCalibration {
+    fn default() -> Self {
+        Self::UNCALIBRATED
+    }
+}
+
+/// Calibration of every analog input, indexed by [`AnalogInput::index`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Calibration {
+    pub axes: [AxisCalibration; AnalogInput::COUNT],
+}
+
+impl Calibration {
+    pub const fn new() -> Self {
+        let mut axes = [AxisCalibration::UNCALIBRATED; AnalogInput::COUNT];
+        let mut index = 0;
+        while index < AnalogInput::COUNT {
+            axes[index] = AxisCalibration::uncalibrated(AnalogInput::ALL[index]);
+            index += 1;
+        }
+        Calibration { axes }
+    }
+
+    pub fn axis(&self, input: AnalogInput) -> &AxisCalibration {
+        &self.axes[input.index()]
+    }
+
+    pub fn axis_mut(&mut self, input: AnalogInput) -> &mut AxisCalibration {
+        &mut self.axes[input.index()]
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Smallest travel (in raw ADC units) for an axis range to be recorded
+pub const MIN_TRAVEL: u16 = 1024;
+
+/// Number of consecutive samples START+BACK must be held to start the routine
+pub const DEFAULT_HOLD_SAMPLES: u32 = 200;
+
+/// Time START+BACK must be held, converted to samples with [`crate::schedule::PollRate::samples`]
+pub const CALIBRATION_HOLD_MS: u32 = 2000;
+
+/// Samples averaged into the centers, later ones are ignored so the sums cannot overflow.
+/// About a minute at 1 kHz.
+pub const MAX_CENTER_SAMPLES: u32 = 1 << 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CalibrationStep {
+    /// Waiting for START+BACK to be held
+    Idle,
+    /// Sticks and triggers are left at rest, A records the centers
+    Center,
+    /// Every axis is moved over its full travel, A records the ranges
+    Range,
+}
+
+/// Guided calibration, driven by the sampled state
+///
+/// 1. Hold START+BACK to start.
+/// 2. Leave sticks and triggers at rest and press A.
+/// 3. Move every axis to both ends of its travel and press A.
+///
+/// Pressing B at any step cancels and keeps the previous calibration.
+pub struct CalibrationRoutine {
+    step: CalibrationStep,
+    hold_samples: u32,
+    combo_samples: u32,
+    a_was_pressed: bool,
+    center_sums: [u32; AnalogInput::COUNT],
+    center_count: u32,
+    recorded: Calibration,
+}
+
+impl CalibrationRoutine {
+    pub fn new(hold_samples: u32) -> Self {
+        CalibrationRoutine {
+            step: CalibrationStep::Idle,
+            hold_samples,
+            combo_samples: 0,
+            a_was_pressed: false,
+            center_sums: [0; AnalogInput::COUNT],
+            center_count: 0,
+            recorded: Calibration::new(),
+        }
+    }
+
+    pub fn step(&self) -> CalibrationStep {
+        self.step
+    }
+
+    /// `true` while the routine is running, reports should not be sent to the host
+    pub fn is_active(&self) -> bool {
+        self.step != CalibrationStep::Idle
+    }
+
+    /// Number of axes moved over at least [`MIN_TRAVEL`] during the range step
+    pub fn calibrated_axes(&self) -> usize {
+        if self.step != CalibrationStep::Range {
+            return 0;
+        }
+        self.recorded
+            .axes
+            .iter()
+            .filter(|axis| has_travel(axis))
+            .count()
+    }
+
+    /// Starts the routine without waiting for START+BACK, e.g. from the mode key
+    pub fn start(&mut self) {
+        self.combo_samples = 0;
+        self.center_sums = [0; AnalogInput::COUNT];
+        self.center_count = 0;
+        self.step = CalibrationStep::Center;
+    }
+
+    /// Feeds one sample to the routine.
+    ///
+    /// `raw_values` are the uncalibrated ADC values indexed by [`AnalogInput::index`].
+    /// Returns the new calibration once the last step is confirmed,
+    /// axes that did not move enough keep their `current` calibration.
+    pub fn update(
+        &mut self,
+        raw_values: &[u16; AnalogInput::COUNT],
+        controller_state: &ControllerState,
+        current: &Calibration,
+    ) -> Option<Calibration> {
+        let a_pressed = controller_state.a && !self.a_was_pressed;
+        self.a_was_pressed = controller_state.a;
+
+        if self.step != CalibrationStep::Idle && controller_state.b {
+            self.step = CalibrationStep::Idle;
+            return None;
+        }
+
+        match self.step {
+            CalibrationStep::Idle => {
+                if controller_state.start && controller_state.back {
+                    self.combo_samples += 1;
+                } else {
+                    self.combo_samples = 0;
+                }
+                if self.combo_samples >= self.hold_samples {
+                    self.start();
+                }
+                None
+            }
+            CalibrationStep::Center => {
+                if self.center_count < MAX_CENTER_SAMPLES {
+                    for (sum, raw) in self.center_sums.iter_mut().zip(raw_values) {
+                        *sum += *raw as u32;
+                    }
+                    self.center_count += 1;
+                }
+
+                if a_pressed {
+                    for (axis, sum) in self.recorded.axes.iter_mut().zip(self.center_sums) {
+                        let center = (sum / self.center_count) as u16;
+                        *axis = AxisCalibration::new(center, center, center);
+                    }
+                    self.step = CalibrationStep::Range;
+                }
+                None
+            }
+            CalibrationStep::Range => {
+                for (axis, raw) in self.recorded.axes.iter_mut().zip(raw_values) {
+                    axis.min = axis.min.min(*raw);
+                    axis.max = axis.max.max(*raw);
+                }
+
+                if !a_pressed {
+                    return None;
+                }
+                self.step = CalibrationStep::Idle;
+
+                let mut calibration = *current;
+                for (axis, recorded) in calibration.axes.iter_mut().zip(self.recorded.axes) {
+                    if has_travel(&recorded) {
+                        *axis = recorded;
+                    }
+                }
+                Some(calibration)
+            }
+        }
+    }
+}
+
+impl Default for CalibrationRoutine {
+    fn default() -> Self {
+        Self::new(DEFAULT_HOLD_SAMPLES)
+    }
+}
+
+fn has_travel(axis: &AxisCalibration) -> bool {
+    axis.max - axis.min >= MIN_TRAVEL
+}
@@ -0,0 +1,142 @@
+//! Little endian binary encoding shared by the settings record and the config protocols
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CodecError {
+    /// The output buffer cannot hold the encoded data
+    BufferTooSmall,
+    /// The input ended in the middle of a value
+    UnexpectedEnd,
+    /// A decoded value is out of the range allowed for its field
+    InvalidValue,
+}
+
+/// Writes values one after the other in a byte buffer
+pub struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Writer {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<(), CodecError> {
+        let end = self.position + bytes.len();
+        self.buffer
+            .get_mut(self.position..end)
+            .ok_or(CodecError::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+
+    pub fn u8(&mut self, value: u8) -> Result<(), CodecError> {
+        self.bytes(&[value])
+    }
+
+    pub fn bool(&mut self, value: bool) -> Result<(), CodecError> {
+        self.u8(value as u8)
+    }
+
+    pub fn u16(&mut self, value: u16) -> Result<(), CodecError> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i16(&mut self, value: i16) -> Result<(), CodecError> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> Result<(), CodecError> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn f32(&mut self, value: f32) -> Result<(), CodecError> {
+        self.bytes(&value.to_le_bytes())
+    }
+}
+
+/// Reads values written by [`Writer`]
+pub struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Reader {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Result<&'a [u8], CodecError> {
+        let end = self.position + length;
+        let bytes = self
+            .buffer
+            .get(self.position..end)
+            .ok_or(CodecError::UnexpectedEnd)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CodecError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, CodecError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, CodecError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CodecError::InvalidValue),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, CodecError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn i16(&mut self) -> Result<i16, CodecError> {
+        Ok(i16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, CodecError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, CodecError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+}
+
+/// CRC-32 (IEEE 802.3, as used by zip and ethernet) of `bytes`
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
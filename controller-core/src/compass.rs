@@ -0,0 +1,232 @@
+//! Registers and scaling of the LSM303DLHC e-compass, independent of the I2C bus
+//!
+//! The firmware writes the register values of a [`CompassConfig`] and keeps it to scale
+//! the readings, so the scaling always matches the range the sensor measures in.
+
+/// I2C address of the accelerometer
+pub const ACCEL_ADDRESS: u8 = 0x19;
+/// I2C address of the magnetometer
+pub const MAG_ADDRESS: u8 = 0x1E;
+
+pub const CTRL_REG1_A: u8 = 0x20;
+pub const CTRL_REG3_A: u8 = 0x22;
+pub const CTRL_REG4_A: u8 = 0x23;
+/// First output register of the accelerometer, `x`, `y` then `z` little endian
+pub const OUT_X_L_A: u8 = 0x28;
+/// Set on a register address to read several accelerometer registers in a row
+pub const AUTO_INCREMENT: u8 = 0x80;
+
+pub const CRA_REG_M: u8 = 0x00;
+pub const CRB_REG_M: u8 = 0x01;
+pub const MR_REG_M: u8 = 0x02;
+/// First output register of the magnetometer, `x`, `z` then `y` big endian
+pub const OUT_X_H_M: u8 = 0x03;
+
+/// `CTRL_REG1_A`: `x`, `y` and `z` enabled
+const ACCEL_XYZ_ENABLE: u8 = 0b0111;
+/// `CTRL_REG3_A`: data ready of the accelerometer on INT1
+const I1_DRDY1: u8 = 1 << 4;
+/// `CTRL_REG4_A`: outputs updated together, so a read never mixes two samples
+const BLOCK_DATA_UPDATE: u8 = 1 << 7;
+/// `CTRL_REG4_A`: 12-bit resolution
+const HIGH_RESOLUTION: u8 = 1 << 3;
+/// `MR_REG_M`: continuous conversion
+const MAG_CONTINUOUS: u8 = 0b00;
+
+/// Full scale of the accelerometer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccelRange {
+    G2,
+    G4,
+    G8,
+    G16,
+}
+
+impl AccelRange {
+    pub const ALL: [AccelRange; 4] = [
+        AccelRange::G2,
+        AccelRange::G4,
+        AccelRange::G8,
+        AccelRange::G16,
+    ];
+
+    /// Largest acceleration measured, in g
+    pub fn g(self) -> f32 {
+        match self {
+            AccelRange::G2 => 2.0,
+            AccelRange::G4 => 4.0,
+            AccelRange::G8 => 8.0,
+            AccelRange::G16 => 16.0,
+        }
+    }
+
+    /// Acceleration of one count of the left justified 16-bit output, in g.
+    ///
+    /// The datasheet gives 1, 2, 4 and 12 mg per digit of the 12-bit value: the ±16 g
+    /// range is not twice the ±8 g one.
+    pub fn g_per_count(self) -> f32 {
+        let mg_per_digit = match self {
+            AccelRange::G2 => 1.0,
+            AccelRange::G4 => 2.0,
+            AccelRange::G8 => 4.0,
+            AccelRange::G16 => 12.0,
+        };
+        mg_per_digit / 1000.0 / 16.0
+    }
+
+    pub fn to_g(self, raw: [i16; 3]) -> [f32; 3] {
+        raw.map(|count| count as f32 * self.g_per_count())
+    }
+
+    fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Output data rate of the accelerometer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccelRate {
+    Hz1 = 1,
+    Hz10,
+    Hz25,
+    Hz50,
+    Hz100,
+    Hz200,
+    Hz400,
+    Hz1344 = 9,
+}
+
+impl AccelRate {
+    pub fn hz(self) -> f32 {
+        match self {
+            AccelRate::Hz1 => 1.0,
+            AccelRate::Hz10 => 10.0,
+            AccelRate::Hz25 => 25.0,
+            AccelRate::Hz50 => 50.0,
+            AccelRate::Hz100 => 100.0,
+            AccelRate::Hz200 => 200.0,
+            AccelRate::Hz400 => 400.0,
+            AccelRate::Hz1344 => 1344.0,
+        }
+    }
+}
+
+/// Full scale of the magnetometer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MagGain {
+    Gauss1_3 = 1,
+    Gauss1_9,
+    Gauss2_5,
+    Gauss4_0,
+    Gauss4_7,
+    Gauss5_6,
+    Gauss8_1,
+}
+
+impl MagGain {
+    /// Counts per gauss of `x` and `y`, then of `z` which is less sensitive
+    pub fn counts_per_gauss(self) -> (f32, f32) {
+        match self {
+            MagGain::Gauss1_3 => (1100.0, 980.0),
+            MagGain::Gauss1_9 => (855.0, 760.0),
+            MagGain::Gauss2_5 => (670.0, 600.0),
+            MagGain::Gauss4_0 => (450.0, 400.0),
+            MagGain::Gauss4_7 => (400.0, 355.0),
+            MagGain::Gauss5_6 => (330.0, 295.0),
+            MagGain::Gauss8_1 => (230.0, 205.0),
+        }
+    }
+
+    pub fn to_gauss(self, raw: [i16; 3]) -> [f32; 3] {
+        let (xy, z) = self.counts_per_gauss();
+        [raw[0] as f32 / xy, raw[1] as f32 / xy, raw[2] as f32 / z]
+    }
+}
+
+/// Output data rate of the magnetometer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MagRate {
+    Hz0_75,
+    Hz1_5,
+    Hz3,
+    Hz7_5,
+    Hz15,
+    Hz30,
+    Hz75,
+    Hz220,
+}
+
+impl MagRate {
+    pub fn hz(self) -> f32 {
+        match self {
+            MagRate::Hz0_75 => 0.75,
+            MagRate::Hz1_5 => 1.5,
+            MagRate::Hz3 => 3.0,
+            MagRate::Hz7_5 => 7.5,
+            MagRate::Hz15 => 15.0,
+            MagRate::Hz30 => 30.0,
+            MagRate::Hz75 => 75.0,
+            MagRate::Hz220 => 220.0,
+        }
+    }
+}
+
+/// Ranges and rates the e-compass measures at
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CompassConfig {
+    pub accel_range: AccelRange,
+    pub accel_rate: AccelRate,
+    pub mag_gain: MagGain,
+    pub mag_rate: MagRate,
+}
+
+impl CompassConfig {
+    /// Tilt needs little range but a fast rate, the heading follows at a lower rate
+    pub const fn new() -> Self {
+        CompassConfig {
+            accel_range: AccelRange::G2,
+            accel_rate: AccelRate::Hz400,
+            mag_gain: MagGain::Gauss1_3,
+            mag_rate: MagRate::Hz75,
+        }
+    }
+
+    /// Register and value pairs configuring the accelerometer, data ready on INT1
+    pub fn accel_registers(&self) -> [(u8, u8); 3] {
+        [
+            (CTRL_REG1_A, (self.accel_rate as u8) << 4 | ACCEL_XYZ_ENABLE),
+            (CTRL_REG3_A, I1_DRDY1),
+            (
+                CTRL_REG4_A,
+                BLOCK_DATA_UPDATE | self.accel_range.bits() << 4 | HIGH_RESOLUTION,
+            ),
+        ]
+    }
+
+    /// Register and value pairs configuring the magnetometer, data ready on DRDY
+    pub fn mag_registers(&self) -> [(u8, u8); 3] {
+        [
+            (CRA_REG_M, (self.mag_rate as u8) << 2),
+            (CRB_REG_M, (self.mag_gain as u8) << 5),
+            (MR_REG_M, MAG_CONTINUOUS),
+        ]
+    }
+}
+
+impl Default for CompassConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts of the accelerometer output registers
+pub fn accel_counts(bytes: &[u8; 6]) -> [i16; 3] {
+    let axis = |index: usize| i16::from_le_bytes([bytes[index], bytes[index + 1]]);
+    [axis(0), axis(2), axis(4)]
+}
+
+/// Counts of the magnetometer output registers, reordered to `x`, `y`, `z`
+pub fn mag_counts(bytes: &[u8; 6]) -> [i16; 3] {
+    let axis = |index: usize| i16::from_be_bytes([bytes[index], bytes[index + 1]]);
+    [axis(0), axis(4), axis(2)]
+}
@@ -0,0 +1,391 @@
+//! Versioned binary configuration protocol, carried by a HID feature report
+//!
+//! The host writes a request with SET_REPORT, then reads the response with GET_REPORT.
+//! Every report is [`REPORT_SIZE`] bytes and starts with [`PROTOCOL_VERSION`] and the
+//! command. The settings move as chunks of their [`Settings::encode`] record: written
+//! chunks are staged on the controller and only applied once committed, so a half
+//! written configuration never reaches the pipeline.
+use crate::codec::{CodecError, Reader, Writer};
+use crate::settings::{Settings, MAX_SETTINGS_SIZE, SETTINGS_VERSION};
+
+/// Written first in every report, bumped on any incompatible change
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Size of the feature report, requests and responses are padded with zeros
+pub const REPORT_SIZE: usize = 64;
+
+/// Largest number of settings bytes in a single report
+pub const MAX_CHUNK_SIZE: usize = 56;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Command {
+    GetVersion = 1,
+    ReadSettings = 2,
+    WriteSettings = 3,
+    CommitSettings = 4,
+    StartCalibration = 5,
+}
+
+impl Command {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Command::GetVersion),
+            2 => Some(Command::ReadSettings),
+            3 => Some(Command::WriteSettings),
+            4 => Some(Command::CommitSettings),
+            5 => Some(Command::StartCalibration),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of a request, written after the command in every response
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    Ok = 0,
+    /// The request is not handled yet, read the response again
+    Pending = 1,
+    /// The request was written with another protocol version
+    UnsupportedVersion = 2,
+    UnknownCommand = 3,
+    /// Malformed request, or a chunk outside of the settings record
+    InvalidRequest = 4,
+    /// The committed record does not decode
+    InvalidSettings = 5,
+    /// The settings were applied but could not be written to flash
+    SaveFailed = 6,
+}
+
+impl Status {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Status::Ok),
+            1 => Some(Status::Pending),
+            2 => Some(Status::UnsupportedVersion),
+            3 => Some(Status::UnknownCommand),
+            4 => Some(Status::InvalidRequest),
+            5 => Some(Status::InvalidSettings),
+            6 => Some(Status::SaveFailed),
+            _ => None,
+        }
+    }
+}
+
+impl From<CodecError> for Status {
+    fn from(_: CodecError) -> Self {
+        Status::InvalidRequest
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Request<'a> {
+    GetVersion,
+    /// Reads up to [`MAX_CHUNK_SIZE`] bytes of the current settings record from `offset`
+    ReadSettings {
+        offset: u16,
+    },
+    /// Stages `data` at `offset` of the record to commit
+    WriteSettings {
+        offset: u16,
+        data: &'a [u8],
+    },
+    /// Applies the first `length` staged bytes, then writes them to flash if `save`
+    CommitSettings {
+        length: u16,
+        save: bool,
+    },
+    /// Starts the same calibration as a long press of the guide button
+    StartCalibration,
+}
+
+impl<'a> Request<'a> {
+    pub fn command(&self) -> Command {
+        match self {
+            Request::GetVersion => Command::GetVersion,
+            Request::ReadSettings { .. } => Command::ReadSettings,
+            Request::WriteSettings { .. } => Command::WriteSettings,
+            Request::CommitSettings { .. } => Command::CommitSettings,
+            Request::StartCalibration => Command::StartCalibration,
+        }
+    }
+
+    pub fn encode(&self) -> Result<[u8; REPORT_SIZE], CodecError> {
+        let mut report = [0; REPORT_SIZE];
+        let mut writer = Writer::new(&mut report);
+        writer.u8(PROTOCOL_VERSION)?;
+        writer.u8(self.command() as u8)?;
+        match *self {
+            Request::ReadSettings { offset } => writer.u16(offset)?,
+            Request::WriteSettings { offset, data } => {
+                if data.len() > MAX_CHUNK_SIZE {
+                    return Err(CodecError::InvalidValue);
+                }
+                writer.u16(offset)?;
+                writer.u8(data.len() as u8)?;
+                writer.bytes(data)?;
+            }
+            Request::CommitSettings { length, save } => {
+                writer.u16(length)?;
+                writer.bool(save)?;
+            }
+            Request::GetVersion | Request::StartCalibration => {}
+        }
+        Ok(report)
+    }
+
+    pub fn decode(report: &'a [u8]) -> Result<Self, Status> {
+        let mut reader = Reader::new(report);
+        if reader.u8()? != PROTOCOL_VERSION {
+            return Err(Status::UnsupportedVersion);
+        }
+        let command = Command::from_u8(reader.u8()?).ok_or(Status::UnknownCommand)?;
+        Ok(match command {
+            Command::GetVersion => Request::GetVersion,
+            Command::ReadSettings => Request::ReadSettings {
+                offset: reader.u16()?,
+            },
+            Command::WriteSettings => {
+                let offset = reader.u16()?;
+                let length = reader.u8()? as usize;
+                if length > MAX_CHUNK_SIZE {
+                    return Err(Status::InvalidRequest);
+                }
+                Request::WriteSettings {
+                    offset,
+                    data: reader.bytes(length)?,
+                }
+            }
+            Command::CommitSettings => Request::CommitSettings {
+                length: reader.u16()?,
+                save: reader.bool()?,
+            },
+            Command::StartCalibration => Request::StartCalibration,
+        })
+    }
+}
+
+/// Versions reported by [`Request::GetVersion`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VersionInfo {
+    /// Major, minor and patch version of the firmware
+    pub firmware: [u8; 3],
+    /// Newest record version the firmware decodes
+    pub settings_version: u16,
+    pub max_settings_size: u16,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Response<'a> {
+    Version(VersionInfo),
+    /// Chunk of the settings record, empty past its end
+    Settings {
+        /// Length of the whole record
+        length: u16,
+        offset: u16,
+        data: &'a [u8],
+    },
+    /// The request succeeded and has nothing to return
+    Done,
+}
+
+impl<'a> Response<'a> {
+    /// Encodes the response to a `command` request, or its error status
+    pub fn encode(
+        command: u8,
+        response: Result<Response, Status>,
+    ) -> Result<[u8; REPORT_SIZE], CodecError> {
+        let mut report = [0; REPORT_SIZE];
+        let mut writer = Writer::new(&mut report);
+        writer.u8(PROTOCOL_VERSION)?;
+        writer.u8(command)?;
+        match response {
+            Err(status) => writer.u8(status as u8)?,
+            Ok(response) => {
+                writer.u8(Status::Ok as u8)?;
+                match response {
+                    Response::Version(version) => {
+                        writer.bytes(&version.firmware)?;
+                        writer.u16(version.settings_version)?;
+                        writer.u16(version.max_settings_size)?;
+                    }
+                    Response::Settings {
+                        length,
+                        offset,
+                        data,
+                    } => {
+                        if data.len() > MAX_CHUNK_SIZE {
+                            return Err(CodecError::InvalidValue);
+                        }
+                        writer.u16(length)?;
+                        writer.u16(offset)?;
+                        writer.u8(data.len() as u8)?;
+                        writer.bytes(data)?;
+                    }
+                    Response::Done => {}
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Decodes the response to a `command` request, its status if it failed.
+    ///
+    /// A response to another command is [`Status::Pending`]: the request is not handled yet.
+    pub fn decode(report: &'a [u8], command: Command) -> Result<Self, Status> {
+        let mut reader = Reader::new(report);
+        if reader.u8()? != PROTOCOL_VERSION {
+            return Err(Status::UnsupportedVersion);
+        }
+        if reader.u8()? != command as u8 {
+            return Err(Status::Pending);
+        }
+        match Status::from_u8(reader.u8()?).ok_or(Status::InvalidRequest)? {
+            Status::Ok => {}
+            status => return Err(status),
+        }
+        Ok(match command {
+            Command::GetVersion => {
+                let mut firmware = [0; 3];
+                firmware.copy_from_slice(reader.bytes(3)?);
+                Response::Version(VersionInfo {
+                    firmware,
+                    settings_version: reader.u16()?,
+                    max_settings_size: reader.u16()?,
+                })
+            }
+            Command::ReadSettings => {
+                let length = reader.u16()?;
+                let offset = reader.u16()?;
+                let chunk_length = reader.u8()? as usize;
+                if chunk_length > MAX_CHUNK_SIZE {
+                    return Err(Status::InvalidRequest);
+                }
+                Response::Settings {
+                    length,
+                    offset,
+                    data: reader.bytes(chunk_length)?,
+                }
+            }
+            _ => Response::Done,
+        })
+    }
+}
+
+/// What the firmware must do after handling a request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    None,
+    /// The settings were replaced and must be applied to the pipeline,
+    /// then written to flash if `save`
+    Apply {
+        save: bool,
+    },
+    StartCalibration,
+}
+
+/// Controller side of the protocol, holds the settings record being written
+pub struct ConfigServer {
+    staged: [u8; MAX_SETTINGS_SIZE],
+}
+
+impl ConfigServer {
+    pub const fn new() -> Self {
+        ConfigServer {
+            staged: [0; MAX_SETTINGS_SIZE],
+        }
+    }
+
+    /// Handles a request report, returns the response report.
+    ///
+    /// The response to a failed [`Action::Apply`] save is left to the caller,
+    /// see [`Status::SaveFailed`].
+    pub fn handle(
+        &mut self,
+        request: &[u8],
+        settings: &mut Settings,
+        firmware: [u8; 3],
+    ) -> ([u8; REPORT_SIZE], Action) {
+        let command = request.get(1).copied().unwrap_or(0);
+        let mut record = [0; MAX_SETTINGS_SIZE];
+        let (response, action) = match Request::decode(request) {
+            Ok(request) => self.run(request, settings, firmware, &mut record),
+            Err(status) => (Err(status), Action::None),
+        };
+        // Every response fits in a report
+        let report = Response::encode(command, response).unwrap_or([0; REPORT_SIZE]);
+        (report, action)
+    }
+
+    /// `record` holds the encoded settings read by [`Request::ReadSettings`]
+    fn run<'r>(
+        &mut self,
+        request: Request,
+        settings: &mut Settings,
+        firmware: [u8; 3],
+        record: &'r mut [u8; MAX_SETTINGS_SIZE],
+    ) -> (Result<Response<'r>, Status>, Action) {
+        match request {
+            Request::GetVersion => (
+                Ok(Response::Version(VersionInfo {
+                    firmware,
+                    settings_version: SETTINGS_VERSION,
+                    max_settings_size: MAX_SETTINGS_SIZE as u16,
+                })),
+                Action::None,
+            ),
+            Request::ReadSettings { offset } => {
+                (read_chunk(settings, offset, record), Action::None)
+            }
+            Request::WriteSettings { offset, data } => {
+                let offset = offset as usize;
+                match self.staged.get_mut(offset..offset + data.len()) {
+                    Some(staged) => {
+                        staged.copy_from_slice(data);
+                        (Ok(Response::Done), Action::None)
+                    }
+                    None => (Err(Status::InvalidRequest), Action::None),
+                }
+            }
+            Request::CommitSettings { length, save } => {
+                let Some(record) = self.staged.get(..length as usize) else {
+                    return (Err(Status::InvalidRequest), Action::None);
+                };
+                match Settings::decode(record) {
+                    Ok(decoded) => {
+                        *settings = decoded;
+                        (Ok(Response::Done), Action::Apply { save })
+                    }
+                    Err(_) => (Err(Status::InvalidSettings), Action::None),
+                }
+            }
+            Request::StartCalibration => (Ok(Response::Done), Action::StartCalibration),
+        }
+    }
+}
+
+impl Default for ConfigServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Chunk of the encoded `settings` at `offset`, `record` receives the whole record
+fn read_chunk<'a>(
+    settings: &Settings,
+    offset: u16,
+    record: &'a mut [u8; MAX_SETTINGS_SIZE],
+) -> Result<Response<'a>, Status> {
+    let length = settings
+        .encode(record)
+        .map_err(|_| Status::InvalidSettings)?;
+    let start = offset as usize;
+    if start > length {
+        return Err(Status::InvalidRequest);
+    }
+    let end = length.min(start + MAX_CHUNK_SIZE);
+    Ok(Response::Settings {
+        length: length as u16,
+        offset,
+        data: &record[start..end],
+    })
+}
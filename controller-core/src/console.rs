@@ -0,0 +1,833 @@
+//! Line based configuration console, served over the USB serial port
+//!
+//! Commands read and modify a copy of the [`Settings`]: the firmware applies the
+//! result to the pipeline, and only writes it to flash on `save`.
+//!
+//! ```text
+//! state                                     live buttons and axes
+//! raw                                       filtered ADC values
+//! timing                                    measured sampling periods
+//! rate [125 | 250 | 500 | 1000]             poll rate, saved and applied on the next boot
+//! calibration [<axis> <min> <center> <max>]
+//! deadzone <left|right> [<axial|radial|scaled> <inner> <outer> <anti>]
+//! curve <axis> [linear | exp <exponent> | scurve <strength> | custom <x> <y>...]
+//! map <profile> <output> [<binding>]
+//! profile [<index> | count <count> | combo <button>...]
+//! turbo [<button> [<hz>]]                   presses per second while held, 0 to disable
+//! macro [<slot> [none | <trigger> <step>...]]
+//! motion [on | off | rest | <roll|pitch|heading> <axis|none> | range <deg> | deadzone <deg> | toggle <button|none>]
+//! compass [calibrate | reset]               magnetometer calibration and heading
+//! leds [status | left | right | lt | rt]    what the compass LEDs show
+//! save
+//! dfu                                       reboot into the USB bootloader
+//! ```
+//!
+//! A macro step is `<button>+<button>...:<ms>`, `wait:<ms>` to hold nothing, with `@<x>,<y>`
+//! before the `:` to also hold the left stick, e.g. `macro 0 guide a:50 wait:80 a@0,-1:50`.
+use core::fmt::{self, Write};
+use core::str::{FromStr, SplitAsciiWhitespace};
+
+use crate::calibration::AxisCalibration;
+use crate::controller::ControllerState;
+use crate::curve::{CurveTable, ResponseCurve, MAX_CURVE_POINTS};
+use crate::deadzone::{DeadzoneMode, StickDeadzone};
+use crate::heading::MagCalibration;
+use crate::input::{AnalogInput, Button};
+use crate::lights::LedMode;
+use crate::macros::{Macro, MacroStep, MAX_MACROS, MAX_MACRO_STEPS, MAX_TURBO_HZ};
+use crate::motion::Orientation;
+use crate::pipeline::{analog_value, button_value, ADC_MAX_VALUE};
+use crate::remap::{AxisBinding, ButtonBinding, ButtonSet, MAX_PROFILES};
+use crate::schedule::{JitterStats, PollRate};
+use crate::settings::Settings;
+
+/// Longest command line, longer ones are rejected as a whole
+pub const MAX_LINE_LEN: usize = 128;
+
+const HELP: &str = "\
+state | raw | timing | save | dfu\r
+rate [125 | 250 | 500 | 1000]\r
+calibration [<axis> <min> <center> <max>]\r
+deadzone <left|right> [<axial|radial|scaled> <inner> <outer> <anti>]\r
+curve <axis> [linear | exp <exponent> | scurve <strength> | custom <x> <y>...]\r
+map <profile> <output> [none | <button> | <axis> <threshold|inverted> | buttons <-|button> <-|button>]\r
+profile [<index> | count <count> | combo <button>...]\r
+turbo [<button> [<hz>]]\r
+macro [<slot> [none | <trigger> <button>+...|wait[@<x>,<y>]:<ms>...]]\r
+motion [on | off | rest | <roll|pitch|heading> <axis|none> | range <deg> | deadzone <deg> | toggle <button|none>]\r
+compass [calibrate | reset]\r
+leds [status | left | right | lt | rt]\r
+axes: lx ly rx ry lt rt other0 other1\r
+buttons: a b x y lb rb ls rs start back up down left right guide\r
+";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConsoleError {
+    LineTooLong,
+    UnknownCommand,
+    MissingArgument,
+    /// Argument that does not parse, or a name that is not known
+    InvalidArgument,
+    TooManyArguments,
+    /// Arguments parsed but the resulting setting is out of range
+    InvalidValue,
+    /// The output does not fit in the buffer it is written to
+    Output,
+    /// The motion sensor gave no reading
+    NoMotionSensor,
+}
+
+impl From<fmt::Error> for ConsoleError {
+    fn from(_: fmt::Error) -> Self {
+        ConsoleError::Output
+    }
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ConsoleError::LineTooLong => "line too long",
+            ConsoleError::UnknownCommand => "unknown command, try help",
+            ConsoleError::MissingArgument => "missing argument",
+            ConsoleError::InvalidArgument => "invalid argument",
+            ConsoleError::TooManyArguments => "too many arguments",
+            ConsoleError::InvalidValue => "value out of range",
+            ConsoleError::Output => "output truncated",
+            ConsoleError::NoMotionSensor => "no reading from the motion sensor",
+        };
+        f.write_str(message)
+    }
+}
+
+/// Complete command line
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Line {
+    bytes: [u8; MAX_LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    pub fn as_str(&self) -> &str {
+        // Only ASCII bytes are ever pushed
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
+/// Gathers the received bytes into lines
+#[derive(Clone, Copy, Debug)]
+pub struct LineBuffer {
+    line: Line,
+    overflowed: bool,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        LineBuffer {
+            line: Line {
+                bytes: [0; MAX_LINE_LEN],
+                len: 0,
+            },
+            overflowed: false,
+        }
+    }
+
+    /// Feeds one received byte, returns the line it completes if any.
+    ///
+    /// Lines end with CR or LF, empty lines are skipped. Backspace removes the last
+    /// character, other control and non ASCII characters are ignored.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Line, ConsoleError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = self.line;
+                let overflowed = self.overflowed;
+                *self = Self::new();
+                if overflowed {
+                    Some(Err(ConsoleError::LineTooLong))
+                } else {
+                    (line.len > 0).then_some(Ok(line))
+                }
+            }
+            0x08 | 0x7F => {
+                self.line.len = self.line.len.saturating_sub(1);
+                None
+            }
+            b' '..=b'~' => {
+                if self.line.len == MAX_LINE_LEN {
+                    self.overflowed = true;
+                } else {
+                    self.line.bytes[self.line.len] = byte;
+                    self.line.len += 1;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the firmware does once a command ran
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    None,
+    /// The settings changed and must be applied to the pipeline
+    Apply,
+    /// The settings must be written to flash
+    Save,
+    /// Reboot into the system bootloader to update the firmware
+    RebootDfu,
+    /// Collect magnetometer readings until the calibration can be fitted, see
+    /// [`crate::heading::MagCalibrationRoutine`]
+    CalibrateCompass,
+}
+
+/// Live values the commands can read
+pub struct ConsoleContext<'a> {
+    pub settings: &'a mut Settings,
+    pub state: &'a ControllerState,
+    pub raw_values: &'a [u16; AnalogInput::COUNT],
+    /// Smoothed reading of the motion sensor, `None` without one
+    pub orientation: Option<Orientation>,
+    /// Periods measured by the sample timer since boot
+    pub jitter: &'a JitterStats,
+    /// What the LEDs show, not part of the settings
+    pub led_mode: &'a mut LedMode,
+}
+
+/// Runs one command line, writing its output to `out`
+pub fn execute<W: Write>(
+    line: &str,
+    context: &mut ConsoleContext,
+    out: &mut W,
+) -> Result<Action, ConsoleError> {
+    let mut args = Args(line.split_ascii_whitespace());
+    let command = match args.0.next() {
+        Some(command) => command,
+        None => return Ok(Action::None),
+    };
+
+    match command {
+        "help" => {
+            out.write_str(HELP)?;
+            Ok(Action::None)
+        }
+        "state" => args.end(|| show_state(context.state, out)),
+        "raw" => args.end(|| show_raw_values(context.raw_values, out)),
+        "timing" => args.end(|| show_timing(context.jitter, out)),
+        "rate" => rate(&mut args, context.settings, out),
+        "calibration" => calibration(&mut args, context.settings, out),
+        "deadzone" => deadzone(&mut args, context.settings, out),
+        "curve" => curve(&mut args, context.settings, out),
+        "map" => map(&mut args, context.settings, out),
+        "profile" => profile(&mut args, context.settings, out),
+        "turbo" => turbo(&mut args, context.settings, out),
+        "macro" => macro_(&mut args, context.settings, out),
+        "motion" => motion(&mut args, context, out),
+        "compass" => compass(&mut args, context, out),
+        "leds" => leds(&mut args, context.led_mode, out),
+        "save" => args.check_end().map(|_| Action::Save),
+        "dfu" => args.check_end().map(|_| Action::RebootDfu),
+        _ => Err(ConsoleError::UnknownCommand),
+    }
+}
+
+type CommandResult = Result<Action, ConsoleError>;
+
+struct Args<'a>(SplitAsciiWhitespace<'a>);
+
+impl<'a> Args<'a> {
+    fn next(&mut self) -> Result<&'a str, ConsoleError> {
+        self.0.next().ok_or(ConsoleError::MissingArgument)
+    }
+
+    fn parse<T: FromStr>(&mut self) -> Result<T, ConsoleError> {
+        self.next()?
+            .parse()
+            .map_err(|_| ConsoleError::InvalidArgument)
+    }
+
+    fn axis(&mut self) -> Result<AnalogInput, ConsoleError> {
+        AnalogInput::from_name(self.next()?).ok_or(ConsoleError::InvalidArgument)
+    }
+
+    fn button(&mut self) -> Result<Button, ConsoleError> {
+        Button::from_name(self.next()?).ok_or(ConsoleError::InvalidArgument)
+    }
+
+    /// `true` if there is no argument left
+    fn is_empty(&self) -> bool {
+        self.0.clone().next().is_none()
+    }
+
+    fn check_end(&mut self) -> Result<(), ConsoleError> {
+        match self.0.next() {
+            Some(_) => Err(ConsoleError::TooManyArguments),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs a read only command once every argument was consumed
+    fn end(&mut self, show: impl FnOnce() -> fmt::Result) -> CommandResult {
+        self.check_end()?;
+        show()?;
+        Ok(Action::None)
+    }
+}
+
+fn show_state<W: Write>(state: &ControllerState, out: &mut W) -> fmt::Result {
+    out.write_str("buttons:")?;
+    for button in Button::ALL
+        .into_iter()
+        .filter(|button| button_value(state, *button))
+    {
+        write!(out, " {}", button.name())?;
+    }
+    out.write_str("\r\naxes:")?;
+    for input in AnalogInput::ALL {
+        write!(out, " {}={:.3}", input.name(), analog_value(state, input))?;
+    }
+    out.write_str("\r\n")
+}
+
+fn show_raw_values<W: Write>(raw_values: &[u16; AnalogInput::COUNT], out: &mut W) -> fmt::Result {
+    for input in AnalogInput::ALL {
+        write!(out, "{}={} ", input.name(), raw_values[input.index()])?;
+    }
+    out.write_str("\r\n")
+}
+
+fn show_timing<W: Write>(jitter: &JitterStats, out: &mut W) -> fmt::Result {
+    write!(out, "period {}us", jitter.expected_us())?;
+    if let (Some(min), Some(max), Some(mean)) = (jitter.min_us(), jitter.max_us(), jitter.mean_us())
+    {
+        write!(
+            out,
+            " min {} max {} mean {} jitter {} samples {}",
+            min,
+            max,
+            mean,
+            jitter.max_jitter_us(),
+            jitter.count()
+        )?;
+    }
+    out.write_str("\r\n")
+}
+
+fn rate<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    if args.is_empty() {
+        write!(out, "{}\r\n", settings.poll_rate.hz())?;
+        return Ok(Action::None);
+    }
+
+    let rate = PollRate::from_hz(args.parse()?).ok_or(ConsoleError::InvalidValue)?;
+    args.check_end()?;
+    // The sample timer and the USB endpoint are only set up at boot
+    settings.poll_rate = rate;
+    out.write_str("applies on the next boot\r\n")?;
+    Ok(Action::Save)
+}
+
+fn calibration<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    if args.is_empty() {
+        for input in AnalogInput::ALL {
+            let axis = settings.calibration.axis(input);
+            writeln!(
+                out,
+                "{} {} {} {}\r",
+                input.name(),
+                axis.min,
+                axis.center,
+                axis.max
+            )?;
+        }
+        return Ok(Action::None);
+    }
+
+    let input = args.axis()?;
+    let (min, center, max) = (args.parse()?, args.parse()?, args.parse()?);
+    args.check_end()?;
+    if min > center || center > max || max > ADC_MAX_VALUE {
+        return Err(ConsoleError::InvalidValue);
+    }
+    *settings.calibration.axis_mut(input) = AxisCalibration::new(min, center, max);
+    Ok(Action::Apply)
+}
+
+fn deadzone<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    let stick = match args.next()? {
+        "left" => &mut settings.deadzones.left_stick,
+        "right" => &mut settings.deadzones.right_stick,
+        _ => return Err(ConsoleError::InvalidArgument),
+    };
+    if args.is_empty() {
+        let mode = match stick.mode {
+            DeadzoneMode::Axial => "axial",
+            DeadzoneMode::Radial => "radial",
+            DeadzoneMode::ScaledRadial => "scaled",
+        };
+        let (inner, outer, anti) = (stick.inner, stick.outer, stick.anti_deadzone);
+        writeln!(out, "{} {:.3} {:.3} {:.3}\r", mode, inner, outer, anti)?;
+        return Ok(Action::None);
+    }
+
+    let mode = match args.next()? {
+        "axial" => DeadzoneMode::Axial,
+        "radial" => DeadzoneMode::Radial,
+        "scaled" => DeadzoneMode::ScaledRadial,
+        _ => return Err(ConsoleError::InvalidArgument),
+    };
+    let deadzone = StickDeadzone {
+        mode,
+        inner: args.parse()?,
+        outer: args.parse()?,
+        anti_deadzone: args.parse()?,
+    };
+    args.check_end()?;
+    if !deadzone.is_valid() {
+        return Err(ConsoleError::InvalidValue);
+    }
+    *stick = deadzone;
+    Ok(Action::Apply)
+}
+
+fn curve<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    let input = args.axis()?;
+    if args.is_empty() {
+        match settings.curves.axis(input) {
+            ResponseCurve::Linear => out.write_str("linear")?,
+            ResponseCurve::Exponential { exponent } => write!(out, "exp {:.3}", exponent)?,
+            ResponseCurve::SCurve { strength } => write!(out, "scurve {:.3}", strength)?,
+            ResponseCurve::Custom(table) => {
+                out.write_str("custom")?;
+                for (x, y) in table.points() {
+                    write!(out, " {:.3} {:.3}", x, y)?;
+                }
+            }
+        }
+        out.write_str("\r\n")?;
+        return Ok(Action::None);
+    }
+
+    let curve = match args.next()? {
+        "linear" => ResponseCurve::Linear,
+        "exp" => ResponseCurve::Exponential {
+            exponent: args.parse()?,
+        },
+        "scurve" => ResponseCurve::SCurve {
+            strength: args.parse()?,
+        },
+        "custom" => {
+            let mut points = [(0f32, 0f32); MAX_CURVE_POINTS];
+            let mut len = 0;
+            while !args.is_empty() {
+                if len == MAX_CURVE_POINTS {
+                    return Err(ConsoleError::TooManyArguments);
+                }
+                points[len] = (args.parse()?, args.parse()?);
+                len += 1;
+            }
+            match CurveTable::new(&points[..len]) {
+                Some(table) => ResponseCurve::Custom(table),
+                None => return Err(ConsoleError::InvalidValue),
+            }
+        }
+        _ => return Err(ConsoleError::InvalidArgument),
+    };
+    args.check_end()?;
+    if !curve.is_valid() {
+        return Err(ConsoleError::InvalidValue);
+    }
+    *settings.curves.axis_mut(input) = curve;
+    Ok(Action::Apply)
+}
+
+fn map<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    let index: usize = args.parse()?;
+    if index >= MAX_PROFILES {
+        return Err(ConsoleError::InvalidValue);
+    }
+    let profile = &mut settings.profiles.profiles[index];
+    let output = args.next()?;
+
+    if let Some(button) = Button::from_name(output) {
+        if args.is_empty() {
+            show_button_binding(profile.button(button), out)?;
+            return Ok(Action::None);
+        }
+        let binding = parse_button_binding(args)?;
+        args.check_end()?;
+        if !binding.is_valid() {
+            return Err(ConsoleError::InvalidValue);
+        }
+        *profile.button_mut(button) = binding;
+    } else if let Some(input) = AnalogInput::from_name(output) {
+        if args.is_empty() {
+            show_axis_binding(profile.axis(input), out)?;
+            return Ok(Action::None);
+        }
+        let binding = parse_axis_binding(args)?;
+        args.check_end()?;
+        *profile.axis_mut(input) = binding;
+    } else {
+        return Err(ConsoleError::InvalidArgument);
+    }
+    Ok(Action::Apply)
+}
+
+fn parse_button_binding(args: &mut Args) -> Result<ButtonBinding, ConsoleError> {
+    let source = args.next()?;
+    if source == "none" {
+        return Ok(ButtonBinding::Unbound);
+    }
+    if let Some(button) = Button::from_name(source) {
+        return Ok(ButtonBinding::Button(button));
+    }
+    let input = AnalogInput::from_name(source).ok_or(ConsoleError::InvalidArgument)?;
+    Ok(ButtonBinding::Axis {
+        input,
+        threshold: args.parse()?,
+    })
+}
+
+fn parse_axis_binding(args: &mut Args) -> Result<AxisBinding, ConsoleError> {
+    let optional_button = |name: &str| match name {
+        "-" => Ok(None),
+        name => Button::from_name(name)
+            .map(Some)
+            .ok_or(ConsoleError::InvalidArgument),
+    };
+
+    match args.next()? {
+        "none" => Ok(AxisBinding::Unbound),
+        "buttons" => Ok(AxisBinding::Buttons {
+            negative: optional_button(args.next()?)?,
+            positive: optional_button(args.next()?)?,
+        }),
+        source => {
+            let input = AnalogInput::from_name(source).ok_or(ConsoleError::InvalidArgument)?;
+            let inverted = match args.0.next() {
+                None => false,
+                Some("inverted") => true,
+                Some(_) => return Err(ConsoleError::InvalidArgument),
+            };
+            Ok(AxisBinding::Axis { input, inverted })
+        }
+    }
+}
+
+fn show_button_binding<W: Write>(binding: &ButtonBinding, out: &mut W) -> fmt::Result {
+    match binding {
+        ButtonBinding::Unbound => out.write_str("none")?,
+        ButtonBinding::Button(button) => out.write_str(button.name())?,
+        ButtonBinding::Axis { input, threshold } => {
+            write!(out, "{} {:.3}", input.name(), threshold)?
+        }
+    }
+    out.write_str("\r\n")
+}
+
+fn show_axis_binding<W: Write>(binding: &AxisBinding, out: &mut W) -> fmt::Result {
+    let name = |button: Option<Button>| button.map_or("-", Button::name);
+    match binding {
+        AxisBinding::Unbound => out.write_str("none")?,
+        AxisBinding::Axis { input, inverted } => {
+            out.write_str(input.name())?;
+            if *inverted {
+                out.write_str(" inverted")?;
+            }
+        }
+        AxisBinding::Buttons { negative, positive } => {
+            write!(out, "buttons {} {}", name(*negative), name(*positive))?
+        }
+    }
+    out.write_str("\r\n")
+}
+
+fn profile<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    let profiles = &mut settings.profiles;
+    if args.is_empty() {
+        write!(
+            out,
+            "profile {} of {}, combo",
+            profiles.active, profiles.count
+        )?;
+        for button in profiles.switch_combo.iter() {
+            write!(out, " {}", button.name())?;
+        }
+        out.write_str("\r\n")?;
+        return Ok(Action::None);
+    }
+
+    match args.next()? {
+        "count" => {
+            let count: u8 = args.parse()?;
+            args.check_end()?;
+            if !(1..=MAX_PROFILES as u8).contains(&count) {
+                return Err(ConsoleError::InvalidValue);
+            }
+            profiles.count = count;
+            profiles.active = profiles.active.min(count - 1);
+        }
+        "combo" => {
+            let mut combo = ButtonSet::EMPTY;
+            while !args.is_empty() {
+                combo = combo.with(args.button()?);
+            }
+            profiles.switch_combo = combo;
+        }
+        index => {
+            let index: u8 = index.parse().map_err(|_| ConsoleError::InvalidArgument)?;
+            args.check_end()?;
+            if index >= profiles.count {
+                return Err(ConsoleError::InvalidValue);
+            }
+            profiles.active = index;
+        }
+    }
+    Ok(Action::Apply)
+}
+
+fn turbo<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    let turbo = &mut settings.turbo;
+    if args.is_empty() {
+        let mut separator = "";
+        for button in Button::ALL
+            .into_iter()
+            .filter(|button| turbo.button(*button) > 0)
+        {
+            write!(
+                out,
+                "{}{} {}",
+                separator,
+                button.name(),
+                turbo.button(button)
+            )?;
+            separator = " ";
+        }
+        out.write_str(if separator.is_empty() {
+            "none\r\n"
+        } else {
+            "\r\n"
+        })?;
+        return Ok(Action::None);
+    }
+
+    let button = args.button()?;
+    if args.is_empty() {
+        write!(out, "{}\r\n", turbo.button(button))?;
+        return Ok(Action::None);
+    }
+    let rate: u8 = args.parse()?;
+    args.check_end()?;
+    if rate > MAX_TURBO_HZ {
+        return Err(ConsoleError::InvalidValue);
+    }
+    *turbo.button_mut(button) = rate;
+    Ok(Action::Apply)
+}
+
+fn macro_<W: Write>(args: &mut Args, settings: &mut Settings, out: &mut W) -> CommandResult {
+    let slots = &mut settings.macros.slots;
+    if args.is_empty() {
+        for (slot, macro_) in slots.iter().enumerate() {
+            write!(out, "{} ", slot)?;
+            show_macro(macro_.as_ref(), out)?;
+        }
+        return Ok(Action::None);
+    }
+
+    let slot: usize = args.parse()?;
+    if slot >= MAX_MACROS {
+        return Err(ConsoleError::InvalidValue);
+    }
+    if args.is_empty() {
+        show_macro(slots[slot].as_ref(), out)?;
+        return Ok(Action::None);
+    }
+
+    let trigger = match args.next()? {
+        "none" => {
+            args.check_end()?;
+            slots[slot] = None;
+            return Ok(Action::Apply);
+        }
+        name => Button::from_name(name).ok_or(ConsoleError::InvalidArgument)?,
+    };
+    let mut steps = [MacroStep::wait(0); MAX_MACRO_STEPS];
+    let mut len = 0;
+    while !args.is_empty() {
+        if len == MAX_MACRO_STEPS {
+            return Err(ConsoleError::TooManyArguments);
+        }
+        steps[len] = parse_macro_step(args.next()?)?;
+        len += 1;
+    }
+    if len == 0 {
+        return Err(ConsoleError::MissingArgument);
+    }
+    slots[slot] = Some(Macro::new(trigger, &steps[..len]).ok_or(ConsoleError::InvalidValue)?);
+    Ok(Action::Apply)
+}
+
+fn show_macro<W: Write>(macro_: Option<&Macro>, out: &mut W) -> fmt::Result {
+    let Some(macro_) = macro_ else {
+        return out.write_str("none\r\n");
+    };
+    out.write_str(macro_.trigger.name())?;
+    for step in macro_.steps() {
+        out.write_str(" ")?;
+        if step.buttons.is_empty() {
+            out.write_str("wait")?;
+        }
+        for (index, button) in step.buttons.iter().enumerate() {
+            if index > 0 {
+                out.write_str("+")?;
+            }
+            out.write_str(button.name())?;
+        }
+        if let Some((x, y)) = step.left_stick {
+            write!(out, "@{},{}", x, y)?;
+        }
+        write!(out, ":{}", step.duration_ms)?;
+    }
+    out.write_str("\r\n")
+}
+
+/// `<button>+<button>...:<ms>` or `wait:<ms>`, with an optional `@<x>,<y>` stick before the `:`
+fn parse_macro_step(token: &str) -> Result<MacroStep, ConsoleError> {
+    let (controls, duration) = token.split_once(':').ok_or(ConsoleError::InvalidArgument)?;
+    let (buttons, stick) = match controls.split_once('@') {
+        Some((buttons, stick)) => (buttons, Some(stick)),
+        None => (controls, None),
+    };
+
+    let mut step = MacroStep::wait(parse_token(duration)?);
+    if buttons != "wait" {
+        for name in buttons.split('+') {
+            let button = Button::from_name(name).ok_or(ConsoleError::InvalidArgument)?;
+            step.buttons = step.buttons.with(button);
+        }
+    }
+    if let Some(stick) = stick {
+        let (x, y) = stick.split_once(',').ok_or(ConsoleError::InvalidArgument)?;
+        step.left_stick = Some((parse_token(x)?, parse_token(y)?));
+    }
+    Ok(step)
+}
+
+fn parse_token<T: FromStr>(token: &str) -> Result<T, ConsoleError> {
+    token.parse().map_err(|_| ConsoleError::InvalidArgument)
+}
+
+fn motion<W: Write>(args: &mut Args, context: &mut ConsoleContext, out: &mut W) -> CommandResult {
+    let motion = &mut context.settings.motion;
+    let name = |axis: Option<AnalogInput>| axis.map_or("none", AnalogInput::name);
+    if args.is_empty() {
+        write!(
+            out,
+            "{} roll {} pitch {} heading {} range {:.1} deadzone {:.1} rest {:.1} {:.1} toggle {}\r\n",
+            if motion.enabled { "on" } else { "off" },
+            name(motion.roll_axis),
+            name(motion.pitch_axis),
+            name(motion.heading_axis),
+            motion.range,
+            motion.deadzone,
+            motion.rest_roll,
+            motion.rest_pitch,
+            motion.toggle.map_or("none", Button::name),
+        )?;
+        if let Some(orientation) = context.orientation {
+            write!(
+                out,
+                "orientation {:.1} {:.1} {:.1}\r\n",
+                orientation.roll, orientation.pitch, orientation.heading
+            )?;
+        }
+        return Ok(Action::None);
+    }
+
+    let mut updated = *motion;
+    match args.next()? {
+        "on" => updated.enabled = true,
+        "off" => updated.enabled = false,
+        "rest" => {
+            let orientation = context.orientation.ok_or(ConsoleError::NoMotionSensor)?;
+            updated.rest_roll = orientation.roll;
+            updated.rest_pitch = orientation.pitch;
+        }
+        "range" => updated.range = args.parse()?,
+        "deadzone" => updated.deadzone = args.parse()?,
+        "toggle" => {
+            updated.toggle = match args.next()? {
+                "none" => None,
+                name => Some(Button::from_name(name).ok_or(ConsoleError::InvalidArgument)?),
+            }
+        }
+        angle => {
+            let axis = match angle {
+                "roll" => &mut updated.roll_axis,
+                "pitch" => &mut updated.pitch_axis,
+                "heading" => &mut updated.heading_axis,
+                _ => return Err(ConsoleError::InvalidArgument),
+            };
+            *axis = match args.next()? {
+                "none" => None,
+                name => Some(AnalogInput::from_name(name).ok_or(ConsoleError::InvalidArgument)?),
+            };
+        }
+    }
+    args.check_end()?;
+    if !updated.is_valid() {
+        return Err(ConsoleError::InvalidValue);
+    }
+    *motion = updated;
+    Ok(Action::Apply)
+}
+
+fn compass<W: Write>(args: &mut Args, context: &mut ConsoleContext, out: &mut W) -> CommandResult {
+    let calibration = &mut context.settings.mag_calibration;
+    if args.is_empty() {
+        let [x, y, z] = calibration.offset;
+        write!(out, "offset {:.3} {:.3} {:.3} matrix", x, y, z)?;
+        for value in calibration.matrix.iter().flatten() {
+            write!(out, " {:.3}", value)?;
+        }
+        out.write_str("\r\n")?;
+        if let Some(orientation) = context.orientation {
+            write!(out, "heading {:.1}\r\n", orientation.heading)?;
+        }
+        return Ok(Action::None);
+    }
+
+    match args.next()? {
+        "calibrate" => {
+            args.check_end()?;
+            context.orientation.ok_or(ConsoleError::NoMotionSensor)?;
+            out.write_str("turn the controller in a figure eight until calibrated\r\n")?;
+            Ok(Action::CalibrateCompass)
+        }
+        "reset" => {
+            args.check_end()?;
+            *calibration = MagCalibration::IDENTITY;
+            Ok(Action::Apply)
+        }
+        _ => Err(ConsoleError::InvalidArgument),
+    }
+}
+
+fn leds<W: Write>(args: &mut Args, mode: &mut LedMode, out: &mut W) -> CommandResult {
+    if args.is_empty() {
+        write!(out, "{}\r\n", mode.name())?;
+        return Ok(Action::None);
+    }
+
+    let selected = LedMode::from_name(args.next()?).ok_or(ConsoleError::InvalidArgument)?;
+    args.check_end()?;
+    *mode = selected;
+    Ok(Action::None)
+}
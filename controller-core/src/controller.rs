@@ -1,3 +1,6 @@
+//! Logical state of the controller, independent of the pins it is read from
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ControllerState {
     pub up: bool,
     pub down: bool,
@@ -14,6 +17,7 @@ pub struct ControllerState {
     pub b: bool,
     pub x: bool,
     pub y: bool,
+    /// Sticks in `-1.0..=1.0`, `x` pointing right and `y` up as on the XInput report
     pub left_thumb_x: f32,
     pub left_thumb_y: f32,
     pub right_thumb_x: f32,
@@ -53,3 +57,9 @@ impl ControllerState {
         }
     }
 }
+
+impl Default for ControllerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
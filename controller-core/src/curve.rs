@@ -0,0 +1,143 @@
+//! Response curves, reshaping each axis after the deadzones
+//!
+//! Curves map the magnitude of an axis in `0.0..=1.0`, the sign is kept,
+//! so both halves of a stick axis respond the same way.
+use crate::input::AnalogInput;
+use libm::powf;
+
+/// Largest number of points in a [`CurveTable`]
+pub const MAX_CURVE_POINTS: usize = 8;
+
+/// User defined piecewise-linear curve.
+///
+/// The curve goes through `(0, 0)`, the points in increasing `x` order, then `(1, 1)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurveTable {
+    len: usize,
+    points: [(f32, f32); MAX_CURVE_POINTS],
+}
+
+impl CurveTable {
+    /// Returns `None` if there are too many points, if they are not strictly
+    /// increasing in `x`, or if a coordinate is out of `0.0..=1.0`
+    pub fn new(points: &[(f32, f32)]) -> Option<Self> {
+        if points.len() > MAX_CURVE_POINTS {
+            return None;
+        }
+
+        let mut previous_x = 0f32;
+        for (index, (x, y)) in points.iter().enumerate() {
+            let increasing = if index == 0 {
+                *x >= previous_x
+            } else {
+                *x > previous_x
+            };
+            if !increasing || *x > 1f32 || !(0f32..=1f32).contains(y) {
+                return None;
+            }
+            previous_x = *x;
+        }
+
+        let mut table = CurveTable {
+            len: points.len(),
+            points: [(0f32, 0f32); MAX_CURVE_POINTS],
+        };
+        table.points[..points.len()].copy_from_slice(points);
+        Some(table)
+    }
+
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points[..self.len]
+    }
+
+    fn apply(&self, magnitude: f32) -> f32 {
+        let mut start = (0f32, 0f32);
+        for end in self.points().iter().copied().chain([(1f32, 1f32)]) {
+            if magnitude <= end.0 {
+                let width = end.0 - start.0;
+                if width <= 0f32 {
+                    return end.1;
+                }
+                return start.1 + (end.1 - start.1) * (magnitude - start.0) / width;
+            }
+            start = end;
+        }
+        1f32
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// `magnitude ^ exponent`, an exponent above 1 gives finer control near the center
+    Exponential {
+        exponent: f32,
+    },
+    /// Blends linear with a smoothstep, slow near the center and near full deflection.
+    /// `strength` goes from 0 (linear) to 1 (smoothstep).
+    SCurve {
+        strength: f32,
+    },
+    Custom(CurveTable),
+}
+
+impl ResponseCurve {
+    /// Quadratic preset, for precise small steering corrections
+    pub const EXPONENTIAL: ResponseCurve = ResponseCurve::Exponential { exponent: 2f32 };
+
+    /// Half smoothstep preset, softens both the center and the end of the travel
+    pub const S_CURVE: ResponseCurve = ResponseCurve::SCurve { strength: 0.5f32 };
+
+    /// Applies the curve to an axis value in `-1.0..=1.0`
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = value.abs().min(1f32);
+        let shaped = match self {
+            ResponseCurve::Linear => magnitude,
+            ResponseCurve::Exponential { exponent } => powf(magnitude, *exponent),
+            ResponseCurve::SCurve { strength } => {
+                let smooth = magnitude * magnitude * (3f32 - 2f32 * magnitude);
+                magnitude + (smooth - magnitude) * strength
+            }
+            ResponseCurve::Custom(table) => table.apply(magnitude),
+        };
+        shaped.copysign(value)
+    }
+
+    /// `true` if the parameters keep the curve going from 0 to 1
+    pub fn is_valid(&self) -> bool {
+        match self {
+            ResponseCurve::Linear | ResponseCurve::Custom(_) => true,
+            ResponseCurve::Exponential { exponent } => *exponent > 0f32 && exponent.is_finite(),
+            ResponseCurve::SCurve { strength } => (0f32..=1f32).contains(strength),
+        }
+    }
+}
+
+/// Curve of every analog input, indexed by [`AnalogInput::index`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResponseCurves {
+    pub axes: [ResponseCurve; AnalogInput::COUNT],
+}
+
+impl ResponseCurves {
+    pub const fn new() -> Self {
+        ResponseCurves {
+            axes: [ResponseCurve::Linear; AnalogInput::COUNT],
+        }
+    }
+
+    pub fn axis(&self, input: AnalogInput) -> &ResponseCurve {
+        &self.axes[input.index()]
+    }
+
+    pub fn axis_mut(&mut self, input: AnalogInput) -> &mut ResponseCurve {
+        &mut self.axes[input.index()]
+    }
+}
+
+impl Default for ResponseCurves {
+    fn default() -> Self {
+        Self::new()
+    }
+}
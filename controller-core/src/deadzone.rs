@@ -0,0 +1,136 @@
+//! Stick deadzones, applied to each `x`/`y` pair after calibration
+use libm::sqrtf;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeadzoneMode {
+    /// Each axis has its own deadzone, snaps to the axes near the center
+    Axial,
+    /// Zero inside a circle, unchanged outside of it
+    Radial,
+    /// Zero inside a circle, the magnitude is rescaled so there is no jump at its edge
+    ScaledRadial,
+}
+
+impl DeadzoneMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(DeadzoneMode::Axial),
+            1 => Some(DeadzoneMode::Radial),
+            2 => Some(DeadzoneMode::ScaledRadial),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Deadzone of one stick, every size is a fraction of the full deflection
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StickDeadzone {
+    pub mode: DeadzoneMode,
+    /// Radius around the center reported as rest
+    pub inner: f32,
+    /// Width of the ring at the edge reported as full deflection
+    pub outer: f32,
+    /// Smallest magnitude reported out of the inner deadzone,
+    /// compensates the deadzone the game applies on top of ours.
+    /// Ignored in [`DeadzoneMode::Radial`].
+    pub anti_deadzone: f32,
+}
+
+impl StickDeadzone {
+    /// Passes values through unchanged
+    pub const NONE: StickDeadzone = StickDeadzone {
+        mode: DeadzoneMode::Axial,
+        inner: 0f32,
+        outer: 0f32,
+        anti_deadzone: 0f32,
+    };
+
+    /// Hides the drift of a worn potentiometer without noticeable loss of precision
+    pub const DEFAULT: StickDeadzone = StickDeadzone {
+        mode: DeadzoneMode::ScaledRadial,
+        inner: 0.05f32,
+        outer: 0.02f32,
+        anti_deadzone: 0f32,
+    };
+
+    /// `true` if the sizes leave some travel between the inner and outer deadzones
+    pub fn is_valid(&self) -> bool {
+        let in_range = |value: f32| (0f32..1f32).contains(&value);
+        in_range(self.inner)
+            && in_range(self.outer)
+            && in_range(self.anti_deadzone)
+            && self.inner + self.outer < 1f32
+    }
+
+    /// Applies the deadzone to a stick position, each axis in `-1.0..=1.0`
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        match self.mode {
+            DeadzoneMode::Axial => (
+                self.rescale(x.abs()).copysign(x),
+                self.rescale(y.abs()).copysign(y),
+            ),
+            DeadzoneMode::Radial => {
+                let magnitude = sqrtf(x * x + y * y);
+                if magnitude <= self.inner {
+                    (0f32, 0f32)
+                } else if magnitude >= 1f32 - self.outer {
+                    (x / magnitude, y / magnitude)
+                } else {
+                    (x, y)
+                }
+            }
+            DeadzoneMode::ScaledRadial => {
+                let magnitude = sqrtf(x * x + y * y);
+                if magnitude <= self.inner {
+                    return (0f32, 0f32);
+                }
+                let scale = self.rescale(magnitude) / magnitude;
+                (x * scale, y * scale)
+            }
+        }
+    }
+
+    /// Maps a magnitude from `inner..=1 - outer` to `anti_deadzone..=1`
+    fn rescale(&self, magnitude: f32) -> f32 {
+        let full = 1f32 - self.outer;
+        if magnitude <= self.inner {
+            0f32
+        } else if magnitude >= full {
+            1f32
+        } else {
+            let travel = (magnitude - self.inner) / (full - self.inner);
+            self.anti_deadzone + (1f32 - self.anti_deadzone) * travel
+        }
+    }
+}
+
+impl Default for StickDeadzone {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deadzones {
+    pub left_stick: StickDeadzone,
+    pub right_stick: StickDeadzone,
+}
+
+impl Deadzones {
+    pub const fn new() -> Self {
+        Deadzones {
+            left_stick: StickDeadzone::DEFAULT,
+            right_stick: StickDeadzone::DEFAULT,
+        }
+    }
+}
+
+impl Default for Deadzones {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,152 @@
+//! Debouncing of the buttons, applied to the GPIO states before anything else
+//!
+//! Switch contacts bounce for a few milliseconds when pressed or released,
+//! sampled at 1 kHz a single press can look like several.
+use crate::input::Button;
+use crate::schedule::PollRate;
+
+/// Debounce time of [`ButtonDebounce::default`], enough for common tactile switches
+pub const DEFAULT_DEBOUNCE_MS: u8 = 5;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DebounceMode {
+    /// Reports the first edge immediately, then ignores the switch for the debounce time.
+    /// No added latency, but a glitch on the line is reported as a press.
+    #[default]
+    Eager,
+    /// Reports a change once the switch kept its new state for the debounce time.
+    /// Adds the debounce time as latency, ignores glitches.
+    Deferred,
+}
+
+impl DebounceMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(DebounceMode::Eager),
+            1 => Some(DebounceMode::Deferred),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            DebounceMode::Eager => 0,
+            DebounceMode::Deferred => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ButtonDebounce {
+    pub mode: DebounceMode,
+    /// 0 disables the debouncing of the button
+    pub time_ms: u8,
+}
+
+impl ButtonDebounce {
+    pub const fn new(mode: DebounceMode, time_ms: u8) -> Self {
+        ButtonDebounce { mode, time_ms }
+    }
+
+    /// Debounce time in samples at `rate`
+    pub fn samples(&self, rate: PollRate) -> u32 {
+        match self.time_ms {
+            0 => 0,
+            time_ms => rate.samples(time_ms as u32),
+        }
+    }
+}
+
+impl Default for ButtonDebounce {
+    fn default() -> Self {
+        Self::new(DebounceMode::Eager, DEFAULT_DEBOUNCE_MS)
+    }
+}
+
+/// Debouncing of every button, indexed by [`Button::index`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ButtonDebounces {
+    pub buttons: [ButtonDebounce; Button::COUNT],
+}
+
+impl ButtonDebounces {
+    pub const fn new() -> Self {
+        ButtonDebounces {
+            buttons: [ButtonDebounce::new(DebounceMode::Eager, DEFAULT_DEBOUNCE_MS); Button::COUNT],
+        }
+    }
+
+    /// Passes every switch state through
+    pub const fn disabled() -> Self {
+        ButtonDebounces {
+            buttons: [ButtonDebounce::new(DebounceMode::Eager, 0); Button::COUNT],
+        }
+    }
+
+    pub fn button(&self, button: Button) -> &ButtonDebounce {
+        &self.buttons[button.index()]
+    }
+
+    pub fn button_mut(&mut self, button: Button) -> &mut ButtonDebounce {
+        &mut self.buttons[button.index()]
+    }
+}
+
+impl Default for ButtonDebounces {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debounces the successive samples of one button
+#[derive(Clone, Copy, Debug)]
+pub struct Debouncer {
+    mode: DebounceMode,
+    samples: u32,
+    /// Debounced state
+    pressed: bool,
+    /// Eager: samples left to ignore. Deferred: samples the switch differed from `pressed`.
+    counter: u32,
+}
+
+impl Debouncer {
+    /// Debounces over `samples` samples, 0 passes the switch state through
+    pub const fn new(mode: DebounceMode, samples: u32) -> Self {
+        Debouncer {
+            mode,
+            samples,
+            pressed: false,
+            counter: 0,
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Feeds one sample of the switch, returns the debounced state
+    pub fn update(&mut self, pressed: bool) -> bool {
+        match self.mode {
+            DebounceMode::Eager => {
+                if self.counter > 0 {
+                    self.counter -= 1;
+                } else if pressed != self.pressed {
+                    self.pressed = pressed;
+                    self.counter = self.samples;
+                }
+            }
+            DebounceMode::Deferred => {
+                if pressed == self.pressed {
+                    self.counter = 0;
+                } else {
+                    self.counter += 1;
+                    if self.counter >= self.samples {
+                        self.pressed = pressed;
+                        self.counter = 0;
+                    }
+                }
+            }
+        }
+        self.pressed
+    }
+}
@@ -0,0 +1,126 @@
+//! D-pad SOCD (simultaneous opposite cardinal directions) resolution and hat switch value
+use crate::controller::ControllerState;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SocdMode {
+    /// Opposite directions cancel each other
+    #[default]
+    Neutral,
+    /// The direction pressed last wins, releasing it goes back to the other one
+    LastWins,
+    /// Up wins over down, left and right cancel each other
+    UpPriority,
+}
+
+impl SocdMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(SocdMode::Neutral),
+            1 => Some(SocdMode::LastWins),
+            2 => Some(SocdMode::UpPriority),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            SocdMode::Neutral => 0,
+            SocdMode::LastWins => 1,
+            SocdMode::UpPriority => 2,
+        }
+    }
+}
+
+/// Hat switch value reported while no direction is held, outside of the logical range
+pub const HAT_NEUTRAL: u8 = 8;
+
+/// One d-pad axis, remembering the last pressed direction for [`SocdMode::LastWins`]
+#[derive(Clone, Copy, Debug, Default)]
+struct AxisHistory {
+    negative_was_pressed: bool,
+    positive_was_pressed: bool,
+    positive_last: bool,
+}
+
+impl AxisHistory {
+    /// Returns the resolved `(negative, positive)` pair, at most one of them set
+    fn resolve(&mut self, mode: SocdMode, negative: bool, positive: bool) -> (bool, bool) {
+        let negative_edge = negative && !self.negative_was_pressed;
+        let positive_edge = positive && !self.positive_was_pressed;
+        self.negative_was_pressed = negative;
+        self.positive_was_pressed = positive;
+        if negative_edge != positive_edge {
+            self.positive_last = positive_edge;
+        }
+
+        if !(negative && positive) {
+            return (negative, positive);
+        }
+        match mode {
+            SocdMode::Neutral => (false, false),
+            SocdMode::LastWins if negative_edge && positive_edge => (false, false),
+            SocdMode::LastWins => (!self.positive_last, self.positive_last),
+            // Only the vertical axis has a priority, see `SocdResolver::resolve`
+            SocdMode::UpPriority => (false, false),
+        }
+    }
+}
+
+/// Cleans the d-pad directions of the state so opposite directions are never both held
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SocdResolver {
+    mode: SocdMode,
+    vertical: AxisHistory,
+    horizontal: AxisHistory,
+}
+
+impl SocdResolver {
+    pub fn new(mode: SocdMode) -> Self {
+        SocdResolver {
+            mode,
+            vertical: AxisHistory::default(),
+            horizontal: AxisHistory::default(),
+        }
+    }
+
+    pub fn mode(&self) -> SocdMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: SocdMode) {
+        self.mode = mode;
+    }
+
+    /// Resolves the raw directions read into `controller_state`, once per sample
+    pub fn resolve(&mut self, controller_state: &mut ControllerState) {
+        let up_priority = self.mode == SocdMode::UpPriority && controller_state.up;
+        (controller_state.down, controller_state.up) =
+            self.vertical
+                .resolve(self.mode, controller_state.down, controller_state.up);
+        if up_priority {
+            (controller_state.down, controller_state.up) = (false, true);
+        }
+
+        (controller_state.left, controller_state.right) =
+            self.horizontal
+                .resolve(self.mode, controller_state.left, controller_state.right);
+    }
+}
+
+/// Hat switch value of the d-pad, `0` is up then clockwise in 45° steps,
+/// [`HAT_NEUTRAL`] when released. Opposite directions held together cancel out.
+pub fn hat_value(controller_state: &ControllerState) -> u8 {
+    let vertical = controller_state.up as i8 - controller_state.down as i8;
+    let horizontal = controller_state.right as i8 - controller_state.left as i8;
+    match (vertical, horizontal) {
+        (1, 0) => 0,
+        (1, 1) => 1,
+        (0, 1) => 2,
+        (-1, 1) => 3,
+        (-1, 0) => 4,
+        (-1, -1) => 5,
+        (0, -1) => 6,
+        (1, -1) => 7,
+        _ => HAT_NEUTRAL,
+    }
+}
@@ -0,0 +1,172 @@
+//! Noise filters, applied to the raw ADC values before the calibration
+//!
+//! The ADCs of the F303 have no hardware oversampling, averaging is done over
+//! consecutive samples instead.
+use crate::input::AnalogInput;
+use crate::pipeline::ADC_MAX_VALUE;
+use core::f32::consts::PI;
+use libm::roundf;
+
+/// Largest window of [`AnalogFilter::Average`]
+pub const MAX_AVERAGE_SAMPLES: usize = 16;
+
+/// Cutoff of the speed estimate of [`AnalogFilter::OneEuro`], as in the reference implementation
+const ONE_EURO_DERIVATIVE_CUTOFF: f32 = 1f32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AnalogFilter {
+    #[default]
+    None,
+    /// Mean of the last `samples` values, in `1..=MAX_AVERAGE_SAMPLES`
+    Average { samples: u8 },
+    /// Exponential moving average, `alpha` is the weight of the new value in `0.0..=1.0`
+    Exponential { alpha: f32 },
+    /// Low pass whose cutoff rises with the speed of the input: smooth at rest,
+    /// no lag on fast flicks. `min_cutoff` is in Hz, `beta` scales the speed in
+    /// full travels per second.
+    // see https://gery.casiez.net/1euro/
+    OneEuro { min_cutoff: f32, beta: f32 },
+    /// Keeps the previous value until the input moved more than `threshold` ADC counts
+    Hysteresis { threshold: u16 },
+}
+
+impl AnalogFilter {
+    /// Preset for the sticks, steady at rest and following flicks within a few samples
+    pub const ONE_EURO: AnalogFilter = AnalogFilter::OneEuro {
+        min_cutoff: 1f32,
+        beta: 5f32,
+    };
+
+    /// `true` if the parameters are in range
+    pub fn is_valid(&self) -> bool {
+        match self {
+            AnalogFilter::None => true,
+            AnalogFilter::Average { samples } => {
+                (1..=MAX_AVERAGE_SAMPLES).contains(&(*samples as usize))
+            }
+            AnalogFilter::Exponential { alpha } => (0f32..=1f32).contains(alpha),
+            AnalogFilter::OneEuro { min_cutoff, beta } => {
+                *min_cutoff > 0f32 && min_cutoff.is_finite() && *beta >= 0f32 && beta.is_finite()
+            }
+            AnalogFilter::Hysteresis { threshold } => *threshold <= ADC_MAX_VALUE,
+        }
+    }
+}
+
+/// Filter of every analog input, indexed by [`AnalogInput::index`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalogFilters {
+    pub axes: [AnalogFilter; AnalogInput::COUNT],
+}
+
+impl AnalogFilters {
+    pub const fn new() -> Self {
+        AnalogFilters {
+            axes: [AnalogFilter::None; AnalogInput::COUNT],
+        }
+    }
+
+    pub fn axis(&self, input: AnalogInput) -> &AnalogFilter {
+        &self.axes[input.index()]
+    }
+
+    pub fn axis_mut(&mut self, input: AnalogInput) -> &mut AnalogFilter {
+        &mut self.axes[input.index()]
+    }
+}
+
+impl Default for AnalogFilters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs an [`AnalogFilter`] on the successive values of one input
+#[derive(Clone, Copy, Debug)]
+pub struct FilterState {
+    filter: AnalogFilter,
+    /// Last values for [`AnalogFilter::Average`], `next` is the oldest one
+    history: [u16; MAX_AVERAGE_SAMPLES],
+    len: usize,
+    next: usize,
+    /// Last output, `None` before the first value
+    value: Option<f32>,
+    /// Filtered speed of [`AnalogFilter::OneEuro`], in full travels per second
+    speed: f32,
+}
+
+impl FilterState {
+    pub const fn new(filter: AnalogFilter) -> Self {
+        FilterState {
+            filter,
+            history: [0; MAX_AVERAGE_SAMPLES],
+            len: 0,
+            next: 0,
+            value: None,
+            speed: 0f32,
+        }
+    }
+
+    pub fn filter(&self) -> &AnalogFilter {
+        &self.filter
+    }
+
+    /// Forgets the previous values, the next one goes through unchanged
+    pub fn reset(&mut self) {
+        *self = Self::new(self.filter);
+    }
+
+    /// Filters the next raw value, `period_s` is the time since the previous one
+    pub fn update(&mut self, raw: u16, period_s: f32) -> u16 {
+        let output = match self.filter {
+            AnalogFilter::None => raw as f32,
+            AnalogFilter::Average { samples } => self.average(raw, samples as usize),
+            AnalogFilter::Exponential { alpha } => match self.value {
+                Some(previous) => previous + (raw as f32 - previous) * alpha,
+                None => raw as f32,
+            },
+            AnalogFilter::OneEuro { min_cutoff, beta } => {
+                self.one_euro(raw, period_s, min_cutoff, beta)
+            }
+            AnalogFilter::Hysteresis { threshold } => match self.value {
+                Some(previous) if (raw as f32 - previous).abs() <= threshold as f32 => previous,
+                _ => raw as f32,
+            },
+        };
+        self.value = Some(output);
+        roundf(output).clamp(0f32, ADC_MAX_VALUE as f32) as u16
+    }
+
+    fn average(&mut self, raw: u16, samples: usize) -> f32 {
+        let samples = samples.clamp(1, MAX_AVERAGE_SAMPLES);
+        self.history[self.next] = raw;
+        self.next = (self.next + 1) % samples;
+        self.len = (self.len + 1).min(samples);
+        let sum: u32 = self.history[..self.len]
+            .iter()
+            .map(|value| *value as u32)
+            .sum();
+        sum as f32 / self.len as f32
+    }
+
+    fn one_euro(&mut self, raw: u16, period_s: f32, min_cutoff: f32, beta: f32) -> f32 {
+        // Normalized to the full travel, so `beta` does not depend on the ADC resolution
+        let position = raw as f32 / ADC_MAX_VALUE as f32;
+        let previous = match self.value {
+            Some(previous) if period_s > 0f32 => previous / ADC_MAX_VALUE as f32,
+            _ => return raw as f32,
+        };
+
+        let speed = (position - previous) / period_s;
+        self.speed += (speed - self.speed) * smoothing(ONE_EURO_DERIVATIVE_CUTOFF, period_s);
+        let cutoff = min_cutoff + beta * self.speed.abs();
+        let filtered = previous + (position - previous) * smoothing(cutoff, period_s);
+        filtered * ADC_MAX_VALUE as f32
+    }
+}
+
+/// Weight of the new value of a first order low pass at `cutoff_hz`
+fn smoothing(cutoff_hz: f32, period_s: f32) -> f32 {
+    let time_constant = 1f32 / (2f32 * PI * cutoff_hz);
+    1f32 / (1f32 + time_constant / period_s)
+}
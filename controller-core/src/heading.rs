@@ -0,0 +1,300 @@
+//! Magnetometer calibration and tilt compensated heading
+//!
+//! Iron close to the sensor distorts the field it measures: hard iron adds a constant
+//! offset, and soft iron stretches the sphere drawn by the field as the controller turns
+//! into an ellipsoid. [`MagCalibrationRoutine`] fits that ellipsoid to the readings taken
+//! while the controller is turned in a figure eight, and [`MagCalibration`] maps the
+//! readings back onto a sphere centered on zero.
+use libm::{atan2f, pow, sqrt, sqrtf};
+
+/// Readings needed before a calibration can be fitted
+pub const MIN_CALIBRATION_SAMPLES: u32 = 300;
+
+/// Smallest spread of the readings along an axis, relative to the widest one.
+/// Below it the controller was not turned enough around that axis to fit the ellipsoid.
+const MIN_COVERAGE: f32 = 0.5;
+
+/// Jacobi rotations tried before giving up on the eigenvalues
+const MAX_JACOBI_SWEEPS: usize = 50;
+
+/// Hard iron offset, then soft iron correction, in gauss
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MagCalibration {
+    pub offset: [f32; 3],
+    /// Row major
+    pub matrix: [[f32; 3]; 3],
+}
+
+impl MagCalibration {
+    pub const IDENTITY: MagCalibration = MagCalibration {
+        offset: [0.0; 3],
+        matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn apply(&self, mag: [f32; 3]) -> [f32; 3] {
+        let centered = [0, 1, 2].map(|axis| mag[axis] - self.offset[axis]);
+        self.matrix.map(|row| dot(row, centered))
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.offset
+            .iter()
+            .chain(self.matrix.iter().flatten())
+            .all(|value| value.is_finite())
+    }
+}
+
+impl Default for MagCalibration {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Direction of the `x` axis from magnetic north toward east, in `-180.0..=180.0` degrees.
+///
+/// `accel` points up at rest, as measured by the accelerometer, and `mag` is calibrated.
+/// The field is projected on the horizontal plane, so the heading holds while tilted.
+/// `None` if either vector is zero or the field is vertical.
+pub fn tilt_compensated_heading(accel: [f32; 3], mag: [f32; 3]) -> Option<f32> {
+    let down = accel.map(|value| -value);
+    let east = cross(down, mag);
+    let north = cross(east, down);
+    let (east_norm, north_norm) = (norm(east), norm(north));
+    if !(east_norm > f32::EPSILON && north_norm > f32::EPSILON) {
+        return None;
+    }
+    Some(atan2f(east[0] / east_norm, north[0] / north_norm).to_degrees())
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn norm(a: [f32; 3]) -> f32 {
+    sqrtf(dot(a, a))
+}
+
+/// Collects magnetometer readings while the controller is turned in a figure eight,
+/// then fits the calibration to them.
+///
+/// Each reading adds to the normal equations of the least squares fit of
+/// `a x² + b y² + c z² + 2d xy + 2e xz + 2f yz + 2g x + 2h y + 2i z = 1`,
+/// so the readings themselves are not kept.
+#[derive(Clone, Copy, Debug)]
+pub struct MagCalibrationRoutine {
+    active: bool,
+    samples: u32,
+    min: [f32; 3],
+    max: [f32; 3],
+    ata: [[f64; 9]; 9],
+    atb: [f64; 9],
+}
+
+impl MagCalibrationRoutine {
+    pub const fn new() -> Self {
+        MagCalibrationRoutine {
+            active: false,
+            samples: 0,
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+            ata: [[0.0; 9]; 9],
+            atb: [0.0; 9],
+        }
+    }
+
+    /// Forgets the readings so far and collects new ones
+    pub fn start(&mut self) {
+        *self = MagCalibrationRoutine {
+            active: true,
+            ..Self::new()
+        };
+    }
+
+    pub fn cancel(&mut self) {
+        self.active = false;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Spread of the readings along the least covered axis, relative to the widest one
+    pub fn coverage(&self) -> f32 {
+        let spans = [0, 1, 2].map(|axis| (self.max[axis] - self.min[axis]).max(0.0));
+        let widest = spans[0].max(spans[1]).max(spans[2]);
+        if widest == 0.0 {
+            return 0.0;
+        }
+        spans[0].min(spans[1]).min(spans[2]) / widest
+    }
+
+    /// Adds a reading in gauss, ignored while not active
+    pub fn add(&mut self, mag: [f32; 3]) {
+        if !self.active {
+            return;
+        }
+        for (axis, value) in mag.iter().enumerate() {
+            self.min[axis] = self.min[axis].min(*value);
+            self.max[axis] = self.max[axis].max(*value);
+        }
+        let [x, y, z] = mag.map(f64::from);
+        let row = [
+            x * x,
+            y * y,
+            z * z,
+            2.0 * x * y,
+            2.0 * x * z,
+            2.0 * y * z,
+            2.0 * x,
+            2.0 * y,
+            2.0 * z,
+        ];
+        for ((ata, atb), a) in self.ata.iter_mut().zip(&mut self.atb).zip(row) {
+            for (value, b) in ata.iter_mut().zip(row) {
+                *value += a * b;
+            }
+            *atb += a;
+        }
+        self.samples += 1;
+    }
+
+    /// `true` once enough readings cover every axis
+    pub fn is_complete(&self) -> bool {
+        self.active && self.samples >= MIN_CALIBRATION_SAMPLES && self.coverage() >= MIN_COVERAGE
+    }
+
+    /// Stops the routine and fits the calibration to the readings.
+    ///
+    /// `None` if they do not lie on an ellipsoid, e.g. with too few or noisy readings.
+    pub fn finish(&mut self) -> Option<MagCalibration> {
+        self.active = false;
+        let [a, b, c, d, e, f, g, h, i] = solve(self.ata, self.atb)?;
+
+        // (p - center)ᵀ Q (p - center) = k with center = -Q⁻¹ v
+        let q = [[a, d, e], [d, b, f], [e, f, c]];
+        let center = solve(q, [-g, -h, -i])?;
+        let q_center = q.map(|row| row[0] * center[0] + row[1] * center[1] + row[2] * center[2]);
+        let k = 1.0 + center[0] * q_center[0] + center[1] * q_center[1] + center[2] * q_center[2];
+        if k <= 0.0 {
+            return None;
+        }
+
+        // The square root of Q / k maps the ellipsoid onto the unit sphere, scaled back to
+        // the geometric mean of its radii to keep the readings in gauss
+        let (eigenvalues, eigenvectors) = jacobi_eigen(q.map(|row| row.map(|value| value / k)))?;
+        if eigenvalues.iter().any(|value| *value <= 0.0) {
+            return None;
+        }
+        let radius = pow(eigenvalues[0] * eigenvalues[1] * eigenvalues[2], -1.0 / 6.0);
+        let roots = eigenvalues.map(sqrt);
+        let mut matrix = [[0f32; 3]; 3];
+        for (row, values) in matrix.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                let sum: f64 = (0..3)
+                    .map(|k| eigenvectors[row][k] * roots[k] * eigenvectors[column][k])
+                    .sum();
+                *value = (radius * sum) as f32;
+            }
+        }
+
+        let calibration = MagCalibration {
+            offset: center.map(|value| value as f32),
+            matrix,
+        };
+        calibration.is_valid().then_some(calibration)
+    }
+}
+
+impl Default for MagCalibrationRoutine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, `None` if singular
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for column in 0..N {
+        let pivot =
+            (column..N).max_by(|i, j| a[*i][column].abs().total_cmp(&a[*j][column].abs()))?;
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        for row in column + 1..N {
+            let pivot_row = a[column];
+            let factor = a[row][column] / pivot_row[column];
+            for (value, pivot) in a[row][column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let sum: f64 = (row + 1..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Eigenvalues and eigenvectors, as columns, of a symmetric matrix
+fn jacobi_eigen(mut a: [[f64; 3]; 3]) -> Option<([f64; 3], [[f64; 3]; 3])> {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..MAX_JACOBI_SWEEPS {
+        let off_diagonal = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+        if off_diagonal < 1e-12 {
+            return Some(([a[0][0], a[1][1], a[2][2]], v));
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + sqrt(theta * theta + 1.0));
+            let c = 1.0 / sqrt(t * t + 1.0);
+            let s = t * c;
+            let mut rotation = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+            rotation[p][p] = c;
+            rotation[q][q] = c;
+            rotation[p][q] = s;
+            rotation[q][p] = -s;
+            a = multiply(transpose(rotation), multiply(a, rotation));
+            v = multiply(v, rotation);
+        }
+    }
+    None
+}
+
+fn multiply(a: [[f64; 3]; 3], b: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut product = [[0.0; 3]; 3];
+    for (row, values) in product.iter_mut().enumerate() {
+        for (column, value) in values.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    product
+}
+
+fn transpose(a: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut transposed = a;
+    for (row, values) in transposed.iter_mut().enumerate() {
+        for (column, value) in values.iter_mut().enumerate() {
+            *value = a[column][row];
+        }
+    }
+    transposed
+}
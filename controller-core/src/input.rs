@@ -0,0 +1,157 @@
+//! Hardware abstraction for the physical inputs of the controller
+//!
+//! The firmware implements these traits for the ADC and GPIO peripherals,
+//! tests implement them with mock values.
+
+/// Analog inputs wired to the ADCs
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnalogInput {
+    LeftThumbX,
+    LeftThumbY,
+    RightThumbX,
+    RightThumbY,
+    LeftTrigger,
+    RightTrigger,
+    Other0,
+    Other1,
+}
+
+impl AnalogInput {
+    pub const COUNT: usize = 8;
+
+    pub const ALL: [AnalogInput; AnalogInput::COUNT] = [
+        AnalogInput::LeftThumbX,
+        AnalogInput::LeftThumbY,
+        AnalogInput::RightThumbX,
+        AnalogInput::RightThumbY,
+        AnalogInput::LeftTrigger,
+        AnalogInput::RightTrigger,
+        AnalogInput::Other0,
+        AnalogInput::Other1,
+    ];
+
+    /// Position of the input in [`Self::ALL`], usable to index per channel tables
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Triggers rest at one end of their travel, unlike sticks resting in the middle
+    pub const fn is_trigger(self) -> bool {
+        matches!(self, AnalogInput::LeftTrigger | AnalogInput::RightTrigger)
+    }
+
+    /// Short name used by the configuration tools
+    pub const fn name(self) -> &'static str {
+        match self {
+            AnalogInput::LeftThumbX => "lx",
+            AnalogInput::LeftThumbY => "ly",
+            AnalogInput::RightThumbX => "rx",
+            AnalogInput::RightThumbY => "ry",
+            AnalogInput::LeftTrigger => "lt",
+            AnalogInput::RightTrigger => "rt",
+            AnalogInput::Other0 => "other0",
+            AnalogInput::Other1 => "other1",
+        }
+    }
+
+    /// Input with the given [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|input| input.name() == name)
+    }
+}
+
+/// Digital inputs wired to GPIOs
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Button {
+    A,
+    B,
+    X,
+    Y,
+    LeftShoulder,
+    RightShoulder,
+    LeftThumb,
+    RightThumb,
+    Start,
+    Back,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Board user button, see [`crate::press::GuideMode`]
+    Guide,
+}
+
+impl Button {
+    pub const COUNT: usize = 15;
+
+    pub const ALL: [Button; Button::COUNT] = [
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::LeftThumb,
+        Button::RightThumb,
+        Button::Start,
+        Button::Back,
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+        Button::Guide,
+    ];
+
+    /// Position of the button in [`Self::ALL`], usable to index per button tables
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Short name used by the configuration tools
+    pub const fn name(self) -> &'static str {
+        match self {
+            Button::A => "a",
+            Button::B => "b",
+            Button::X => "x",
+            Button::Y => "y",
+            Button::LeftShoulder => "lb",
+            Button::RightShoulder => "rb",
+            Button::LeftThumb => "ls",
+            Button::RightThumb => "rs",
+            Button::Start => "start",
+            Button::Back => "back",
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Left => "left",
+            Button::Right => "right",
+            Button::Guide => "guide",
+        }
+    }
+
+    /// Button with the given [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|button| button.name() == name)
+    }
+}
+
+/// Source of raw 12-bit analog samples
+pub trait AnalogSource {
+    type Error;
+
+    /// Samples the given input, returns the raw ADC value (0..=4095)
+    fn read_raw(&mut self, input: AnalogInput) -> Result<u16, Self::Error>;
+
+    /// Called before the inputs of a sample are read. Sources converting in the
+    /// background take their snapshot here, so all the inputs of a sample match.
+    fn begin_sample(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Source of button states
+pub trait DigitalSource {
+    type Error;
+
+    /// Returns `true` while the given button is held down
+    fn is_pressed(&mut self, button: Button) -> Result<bool, Self::Error>;
+}
@@ -0,0 +1,31 @@
+#![no_std]
+extern crate packed_struct;
+#[macro_use]
+extern crate packed_struct_codegen;
+
+pub mod board;
+pub mod calibration;
+pub mod codec;
+pub mod compass;
+pub mod config;
+pub mod console;
+pub mod controller;
+pub mod curve;
+pub mod deadzone;
+pub mod debounce;
+pub mod dpad;
+pub mod filter;
+pub mod heading;
+pub mod input;
+pub mod lights;
+pub mod macros;
+pub mod motion;
+pub mod pipeline;
+pub mod press;
+pub mod remap;
+pub mod report;
+pub mod scan;
+pub mod schedule;
+pub mod settings;
+pub mod storage;
+pub mod xinput;
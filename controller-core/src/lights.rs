@@ -0,0 +1,391 @@
+//! Status and animations of the eight compass LEDs, rendered to brightness levels
+//!
+//! Every [`LedPriority`] holds at most one request: the highest one shows, and once it is
+//! cleared or times out the next one below shows again. The firmware renders a [`Frame`]
+//! on every tick of its LED timer and drives the LEDs through a [`SoftPwm`].
+//!
+//! LEDs are indexed clockwise from North, as the board marks them.
+use libm::{atan2f, roundf, sqrtf};
+
+use crate::calibration::CalibrationStep;
+use crate::controller::ControllerState;
+
+pub const LED_COUNT: usize = 8;
+
+/// Brightness of a LED fully on, `0` is off
+pub const MAX_BRIGHTNESS: u8 = 15;
+
+/// Time the LEDs show the active profile after a switch
+pub const PROFILE_SHOWN_MS: u32 = 1000;
+
+/// Time an error shows after it last happened
+pub const ERROR_SHOWN_MS: u32 = 2000;
+
+/// Brightness of each LED, clockwise from North
+pub type Frame = [u8; LED_COUNT];
+
+pub const OFF: Frame = [0; LED_COUNT];
+
+/// LEDs of a bit mask, bit 0 is North
+fn masked(mask: u8, brightness: u8) -> Frame {
+    let mut frame = OFF;
+    for (index, level) in frame.iter_mut().enumerate() {
+        if mask & 1 << index != 0 {
+            *level = brightness;
+        }
+    }
+    frame
+}
+
+/// LEDs drawn over time, from the start of the request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Animation {
+    Solid(Frame),
+    /// LEDs of the mask on for the first half of each period
+    Blink {
+        mask: u8,
+        period_ms: u16,
+    },
+    /// LEDs of the mask fading in then out over each period
+    Breathe {
+        mask: u8,
+        period_ms: u16,
+    },
+    /// A LED going around clockwise, trailed by dimmer ones, once per period
+    Spin {
+        period_ms: u16,
+    },
+    /// The LED nearest an angle, in degrees clockwise from North, as bright as the magnitude
+    /// in `0.0..=1.0`
+    Compass {
+        angle: f32,
+        magnitude: f32,
+    },
+    /// LEDs filled clockwise from North up to a level in `0.0..=1.0`, the last one dimmed
+    /// by the remainder
+    Ring {
+        level: f32,
+    },
+}
+
+impl Animation {
+    pub fn frame(&self, elapsed_ms: u32) -> Frame {
+        match *self {
+            Animation::Solid(frame) => frame,
+            Animation::Blink { mask, period_ms } => {
+                let on = elapsed_ms % period_ms.max(1) as u32 * 2 < period_ms as u32;
+                masked(mask, if on { MAX_BRIGHTNESS } else { 0 })
+            }
+            Animation::Breathe { mask, period_ms } => {
+                let period = period_ms.max(1) as u32;
+                let phase = elapsed_ms % period;
+                // Triangle wave, squared so the fade looks even to the eye
+                let level = 1.0 - (2.0 * phase as f32 / period as f32 - 1.0).abs();
+                masked(mask, roundf(level * level * MAX_BRIGHTNESS as f32) as u8)
+            }
+            Animation::Spin { period_ms } => {
+                let period = period_ms.max(1) as u32;
+                let head = (elapsed_ms % period * LED_COUNT as u32 / period) as usize;
+                let mut frame = OFF;
+                for (distance, level) in [MAX_BRIGHTNESS, MAX_BRIGHTNESS / 3, 1]
+                    .into_iter()
+                    .enumerate()
+                {
+                    frame[(head + LED_COUNT - distance) % LED_COUNT] = level;
+                }
+                frame
+            }
+            Animation::Compass { angle, magnitude } => {
+                let mut frame = OFF;
+                let step = 360.0 / LED_COUNT as f32;
+                let index = (roundf(angle / step) as i32).rem_euclid(LED_COUNT as i32) as usize;
+                frame[index] = roundf(magnitude.clamp(0.0, 1.0) * MAX_BRIGHTNESS as f32) as u8;
+                frame
+            }
+            Animation::Ring { level } => {
+                let filled =
+                    roundf(level.clamp(0.0, 1.0) * (LED_COUNT as u8 * MAX_BRIGHTNESS) as f32);
+                let mut frame = OFF;
+                for (index, brightness) in frame.iter_mut().enumerate() {
+                    let below = index as f32 * MAX_BRIGHTNESS as f32;
+                    *brightness = (filled - below).clamp(0.0, MAX_BRIGHTNESS as f32) as u8;
+                }
+                frame
+            }
+        }
+    }
+}
+
+/// Which request shows over the others, from lowest to highest
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LedPriority {
+    Usb,
+    Diagnostic,
+    Profile,
+    Calibration,
+    Error,
+}
+
+impl LedPriority {
+    pub const COUNT: usize = 5;
+
+    pub const ALL: [LedPriority; Self::COUNT] = [
+        LedPriority::Usb,
+        LedPriority::Diagnostic,
+        LedPriority::Profile,
+        LedPriority::Calibration,
+        LedPriority::Error,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Named states of the controller, each with its own animation
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LedState {
+    /// Plugged in, waiting for the host to configure the device
+    Enumerating,
+    /// Configured by the host, ready to send reports
+    Configured,
+    /// The host suspended the bus
+    Suspended,
+    /// Something failed, e.g. sampling or saving the settings
+    Error,
+    /// Guided stick calibration, with the number of axes moved over their full travel
+    Calibration {
+        step: CalibrationStep,
+        calibrated_axes: usize,
+    },
+    /// Profile switched to, one LED per profile
+    Profile(u8),
+}
+
+impl LedState {
+    pub fn priority(&self) -> LedPriority {
+        match self {
+            LedState::Enumerating | LedState::Configured | LedState::Suspended => LedPriority::Usb,
+            LedState::Profile(_) => LedPriority::Profile,
+            LedState::Calibration { .. } => LedPriority::Calibration,
+            LedState::Error => LedPriority::Error,
+        }
+    }
+
+    pub fn animation(&self) -> Animation {
+        match *self {
+            LedState::Enumerating => Animation::Spin { period_ms: 800 },
+            // A dim North LED as power light
+            LedState::Configured => Animation::Solid(masked(0b0000_0001, MAX_BRIGHTNESS / 4)),
+            LedState::Suspended => Animation::Breathe {
+                mask: 0b0000_0001,
+                period_ms: 4000,
+            },
+            LedState::Error => Animation::Blink {
+                mask: 0xFF,
+                period_ms: 200,
+            },
+            // Cardinal directions while waiting for the centers to be recorded
+            LedState::Calibration {
+                step: CalibrationStep::Center,
+                ..
+            } => Animation::Blink {
+                mask: 0b0101_0101,
+                period_ms: 1000,
+            },
+            // One LED per axis moved over its full travel
+            LedState::Calibration {
+                calibrated_axes, ..
+            } => Animation::Solid(masked(
+                (1u16 << calibrated_axes.min(LED_COUNT)).wrapping_sub(1) as u8,
+                MAX_BRIGHTNESS,
+            )),
+            LedState::Profile(profile) => Animation::Solid(masked(
+                (2u16 << (profile as usize).min(LED_COUNT - 1)).wrapping_sub(1) as u8,
+                MAX_BRIGHTNESS,
+            )),
+        }
+    }
+
+    /// Time the state shows unless shown again, `None` until cleared
+    pub fn duration_ms(&self) -> Option<u32> {
+        match self {
+            LedState::Profile(_) => Some(PROFILE_SHOWN_MS),
+            LedState::Error => Some(ERROR_SHOWN_MS),
+            _ => None,
+        }
+    }
+}
+
+/// What the LEDs show under the profile switches, calibration and errors
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LedMode {
+    /// The USB state only
+    #[default]
+    Status,
+    /// Where the stick points, as bright as it is pushed
+    LeftStick,
+    RightStick,
+    /// How far the trigger is pulled, as a ring filling clockwise
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl LedMode {
+    pub const ALL: [LedMode; 5] = [
+        LedMode::Status,
+        LedMode::LeftStick,
+        LedMode::RightStick,
+        LedMode::LeftTrigger,
+        LedMode::RightTrigger,
+    ];
+
+    /// Name in the console
+    pub const fn name(self) -> &'static str {
+        match self {
+            LedMode::Status => "status",
+            LedMode::LeftStick => "left",
+            LedMode::RightStick => "right",
+            LedMode::LeftTrigger => "lt",
+            LedMode::RightTrigger => "rt",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// Animation of the inputs shown with [`LedPriority::Diagnostic`], `None` for the status
+    pub fn animation(self, state: &ControllerState) -> Option<Animation> {
+        let stick = |x: f32, y: f32| Animation::Compass {
+            // Up is North and right is East
+            angle: atan2f(x, y).to_degrees(),
+            magnitude: sqrtf(x * x + y * y),
+        };
+        match self {
+            LedMode::Status => None,
+            LedMode::LeftStick => Some(stick(state.left_thumb_x, state.left_thumb_y)),
+            LedMode::RightStick => Some(stick(state.right_thumb_x, state.right_thumb_y)),
+            LedMode::LeftTrigger => Some(Animation::Ring {
+                level: state.left_trigger,
+            }),
+            LedMode::RightTrigger => Some(Animation::Ring {
+                level: state.right_trigger,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Request {
+    animation: Animation,
+    started_ms: u32,
+    /// `None` until cleared
+    until_ms: Option<u32>,
+}
+
+/// Picks the request to show and renders it, keeps the time of the last render
+#[derive(Clone, Copy, Debug)]
+pub struct LedEngine {
+    requests: [Option<Request>; LedPriority::COUNT],
+    now_ms: u32,
+}
+
+impl LedEngine {
+    pub const fn new() -> Self {
+        LedEngine {
+            requests: [None; LedPriority::COUNT],
+            now_ms: 0,
+        }
+    }
+
+    /// Shows a named state, see [`Self::request`]
+    pub fn show(&mut self, state: LedState) {
+        self.request(state.priority(), state.animation(), state.duration_ms());
+    }
+
+    /// Replaces the request of `priority`, until cleared or for `duration_ms`.
+    ///
+    /// Requesting the animation already shown only pushes its end back, so it carries on
+    /// rather than starting over.
+    pub fn request(
+        &mut self,
+        priority: LedPriority,
+        animation: Animation,
+        duration_ms: Option<u32>,
+    ) {
+        let now_ms = self.now_ms;
+        let slot = &mut self.requests[priority.index()];
+        let started_ms = match slot {
+            Some(request) if request.animation == animation => request.started_ms,
+            _ => now_ms,
+        };
+        *slot = Some(Request {
+            animation,
+            started_ms,
+            until_ms: duration_ms.map(|duration| now_ms.wrapping_add(duration)),
+        });
+    }
+
+    pub fn clear(&mut self, priority: LedPriority) {
+        self.requests[priority.index()] = None;
+    }
+
+    /// Priority of the request shown
+    pub fn current(&self) -> Option<LedPriority> {
+        self.current_request()
+            .map(|(index, _)| LedPriority::ALL[index])
+    }
+
+    /// Drops the requests ended by `now_ms` and renders the highest one left, all off
+    /// without any. The time only goes forward and may wrap around.
+    pub fn render(&mut self, now_ms: u32) -> Frame {
+        self.now_ms = now_ms;
+        for slot in self.requests.iter_mut() {
+            let ended = matches!(slot, Some(Request { until_ms: Some(until_ms), .. })
+                if (now_ms.wrapping_sub(*until_ms) as i32) >= 0);
+            if ended {
+                *slot = None;
+            }
+        }
+        match self.current_request() {
+            Some((_, request)) => request
+                .animation
+                .frame(now_ms.wrapping_sub(request.started_ms)),
+            None => OFF,
+        }
+    }
+
+    fn current_request(&self) -> Option<(usize, &Request)> {
+        self.requests
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, request)| Some((index, request.as_ref()?)))
+    }
+}
+
+impl Default for LedEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Software PWM: over [`MAX_BRIGHTNESS`] ticks, a LED is on for as many ticks as its brightness
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SoftPwm {
+    phase: u8,
+}
+
+impl SoftPwm {
+    pub const fn new() -> Self {
+        SoftPwm { phase: 0 }
+    }
+
+    /// LEDs to light for this tick
+    pub fn next(&mut self, frame: &Frame) -> [bool; LED_COUNT] {
+        let phase = self.phase;
+        self.phase = (self.phase + 1) % MAX_BRIGHTNESS;
+        frame.map(|level| level > phase)
+    }
+}
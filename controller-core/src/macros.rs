@@ -0,0 +1,225 @@
+//! Turbo and macro playback, applied to the reported state after the remapping
+//!
+//! Both run on a millisecond clock given by the caller: the pipeline counts its
+//! samples, so a step lasts a whole number of samples, and tests step it directly.
+use crate::controller::ControllerState;
+use crate::input::Button;
+use crate::pipeline::{button_value, button_value_mut};
+use crate::remap::ButtonSet;
+
+/// Number of stored macros
+pub const MAX_MACROS: usize = 4;
+
+/// Largest number of steps in a [`Macro`]
+pub const MAX_MACRO_STEPS: usize = 6;
+
+/// Fastest turbo, a press and a release must each last a few samples at the lowest poll rate
+pub const MAX_TURBO_HZ: u8 = 30;
+
+/// Auto-repeat of every reported button, indexed by [`Button::index`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TurboRates {
+    /// Presses per second while held, in `0..=MAX_TURBO_HZ`, 0 disables the turbo
+    pub buttons: [u8; Button::COUNT],
+}
+
+impl TurboRates {
+    pub const fn new() -> Self {
+        TurboRates {
+            buttons: [0; Button::COUNT],
+        }
+    }
+
+    pub fn button(&self, button: Button) -> u8 {
+        self.buttons[button.index()]
+    }
+
+    pub fn button_mut(&mut self, button: Button) -> &mut u8 {
+        &mut self.buttons[button.index()]
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.buttons.iter().all(|rate| *rate <= MAX_TURBO_HZ)
+    }
+}
+
+impl Default for TurboRates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Repeats the held buttons at their turbo rate
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Turbo {
+    /// Time each held button was pressed, `None` while released
+    pressed_at: [Option<u32>; Button::COUNT],
+}
+
+impl Turbo {
+    /// Turns the held buttons of `output` into presses and releases at their rate,
+    /// starting with a press
+    pub fn update(&mut self, rates: &TurboRates, now_ms: u32, output: &mut ControllerState) {
+        for button in Button::ALL {
+            let rate = rates.button(button) as u32;
+            let pressed = button_value_mut(output, button);
+            let pressed_at = &mut self.pressed_at[button.index()];
+            if !*pressed || rate == 0 {
+                *pressed_at = None;
+                continue;
+            }
+
+            let elapsed = now_ms.wrapping_sub(*pressed_at.get_or_insert(now_ms));
+            let period_ms = 1000 / rate;
+            *pressed = elapsed % period_ms < period_ms / 2;
+        }
+    }
+}
+
+/// Controls held for the duration of a step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MacroStep {
+    /// Pressed on top of the inputs, nothing for a wait
+    pub buttons: ButtonSet,
+    /// Left stick position replacing the input, `x` then `y` in `-1.0..=1.0`
+    pub left_stick: Option<(f32, f32)>,
+    pub duration_ms: u16,
+}
+
+impl MacroStep {
+    /// Holds nothing for `duration_ms`
+    pub const fn wait(duration_ms: u16) -> Self {
+        MacroStep {
+            buttons: ButtonSet::EMPTY,
+            left_stick: None,
+            duration_ms,
+        }
+    }
+
+    pub const fn press(buttons: ButtonSet, duration_ms: u16) -> Self {
+        MacroStep {
+            buttons,
+            left_stick: None,
+            duration_ms,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.left_stick
+            .is_none_or(|(x, y)| (-1f32..=1f32).contains(&x) && (-1f32..=1f32).contains(&y))
+    }
+
+    fn apply(&self, output: &mut ControllerState) {
+        for button in self.buttons.iter() {
+            *button_value_mut(output, button) = true;
+        }
+        if let Some((x, y)) = self.left_stick {
+            output.left_thumb_x = x;
+            output.left_thumb_y = y;
+        }
+    }
+}
+
+/// Steps played one after the other when `trigger` is pressed.
+///
+/// The trigger is read before the remapping and is never reported itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Macro {
+    pub trigger: Button,
+    len: usize,
+    steps: [MacroStep; MAX_MACRO_STEPS],
+}
+
+impl Macro {
+    /// Returns `None` if there are no steps, too many, or a step is invalid
+    pub fn new(trigger: Button, steps: &[MacroStep]) -> Option<Self> {
+        if steps.is_empty()
+            || steps.len() > MAX_MACRO_STEPS
+            || !steps.iter().all(MacroStep::is_valid)
+        {
+            return None;
+        }
+
+        let mut macro_steps = [MacroStep::wait(0); MAX_MACRO_STEPS];
+        macro_steps[..steps.len()].copy_from_slice(steps);
+        Some(Macro {
+            trigger,
+            len: steps.len(),
+            steps: macro_steps,
+        })
+    }
+
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps[..self.len]
+    }
+
+    /// Step played `elapsed_ms` after the start, `None` once the macro is over
+    pub fn step_at(&self, elapsed_ms: u32) -> Option<&MacroStep> {
+        let mut end_ms = 0;
+        self.steps().iter().find(|step| {
+            end_ms += step.duration_ms as u32;
+            elapsed_ms < end_ms
+        })
+    }
+}
+
+/// Stored macros, indexed by slot
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Macros {
+    pub slots: [Option<Macro>; MAX_MACROS],
+}
+
+impl Macros {
+    pub const fn new() -> Self {
+        Macros {
+            slots: [None; MAX_MACROS],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Macro> {
+        self.slots.iter().flatten()
+    }
+}
+
+/// Plays the macros whose trigger gets pressed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MacroPlayer {
+    /// Start of the playback of each slot, `None` when not playing
+    started_at: [Option<u32>; MAX_MACROS],
+    trigger_was_pressed: [bool; MAX_MACROS],
+}
+
+impl MacroPlayer {
+    /// Starts the macros whose trigger was just pressed in `physical`, then releases
+    /// every trigger in `physical`. A macro pressed again while playing is not restarted.
+    pub fn read_triggers(&mut self, macros: &Macros, now_ms: u32, physical: &mut ControllerState) {
+        for (slot, macro_) in macros.slots.iter().enumerate() {
+            let pressed = macro_.is_some_and(|macro_| button_value(physical, macro_.trigger));
+            if pressed && !self.trigger_was_pressed[slot] && self.started_at[slot].is_none() {
+                self.started_at[slot] = Some(now_ms);
+            }
+            self.trigger_was_pressed[slot] = pressed;
+        }
+        for macro_ in macros.iter() {
+            *button_value_mut(physical, macro_.trigger) = false;
+        }
+    }
+
+    /// Applies the current step of every playing macro to `output`
+    pub fn play(&mut self, macros: &Macros, now_ms: u32, output: &mut ControllerState) {
+        for (started_at, macro_) in self.started_at.iter_mut().zip(macros.slots) {
+            let (Some(start), Some(macro_)) = (*started_at, macro_) else {
+                *started_at = None;
+                continue;
+            };
+            match macro_.step_at(now_ms.wrapping_sub(start)) {
+                Some(step) => step.apply(output),
+                None => *started_at = None,
+            }
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.started_at.iter().any(Option::is_some)
+    }
+}
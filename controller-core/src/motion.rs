@@ -0,0 +1,203 @@
+//! Motion input: tilting the controller steers an axis, e.g. air roll on the roll
+//!
+//! The LSM303DLHC of the board has no gyroscope. Roll and pitch come from the direction
+//! of gravity measured by the accelerometer and the heading from the magnetometer, tilt
+//! compensated with that same gravity, so the angles are only accurate while the controller
+//! is not shaken. Angles are in degrees.
+use libm::{atan2f, fabsf, sqrtf};
+
+use crate::controller::ControllerState;
+use crate::heading::tilt_compensated_heading;
+use crate::input::{AnalogInput, Button};
+use crate::pipeline::{analog_value_mut, button_value, button_value_mut};
+
+/// Largest tilt range, past it the angles of the accelerometer fold back
+pub const MAX_MOTION_RANGE: f32 = 90.0;
+
+/// Weight of a new reading in the smoothed orientation
+const SMOOTHING: f32 = 0.25;
+
+/// Angles of the board, `x` pointing to its USB connectors and `z` up when lying flat
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    /// Rotation around `x`, positive when the right side goes down
+    pub roll: f32,
+    /// Rotation around `y`, positive when the front goes up
+    pub pitch: f32,
+    /// Direction of `x` from magnetic north, in `-180.0..=180.0`
+    pub heading: f32,
+}
+
+impl Orientation {
+    pub const LEVEL: Orientation = Orientation {
+        roll: 0.0,
+        pitch: 0.0,
+        heading: 0.0,
+    };
+
+    /// Orientation from the acceleration in g and the calibrated magnetic field in any unit,
+    /// see [`tilt_compensated_heading`]. Without a magnetometer the heading is 0.
+    pub fn from_sensors(accel: [f32; 3], mag: Option<[f32; 3]>) -> Self {
+        let [x, y, z] = accel;
+        Orientation {
+            roll: atan2f(y, z).to_degrees(),
+            pitch: atan2f(-x, sqrtf(y * y + z * z)).to_degrees(),
+            heading: mag
+                .and_then(|mag| tilt_compensated_heading(accel, mag))
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+/// Angle in `-180.0..=180.0`, the same direction as `angle`
+fn wrap_degrees(angle: f32) -> f32 {
+    let angle = angle % 360.0;
+    if angle > 180.0 {
+        angle - 360.0
+    } else if angle < -180.0 {
+        angle + 360.0
+    } else {
+        angle
+    }
+}
+
+/// Mapping of the orientation onto the axes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionSettings {
+    pub enabled: bool,
+    /// Axis steered by the roll, `None` to ignore it
+    pub roll_axis: Option<AnalogInput>,
+    pub pitch_axis: Option<AnalogInput>,
+    /// Axis steered by turning the controller, from the heading it had when motion turned on
+    pub heading_axis: Option<AnalogInput>,
+    /// Angle from the rest orientation for a full deflection, the sensitivity
+    pub range: f32,
+    /// Angle from the rest orientation ignored, in `0.0..range`
+    pub deadzone: f32,
+    /// Roll and pitch of the controller held at rest
+    pub rest_roll: f32,
+    pub rest_pitch: f32,
+    /// Button turning motion on and off, released in the report. Without one, motion is
+    /// always on while enabled.
+    pub toggle: Option<Button>,
+}
+
+impl MotionSettings {
+    pub const fn new() -> Self {
+        MotionSettings {
+            enabled: false,
+            roll_axis: Some(AnalogInput::LeftThumbX),
+            pitch_axis: None,
+            heading_axis: None,
+            range: 30.0,
+            deadzone: 3.0,
+            rest_roll: 0.0,
+            rest_pitch: 0.0,
+            toggle: None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let rest = -180f32..=180f32;
+        0.0 <= self.deadzone
+            && self.deadzone < self.range
+            && self.range <= MAX_MOTION_RANGE
+            && rest.contains(&self.rest_roll)
+            && rest.contains(&self.rest_pitch)
+    }
+
+    /// Deflection for an angle from the rest orientation, in `-1.0..=1.0`
+    pub fn deflection(&self, angle: f32) -> f32 {
+        let magnitude = (fabsf(angle) - self.deadzone) / (self.range - self.deadzone);
+        magnitude.clamp(0.0, 1.0).copysign(angle)
+    }
+}
+
+impl Default for MotionSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Smooths the orientation readings and adds them to the axes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MotionInput {
+    /// Smoothed orientation, `None` until the first reading
+    orientation: Option<Orientation>,
+    /// Heading when motion turned on, `None` while off
+    heading_center: Option<f32>,
+    /// Motion turned on by the toggle button
+    toggled_on: bool,
+    toggle_was_pressed: bool,
+}
+
+impl MotionInput {
+    /// Feeds a reading of the sensors
+    pub fn update(&mut self, reading: Orientation) {
+        // Roll and heading wrap around, they move the short way to the reading
+        let smooth =
+            |last: f32, new: f32| wrap_degrees(last + SMOOTHING * wrap_degrees(new - last));
+        self.orientation = Some(match self.orientation {
+            None => reading,
+            Some(last) => Orientation {
+                roll: smooth(last.roll, reading.roll),
+                pitch: last.pitch + SMOOTHING * (reading.pitch - last.pitch),
+                heading: smooth(last.heading, reading.heading),
+            },
+        });
+    }
+
+    /// Smoothed orientation, `None` without a reading
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.orientation
+    }
+
+    /// `true` while the tilt steers the axes
+    pub fn is_active(&self, settings: &MotionSettings) -> bool {
+        settings.enabled && (settings.toggle.is_none() || self.toggled_on)
+    }
+
+    /// Reads the toggle button of `physical` then releases it, and adds the deflections
+    /// to the axes of `physical`, clamped to their range
+    pub fn apply(&mut self, settings: &MotionSettings, physical: &mut ControllerState) {
+        if !settings.enabled {
+            self.toggled_on = false;
+            self.toggle_was_pressed = false;
+            self.heading_center = None;
+            return;
+        }
+
+        if let Some(toggle) = settings.toggle {
+            let pressed = button_value(physical, toggle);
+            if pressed && !self.toggle_was_pressed {
+                self.toggled_on = !self.toggled_on;
+            }
+            self.toggle_was_pressed = pressed;
+            *button_value_mut(physical, toggle) = false;
+        }
+
+        let (true, Some(orientation)) = (self.is_active(settings), self.orientation) else {
+            self.heading_center = None;
+            return;
+        };
+        let center = *self.heading_center.get_or_insert(orientation.heading);
+
+        let angles = [
+            (
+                settings.roll_axis,
+                wrap_degrees(orientation.roll - settings.rest_roll),
+            ),
+            (settings.pitch_axis, orientation.pitch - settings.rest_pitch),
+            (
+                settings.heading_axis,
+                wrap_degrees(orientation.heading - center),
+            ),
+        ];
+        for (axis, angle) in angles {
+            let Some(axis) = axis else { continue };
+            let min = if axis.is_trigger() { 0.0 } else { -1.0 };
+            let value = analog_value_mut(physical, axis);
+            *value = (*value + settings.deflection(angle)).clamp(min, 1.0);
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! Sampling -> debouncing -> d-pad SOCD resolution, filters -> calibration -> deadzones -> response curves -> motion -> remapping -> turbo and macros -> report pipeline
+use crate::calibration::Calibration;
+use crate::controller::ControllerState;
+use crate::curve::ResponseCurves;
+use crate::deadzone::Deadzones;
+use crate::debounce::{ButtonDebounces, Debouncer};
+use crate::dpad::{SocdMode, SocdResolver};
+use crate::filter::{AnalogFilter, AnalogFilters, FilterState};
+use crate::heading::MagCalibration;
+use crate::input::{AnalogInput, AnalogSource, Button, DigitalSource};
+use crate::macros::{MacroPlayer, Macros, Turbo, TurboRates};
+use crate::motion::{MotionInput, MotionSettings, Orientation};
+use crate::press::{GuideMode, PressDetector, PressEvent};
+use crate::remap::{ButtonSet, ProfileSwitch, Profiles};
+use crate::report::{get_report, ReportSink, XboxJoystickReport};
+use crate::schedule::PollRate;
+use crate::settings::Settings;
+use crate::xinput::{get_xinput_report, XInputReport};
+
+/// Highest value returned by the 12-bit ADCs
+pub const ADC_MAX_VALUE: u16 = 4095;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PipelineError<A, D> {
+    Analog(A),
+    Digital(D),
+}
+
+/// Reads the inputs into a [`ControllerState`] and turns it into reports
+pub struct InputPipeline {
+    /// Inputs as wired
+    controller_state: ControllerState,
+    /// Controls reported to the host, remapped by the active profile
+    output_state: ControllerState,
+    raw_values: [u16; AnalogInput::COUNT],
+    filters: AnalogFilters,
+    filter_states: [FilterState; AnalogInput::COUNT],
+    /// Rate of the calls to [`Self::sample`], for the filters and debouncers
+    poll_rate: PollRate,
+    debounces: ButtonDebounces,
+    debouncers: [Debouncer; Button::COUNT],
+    calibration: Calibration,
+    deadzones: Deadzones,
+    curves: ResponseCurves,
+    socd: SocdResolver,
+    guide_mode: GuideMode,
+    guide_press: PressDetector,
+    press_event: Option<PressEvent>,
+    profiles: Profiles,
+    profile_switch: ProfileSwitch,
+    profile_switched: bool,
+    turbo_rates: TurboRates,
+    turbo: Turbo,
+    macros: Macros,
+    macro_player: MacroPlayer,
+    motion: MotionSettings,
+    motion_input: MotionInput,
+    mag_calibration: MagCalibration,
+    /// Time of the last sample in ms, counted from the poll rate
+    clock_ms: u32,
+}
+
+impl InputPipeline {
+    pub fn new() -> Self {
+        InputPipeline {
+            controller_state: ControllerState::new(),
+            output_state: ControllerState::new(),
+            raw_values: [0; AnalogInput::COUNT],
+            filters: AnalogFilters::new(),
+            filter_states: [FilterState::new(AnalogFilter::None); AnalogInput::COUNT],
+            poll_rate: PollRate::Hz1000,
+            debounces: ButtonDebounces::new(),
+            debouncers: debouncers(&ButtonDebounces::new(), PollRate::Hz1000),
+            calibration: Calibration::new(),
+            deadzones: Deadzones::new(),
+            curves: ResponseCurves::new(),
+            socd: SocdResolver::default(),
+            guide_mode: GuideMode::Guide,
+            guide_press: PressDetector::default(),
+            press_event: None,
+            profiles: Profiles::new(),
+            profile_switch: ProfileSwitch::default(),
+            profile_switched: false,
+            turbo_rates: TurboRates::new(),
+            turbo: Turbo::default(),
+            macros: Macros::new(),
+            macro_player: MacroPlayer::default(),
+            motion: MotionSettings::new(),
+            motion_input: MotionInput::default(),
+            mag_calibration: MagCalibration::IDENTITY,
+            clock_ms: 0,
+        }
+    }
+
+    /// Applies every setting affecting the inputs, from the next call to [`Self::sample`]
+    ///
+    /// The poll rate is left out: it is set once at boot with [`Self::set_poll_rate`], along with
+    /// the sample timer and the USB endpoint.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.set_debounces(settings.debounces);
+        self.set_filters(settings.filters);
+        self.set_calibration(settings.calibration);
+        self.set_deadzones(settings.deadzones);
+        self.set_curves(settings.curves);
+        self.set_socd_mode(settings.socd_mode);
+        self.set_guide_mode(settings.guide_mode);
+        self.set_profiles(settings.profiles);
+        self.set_turbo_rates(settings.turbo);
+        self.set_macros(settings.macros);
+        self.set_motion(settings.motion);
+        self.set_mag_calibration(settings.mag_calibration);
+    }
+
+    /// Rate at which [`Self::sample`] is called, the filters and debouncers depend on it
+    pub fn set_poll_rate(&mut self, rate: PollRate) {
+        self.poll_rate = rate;
+        self.debouncers = debouncers(&self.debounces, rate);
+    }
+
+    pub fn debounces(&self) -> &ButtonDebounces {
+        &self.debounces
+    }
+
+    /// Replaces the debouncing, every button starts released
+    pub fn set_debounces(&mut self, debounces: ButtonDebounces) {
+        self.debounces = debounces;
+        self.debouncers = debouncers(&debounces, self.poll_rate);
+    }
+
+    pub fn filters(&self) -> &AnalogFilters {
+        &self.filters
+    }
+
+    /// Replaces the filters, they start again from the next value
+    pub fn set_filters(&mut self, filters: AnalogFilters) {
+        self.filters = filters;
+        for (state, filter) in self.filter_states.iter_mut().zip(filters.axes) {
+            *state = FilterState::new(filter);
+        }
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    /// Replaces the calibration, applied from the next call to [`Self::sample`]
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    pub fn deadzones(&self) -> &Deadzones {
+        &self.deadzones
+    }
+
+    pub fn set_deadzones(&mut self, deadzones: Deadzones) {
+        self.deadzones = deadzones;
+    }
+
+    pub fn curves(&self) -> &ResponseCurves {
+        &self.curves
+    }
+
+    pub fn set_curves(&mut self, curves: ResponseCurves) {
+        self.curves = curves;
+    }
+
+    pub fn socd_mode(&self) -> SocdMode {
+        self.socd.mode()
+    }
+
+    pub fn set_socd_mode(&mut self, mode: SocdMode) {
+        self.socd.set_mode(mode);
+    }
+
+    pub fn guide_mode(&self) -> GuideMode {
+        self.guide_mode
+    }
+
+    pub fn set_guide_mode(&mut self, mode: GuideMode) {
+        self.guide_mode = mode;
+    }
+
+    /// Replaces the mode key press detection, e.g. to match the sampling rate
+    pub fn set_guide_press(&mut self, detector: PressDetector) {
+        self.guide_press = detector;
+    }
+
+    /// Press of the mode key completed by the last sample, always `None` in [`GuideMode::Guide`]
+    pub fn press_event(&self) -> Option<PressEvent> {
+        self.press_event
+    }
+
+    pub fn profiles(&self) -> &Profiles {
+        &self.profiles
+    }
+
+    pub fn set_profiles(&mut self, profiles: Profiles) {
+        self.profiles = profiles;
+    }
+
+    /// Switches to the next profile, from the next call to [`Self::sample`]
+    pub fn next_profile(&mut self) {
+        self.profiles.next();
+    }
+
+    /// `true` if the last sample completed the profile switch combo
+    pub fn profile_switched(&self) -> bool {
+        self.profile_switched
+    }
+
+    pub fn turbo_rates(&self) -> &TurboRates {
+        &self.turbo_rates
+    }
+
+    pub fn set_turbo_rates(&mut self, rates: TurboRates) {
+        self.turbo_rates = rates;
+    }
+
+    pub fn macros(&self) -> &Macros {
+        &self.macros
+    }
+
+    /// Replaces the macros, stopping the ones playing
+    pub fn set_macros(&mut self, macros: Macros) {
+        self.macros = macros;
+        self.macro_player = MacroPlayer::default();
+    }
+
+    /// `true` while a macro drives the report
+    pub fn is_playing_macro(&self) -> bool {
+        self.macro_player.is_playing()
+    }
+
+    pub fn motion(&self) -> &MotionSettings {
+        &self.motion
+    }
+
+    pub fn set_motion(&mut self, motion: MotionSettings) {
+        self.motion = motion;
+    }
+
+    pub fn mag_calibration(&self) -> &MagCalibration {
+        &self.mag_calibration
+    }
+
+    /// Calibration applied to the magnetometer readings of [`Self::read_sensors`]
+    pub fn set_mag_calibration(&mut self, calibration: MagCalibration) {
+        self.mag_calibration = calibration;
+    }
+
+    /// Feeds a reading of the motion sensors, used from the next call to [`Self::sample`]
+    pub fn set_orientation(&mut self, reading: Orientation) {
+        self.motion_input.update(reading);
+    }
+
+    /// Feeds the acceleration in g and the raw magnetic field in gauss, `None` without a new
+    /// reading of the magnetometer
+    pub fn read_sensors(&mut self, accel: [f32; 3], mag: Option<[f32; 3]>) {
+        let mag = mag.map(|mag| self.mag_calibration.apply(mag));
+        self.set_orientation(Orientation::from_sensors(accel, mag));
+    }
+
+    /// Smoothed orientation of the controller, `None` until the first reading
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.motion_input.orientation()
+    }
+
+    /// `true` while tilting the controller steers the axes
+    pub fn is_motion_active(&self) -> bool {
+        self.motion_input.is_active(&self.motion)
+    }
+
+    /// Filtered but uncalibrated ADC values of the last sample, indexed by [`AnalogInput::index`]
+    pub fn raw_values(&self) -> &[u16; AnalogInput::COUNT] {
+        &self.raw_values
+    }
+
+    /// Inputs as wired, resulting from the last call to [`Self::sample`]
+    pub fn state(&self) -> &ControllerState {
+        &self.controller_state
+    }
+
+    /// State reported to the host, the last sample remapped by the active profile
+    pub fn output_state(&self) -> &ControllerState {
+        &self.output_state
+    }
+
+    /// Reads every button and analog input.
+    ///
+    /// On error the state is left partially updated, the next sample overwrites it.
+    pub fn sample<A: AnalogSource, D: DigitalSource>(
+        &mut self,
+        analog: &mut A,
+        digital: &mut D,
+    ) -> Result<(), PipelineError<A::Error, D::Error>> {
+        self.clock_ms = self
+            .clock_ms
+            .wrapping_add(self.poll_rate.period_us() / 1000);
+        read_buttons_states(digital, &mut self.controller_state).map_err(PipelineError::Digital)?;
+        self.debounce_buttons();
+        self.socd.resolve(&mut self.controller_state);
+        self.read_mode_key();
+        self.read_joystick_states(analog)
+            .map_err(PipelineError::Analog)?;
+        self.apply_deadzones();
+        self.apply_curves();
+        self.remap();
+        Ok(())
+    }
+
+    fn remap(&mut self) {
+        let mut physical = self.controller_state;
+        // With a single profile the combo buttons reach the host as any other
+        let combo = if self.profiles.count > 1 {
+            self.profiles.switch_combo
+        } else {
+            ButtonSet::EMPTY
+        };
+        self.profile_switched = self.profile_switch.update(combo, &mut physical);
+        if self.profile_switched {
+            self.profiles.next();
+        }
+        self.motion_input.apply(&self.motion, &mut physical);
+        self.macro_player
+            .read_triggers(&self.macros, self.clock_ms, &mut physical);
+        self.output_state = self.profiles.active_profile().apply(&physical);
+        self.turbo
+            .update(&self.turbo_rates, self.clock_ms, &mut self.output_state);
+        self.macro_player
+            .play(&self.macros, self.clock_ms, &mut self.output_state);
+    }
+
+    fn debounce_buttons(&mut self) {
+        for button in Button::ALL {
+            let pressed = button_value_mut(&mut self.controller_state, button);
+            *pressed = self.debouncers[button.index()].update(*pressed);
+        }
+    }
+
+    fn read_mode_key(&mut self) {
+        self.press_event = None;
+        if self.guide_mode == GuideMode::ModeKey {
+            self.press_event = self.guide_press.update(self.controller_state.guide);
+            self.controller_state.guide = false;
+        }
+    }
+
+    fn apply_curves(&mut self) {
+        for input in AnalogInput::ALL {
+            let value = analog_value_mut(&mut self.controller_state, input);
+            *value = self.curves.axis(input).apply(*value);
+        }
+    }
+
+    fn apply_deadzones(&mut self) {
+        let state = &mut self.controller_state;
+        (state.left_thumb_x, state.left_thumb_y) = self
+            .deadzones
+            .left_stick
+            .apply(state.left_thumb_x, state.left_thumb_y);
+        (state.right_thumb_x, state.right_thumb_y) = self
+            .deadzones
+            .right_stick
+            .apply(state.right_thumb_x, state.right_thumb_y);
+    }
+
+    fn read_joystick_states<A: AnalogSource>(&mut self, analog: &mut A) -> Result<(), A::Error> {
+        analog.begin_sample()?;
+        let period_s = self.poll_rate.period_us() as f32 / 1e6;
+        for input in AnalogInput::ALL {
+            let raw = analog.read_raw(input)?;
+            let raw = self.filter_states[input.index()].update(raw, period_s);
+            self.raw_values[input.index()] = raw;
+            *analog_value_mut(&mut self.controller_state, input) =
+                self.calibration.axis(input).apply(raw);
+        }
+        Ok(())
+    }
+
+    /// Report matching the current state
+    pub fn report(&self) -> XboxJoystickReport {
+        get_report(&self.output_state)
+    }
+
+    /// XInput report matching the current state
+    pub fn xinput_report(&self) -> XInputReport {
+        get_xinput_report(&self.output_state)
+    }
+
+    /// Sends the report matching the current state to `sink`
+    pub fn publish<S: ReportSink>(&self, sink: &mut S) -> Result<(), S::Error> {
+        sink.write_report(&self.report())
+    }
+}
+
+impl Default for InputPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn debouncers(debounces: &ButtonDebounces, rate: PollRate) -> [Debouncer; Button::COUNT] {
+    debounces
+        .buttons
+        .map(|debounce| Debouncer::new(debounce.mode, debounce.samples(rate)))
+}
+
+pub fn read_buttons_states<D: DigitalSource>(
+    digital: &mut D,
+    controller_state: &mut ControllerState,
+) -> Result<(), D::Error> {
+    for button in Button::ALL {
+        *button_value_mut(controller_state, button) = digital.is_pressed(button)?;
+    }
+    Ok(())
+}
+
+/// Field of the state driven by the given analog input
+pub fn analog_value_mut(controller_state: &mut ControllerState, input: AnalogInput) -> &mut f32 {
+    match input {
+        AnalogInput::LeftThumbX => &mut controller_state.left_thumb_x,
+        AnalogInput::LeftThumbY => &mut controller_state.left_thumb_y,
+        AnalogInput::RightThumbX => &mut controller_state.right_thumb_x,
+        AnalogInput::RightThumbY => &mut controller_state.right_thumb_y,
+        AnalogInput::LeftTrigger => &mut controller_state.left_trigger,
+        AnalogInput::RightTrigger => &mut controller_state.right_trigger,
+        AnalogInput::Other0 => &mut controller_state.other_value_0,
+        AnalogInput::Other1 => &mut controller_state.other_value_1,
+    }
+}
+
+/// Field of the state driven by the given button
+pub fn button_value_mut(controller_state: &mut ControllerState, button: Button) -> &mut bool {
+    match button {
+        Button::A => &mut controller_state.a,
+        Button::B => &mut controller_state.b,
+        Button::X => &mut controller_state.x,
+        Button::Y => &mut controller_state.y,
+        Button::LeftShoulder => &mut controller_state.left_shoulder,
+        Button::RightShoulder => &mut controller_state.right_shoulder,
+        Button::LeftThumb => &mut controller_state.left_thumb,
+        Button::RightThumb => &mut controller_state.right_thumb,
+        Button::Start => &mut controller_state.start,
+        Button::Back => &mut controller_state.back,
+        Button::Up => &mut controller_state.up,
+        Button::Down => &mut controller_state.down,
+        Button::Left => &mut controller_state.left,
+        Button::Right => &mut controller_state.right,
+        Button::Guide => &mut controller_state.guide,
+    }
+}
+
+/// Value of the state driven by the given analog input
+pub fn analog_value(controller_state: &ControllerState, input: AnalogInput) -> f32 {
+    match input {
+        AnalogInput::LeftThumbX => controller_state.left_thumb_x,
+        AnalogInput::LeftThumbY => controller_state.left_thumb_y,
+        AnalogInput::RightThumbX => controller_state.right_thumb_x,
+        AnalogInput::RightThumbY => controller_state.right_thumb_y,
+        AnalogInput::LeftTrigger => controller_state.left_trigger,
+        AnalogInput::RightTrigger => controller_state.right_trigger,
+        AnalogInput::Other0 => controller_state.other_value_0,
+        AnalogInput::Other1 => controller_state.other_value_1,
+    }
+}
+
+/// Value of the state driven by the given button
+pub fn button_value(controller_state: &ControllerState, button: Button) -> bool {
+    match button {
+        Button::A => controller_state.a,
+        Button::B => controller_state.b,
+        Button::X => controller_state.x,
+        Button::Y => controller_state.y,
+        Button::LeftShoulder => controller_state.left_shoulder,
+        Button::RightShoulder => controller_state.right_shoulder,
+        Button::LeftThumb => controller_state.left_thumb,
+        Button::RightThumb => controller_state.right_thumb,
+        Button::Start => controller_state.start,
+        Button::Back => controller_state.back,
+        Button::Up => controller_state.up,
+        Button::Down => controller_state.down,
+        Button::Left => controller_state.left,
+        Button::Right => controller_state.right,
+        Button::Guide => controller_state.guide,
+    }
+}
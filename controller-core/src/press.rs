@@ -0,0 +1,124 @@
+//! Short, long and double press detection, used when the guide input acts as a mode key
+
+/// Number of samples a press must be held to be a long press
+pub const DEFAULT_LONG_PRESS_SAMPLES: u32 = 100;
+
+/// Largest number of released samples between the two presses of a double press
+pub const DEFAULT_DOUBLE_PRESS_GAP: u32 = 25;
+
+/// Long press delay, converted to samples with [`crate::schedule::PollRate::samples`]
+pub const LONG_PRESS_MS: u32 = 800;
+
+/// Double press gap, converted to samples with [`crate::schedule::PollRate::samples`]
+pub const DOUBLE_PRESS_GAP_MS: u32 = 250;
+
+/// Role of the input wired as [`crate::input::Button::Guide`], the user button on the board
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GuideMode {
+    /// Reported to the host as the guide button
+    #[default]
+    Guide,
+    /// Kept from the host, its presses are turned into [`PressEvent`]s for the firmware
+    ModeKey,
+}
+
+impl GuideMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(GuideMode::Guide),
+            1 => Some(GuideMode::ModeKey),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            GuideMode::Guide => 0,
+            GuideMode::ModeKey => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PressEvent {
+    /// Released before the long press delay, with no second press following it
+    Short,
+    /// Held for the long press delay, sent while still held
+    Long,
+    /// Pressed again shortly after a short press, sent on the second press
+    Double,
+}
+
+/// Turns the sampled state of a button into [`PressEvent`]s.
+///
+/// A short press is only sent once the double press gap is over,
+/// so a double press never starts with a short press.
+pub struct PressDetector {
+    long_press_samples: u32,
+    double_press_gap: u32,
+    was_pressed: bool,
+    held_samples: u32,
+    released_samples: u32,
+    /// A short press waiting for the double press gap to end
+    pending_short: bool,
+    /// The current press already sent its event
+    handled: bool,
+}
+
+impl PressDetector {
+    pub fn new(long_press_samples: u32, double_press_gap: u32) -> Self {
+        PressDetector {
+            long_press_samples,
+            double_press_gap,
+            was_pressed: false,
+            held_samples: 0,
+            released_samples: 0,
+            pending_short: false,
+            handled: false,
+        }
+    }
+
+    /// Feeds one sample of the button, returns the event it completes if any
+    pub fn update(&mut self, pressed: bool) -> Option<PressEvent> {
+        let pressed_edge = pressed && !self.was_pressed;
+        let released_edge = !pressed && self.was_pressed;
+        self.was_pressed = pressed;
+
+        if pressed {
+            if pressed_edge {
+                self.held_samples = 0;
+                self.handled = false;
+                if self.pending_short {
+                    self.pending_short = false;
+                    self.handled = true;
+                    return Some(PressEvent::Double);
+                }
+            }
+            self.held_samples += 1;
+            if !self.handled && self.held_samples >= self.long_press_samples {
+                self.handled = true;
+                return Some(PressEvent::Long);
+            }
+            return None;
+        }
+
+        if released_edge && !self.handled {
+            self.pending_short = true;
+            self.released_samples = 0;
+        }
+        if self.pending_short {
+            self.released_samples += 1;
+            if self.released_samples > self.double_press_gap {
+                self.pending_short = false;
+                return Some(PressEvent::Short);
+            }
+        }
+        None
+    }
+}
+
+impl Default for PressDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_LONG_PRESS_SAMPLES, DEFAULT_DOUBLE_PRESS_GAP)
+    }
+}
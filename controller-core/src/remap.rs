@@ -0,0 +1,282 @@
+//! Remapping of the physical inputs to the reported controls, with switchable profiles
+//!
+//! The pipeline processes the inputs as wired, then a [`Profile`] picks the source of
+//! every reported button and axis. Any control can drive any other one, an axis can
+//! drive a button past a threshold and buttons can drive an axis.
+use crate::controller::ControllerState;
+use crate::input::{AnalogInput, Button};
+use crate::pipeline::{analog_value, analog_value_mut, button_value, button_value_mut};
+
+/// Number of stored profiles, one per LED at most
+pub const MAX_PROFILES: usize = 4;
+
+/// Default combo switching to the next profile, START+BACK is left to the calibration
+pub const DEFAULT_SWITCH_COMBO: ButtonSet = ButtonSet::EMPTY
+    .with(Button::RightShoulder)
+    .with(Button::Back);
+
+/// Source of a reported button
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ButtonBinding {
+    /// Never pressed
+    Unbound,
+    Button(Button),
+    /// Pressed while the axis is past `threshold`, in `-1.0..=1.0` without 0.
+    /// A negative threshold presses the button on the negative side of the axis.
+    Axis {
+        input: AnalogInput,
+        threshold: f32,
+    },
+}
+
+impl ButtonBinding {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            ButtonBinding::Axis { threshold, .. } => {
+                *threshold != 0f32 && (-1f32..=1f32).contains(threshold)
+            }
+            _ => true,
+        }
+    }
+
+    fn apply(&self, physical: &ControllerState) -> bool {
+        match *self {
+            ButtonBinding::Unbound => false,
+            ButtonBinding::Button(button) => button_value(physical, button),
+            ButtonBinding::Axis { input, threshold } => {
+                let value = analog_value(physical, input);
+                if threshold > 0f32 {
+                    value >= threshold
+                } else {
+                    value <= threshold
+                }
+            }
+        }
+    }
+}
+
+/// Source of a reported axis
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AxisBinding {
+    /// Always at rest
+    Unbound,
+    /// `inverted` reverses the travel of `input`: sticks are negated,
+    /// triggers report their rest value when fully pulled
+    Axis { input: AnalogInput, inverted: bool },
+    /// Full deflection towards the held button, at rest when both or none are held.
+    /// A trigger only reacts to `positive`.
+    Buttons {
+        negative: Option<Button>,
+        positive: Option<Button>,
+    },
+}
+
+impl AxisBinding {
+    fn apply(&self, physical: &ControllerState, output: AnalogInput) -> f32 {
+        let value = match *self {
+            AxisBinding::Unbound => 0f32,
+            AxisBinding::Axis { input, inverted } => {
+                let value = analog_value(physical, input);
+                match (inverted, input.is_trigger()) {
+                    (false, _) => value,
+                    (true, false) => -value,
+                    (true, true) => 1f32 - value,
+                }
+            }
+            AxisBinding::Buttons { negative, positive } => {
+                let held = |button: Option<Button>| {
+                    button.is_some_and(|button| button_value(physical, button))
+                };
+                match (held(negative), held(positive)) {
+                    (true, false) => -1f32,
+                    (false, true) => 1f32,
+                    _ => 0f32,
+                }
+            }
+        };
+
+        if output.is_trigger() {
+            value.clamp(0f32, 1f32)
+        } else {
+            value.clamp(-1f32, 1f32)
+        }
+    }
+}
+
+/// Set of buttons, bit [`Button::index`] for each button
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ButtonSet(pub u16);
+
+impl ButtonSet {
+    pub const EMPTY: ButtonSet = ButtonSet(0);
+
+    /// Returns `None` if a bit does not match a button
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        (bits >> Button::COUNT == 0).then_some(ButtonSet(bits))
+    }
+
+    pub const fn with(self, button: Button) -> Self {
+        ButtonSet(self.0 | 1 << button as usize)
+    }
+
+    pub fn contains(self, button: Button) -> bool {
+        self.0 & 1 << button.index() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Button> {
+        Button::ALL
+            .into_iter()
+            .filter(move |button| self.contains(*button))
+    }
+
+    /// `true` if every button of the set is held in `state`, `false` for an empty set
+    pub fn is_held(self, state: &ControllerState) -> bool {
+        !self.is_empty() && self.iter().all(|button| button_value(state, button))
+    }
+}
+
+/// Source of every reported control
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Profile {
+    /// Indexed by the reported [`Button::index`]
+    pub buttons: [ButtonBinding; Button::COUNT],
+    /// Indexed by the reported [`AnalogInput::index`]
+    pub axes: [AxisBinding; AnalogInput::COUNT],
+}
+
+impl Profile {
+    /// Reports every input as wired
+    pub const IDENTITY: Profile = Profile::identity();
+
+    const fn identity() -> Self {
+        let mut buttons = [ButtonBinding::Unbound; Button::COUNT];
+        let mut index = 0;
+        while index < Button::COUNT {
+            buttons[index] = ButtonBinding::Button(Button::ALL[index]);
+            index += 1;
+        }
+
+        let mut axes = [AxisBinding::Unbound; AnalogInput::COUNT];
+        let mut index = 0;
+        while index < AnalogInput::COUNT {
+            axes[index] = AxisBinding::Axis {
+                input: AnalogInput::ALL[index],
+                inverted: false,
+            };
+            index += 1;
+        }
+
+        Profile { buttons, axes }
+    }
+
+    pub fn button(&self, output: Button) -> &ButtonBinding {
+        &self.buttons[output.index()]
+    }
+
+    pub fn button_mut(&mut self, output: Button) -> &mut ButtonBinding {
+        &mut self.buttons[output.index()]
+    }
+
+    pub fn axis(&self, output: AnalogInput) -> &AxisBinding {
+        &self.axes[output.index()]
+    }
+
+    pub fn axis_mut(&mut self, output: AnalogInput) -> &mut AxisBinding {
+        &mut self.axes[output.index()]
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.buttons.iter().all(ButtonBinding::is_valid)
+    }
+
+    /// State reported for the `physical` state
+    pub fn apply(&self, physical: &ControllerState) -> ControllerState {
+        let mut output = ControllerState::new();
+        for button in Button::ALL {
+            *button_value_mut(&mut output, button) = self.button(button).apply(physical);
+        }
+        for input in AnalogInput::ALL {
+            *analog_value_mut(&mut output, input) = self.axis(input).apply(physical, input);
+        }
+        output
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Stored profiles and the one in use
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Profiles {
+    /// Only the first `count` profiles are used
+    pub profiles: [Profile; MAX_PROFILES],
+    /// In `1..=MAX_PROFILES`
+    pub count: u8,
+    /// Index of the profile in use, below `count`
+    pub active: u8,
+    /// Buttons switching to the next profile when held together, empty to disable
+    pub switch_combo: ButtonSet,
+}
+
+impl Profiles {
+    pub const fn new() -> Self {
+        Profiles {
+            profiles: [Profile::IDENTITY; MAX_PROFILES],
+            count: 1,
+            active: 0,
+            switch_combo: DEFAULT_SWITCH_COMBO,
+        }
+    }
+
+    pub fn active_profile(&self) -> &Profile {
+        &self.profiles[self.active as usize]
+    }
+
+    /// Makes the next profile active, back to the first one after the last
+    pub fn next(&mut self) {
+        self.active = (self.active + 1) % self.count;
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_PROFILES).contains(&(self.count as usize))
+            && self.active < self.count
+            && self.profiles.iter().all(Profile::is_valid)
+    }
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Detects the profile switch combo on the physical buttons
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProfileSwitch {
+    was_held: bool,
+}
+
+impl ProfileSwitch {
+    /// Feeds one sample, returns `true` when the combo gets completed.
+    ///
+    /// The combo buttons are released in `physical` while the combo is held,
+    /// so they do not reach the host through the profile.
+    pub fn update(&mut self, combo: ButtonSet, physical: &mut ControllerState) -> bool {
+        let held = combo.is_held(physical);
+        let switched = held && !self.was_held;
+        self.was_held = held;
+        if held {
+            for button in combo.iter() {
+                *button_value_mut(physical, button) = false;
+            }
+        }
+        switched
+    }
+}
@@ -0,0 +1,216 @@
+//! HID joystick report and its descriptor
+use crate::controller::ControllerState;
+use crate::dpad::hat_value;
+use crate::input::Button;
+use libm::roundf;
+
+// from https://github.com/nefarius/ViGEmBus/issues/40
+// see https://github.com/dlkj/usbd-human-interface-device/blob/main/src/device/joystick.rs
+// see https://github.com/TimDeve/sunrs/blob/0d335dffa3ebe8909a0308639af4bb66dccee902/src/main.rs
+// see https://usb.org/sites/default/files/hut1_2.pdf
+// see http://who-t.blogspot.com/2018/12/understanding-hid-report-descriptors.html
+
+/// Vendor and product id of the HID joystick, also used by the host tools to find it
+pub const HID_VID: u16 = 0x16c0;
+pub const HID_PID: u16 = 0x27dd;
+
+/// Button of each bit of [`XboxJoystickReport::buttons`], the d-pad is on the hat switch
+pub const REPORT_BUTTONS: [Button; 11] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::Start,
+    Button::Back,
+    Button::LeftThumb,
+    Button::RightThumb,
+    Button::Guide,
+];
+
+/// Largest magnitude of a stick axis, reported in `-JOYSTICK_MAX_VALUE..=JOYSTICK_MAX_VALUE`
+pub const JOYSTICK_MAX_VALUE: i16 = i16::MAX;
+/// Full pull of a trigger, reported in `0..=TRIGGER_MAX_VALUE`
+pub const TRIGGER_MAX_VALUE: u16 = u16::MAX;
+
+#[rustfmt::skip]
+pub const XBOX_JOYSTICK_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,                   // Usage Page (Generic Desktop)         5,   1
+    0x09, 0x04,                   // Usage (Joystick)                     9,   4
+    0xa1, 0x01,                   // Collection (Application)             161, 1
+    0x09, 0x01,                   //   Usage Page (Pointer)               9,   1
+    0xa1, 0x00,                   //   Collection (Physical)              161, 0
+    0x09, 0x30,                   //     Usage (X)                        9,   48
+    0x09, 0x31,                   //     Usage (Y)                        9,   49
+    0x09, 0x32,                   //     Usage (Z)                        9,   50
+    0x09, 0x33,                   //     Usage (Rx)                       9,   51
+    0x16, 0x01, 0x80,             //     Logical Minimum (-32767)         22,  1,   128
+    0x26, 0xff, 0x7f,             //     Logical Maximum (32767)          38,  255, 127
+    0x75, 0x10,                   //     Report Size (16)                 117, 16
+    0x95, 0x04,                   //     Report count (4)                 149, 4,
+    0x81, 0x02,                   //     Input (Data, Variable, Absolute) 129, 2,
+    0x09, 0x34,                   //     Usage (Ry)                       9,   52
+    0x09, 0x35,                   //     Usage (Rz)                       9,   53
+    0x15, 0x00,                   //     Logical Minimum (0)              21,  0
+    0x27, 0xff, 0xff, 0x00, 0x00, //     Logical Maximum (65535)          39,  255, 255, 0, 0
+    0x95, 0x02,                   //     Report count (2)                 149, 2,
+    0x81, 0x02,                   //     Input (Data, Variable, Absolute) 129, 2,
+    0xc0,                         //   End Collection                     192,
+    0x05, 0x09,                   //   Usage Page (Button)                5,   9,
+    0x19, 0x01,                   //   Usage Minimum (0)                  25,  1,
+    0x29, 0x10,                   //   Usage Maximum (8)                  41,  8,
+    0x15, 0x00,                   //   Logical Minimum (0)                21,  0
+    0x25, 0x01,                   //   Logical Maximum (1)                37,  1,
+    0x75, 0x01,                   //   Report Size (1)                    117, 1,
+    0x95, 0x10,                   //   Report Count (16)                  149, 8
+    0x81, 0x02,                   //   Input (Data, Variable, Absolute)   129, 2,
+    0x05, 0x01,                   //   Usage Page (Generic Desktop)       5,   1
+    0x09, 0x39,                   //   Usage (Hat switch)                 9,   57
+    0x15, 0x00,                   //   Logical Minimum (0)                21,  0
+    0x25, 0x07,                   //   Logical Maximum (7)                37,  7
+    0x35, 0x00,                   //   Physical Minimum (0)               53,  0
+    0x46, 0x3b, 0x01,             //   Physical Maximum (315)             70,  59,  1
+    0x65, 0x14,                   //   Unit (Degrees)                     101, 20
+    0x75, 0x04,                   //   Report Size (4)                    117, 4
+    0x95, 0x01,                   //   Report Count (1)                   149, 1
+    0x81, 0x42,                   //   Input (Data, Variable, Null state) 129, 66
+    0x65, 0x00,                   //   Unit (None)                        101, 0
+    0x95, 0x01,                   //   Report Count (1)                   149, 1
+    0x81, 0x03,                   //   Input (Constant) padding           129, 3
+    0x06, 0x00, 0xff,             //   Usage Page (Vendor Defined)        6,   0,   255
+    0x09, 0x01,                   //   Usage (Configuration)              9,   1
+    0x15, 0x00,                   //   Logical Minimum (0)                21,  0
+    0x26, 0xff, 0x00,             //   Logical Maximum (255)              38,  255, 0
+    0x75, 0x08,                   //   Report Size (8)                    117, 8
+    0x95, 0x40,                   //   Report Count (64)                  149, 64
+    0xb1, 0x02,                   //   Feature (Data, Variable, Absolute) 177, 2
+    0xc0,                         // End Collection                       192
+];
+
+pub use packed::XboxJoystickReport;
+
+// The packed_struct 0.3 derive emits `&mut target[..].copy_from_slice(..);`, which trips
+// `unused_must_use` in the generated impls, out of reach of an attribute on the struct
+#[allow(unused_must_use)]
+mod packed {
+    /// Sticks on X, Y, Z and Rx with Y and Rx pointing down, triggers on Ry and Rz,
+    /// d-pad on the hat switch
+    #[derive(Clone, Copy, Debug, Default, PackedStruct)]
+    #[packed_struct(endian = "lsb", size_bytes = "15")]
+    pub struct XboxJoystickReport {
+        #[packed_field]
+        pub x: i16,
+        #[packed_field]
+        pub y: i16,
+        #[packed_field]
+        pub z: i16,
+        #[packed_field]
+        pub rx: i16,
+        #[packed_field]
+        pub ry: u16,
+        #[packed_field]
+        pub rz: u16,
+        #[packed_field]
+        pub buttons: u16,
+        /// See [`crate::dpad::hat_value`], the upper 4 bits are padding
+        #[packed_field]
+        pub hat: u8,
+    }
+}
+
+/// Destination of the reports built from the controller state, e.g. the USB HID class
+pub trait ReportSink {
+    type Error;
+
+    fn write_report(&mut self, report: &XboxJoystickReport) -> Result<(), Self::Error>;
+}
+
+pub fn get_report(controller_state: &ControllerState) -> XboxJoystickReport {
+    let mut buttons = 0;
+
+    let mut button_index = 0;
+
+    if controller_state.a {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.b {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.x {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.y {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.left_shoulder {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.right_shoulder {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.start {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.back {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.left_thumb {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.right_thumb {
+        buttons |= 1 << button_index;
+    }
+    button_index += 1;
+
+    if controller_state.guide {
+        buttons |= 1 << button_index;
+    }
+
+    let x = stick_value(controller_state.left_thumb_x);
+    // HID `y` axes point down, the controller state ones up
+    let y = stick_value(-controller_state.left_thumb_y);
+    let z = stick_value(controller_state.right_thumb_x);
+    let rx = stick_value(-controller_state.right_thumb_y);
+    let ry = trigger_value(controller_state.left_trigger);
+    let rz = trigger_value(controller_state.right_trigger);
+
+    XboxJoystickReport {
+        buttons,
+        x,
+        y,
+        z,
+        rx,
+        ry,
+        rz,
+        hat: hat_value(controller_state),
+    }
+}
+
+/// Scales a stick axis from `-1.0..=1.0`
+pub fn stick_value(value: f32) -> i16 {
+    roundf(value.clamp(-1f32, 1f32) * JOYSTICK_MAX_VALUE as f32) as i16
+}
+
+/// Scales a trigger from `0.0..=1.0`
+pub fn trigger_value(value: f32) -> u16 {
+    roundf(value.clamp(0f32, 1f32) * TRIGGER_MAX_VALUE as f32) as u16
+}
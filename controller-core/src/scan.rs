@@ -0,0 +1,77 @@
+//! Continuous ADC scans copied by DMA into a double buffer
+//!
+//! The buffer holds two complete scans, the DMA fills one half while the
+//! other one holds the last complete scan.
+
+/// Largest number of channels in a regular sequence
+pub const MAX_SEQUENCE_LEN: usize = 16;
+
+/// One of the two scans of the double buffer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Half {
+    First,
+    Second,
+}
+
+impl Half {
+    /// Half the DMA is writing, from the number of transfers left before it wraps around
+    pub fn being_written(remaining: u16, scan_len: usize) -> Half {
+        if remaining as usize > scan_len {
+            Half::First
+        } else {
+            Half::Second
+        }
+    }
+
+    pub fn other(self) -> Half {
+        match self {
+            Half::First => Half::Second,
+            Half::Second => Half::First,
+        }
+    }
+
+    /// Position of the half in the buffer, counted in scans
+    pub fn index(self) -> usize {
+        match self {
+            Half::First => 0,
+            Half::Second => 1,
+        }
+    }
+}
+
+/// Values of `SQR1..=SQR4` converting `channels` in order, `SQR1` includes the length.
+///
+/// # Panics
+/// If the sequence is empty or longer than [`MAX_SEQUENCE_LEN`]
+pub fn sequence_registers(channels: &[u8]) -> [u32; 4] {
+    assert!(
+        !channels.is_empty() && channels.len() <= MAX_SEQUENCE_LEN,
+        "ADC sequence length must be in 1..=16"
+    );
+
+    let mut registers = [0u32; 4];
+    registers[0] = (channels.len() - 1) as u32;
+    for (rank, &channel) in channels.iter().enumerate() {
+        // SQR1 starts with the length, the following registers hold 5 ranks each
+        let (register, slot) = match rank {
+            0..=3 => (0, rank + 1),
+            _ => ((rank + 1) / 5, (rank + 1) % 5),
+        };
+        registers[register] |= ((channel & 0x1f) as u32) << (slot * 6);
+    }
+    registers
+}
+
+/// Values of `SMPR1` and `SMPR2` applying `sample_time` to each of `channels`
+pub fn sample_time_registers(channels: &[u8], sample_time: u8) -> [u32; 2] {
+    let mut registers = [0u32; 2];
+    for &channel in channels {
+        // SMPR1 holds channels 1..=9 from bit 3, SMPR2 channels 10..=18 from bit 0
+        let (register, slot) = match channel {
+            0..=9 => (0, channel as usize),
+            _ => (1, channel as usize - 10),
+        };
+        registers[register] |= ((sample_time & 0x7) as u32) << (slot * 3);
+    }
+    registers
+}
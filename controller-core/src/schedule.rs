@@ -0,0 +1,134 @@
+//! Fixed sampling rate and the jitter measured against it
+
+/// Rate at which inputs are sampled and reports are sent, matching the USB endpoint interval
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PollRate {
+    Hz125,
+    Hz250,
+    Hz500,
+    #[default]
+    Hz1000,
+}
+
+impl PollRate {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PollRate::Hz125),
+            1 => Some(PollRate::Hz250),
+            2 => Some(PollRate::Hz500),
+            3 => Some(PollRate::Hz1000),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            PollRate::Hz125 => 0,
+            PollRate::Hz250 => 1,
+            PollRate::Hz500 => 2,
+            PollRate::Hz1000 => 3,
+        }
+    }
+
+    /// Rate of `hz` samples per second, `None` if it is not one of the supported rates
+    pub fn from_hz(hz: u32) -> Option<Self> {
+        match hz {
+            125 => Some(PollRate::Hz125),
+            250 => Some(PollRate::Hz250),
+            500 => Some(PollRate::Hz500),
+            1000 => Some(PollRate::Hz1000),
+            _ => None,
+        }
+    }
+
+    pub fn hz(self) -> u32 {
+        match self {
+            PollRate::Hz125 => 125,
+            PollRate::Hz250 => 250,
+            PollRate::Hz500 => 500,
+            PollRate::Hz1000 => 1000,
+        }
+    }
+
+    /// Time between two samples
+    pub fn period_us(self) -> u32 {
+        1_000_000 / self.hz()
+    }
+
+    /// Number of samples taken during `duration_ms`, at least one
+    pub fn samples(self, duration_ms: u32) -> u32 {
+        (duration_ms * self.hz() / 1000).max(1)
+    }
+
+    /// `bInterval` of the interrupt IN endpoint, so the host polls once per sample
+    pub fn endpoint_interval_ms(self) -> u8 {
+        (1000 / self.hz()) as u8
+    }
+}
+
+/// Statistics of the measured time between samples
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct JitterStats {
+    expected_us: u32,
+    count: u32,
+    min_us: u32,
+    max_us: u32,
+    total_us: u64,
+}
+
+impl JitterStats {
+    pub const fn new(expected_us: u32) -> Self {
+        JitterStats {
+            expected_us,
+            count: 0,
+            min_us: u32::MAX,
+            max_us: 0,
+            total_us: 0,
+        }
+    }
+
+    /// Adds the time elapsed since the previous sample
+    pub fn record(&mut self, period_us: u32) {
+        self.count = self.count.saturating_add(1);
+        self.min_us = self.min_us.min(period_us);
+        self.max_us = self.max_us.max(period_us);
+        self.total_us += period_us as u64;
+    }
+
+    /// Forgets every recorded period, keeps the expected one
+    pub fn reset(&mut self) {
+        *self = Self::new(self.expected_us);
+    }
+
+    pub fn expected_us(&self) -> u32 {
+        self.expected_us
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Shortest period, `None` before the first one
+    pub fn min_us(&self) -> Option<u32> {
+        (self.count > 0).then_some(self.min_us)
+    }
+
+    /// Longest period, `None` before the first one
+    pub fn max_us(&self) -> Option<u32> {
+        (self.count > 0).then_some(self.max_us)
+    }
+
+    /// Average period, `None` before the first one
+    pub fn mean_us(&self) -> Option<u32> {
+        (self.count > 0).then(|| (self.total_us / self.count as u64) as u32)
+    }
+
+    /// Largest difference between a period and the expected one
+    pub fn max_jitter_us(&self) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+        (self.expected_us - self.min_us.min(self.expected_us))
+            .max(self.max_us.saturating_sub(self.expected_us))
+    }
+}
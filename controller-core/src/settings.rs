@@ -0,0 +1,546 @@
+//! Persistent controller settings and their versioned binary encoding
+//!
+//! Fields are only ever appended: a record written by an older firmware decodes with
+//! the fields it does not contain set to their defaults.
+use crate::calibration::{AxisCalibration, Calibration};
+use crate::codec::{CodecError, Reader, Writer};
+use crate::curve::{CurveTable, ResponseCurve, ResponseCurves, MAX_CURVE_POINTS};
+use crate::deadzone::{DeadzoneMode, Deadzones, StickDeadzone};
+use crate::debounce::{ButtonDebounce, ButtonDebounces, DebounceMode};
+use crate::dpad::SocdMode;
+use crate::filter::{AnalogFilter, AnalogFilters};
+use crate::heading::MagCalibration;
+use crate::input::{AnalogInput, Button};
+use crate::macros::{Macro, MacroStep, Macros, TurboRates, MAX_MACRO_STEPS};
+use crate::motion::MotionSettings;
+use crate::press::GuideMode;
+use crate::remap::{AxisBinding, ButtonBinding, ButtonSet, Profile, Profiles, MAX_PROFILES};
+use crate::schedule::PollRate;
+use crate::xinput::UsbMode;
+
+/// Version written in front of every encoded record
+pub const SETTINGS_VERSION: u16 = 1;
+
+/// Upper bound of [`Settings::encode`] output, sizes the buffers of the store
+pub const MAX_SETTINGS_SIZE: usize = 1600;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SettingsError {
+    Codec(CodecError),
+    /// Record written by a newer firmware
+    UnsupportedVersion(u16),
+}
+
+impl From<CodecError> for SettingsError {
+    fn from(error: CodecError) -> Self {
+        SettingsError::Codec(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Settings {
+    pub calibration: Calibration,
+    pub deadzones: Deadzones,
+    pub curves: ResponseCurves,
+    pub socd_mode: SocdMode,
+    pub guide_mode: GuideMode,
+    pub usb_mode: UsbMode,
+    pub poll_rate: PollRate,
+    pub filters: AnalogFilters,
+    pub debounces: ButtonDebounces,
+    pub profiles: Profiles,
+    pub turbo: TurboRates,
+    pub macros: Macros,
+    pub motion: MotionSettings,
+    pub mag_calibration: MagCalibration,
+}
+
+impl Settings {
+    pub const fn new() -> Self {
+        Settings {
+            calibration: Calibration::new(),
+            deadzones: Deadzones::new(),
+            curves: ResponseCurves::new(),
+            socd_mode: SocdMode::Neutral,
+            guide_mode: GuideMode::Guide,
+            usb_mode: UsbMode::Hid,
+            poll_rate: PollRate::Hz1000,
+            filters: AnalogFilters::new(),
+            debounces: ButtonDebounces::new(),
+            profiles: Profiles::new(),
+            turbo: TurboRates::new(),
+            macros: Macros::new(),
+            motion: MotionSettings::new(),
+            mag_calibration: MagCalibration::IDENTITY,
+        }
+    }
+
+    /// Writes the record to `buffer`, returns the number of bytes used
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, SettingsError> {
+        let mut writer = Writer::new(buffer);
+        writer.u16(SETTINGS_VERSION)?;
+
+        for axis in &self.calibration.axes {
+            writer.u16(axis.min)?;
+            writer.u16(axis.center)?;
+            writer.u16(axis.max)?;
+        }
+
+        for stick in [&self.deadzones.left_stick, &self.deadzones.right_stick] {
+            writer.u8(stick.mode.as_u8())?;
+            writer.f32(stick.inner)?;
+            writer.f32(stick.outer)?;
+            writer.f32(stick.anti_deadzone)?;
+        }
+
+        for curve in &self.curves.axes {
+            write_curve(&mut writer, curve)?;
+        }
+
+        writer.u8(self.socd_mode.as_u8())?;
+        writer.u8(self.guide_mode.as_u8())?;
+        writer.u8(self.usb_mode.as_u8())?;
+        writer.u8(self.poll_rate.as_u8())?;
+
+        for filter in &self.filters.axes {
+            write_filter(&mut writer, filter)?;
+        }
+
+        for debounce in &self.debounces.buttons {
+            writer.u8(debounce.mode.as_u8())?;
+            writer.u8(debounce.time_ms)?;
+        }
+
+        // Only the profiles in use are written
+        writer.u8(self.profiles.count)?;
+        writer.u8(self.profiles.active)?;
+        writer.u16(self.profiles.switch_combo.0)?;
+        for profile in &self.profiles.profiles[..self.profiles.count as usize] {
+            write_profile(&mut writer, profile)?;
+        }
+
+        writer.bytes(&self.turbo.buttons)?;
+        for slot in &self.macros.slots {
+            write_macro(&mut writer, slot.as_ref())?;
+        }
+
+        write_motion(&mut writer, &self.motion)?;
+
+        for value in self
+            .mag_calibration
+            .offset
+            .iter()
+            .chain(self.mag_calibration.matrix.iter().flatten())
+        {
+            writer.f32(*value)?;
+        }
+
+        Ok(writer.position())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Settings, SettingsError> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.u16()?;
+        if version == 0 || version > SETTINGS_VERSION {
+            return Err(SettingsError::UnsupportedVersion(version));
+        }
+
+        let mut settings = Settings::new();
+
+        for axis in settings.calibration.axes.iter_mut() {
+            let min = reader.u16()?;
+            let center = reader.u16()?;
+            let max = reader.u16()?;
+            if min > center || center > max {
+                return Err(CodecError::InvalidValue.into());
+            }
+            *axis = AxisCalibration::new(min, center, max);
+        }
+
+        settings.deadzones.left_stick = read_stick_deadzone(&mut reader)?;
+        settings.deadzones.right_stick = read_stick_deadzone(&mut reader)?;
+
+        for curve in settings.curves.axes.iter_mut() {
+            *curve = read_curve(&mut reader)?;
+        }
+
+        settings.socd_mode = SocdMode::from_u8(reader.u8()?).ok_or(CodecError::InvalidValue)?;
+        settings.guide_mode = GuideMode::from_u8(reader.u8()?).ok_or(CodecError::InvalidValue)?;
+        settings.usb_mode = UsbMode::from_u8(reader.u8()?).ok_or(CodecError::InvalidValue)?;
+        settings.poll_rate = PollRate::from_u8(reader.u8()?).ok_or(CodecError::InvalidValue)?;
+
+        for filter in settings.filters.axes.iter_mut() {
+            *filter = read_filter(&mut reader)?;
+        }
+
+        for debounce in settings.debounces.buttons.iter_mut() {
+            let mode = DebounceMode::from_u8(reader.u8()?).ok_or(CodecError::InvalidValue)?;
+            *debounce = ButtonDebounce::new(mode, reader.u8()?);
+        }
+
+        settings.profiles = read_profiles(&mut reader)?;
+
+        settings
+            .turbo
+            .buttons
+            .copy_from_slice(reader.bytes(Button::COUNT)?);
+        if !settings.turbo.is_valid() {
+            return Err(CodecError::InvalidValue.into());
+        }
+        for slot in settings.macros.slots.iter_mut() {
+            *slot = read_macro(&mut reader)?;
+        }
+
+        settings.motion = read_motion(&mut reader)?;
+
+        let calibration = &mut settings.mag_calibration;
+        for value in calibration
+            .offset
+            .iter_mut()
+            .chain(calibration.matrix.iter_mut().flatten())
+        {
+            *value = reader.f32()?;
+        }
+        if !calibration.is_valid() {
+            return Err(CodecError::InvalidValue.into());
+        }
+
+        Ok(settings)
+    }
+}
+
+fn read_stick_deadzone(reader: &mut Reader) -> Result<StickDeadzone, SettingsError> {
+    let mode = DeadzoneMode::from_u8(reader.u8()?).ok_or(CodecError::InvalidValue)?;
+    let deadzone = StickDeadzone {
+        mode,
+        inner: reader.f32()?,
+        outer: reader.f32()?,
+        anti_deadzone: reader.f32()?,
+    };
+    if !deadzone.is_valid() {
+        return Err(CodecError::InvalidValue.into());
+    }
+    Ok(deadzone)
+}
+
+const CURVE_LINEAR: u8 = 0;
+const CURVE_EXPONENTIAL: u8 = 1;
+const CURVE_S_CURVE: u8 = 2;
+const CURVE_CUSTOM: u8 = 3;
+
+fn write_curve(writer: &mut Writer, curve: &ResponseCurve) -> Result<(), SettingsError> {
+    match curve {
+        ResponseCurve::Linear => writer.u8(CURVE_LINEAR)?,
+        ResponseCurve::Exponential { exponent } => {
+            writer.u8(CURVE_EXPONENTIAL)?;
+            writer.f32(*exponent)?;
+        }
+        ResponseCurve::SCurve { strength } => {
+            writer.u8(CURVE_S_CURVE)?;
+            writer.f32(*strength)?;
+        }
+        ResponseCurve::Custom(table) => {
+            writer.u8(CURVE_CUSTOM)?;
+            writer.u8(table.points().len() as u8)?;
+            for (x, y) in table.points() {
+                writer.f32(*x)?;
+                writer.f32(*y)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_curve(reader: &mut Reader) -> Result<ResponseCurve, SettingsError> {
+    let curve = match reader.u8()? {
+        CURVE_LINEAR => ResponseCurve::Linear,
+        CURVE_EXPONENTIAL => ResponseCurve::Exponential {
+            exponent: reader.f32()?,
+        },
+        CURVE_S_CURVE => ResponseCurve::SCurve {
+            strength: reader.f32()?,
+        },
+        CURVE_CUSTOM => {
+            let len = reader.u8()? as usize;
+            if len > MAX_CURVE_POINTS {
+                return Err(CodecError::InvalidValue.into());
+            }
+            let mut points = [(0f32, 0f32); MAX_CURVE_POINTS];
+            for point in points[..len].iter_mut() {
+                *point = (reader.f32()?, reader.f32()?);
+            }
+            let table = CurveTable::new(&points[..len]).ok_or(CodecError::InvalidValue)?;
+            ResponseCurve::Custom(table)
+        }
+        _ => return Err(CodecError::InvalidValue.into()),
+    };
+    if !curve.is_valid() {
+        return Err(CodecError::InvalidValue.into());
+    }
+    Ok(curve)
+}
+
+const FILTER_NONE: u8 = 0;
+const FILTER_AVERAGE: u8 = 1;
+const FILTER_EXPONENTIAL: u8 = 2;
+const FILTER_ONE_EURO: u8 = 3;
+const FILTER_HYSTERESIS: u8 = 4;
+
+fn write_filter(writer: &mut Writer, filter: &AnalogFilter) -> Result<(), SettingsError> {
+    match filter {
+        AnalogFilter::None => writer.u8(FILTER_NONE)?,
+        AnalogFilter::Average { samples } => {
+            writer.u8(FILTER_AVERAGE)?;
+            writer.u8(*samples)?;
+        }
+        AnalogFilter::Exponential { alpha } => {
+            writer.u8(FILTER_EXPONENTIAL)?;
+            writer.f32(*alpha)?;
+        }
+        AnalogFilter::OneEuro { min_cutoff, beta } => {
+            writer.u8(FILTER_ONE_EURO)?;
+            writer.f32(*min_cutoff)?;
+            writer.f32(*beta)?;
+        }
+        AnalogFilter::Hysteresis { threshold } => {
+            writer.u8(FILTER_HYSTERESIS)?;
+            writer.u16(*threshold)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_filter(reader: &mut Reader) -> Result<AnalogFilter, SettingsError> {
+    let filter = match reader.u8()? {
+        FILTER_NONE => AnalogFilter::None,
+        FILTER_AVERAGE => AnalogFilter::Average {
+            samples: reader.u8()?,
+        },
+        FILTER_EXPONENTIAL => AnalogFilter::Exponential {
+            alpha: reader.f32()?,
+        },
+        FILTER_ONE_EURO => AnalogFilter::OneEuro {
+            min_cutoff: reader.f32()?,
+            beta: reader.f32()?,
+        },
+        FILTER_HYSTERESIS => AnalogFilter::Hysteresis {
+            threshold: reader.u16()?,
+        },
+        _ => return Err(CodecError::InvalidValue.into()),
+    };
+    if !filter.is_valid() {
+        return Err(CodecError::InvalidValue.into());
+    }
+    Ok(filter)
+}
+
+const BINDING_UNBOUND: u8 = 0;
+const BINDING_BUTTON: u8 = 1;
+const BINDING_AXIS: u8 = 2;
+const BINDING_BUTTONS: u8 = 3;
+/// Missing button of [`AxisBinding::Buttons`]
+const NO_BUTTON: u8 = 0xFF;
+
+fn write_profile(writer: &mut Writer, profile: &Profile) -> Result<(), SettingsError> {
+    for binding in &profile.buttons {
+        match binding {
+            ButtonBinding::Unbound => writer.u8(BINDING_UNBOUND)?,
+            ButtonBinding::Button(button) => {
+                writer.u8(BINDING_BUTTON)?;
+                writer.u8(button.index() as u8)?;
+            }
+            ButtonBinding::Axis { input, threshold } => {
+                writer.u8(BINDING_AXIS)?;
+                writer.u8(input.index() as u8)?;
+                writer.f32(*threshold)?;
+            }
+        }
+    }
+
+    for binding in &profile.axes {
+        match binding {
+            AxisBinding::Unbound => writer.u8(BINDING_UNBOUND)?,
+            AxisBinding::Axis { input, inverted } => {
+                writer.u8(BINDING_AXIS)?;
+                writer.u8(input.index() as u8)?;
+                writer.bool(*inverted)?;
+            }
+            AxisBinding::Buttons { negative, positive } => {
+                writer.u8(BINDING_BUTTONS)?;
+                for button in [negative, positive] {
+                    writer.u8(button.map_or(NO_BUTTON, |button| button.index() as u8))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_profiles(reader: &mut Reader) -> Result<Profiles, SettingsError> {
+    let mut profiles = Profiles::new();
+    profiles.count = reader.u8()?;
+    profiles.active = reader.u8()?;
+    profiles.switch_combo = ButtonSet::from_bits(reader.u16()?).ok_or(CodecError::InvalidValue)?;
+    if profiles.count as usize > MAX_PROFILES {
+        return Err(CodecError::InvalidValue.into());
+    }
+    for profile in profiles.profiles[..profiles.count as usize].iter_mut() {
+        *profile = read_profile(reader)?;
+    }
+    if !profiles.is_valid() {
+        return Err(CodecError::InvalidValue.into());
+    }
+    Ok(profiles)
+}
+
+fn read_profile(reader: &mut Reader) -> Result<Profile, SettingsError> {
+    let mut profile = Profile::IDENTITY;
+
+    for binding in profile.buttons.iter_mut() {
+        *binding = match reader.u8()? {
+            BINDING_UNBOUND => ButtonBinding::Unbound,
+            BINDING_BUTTON => ButtonBinding::Button(read_button(reader)?),
+            BINDING_AXIS => ButtonBinding::Axis {
+                input: read_input(reader)?,
+                threshold: reader.f32()?,
+            },
+            _ => return Err(CodecError::InvalidValue.into()),
+        };
+    }
+
+    for binding in profile.axes.iter_mut() {
+        *binding = match reader.u8()? {
+            BINDING_UNBOUND => AxisBinding::Unbound,
+            BINDING_AXIS => AxisBinding::Axis {
+                input: read_input(reader)?,
+                inverted: reader.bool()?,
+            },
+            BINDING_BUTTONS => {
+                let mut read_optional = || match reader.u8()? {
+                    NO_BUTTON => Ok(None),
+                    index => button_from_index(index).map(Some),
+                };
+                AxisBinding::Buttons {
+                    negative: read_optional()?,
+                    positive: read_optional()?,
+                }
+            }
+            _ => return Err(CodecError::InvalidValue.into()),
+        };
+    }
+
+    if !profile.is_valid() {
+        return Err(CodecError::InvalidValue.into());
+    }
+    Ok(profile)
+}
+
+fn read_button(reader: &mut Reader) -> Result<Button, SettingsError> {
+    button_from_index(reader.u8()?)
+}
+
+fn button_from_index(index: u8) -> Result<Button, SettingsError> {
+    Ok(*Button::ALL
+        .get(index as usize)
+        .ok_or(CodecError::InvalidValue)?)
+}
+
+fn read_input(reader: &mut Reader) -> Result<AnalogInput, SettingsError> {
+    input_from_index(reader.u8()?)
+}
+
+fn input_from_index(index: u8) -> Result<AnalogInput, SettingsError> {
+    Ok(*AnalogInput::ALL
+        .get(index as usize)
+        .ok_or(CodecError::InvalidValue)?)
+}
+
+/// Written for an empty macro slot, a used one starts with its number of steps
+const MACRO_EMPTY: u8 = 0;
+
+fn write_macro(writer: &mut Writer, macro_: Option<&Macro>) -> Result<(), SettingsError> {
+    let Some(macro_) = macro_ else {
+        writer.u8(MACRO_EMPTY)?;
+        return Ok(());
+    };
+
+    writer.u8(macro_.steps().len() as u8)?;
+    writer.u8(macro_.trigger.index() as u8)?;
+    for step in macro_.steps() {
+        writer.u16(step.buttons.0)?;
+        writer.u16(step.duration_ms)?;
+        writer.bool(step.left_stick.is_some())?;
+        if let Some((x, y)) = step.left_stick {
+            writer.f32(x)?;
+            writer.f32(y)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_macro(reader: &mut Reader) -> Result<Option<Macro>, SettingsError> {
+    let len = reader.u8()? as usize;
+    if len == MACRO_EMPTY as usize {
+        return Ok(None);
+    }
+    if len > MAX_MACRO_STEPS {
+        return Err(CodecError::InvalidValue.into());
+    }
+
+    let trigger = read_button(reader)?;
+    let mut steps = [MacroStep::wait(0); MAX_MACRO_STEPS];
+    for step in steps[..len].iter_mut() {
+        step.buttons = ButtonSet::from_bits(reader.u16()?).ok_or(CodecError::InvalidValue)?;
+        step.duration_ms = reader.u16()?;
+        if reader.bool()? {
+            step.left_stick = Some((reader.f32()?, reader.f32()?));
+        }
+    }
+    let macro_ = Macro::new(trigger, &steps[..len]).ok_or(CodecError::InvalidValue)?;
+    Ok(Some(macro_))
+}
+
+/// Axis of [`MotionSettings`] left alone
+const NO_INPUT: u8 = 0xFF;
+
+fn write_motion(writer: &mut Writer, motion: &MotionSettings) -> Result<(), SettingsError> {
+    writer.bool(motion.enabled)?;
+    for axis in [motion.roll_axis, motion.pitch_axis, motion.heading_axis] {
+        writer.u8(axis.map_or(NO_INPUT, |axis| axis.index() as u8))?;
+    }
+    writer.f32(motion.range)?;
+    writer.f32(motion.deadzone)?;
+    writer.f32(motion.rest_roll)?;
+    writer.f32(motion.rest_pitch)?;
+    writer.u8(motion
+        .toggle
+        .map_or(NO_BUTTON, |button| button.index() as u8))?;
+    Ok(())
+}
+
+fn read_motion(reader: &mut Reader) -> Result<MotionSettings, SettingsError> {
+    let enabled = reader.bool()?;
+    let mut read_axis = || match reader.u8()? {
+        NO_INPUT => Ok(None),
+        index => input_from_index(index).map(Some),
+    };
+    let (roll_axis, pitch_axis, heading_axis) = (read_axis()?, read_axis()?, read_axis()?);
+    let motion = MotionSettings {
+        enabled,
+        roll_axis,
+        pitch_axis,
+        heading_axis,
+        range: reader.f32()?,
+        deadzone: reader.f32()?,
+        rest_roll: reader.f32()?,
+        rest_pitch: reader.f32()?,
+        toggle: match reader.u8()? {
+            NO_BUTTON => None,
+            index => Some(button_from_index(index)?),
+        },
+    };
+    if !motion.is_valid() {
+        return Err(CodecError::InvalidValue.into());
+    }
+    Ok(motion)
+}
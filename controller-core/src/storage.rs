@@ -0,0 +1,243 @@
+//! Wear leveled settings store on top of erasable flash pages
+//!
+//! Records are appended one after the other in the active page. Once it is full,
+//! the next page is erased and becomes active, so every page is erased in turn.
+//! Each record carries a sequence number and a CRC: the valid record with the
+//! highest sequence is the current one, a record interrupted by a reset is skipped.
+//!
+//! Record layout, little endian, aligned on [`RECORD_ALIGN`] bytes:
+//!
+//! | magic: u16 | length: u16 | sequence: u32 | payload: [u8; length] | crc32: u32 |
+use crate::codec::crc32;
+use crate::settings::{Settings, MAX_SETTINGS_SIZE};
+
+pub const RECORD_MAGIC: u16 = 0x5E77;
+pub const RECORD_ALIGN: usize = 4;
+const HEADER_SIZE: usize = 8;
+const CRC_SIZE: usize = 4;
+const MAX_RECORD_SIZE: usize = HEADER_SIZE + MAX_SETTINGS_SIZE + CRC_SIZE;
+
+/// Flash area reserved for the store, addressed from its first byte
+pub trait FlashRegion {
+    type Error;
+
+    /// Size in bytes of an erasable page
+    fn page_size(&self) -> usize;
+
+    /// Number of pages reserved for the store, at least 2
+    fn page_count(&self) -> usize;
+
+    fn read(&mut self, address: usize, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Programs erased bytes.
+    /// `address` and `bytes.len()` are multiples of [`RECORD_ALIGN`].
+    fn write(&mut self, address: usize, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Sets every byte of the page to `0xFF`
+    fn erase_page(&mut self, page: usize) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StoreError<E> {
+    Flash(E),
+    /// The encoded settings do not fit in a page
+    TooLarge,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Position {
+    page: usize,
+    /// First free byte in the page
+    end: usize,
+    sequence: u32,
+}
+
+pub struct SettingsStore<F: FlashRegion> {
+    flash: F,
+    /// Where the next record goes, `None` until the flash was scanned
+    position: Option<Position>,
+}
+
+impl<F: FlashRegion> SettingsStore<F> {
+    pub fn new(flash: F) -> Self {
+        SettingsStore {
+            flash,
+            position: None,
+        }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Returns the last saved settings, or the defaults if no valid record is found
+    pub fn load(&mut self) -> Result<Settings, F::Error> {
+        // Address and sequence of the latest valid record
+        let mut latest: Option<(usize, u32)> = None;
+        let mut active = None;
+
+        for page in 0..self.flash.page_count() {
+            let mut page_latest: Option<(usize, u32)> = None;
+            let end = self.scan_page(page, |address, sequence| {
+                if page_latest.is_none_or(|(_, latest)| sequence > latest) {
+                    page_latest = Some((address, sequence));
+                }
+            })?;
+
+            if let Some((address, sequence)) = page_latest {
+                if latest.is_none_or(|(_, latest)| sequence > latest) {
+                    latest = Some((address, sequence));
+                    active = Some(Position {
+                        page,
+                        end,
+                        sequence,
+                    });
+                }
+            }
+        }
+
+        let settings = match latest {
+            Some((address, _)) => {
+                let mut buffer = [0u8; MAX_RECORD_SIZE];
+                let length = self.read_record(address, &mut buffer)?.unwrap_or(0);
+                Settings::decode(&buffer[HEADER_SIZE..HEADER_SIZE + length]).unwrap_or_default()
+            }
+            None => Settings::default(),
+        };
+
+        // Without any valid record the pages may hold garbage,
+        // mark the last one as full so the next save starts over in an erased first page
+        self.position = Some(active.unwrap_or(Position {
+            page: self.flash.page_count() - 1,
+            end: self.flash.page_size(),
+            sequence: 0,
+        }));
+
+        Ok(settings)
+    }
+
+    /// Appends a record holding `settings`, moving to the next page when the active one is full
+    pub fn save(&mut self, settings: &Settings) -> Result<(), StoreError<F::Error>> {
+        let position = match self.position {
+            Some(position) => position,
+            None => {
+                self.load().map_err(StoreError::Flash)?;
+                self.position.unwrap()
+            }
+        };
+
+        let mut buffer = [0xFFu8; MAX_RECORD_SIZE];
+        let length = settings
+            .encode(&mut buffer[HEADER_SIZE..HEADER_SIZE + MAX_SETTINGS_SIZE])
+            .map_err(|_| StoreError::TooLarge)?;
+        let record_size = align(HEADER_SIZE + length + CRC_SIZE);
+        let page_size = self.flash.page_size();
+        if record_size > page_size {
+            return Err(StoreError::TooLarge);
+        }
+
+        let sequence = position.sequence.wrapping_add(1);
+        buffer[0..2].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        buffer[2..4].copy_from_slice(&(length as u16).to_le_bytes());
+        buffer[4..8].copy_from_slice(&sequence.to_le_bytes());
+        let crc = crc32(&buffer[..HEADER_SIZE + length]);
+        buffer[HEADER_SIZE + length..HEADER_SIZE + length + CRC_SIZE]
+            .copy_from_slice(&crc.to_le_bytes());
+
+        let (page, offset) = if position.end + record_size <= page_size {
+            (position.page, position.end)
+        } else {
+            let next_page = (position.page + 1) % self.flash.page_count();
+            self.flash
+                .erase_page(next_page)
+                .map_err(StoreError::Flash)?;
+            (next_page, 0)
+        };
+
+        // Track the position before writing: after a failed write the space is no longer erased
+        self.position = Some(Position {
+            page,
+            end: offset + record_size,
+            sequence,
+        });
+        self.flash
+            .write(page * page_size + offset, &buffer[..record_size])
+            .map_err(StoreError::Flash)
+    }
+
+    /// Calls `on_record` with the address and sequence of every valid record of `page`,
+    /// returns the offset of the first free byte in the page
+    fn scan_page(
+        &mut self,
+        page: usize,
+        mut on_record: impl FnMut(usize, u32),
+    ) -> Result<usize, F::Error> {
+        let page_size = self.flash.page_size();
+        let page_start = page * page_size;
+        let mut offset = 0;
+
+        while offset + HEADER_SIZE + CRC_SIZE <= page_size {
+            let mut header = [0u8; HEADER_SIZE];
+            self.flash.read(page_start + offset, &mut header)?;
+            if header.iter().all(|byte| *byte == 0xFF) {
+                return Ok(offset);
+            }
+
+            let (magic, length, sequence) = parse_header(&header);
+            let record_size = align(HEADER_SIZE + length + CRC_SIZE);
+            if magic != RECORD_MAGIC
+                || length > MAX_SETTINGS_SIZE
+                || offset + record_size > page_size
+            {
+                // Not written by the store, nothing after it can be trusted
+                return Ok(page_size);
+            }
+
+            let mut buffer = [0u8; MAX_RECORD_SIZE];
+            if self
+                .read_record(page_start + offset, &mut buffer)?
+                .is_some()
+            {
+                on_record(page_start + offset, sequence);
+            }
+            offset += record_size;
+        }
+
+        Ok(page_size)
+    }
+
+    /// Reads the record at `address` into `buffer`,
+    /// returns its payload length if the CRC matches
+    fn read_record(
+        &mut self,
+        address: usize,
+        buffer: &mut [u8; MAX_RECORD_SIZE],
+    ) -> Result<Option<usize>, F::Error> {
+        self.flash.read(address, &mut buffer[..HEADER_SIZE])?;
+        let (_, length, _) = parse_header(&buffer[..HEADER_SIZE]);
+        if length > MAX_SETTINGS_SIZE {
+            return Ok(None);
+        }
+
+        let record = &mut buffer[..HEADER_SIZE + length + CRC_SIZE];
+        self.flash.read(address, record)?;
+        let mut crc = [0u8; CRC_SIZE];
+        crc.copy_from_slice(&record[HEADER_SIZE + length..]);
+        if u32::from_le_bytes(crc) == crc32(&record[..HEADER_SIZE + length]) {
+            Ok(Some(length))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn parse_header(header: &[u8]) -> (u16, usize, u32) {
+    let magic = u16::from_le_bytes([header[0], header[1]]);
+    let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let sequence = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    (magic, length, sequence)
+}
+
+fn align(size: usize) -> usize {
+    size.div_ceil(RECORD_ALIGN) * RECORD_ALIGN
+}
@@ -0,0 +1,178 @@
+//! XInput (Xbox 360 wired controller) reports
+//!
+//! XInput uses a vendor specific interface rather than HID, Windows games see it
+//! as an Xbox controller without any remapping.
+// see https://www.partsnotincluded.com/understanding-the-xbox-360-wired-controllers-usb-data/
+// see https://github.com/paroj/xpad/blob/master/xpad.c
+use crate::controller::ControllerState;
+use crate::report::stick_value;
+use libm::roundf;
+
+/// Vendor and product id of the wired Xbox 360 controller, needed by the Windows driver
+pub const XINPUT_VID: u16 = 0x045e;
+pub const XINPUT_PID: u16 = 0x028e;
+
+pub const XINPUT_INTERFACE_CLASS: u8 = 0xff;
+pub const XINPUT_INTERFACE_SUBCLASS: u8 = 0x5d;
+pub const XINPUT_INTERFACE_PROTOCOL: u8 = 0x01;
+
+/// Undocumented descriptor following the interface descriptor, type `0x21`
+/// like a HID descriptor. Declares the endpoint addresses and report sizes.
+#[rustfmt::skip]
+pub const XINPUT_UNKNOWN_DESCRIPTOR: &[u8] = &[
+    0x00, 0x01, 0x01, 0x25, // Unknown, always the same on genuine controllers
+    0x81, 0x14,             // IN endpoint 1, 20 bytes reports
+    0x00, 0x00, 0x00, 0x00,
+    0x13, 0x01, 0x08,       // OUT endpoint 1, 8 bytes reports
+    0x00, 0x00,
+];
+
+/// Descriptor type of [`XINPUT_UNKNOWN_DESCRIPTOR`]
+pub const XINPUT_UNKNOWN_DESCRIPTOR_TYPE: u8 = 0x21;
+
+/// Size of [`XInputReport`] once packed
+pub const XINPUT_REPORT_SIZE: u8 = 20;
+
+pub use packed::XInputReport;
+
+// Same packed_struct 0.3 derive bug as `crate::report::XboxJoystickReport`: the generated
+// impls discard a `&mut` borrow, which trips `unused_must_use`
+#[allow(unused_must_use)]
+mod packed {
+    /// Input report, sticks are signed with `y` pointing up, triggers are 8-bit
+    #[derive(Clone, Copy, Debug, Default, PackedStruct)]
+    #[packed_struct(endian = "lsb", size_bytes = "20")]
+    pub struct XInputReport {
+        #[packed_field]
+        pub message_type: u8,
+        #[packed_field]
+        pub size: u8,
+        #[packed_field]
+        pub buttons: u16,
+        #[packed_field]
+        pub left_trigger: u8,
+        #[packed_field]
+        pub right_trigger: u8,
+        #[packed_field]
+        pub left_x: i16,
+        #[packed_field]
+        pub left_y: i16,
+        #[packed_field]
+        pub right_x: i16,
+        #[packed_field]
+        pub right_y: i16,
+        #[packed_field]
+        pub reserved: [u8; 6],
+    }
+}
+
+/// Bits of [`XInputReport::buttons`], bit 11 is unused
+pub mod buttons {
+    pub const UP: u16 = 1 << 0;
+    pub const DOWN: u16 = 1 << 1;
+    pub const LEFT: u16 = 1 << 2;
+    pub const RIGHT: u16 = 1 << 3;
+    pub const START: u16 = 1 << 4;
+    pub const BACK: u16 = 1 << 5;
+    pub const LEFT_THUMB: u16 = 1 << 6;
+    pub const RIGHT_THUMB: u16 = 1 << 7;
+    pub const LEFT_SHOULDER: u16 = 1 << 8;
+    pub const RIGHT_SHOULDER: u16 = 1 << 9;
+    pub const GUIDE: u16 = 1 << 10;
+    pub const A: u16 = 1 << 12;
+    pub const B: u16 = 1 << 13;
+    pub const X: u16 = 1 << 14;
+    pub const Y: u16 = 1 << 15;
+}
+
+pub fn get_xinput_report(controller_state: &ControllerState) -> XInputReport {
+    let pressed = [
+        (controller_state.up, buttons::UP),
+        (controller_state.down, buttons::DOWN),
+        (controller_state.left, buttons::LEFT),
+        (controller_state.right, buttons::RIGHT),
+        (controller_state.start, buttons::START),
+        (controller_state.back, buttons::BACK),
+        (controller_state.left_thumb, buttons::LEFT_THUMB),
+        (controller_state.right_thumb, buttons::RIGHT_THUMB),
+        (controller_state.left_shoulder, buttons::LEFT_SHOULDER),
+        (controller_state.right_shoulder, buttons::RIGHT_SHOULDER),
+        (controller_state.guide, buttons::GUIDE),
+        (controller_state.a, buttons::A),
+        (controller_state.b, buttons::B),
+        (controller_state.x, buttons::X),
+        (controller_state.y, buttons::Y),
+    ];
+    let buttons = pressed
+        .iter()
+        .filter(|(pressed, _)| *pressed)
+        .fold(0, |buttons, (_, bit)| buttons | bit);
+
+    XInputReport {
+        message_type: 0x00,
+        size: XINPUT_REPORT_SIZE,
+        buttons,
+        left_trigger: xinput_trigger_value(controller_state.left_trigger),
+        right_trigger: xinput_trigger_value(controller_state.right_trigger),
+        left_x: stick_value(controller_state.left_thumb_x),
+        left_y: stick_value(controller_state.left_thumb_y),
+        right_x: stick_value(controller_state.right_thumb_x),
+        right_y: stick_value(controller_state.right_thumb_y),
+        reserved: [0; 6],
+    }
+}
+
+/// Scales a trigger from `0.0..=1.0` to the 8-bit XInput range
+pub fn xinput_trigger_value(value: f32) -> u8 {
+    roundf(value.clamp(0f32, 1f32) * u8::MAX as f32) as u8
+}
+
+/// Reports sent by the host on the OUT endpoint
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum XInputOutput {
+    /// Speed of the large (left) and small (right) rumble motors
+    Rumble { left: u8, right: u8 },
+    /// Ring of light animation, see the xpad driver for the values
+    Led(u8),
+}
+
+impl XInputOutput {
+    /// Returns `None` for unknown or truncated reports
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x00, 0x08, _, left, right, ..] if bytes.len() >= 8 => Some(XInputOutput::Rumble {
+                left: *left,
+                right: *right,
+            }),
+            [0x01, 0x03, pattern, ..] => Some(XInputOutput::Led(*pattern)),
+            _ => None,
+        }
+    }
+}
+
+/// USB device class exposed to the host, chosen at boot
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UsbMode {
+    /// Generic HID joystick, see [`crate::report`]
+    #[default]
+    Hid,
+    /// Xbox 360 controller, see [`XInputReport`]
+    XInput,
+}
+
+impl UsbMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(UsbMode::Hid),
+            1 => Some(UsbMode::XInput),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            UsbMode::Hid => 0,
+            UsbMode::XInput => 1,
+        }
+    }
+}
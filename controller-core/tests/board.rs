@@ -0,0 +1,165 @@
+use controller_core::board::{
+    ActiveLevel, Adc, AnalogPin, Board, BoardError, DigitalPin, PinId, Port, Pull,
+};
+use controller_core::input::{AnalogInput, Button};
+
+const fn button(port: Port, pin: u8, button: Button) -> DigitalPin {
+    DigitalPin {
+        pin: PinId::new(port, pin),
+        pull: Pull::Up,
+        active: ActiveLevel::Low,
+        button,
+    }
+}
+
+const fn analog(port: Port, pin: u8, adc: Adc, channel: u8, input: AnalogInput) -> AnalogPin {
+    AnalogPin {
+        pin: PinId::new(port, pin),
+        adc,
+        channel,
+        input,
+    }
+}
+
+const BOARD: Board = Board {
+    digital: &[
+        button(Port::D, 3, Button::A),
+        button(Port::D, 4, Button::B),
+        button(Port::C, 6, Button::Up),
+    ],
+    analog: &[
+        analog(Port::D, 10, Adc::Adc3, 7, AnalogInput::RightThumbX),
+        analog(Port::D, 8, Adc::Adc4, 12, AnalogInput::LeftThumbX),
+        analog(Port::D, 11, Adc::Adc3, 8, AnalogInput::RightThumbY),
+    ],
+};
+
+/// Sized at compile time from the table, like the firmware scans
+const ADC3_LEN: usize = BOARD.adc_channel_count(Adc::Adc3);
+
+fn with_digital(digital: &'static [DigitalPin]) -> Board {
+    Board { digital, ..BOARD }
+}
+
+fn with_analog(analog: &'static [AnalogPin]) -> Board {
+    Board { analog, ..BOARD }
+}
+
+#[test]
+fn valid_board_passes() {
+    assert_eq!(BOARD.validate(&[PinId::new(Port::A, 11)]), Ok(()));
+}
+
+#[test]
+fn lookups_find_controls() {
+    assert_eq!(
+        BOARD.digital_pin(Button::B).unwrap().pin,
+        PinId::new(Port::D, 4)
+    );
+    assert_eq!(BOARD.digital_pin(Button::Guide), None);
+    assert_eq!(
+        BOARD.analog_pin(AnalogInput::LeftThumbX).unwrap().channel,
+        12
+    );
+}
+
+#[test]
+fn adc_channels_keep_table_order() {
+    assert_eq!(ADC3_LEN, 2);
+    assert_eq!(
+        BOARD.adc_channels::<ADC3_LEN>(Adc::Adc3),
+        [(AnalogInput::RightThumbX, 7), (AnalogInput::RightThumbY, 8)]
+    );
+    assert_eq!(BOARD.adc_channel_count(Adc::Adc1), 0);
+}
+
+#[test]
+#[should_panic]
+fn adc_channels_check_scan_length() {
+    BOARD.adc_channels::<3>(Adc::Adc4);
+}
+
+#[test]
+fn active_level_sets_pressed_state() {
+    assert!(ActiveLevel::Low.is_active(false));
+    assert!(!ActiveLevel::Low.is_active(true));
+    assert!(ActiveLevel::High.is_active(true));
+}
+
+#[test]
+fn reserved_and_shared_pins_conflict() {
+    let pin = PinId::new(Port::D, 3);
+    assert_eq!(BOARD.validate(&[pin]), Err(BoardError::PinConflict(pin)));
+
+    let shared = with_analog({
+        const PINS: &[AnalogPin] = &[analog(Port::D, 4, Adc::Adc3, 7, AnalogInput::Other0)];
+        PINS
+    });
+    assert_eq!(
+        shared.validate(&[]),
+        Err(BoardError::PinConflict(PinId::new(Port::D, 4)))
+    );
+}
+
+#[test]
+fn invalid_pins_and_channels_are_rejected() {
+    let board = with_digital({
+        const PINS: &[DigitalPin] = &[button(Port::B, 16, Button::A)];
+        PINS
+    });
+    assert_eq!(
+        board.validate(&[]),
+        Err(BoardError::InvalidPin(PinId::new(Port::B, 16)))
+    );
+
+    let board = with_analog({
+        const PINS: &[AnalogPin] = &[analog(Port::D, 10, Adc::Adc3, 0, AnalogInput::Other0)];
+        PINS
+    });
+    assert_eq!(
+        board.validate(&[]),
+        Err(BoardError::InvalidChannel {
+            adc: Adc::Adc3,
+            channel: 0
+        })
+    );
+}
+
+#[test]
+fn controls_are_driven_once() {
+    let board = with_digital({
+        const PINS: &[DigitalPin] = &[button(Port::D, 3, Button::A), button(Port::D, 4, Button::A)];
+        PINS
+    });
+    assert_eq!(
+        board.validate(&[]),
+        Err(BoardError::DuplicateButton(Button::A))
+    );
+
+    let board = with_analog({
+        const PINS: &[AnalogPin] = &[
+            analog(Port::D, 10, Adc::Adc3, 7, AnalogInput::Other0),
+            analog(Port::D, 11, Adc::Adc3, 7, AnalogInput::Other1),
+        ];
+        PINS
+    });
+    assert_eq!(
+        board.validate(&[]),
+        Err(BoardError::ChannelConflict {
+            adc: Adc::Adc3,
+            channel: 7
+        })
+    );
+
+    let board = with_analog({
+        const PINS: &[AnalogPin] = &[
+            analog(Port::D, 10, Adc::Adc3, 7, AnalogInput::Other0),
+            analog(Port::D, 11, Adc::Adc4, 7, AnalogInput::Other0),
+        ];
+        PINS
+    });
+    assert_eq!(
+        board.validate(&[]),
+        Err(BoardError::DuplicateInput(AnalogInput::Other0))
+    );
+}
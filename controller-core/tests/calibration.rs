@@ -0,0 +1,174 @@
+use controller_core::calibration::{
+    AxisCalibration, Calibration, CalibrationRoutine, CalibrationStep, MAX_CENTER_SAMPLES,
+};
+use controller_core::controller::ControllerState;
+use controller_core::input::AnalogInput;
+
+#[test]
+fn uncalibrated_axis_spans_adc_range() {
+    let axis = AxisCalibration::UNCALIBRATED;
+    assert_eq!(axis.apply(0), -1.0);
+    assert_eq!(axis.apply(2048), 0.0);
+    assert_eq!(axis.apply(4095), 1.0);
+}
+
+#[test]
+fn off_center_axis_reaches_full_deflection() {
+    let axis = AxisCalibration::new(400, 1500, 3600);
+    assert_eq!(axis.apply(1500), 0.0);
+    assert_eq!(axis.apply(400), -1.0);
+    assert_eq!(axis.apply(3600), 1.0);
+    assert_eq!(axis.apply(950), -0.5);
+    assert_eq!(axis.apply(2550), 0.5);
+}
+
+#[test]
+fn values_past_recorded_range_are_clamped() {
+    let axis = AxisCalibration::new(400, 1500, 3600);
+    assert_eq!(axis.apply(0), -1.0);
+    assert_eq!(axis.apply(4095), 1.0);
+}
+
+#[test]
+fn trigger_resting_at_min_only_moves_positive() {
+    let axis = AxisCalibration::new(200, 200, 3800);
+    assert_eq!(axis.apply(100), 0.0);
+    assert_eq!(axis.apply(200), 0.0);
+    assert_eq!(axis.apply(3800), 1.0);
+}
+
+fn buttons(start: bool, back: bool, a: bool, b: bool) -> ControllerState {
+    let mut state = ControllerState::new();
+    state.start = start;
+    state.back = back;
+    state.a = a;
+    state.b = b;
+    state
+}
+
+const REST: [u16; AnalogInput::COUNT] = [1900, 2100, 2000, 2000, 300, 350, 2048, 2048];
+
+fn start_routine(routine: &mut CalibrationRoutine, current: &Calibration) {
+    for _ in 0..3 {
+        assert_eq!(
+            routine.update(&REST, &buttons(true, true, false, false), current),
+            None
+        );
+    }
+    assert_eq!(routine.step(), CalibrationStep::Center);
+}
+
+#[test]
+fn combo_must_be_held() {
+    let current = Calibration::new();
+    let mut routine = CalibrationRoutine::new(3);
+
+    routine.update(&REST, &buttons(true, true, false, false), &current);
+    routine.update(&REST, &buttons(true, false, false, false), &current);
+    routine.update(&REST, &buttons(true, true, false, false), &current);
+    routine.update(&REST, &buttons(true, true, false, false), &current);
+    assert!(!routine.is_active());
+
+    routine.update(&REST, &buttons(true, true, false, false), &current);
+    assert!(routine.is_active());
+}
+
+#[test]
+fn guided_routine_records_center_and_range() {
+    let current = Calibration::new();
+    let mut routine = CalibrationRoutine::new(3);
+    let idle = buttons(false, false, false, false);
+    let confirm = buttons(false, false, true, false);
+    start_routine(&mut routine, &current);
+
+    routine.update(&REST, &idle, &current);
+    routine.update(&REST, &confirm, &current);
+    assert_eq!(routine.step(), CalibrationStep::Range);
+    assert_eq!(routine.calibrated_axes(), 0);
+
+    // A still held from the center step must not confirm the range
+    let low = [100, 150, 200, 250, 300, 350, 2048, 2048];
+    assert_eq!(routine.update(&low, &confirm, &current), None);
+    let high = [4000, 3950, 3900, 3850, 3800, 3750, 2048, 2048];
+    routine.update(&high, &idle, &current);
+    assert_eq!(routine.calibrated_axes(), 6);
+
+    let calibration = routine.update(&REST, &confirm, &current).unwrap();
+    assert!(!routine.is_active());
+    assert_eq!(
+        *calibration.axis(AnalogInput::LeftThumbX),
+        AxisCalibration::new(100, 1900, 4000)
+    );
+    assert_eq!(
+        *calibration.axis(AnalogInput::LeftTrigger),
+        AxisCalibration::new(300, 300, 3800)
+    );
+    // Unconnected inputs did not move and keep their previous calibration
+    assert_eq!(
+        *calibration.axis(AnalogInput::Other0),
+        AxisCalibration::UNCALIBRATED
+    );
+}
+
+#[test]
+fn center_is_averaged_over_the_step() {
+    let current = Calibration::new();
+    let mut routine = CalibrationRoutine::new(3);
+    start_routine(&mut routine, &current);
+
+    let mut rest = REST;
+    rest[0] = 1800;
+    routine.update(&rest, &buttons(false, false, false, false), &current);
+    rest[0] = 2000;
+    routine.update(&rest, &buttons(false, false, true, false), &current);
+
+    let mut full = REST;
+    full[0] = 0;
+    routine.update(&full, &buttons(false, false, false, false), &current);
+    full[0] = 4095;
+    routine.update(&full, &buttons(false, false, false, false), &current);
+    let calibration = routine
+        .update(&REST, &buttons(false, false, true, false), &current)
+        .unwrap();
+
+    assert_eq!(calibration.axis(AnalogInput::LeftThumbX).center, 1900);
+}
+
+#[test]
+fn center_stops_averaging_at_the_cap() {
+    let current = Calibration::new();
+    let mut routine = CalibrationRoutine::new(3);
+    start_routine(&mut routine, &current);
+
+    let idle = buttons(false, false, false, false);
+    let mut rest = [4095; AnalogInput::COUNT];
+    for _ in 0..MAX_CENTER_SAMPLES {
+        routine.update(&rest, &idle, &current);
+    }
+    // Left on the step, later samples no longer count
+    rest = [0; AnalogInput::COUNT];
+    for _ in 0..MAX_CENTER_SAMPLES {
+        routine.update(&rest, &idle, &current);
+    }
+    routine.update(&rest, &buttons(false, false, true, false), &current);
+
+    routine.update(&[0; AnalogInput::COUNT], &idle, &current);
+    routine.update(&[4095; AnalogInput::COUNT], &idle, &current);
+    let calibration = routine
+        .update(&REST, &buttons(false, false, true, false), &current)
+        .unwrap();
+    assert_eq!(calibration.axis(AnalogInput::LeftThumbX).center, 4095);
+}
+
+#[test]
+fn b_cancels_routine() {
+    let current = Calibration::new();
+    let mut routine = CalibrationRoutine::new(3);
+    start_routine(&mut routine, &current);
+
+    assert_eq!(
+        routine.update(&REST, &buttons(false, false, false, true), &current),
+        None
+    );
+    assert_eq!(routine.step(), CalibrationStep::Idle);
+}
@@ -0,0 +1,151 @@
+//! Mock hardware shared by the host tests
+#![allow(dead_code)]
+
+use controller_core::input::{AnalogInput, AnalogSource, Button, DigitalSource};
+use controller_core::report::{ReportSink, XboxJoystickReport};
+use controller_core::storage::{FlashRegion, RECORD_ALIGN};
+
+/// ADC returning fixed raw values, one per input
+pub struct MockAdc {
+    pub values: [u16; AnalogInput::COUNT],
+    pub failing: Option<AnalogInput>,
+    /// Number of calls to `begin_sample`
+    pub samples: u32,
+}
+
+impl MockAdc {
+    pub fn centered() -> Self {
+        MockAdc {
+            values: [2048; AnalogInput::COUNT],
+            failing: None,
+            samples: 0,
+        }
+    }
+
+    pub fn set(&mut self, input: AnalogInput, raw: u16) {
+        self.values[input.index()] = raw;
+    }
+}
+
+impl AnalogSource for MockAdc {
+    type Error = ();
+
+    fn read_raw(&mut self, input: AnalogInput) -> Result<u16, ()> {
+        if self.failing == Some(input) {
+            return Err(());
+        }
+        Ok(self.values[input.index()])
+    }
+
+    fn begin_sample(&mut self) -> Result<(), ()> {
+        self.samples += 1;
+        Ok(())
+    }
+}
+
+/// GPIOs returning fixed button states
+#[derive(Default)]
+pub struct MockGpio {
+    pub pressed: [bool; Button::COUNT],
+}
+
+impl MockGpio {
+    pub fn press(&mut self, button: Button) {
+        self.pressed[button.index()] = true;
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.pressed[button.index()] = false;
+    }
+}
+
+impl DigitalSource for MockGpio {
+    type Error = core::convert::Infallible;
+
+    fn is_pressed(&mut self, button: Button) -> Result<bool, Self::Error> {
+        Ok(self.pressed[button.index()])
+    }
+}
+
+/// Records every report written to it
+#[derive(Default)]
+pub struct MockSink {
+    pub reports: Vec<XboxJoystickReport>,
+}
+
+impl ReportSink for MockSink {
+    type Error = ();
+
+    fn write_report(&mut self, report: &XboxJoystickReport) -> Result<(), ()> {
+        self.reports.push(*report);
+        Ok(())
+    }
+}
+
+/// In-memory flash enforcing the programming rules of the STM32 flash
+pub struct MockFlash {
+    pub data: Vec<u8>,
+    pub page_size: usize,
+    pub erase_counts: Vec<u32>,
+    /// Simulates a reset during the next write: only this many bytes get programmed
+    pub interrupt_write_after: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WriteInterrupted;
+
+impl MockFlash {
+    pub fn new(page_size: usize, page_count: usize) -> Self {
+        MockFlash {
+            data: vec![0xFF; page_size * page_count],
+            page_size,
+            erase_counts: vec![0; page_count],
+            interrupt_write_after: None,
+        }
+    }
+}
+
+impl FlashRegion for MockFlash {
+    type Error = WriteInterrupted;
+
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn page_count(&self) -> usize {
+        self.erase_counts.len()
+    }
+
+    fn read(&mut self, address: usize, bytes: &mut [u8]) -> Result<(), WriteInterrupted> {
+        bytes.copy_from_slice(&self.data[address..address + bytes.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, address: usize, bytes: &[u8]) -> Result<(), WriteInterrupted> {
+        assert_eq!(address % RECORD_ALIGN, 0);
+        assert_eq!(bytes.len() % RECORD_ALIGN, 0);
+        let target = &mut self.data[address..address + bytes.len()];
+        assert!(
+            target.iter().all(|byte| *byte == 0xFF),
+            "programming non erased flash at {address:#x}"
+        );
+
+        match self.interrupt_write_after.take() {
+            Some(written) => {
+                target[..written].copy_from_slice(&bytes[..written]);
+                Err(WriteInterrupted)
+            }
+            None => {
+                target.copy_from_slice(bytes);
+                Ok(())
+            }
+        }
+    }
+
+    fn erase_page(&mut self, page: usize) -> Result<(), WriteInterrupted> {
+        let start = page * self.page_size;
+        self.data[start..start + self.page_size].fill(0xFF);
+        self.erase_counts[page] += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use controller_core::compass::{
+    accel_counts, mag_counts, AccelRange, AccelRate, CompassConfig, MagGain, MagRate, CRA_REG_M,
+    CRB_REG_M, CTRL_REG1_A, CTRL_REG3_A, CTRL_REG4_A, MR_REG_M,
+};
+
+#[test]
+fn default_config_registers() {
+    let config = CompassConfig::new();
+    assert_eq!(
+        config.accel_registers(),
+        [
+            (CTRL_REG1_A, 0x77),
+            (CTRL_REG3_A, 0x10),
+            (CTRL_REG4_A, 0x88)
+        ]
+    );
+    assert_eq!(
+        config.mag_registers(),
+        [(CRA_REG_M, 0x18), (CRB_REG_M, 0x20), (MR_REG_M, 0x00)]
+    );
+}
+
+#[test]
+fn registers_follow_the_config() {
+    let config = CompassConfig {
+        accel_range: AccelRange::G16,
+        accel_rate: AccelRate::Hz1344,
+        mag_gain: MagGain::Gauss8_1,
+        mag_rate: MagRate::Hz220,
+    };
+    let accel = config.accel_registers();
+    assert_eq!(accel[0], (CTRL_REG1_A, 0x97));
+    assert_eq!(accel[2], (CTRL_REG4_A, 0xB8));
+    let mag = config.mag_registers();
+    assert_eq!(mag[0], (CRA_REG_M, 0x1C));
+    assert_eq!(mag[1], (CRB_REG_M, 0xE0));
+}
+
+#[test]
+fn accel_is_scaled_by_range() {
+    // 1 g lying flat, as a left justified 12-bit value
+    assert_eq!(
+        AccelRange::G2.to_g([0, 1000 << 4, -1000 << 4]),
+        [0.0, 1.0, -1.0]
+    );
+    assert_eq!(AccelRange::G4.to_g([500 << 4, 0, 0]), [1.0, 0.0, 0.0]);
+    // The largest count reads close to the end of every range but the ±16 g one
+    for range in AccelRange::ALL {
+        let [full, _, _] = range.to_g([i16::MAX, 0, 0]);
+        assert!(full >= range.g(), "{:?} reads {}", range, full);
+    }
+}
+
+#[test]
+fn mag_z_is_less_sensitive() {
+    assert_eq!(
+        MagGain::Gauss1_3.to_gauss([1100, -550, 980]),
+        [1.0, -0.5, 1.0]
+    );
+    assert_eq!(MagGain::Gauss8_1.to_gauss([230, 0, 205]), [1.0, 0.0, 1.0]);
+}
+
+#[test]
+fn output_registers_are_decoded() {
+    assert_eq!(
+        accel_counts(&[0x10, 0x00, 0xF0, 0xFF, 0x00, 0x40]),
+        [16, -16, 0x4000]
+    );
+    // x, z then y, high byte first
+    assert_eq!(
+        mag_counts(&[0x01, 0x00, 0xFF, 0xFE, 0x00, 0x02]),
+        [256, 2, -2]
+    );
+}
+
+#[test]
+fn rates_in_hz() {
+    assert_eq!(AccelRate::Hz400.hz(), 400.0);
+    assert_eq!(MagRate::Hz0_75.hz(), 0.75);
+}
@@ -0,0 +1,280 @@
+use controller_core::config::{
+    Action, Command, ConfigServer, Request, Response, Status, VersionInfo, MAX_CHUNK_SIZE,
+    PROTOCOL_VERSION, REPORT_SIZE,
+};
+use controller_core::deadzone::DeadzoneMode;
+use controller_core::settings::{Settings, MAX_SETTINGS_SIZE, SETTINGS_VERSION};
+
+const FIRMWARE: [u8; 3] = [0, 1, 0];
+
+/// Sends `request` to `server`, returns the response report and the action
+fn send(
+    server: &mut ConfigServer,
+    request: Request,
+    settings: &mut Settings,
+) -> ([u8; REPORT_SIZE], Action) {
+    server.handle(&request.encode().unwrap(), settings, FIRMWARE)
+}
+
+/// Reads the whole settings record chunk by chunk, as a host would
+fn read_record(server: &mut ConfigServer, settings: &mut Settings) -> Vec<u8> {
+    let mut record = Vec::new();
+    loop {
+        let offset = record.len() as u16;
+        let (report, _) = send(server, Request::ReadSettings { offset }, settings);
+        match Response::decode(&report, Command::ReadSettings).unwrap() {
+            Response::Settings { length, data, .. } => {
+                record.extend_from_slice(data);
+                if record.len() == length as usize {
+                    return record;
+                }
+            }
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+}
+
+fn write_record(
+    server: &mut ConfigServer,
+    settings: &mut Settings,
+    record: &[u8],
+    save: bool,
+) -> Action {
+    for (index, chunk) in record.chunks(MAX_CHUNK_SIZE).enumerate() {
+        let offset = (index * MAX_CHUNK_SIZE) as u16;
+        let request = Request::WriteSettings {
+            offset,
+            data: chunk,
+        };
+        let (report, action) = send(server, request, settings);
+        assert_eq!(
+            Response::decode(&report, Command::WriteSettings),
+            Ok(Response::Done)
+        );
+        assert_eq!(action, Action::None);
+    }
+    let commit = Request::CommitSettings {
+        length: record.len() as u16,
+        save,
+    };
+    let (report, action) = send(server, commit, settings);
+    assert_eq!(
+        Response::decode(&report, Command::CommitSettings),
+        Ok(Response::Done)
+    );
+    action
+}
+
+#[test]
+fn requests_round_trip() {
+    let data = [1, 2, 3];
+    let requests = [
+        Request::GetVersion,
+        Request::ReadSettings { offset: 300 },
+        Request::WriteSettings {
+            offset: 56,
+            data: &data,
+        },
+        Request::CommitSettings {
+            length: 700,
+            save: true,
+        },
+        Request::StartCalibration,
+    ];
+    for request in requests {
+        let report = request.encode().unwrap();
+        assert_eq!(report[0], PROTOCOL_VERSION);
+        assert_eq!(Request::decode(&report), Ok(request));
+    }
+}
+
+#[test]
+fn chunks_longer_than_a_report_are_rejected() {
+    let data = [0; MAX_CHUNK_SIZE + 1];
+    let request = Request::WriteSettings {
+        offset: 0,
+        data: &data,
+    };
+    assert!(request.encode().is_err());
+
+    let mut report = Request::WriteSettings {
+        offset: 0,
+        data: &[],
+    }
+    .encode()
+    .unwrap();
+    report[4] = MAX_CHUNK_SIZE as u8 + 1;
+    assert_eq!(Request::decode(&report), Err(Status::InvalidRequest));
+}
+
+#[test]
+fn reports_the_versions() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+    let (report, action) = send(&mut server, Request::GetVersion, &mut settings);
+    assert_eq!(action, Action::None);
+    assert_eq!(
+        Response::decode(&report, Command::GetVersion),
+        Ok(Response::Version(VersionInfo {
+            firmware: FIRMWARE,
+            settings_version: SETTINGS_VERSION,
+            max_settings_size: MAX_SETTINGS_SIZE as u16,
+        }))
+    );
+}
+
+#[test]
+fn reads_the_encoded_settings() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+    settings.deadzones.left_stick.inner = 0.2;
+
+    let mut expected = [0; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut expected).unwrap();
+    let record = read_record(&mut server, &mut settings);
+    assert!(record.len() > MAX_CHUNK_SIZE);
+    assert_eq!(record, &expected[..length]);
+}
+
+#[test]
+fn reading_past_the_record_fails() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+    let (report, _) = send(
+        &mut server,
+        Request::ReadSettings { offset: 1500 },
+        &mut settings,
+    );
+    assert_eq!(
+        Response::decode(&report, Command::ReadSettings),
+        Err(Status::InvalidRequest)
+    );
+}
+
+#[test]
+fn committed_settings_replace_the_current_ones() {
+    let mut server = ConfigServer::new();
+    let mut new_settings = Settings::new();
+    new_settings.deadzones.right_stick.mode = DeadzoneMode::Axial;
+    new_settings.deadzones.right_stick.outer = 0.9;
+    let mut record = [0; MAX_SETTINGS_SIZE];
+    let length = new_settings.encode(&mut record).unwrap();
+
+    let mut settings = Settings::new();
+    let action = write_record(&mut server, &mut settings, &record[..length], true);
+    assert_eq!(action, Action::Apply { save: true });
+    assert_eq!(settings, new_settings);
+}
+
+#[test]
+fn writes_are_only_applied_on_commit() {
+    let mut server = ConfigServer::new();
+    let mut new_settings = Settings::new();
+    new_settings.deadzones.left_stick.inner = 0.3;
+    let mut record = [0; MAX_SETTINGS_SIZE];
+    new_settings.encode(&mut record).unwrap();
+
+    let mut settings = Settings::new();
+    let request = Request::WriteSettings {
+        offset: 0,
+        data: &record[..MAX_CHUNK_SIZE],
+    };
+    send(&mut server, request, &mut settings);
+    assert_eq!(settings, Settings::new());
+}
+
+#[test]
+fn invalid_records_are_not_committed() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+    let data = [0xFF; 8];
+    send(
+        &mut server,
+        Request::WriteSettings {
+            offset: 0,
+            data: &data,
+        },
+        &mut settings,
+    );
+
+    let commit = Request::CommitSettings {
+        length: data.len() as u16,
+        save: false,
+    };
+    let (report, action) = send(&mut server, commit, &mut settings);
+    assert_eq!(action, Action::None);
+    assert_eq!(
+        Response::decode(&report, Command::CommitSettings),
+        Err(Status::InvalidSettings)
+    );
+    assert_eq!(settings, Settings::new());
+
+    let commit = Request::CommitSettings {
+        length: MAX_SETTINGS_SIZE as u16 + 1,
+        save: false,
+    };
+    let (report, _) = send(&mut server, commit, &mut settings);
+    assert_eq!(
+        Response::decode(&report, Command::CommitSettings),
+        Err(Status::InvalidRequest)
+    );
+}
+
+#[test]
+fn writes_outside_the_record_fail() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+    let request = Request::WriteSettings {
+        offset: (MAX_SETTINGS_SIZE - 2) as u16,
+        data: &[0; 4],
+    };
+    let (report, _) = send(&mut server, request, &mut settings);
+    assert_eq!(
+        Response::decode(&report, Command::WriteSettings),
+        Err(Status::InvalidRequest)
+    );
+}
+
+#[test]
+fn starts_the_calibration() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+    let (report, action) = send(&mut server, Request::StartCalibration, &mut settings);
+    assert_eq!(action, Action::StartCalibration);
+    assert_eq!(
+        Response::decode(&report, Command::StartCalibration),
+        Ok(Response::Done)
+    );
+}
+
+#[test]
+fn bad_requests_get_an_error_status() {
+    let mut server = ConfigServer::new();
+    let mut settings = Settings::new();
+
+    let mut report = Request::GetVersion.encode().unwrap();
+    report[0] = PROTOCOL_VERSION + 1;
+    let (response, _) = server.handle(&report, &mut settings, FIRMWARE);
+    assert_eq!(response[2], Status::UnsupportedVersion as u8);
+
+    let mut report = Request::GetVersion.encode().unwrap();
+    report[1] = 0x7F;
+    let (response, _) = server.handle(&report, &mut settings, FIRMWARE);
+    assert_eq!(response[1], 0x7F);
+    assert_eq!(response[2], Status::UnknownCommand as u8);
+}
+
+#[test]
+fn responses_to_another_command_are_pending() {
+    let report = Response::encode(Command::GetVersion as u8, Ok(Response::Done)).unwrap();
+    assert_eq!(
+        Response::decode(&report, Command::ReadSettings),
+        Err(Status::Pending)
+    );
+
+    let report = Response::encode(Command::ReadSettings as u8, Err(Status::Pending)).unwrap();
+    assert_eq!(
+        Response::decode(&report, Command::ReadSettings),
+        Err(Status::Pending)
+    );
+}
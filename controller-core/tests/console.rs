@@ -0,0 +1,479 @@
+use controller_core::calibration::AxisCalibration;
+use controller_core::console::{
+    execute, Action, ConsoleContext, ConsoleError, LineBuffer, MAX_LINE_LEN,
+};
+use controller_core::controller::ControllerState;
+use controller_core::curve::ResponseCurve;
+use controller_core::deadzone::DeadzoneMode;
+use controller_core::heading::MagCalibration;
+use controller_core::input::{AnalogInput, Button};
+use controller_core::lights::LedMode;
+use controller_core::macros::{Macro, MacroStep, MAX_MACRO_STEPS};
+use controller_core::motion::Orientation;
+use controller_core::remap::{AxisBinding, ButtonBinding, ButtonSet};
+use controller_core::schedule::{JitterStats, PollRate};
+use controller_core::settings::Settings;
+
+/// Runs `line` on `settings`, returns the result and the output
+fn run(line: &str, settings: &mut Settings) -> (Result<Action, ConsoleError>, String) {
+    run_with_leds(line, settings, &mut LedMode::default())
+}
+
+fn run_with_leds(
+    line: &str,
+    settings: &mut Settings,
+    led_mode: &mut LedMode,
+) -> (Result<Action, ConsoleError>, String) {
+    run_with(line, settings, led_mode, &JitterStats::new(1000))
+}
+
+fn run_with(
+    line: &str,
+    settings: &mut Settings,
+    led_mode: &mut LedMode,
+    jitter: &JitterStats,
+) -> (Result<Action, ConsoleError>, String) {
+    let mut state = ControllerState::new();
+    state.a = true;
+    state.start = true;
+    state.left_thumb_x = -0.5;
+    let raw_values = [2048; AnalogInput::COUNT];
+    let mut context = ConsoleContext {
+        settings,
+        state: &state,
+        raw_values: &raw_values,
+        orientation: Some(Orientation {
+            roll: 12.5,
+            pitch: -4.0,
+            heading: 90.0,
+        }),
+        jitter,
+        led_mode,
+    };
+    let mut out = String::new();
+    let result = execute(line, &mut context, &mut out);
+    (result, out)
+}
+
+fn lines(bytes: &[u8]) -> Vec<Result<String, ConsoleError>> {
+    let mut buffer = LineBuffer::new();
+    bytes
+        .iter()
+        .filter_map(|byte| buffer.push(*byte))
+        .map(|line| line.map(|line| line.as_str().to_string()))
+        .collect()
+}
+
+#[test]
+fn line_buffer_splits_lines() {
+    assert_eq!(
+        lines(b"state\r\n\r\nmap 0 a\n"),
+        [Ok("state".to_string()), Ok("map 0 a".to_string())]
+    );
+    assert_eq!(lines(b"stx\x7fate\x1b\r"), [Ok("state".to_string())]);
+}
+
+#[test]
+fn line_buffer_rejects_long_lines() {
+    let mut bytes = vec![b'a'; MAX_LINE_LEN + 1];
+    bytes.extend(b"\rraw\r");
+    assert_eq!(
+        lines(&bytes),
+        [Err(ConsoleError::LineTooLong), Ok("raw".to_string())]
+    );
+}
+
+#[test]
+fn state_shows_live_values() {
+    let (result, out) = run("state", &mut Settings::new());
+    assert_eq!(result, Ok(Action::None));
+    assert!(out.starts_with("buttons: a start\r\naxes: lx=-0.500 ly=0.000"));
+
+    let (_, out) = run("raw", &mut Settings::new());
+    assert!(out.starts_with("lx=2048 ly=2048"));
+}
+
+#[test]
+fn unknown_and_malformed_commands_are_rejected() {
+    let mut settings = Settings::new();
+    assert_eq!(
+        run("jump", &mut settings).0,
+        Err(ConsoleError::UnknownCommand)
+    );
+    assert_eq!(
+        run("state now", &mut settings).0,
+        Err(ConsoleError::TooManyArguments)
+    );
+    assert_eq!(
+        run("curve", &mut settings).0,
+        Err(ConsoleError::MissingArgument)
+    );
+    assert_eq!(
+        run("curve lz", &mut settings).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+    assert_eq!(
+        run("deadzone left axial 0.1 x 0", &mut settings).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+    assert_eq!(settings, Settings::new());
+}
+
+#[test]
+fn calibration_is_shown_and_set() {
+    let mut settings = Settings::new();
+    assert_eq!(
+        run("calibration lt 100 100 3900", &mut settings).0,
+        Ok(Action::Apply)
+    );
+    assert_eq!(
+        *settings.calibration.axis(AnalogInput::LeftTrigger),
+        AxisCalibration::new(100, 100, 3900)
+    );
+    assert!(run("calibration", &mut settings)
+        .1
+        .contains("lt 100 100 3900\r\n"));
+
+    assert_eq!(
+        run("calibration lx 300 200 3900", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(
+        run("calibration lx 0 2000 5000", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+}
+
+#[test]
+fn deadzone_is_shown_and_set() {
+    let mut settings = Settings::new();
+    assert_eq!(
+        run("deadzone right radial 0.2 0.05 0", &mut settings).0,
+        Ok(Action::Apply)
+    );
+    assert_eq!(settings.deadzones.right_stick.mode, DeadzoneMode::Radial);
+    assert_eq!(
+        run("deadzone right", &mut settings).1,
+        "radial 0.200 0.050 0.000\r\n"
+    );
+    assert_eq!(
+        run("deadzone left scaled 0.9 0.5 0", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+}
+
+#[test]
+fn curve_is_shown_and_set() {
+    let mut settings = Settings::new();
+    assert_eq!(run("curve rt exp 2.5", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(
+        *settings.curves.axis(AnalogInput::RightTrigger),
+        ResponseCurve::Exponential { exponent: 2.5 }
+    );
+    assert_eq!(run("curve rt", &mut settings).1, "exp 2.500\r\n");
+
+    assert_eq!(
+        run("curve lx custom 0.2 0 0.6 0.9", &mut settings).0,
+        Ok(Action::Apply)
+    );
+    assert_eq!(
+        run("curve lx", &mut settings).1,
+        "custom 0.200 0.000 0.600 0.900\r\n"
+    );
+    assert_eq!(
+        run("curve lx custom 0.6 0 0.2 0.9", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(
+        run("curve lx custom 0.6", &mut settings).0,
+        Err(ConsoleError::MissingArgument)
+    );
+}
+
+#[test]
+fn mappings_are_shown_and_set() {
+    let mut settings = Settings::new();
+    let mut set = |line| run(line, &mut settings).0;
+    assert_eq!(set("map 1 rb a"), Ok(Action::Apply));
+    assert_eq!(set("map 1 a none"), Ok(Action::Apply));
+    assert_eq!(set("map 1 up ly 0.5"), Ok(Action::Apply));
+    assert_eq!(set("map 1 rt buttons - y"), Ok(Action::Apply));
+    assert_eq!(set("map 1 ry ry inverted"), Ok(Action::Apply));
+    assert_eq!(set("map 1 up ly 0"), Err(ConsoleError::InvalidValue));
+    assert_eq!(set("map 4 a b"), Err(ConsoleError::InvalidValue));
+    assert_eq!(
+        set("map 1 ry ry upside"),
+        Err(ConsoleError::InvalidArgument)
+    );
+
+    let profile = &settings.profiles.profiles[1];
+    assert_eq!(
+        *profile.button(Button::RightShoulder),
+        ButtonBinding::Button(Button::A)
+    );
+    assert_eq!(*profile.button(Button::A), ButtonBinding::Unbound);
+    assert_eq!(
+        *profile.axis(AnalogInput::RightTrigger),
+        AxisBinding::Buttons {
+            negative: None,
+            positive: Some(Button::Y)
+        }
+    );
+    assert_eq!(run("map 1 up", &mut settings).1, "ly 0.500\r\n");
+    assert_eq!(run("map 1 rt", &mut settings).1, "buttons - y\r\n");
+    assert_eq!(run("map 1 ry", &mut settings).1, "ry inverted\r\n");
+}
+
+#[test]
+fn profiles_are_selected() {
+    let mut settings = Settings::new();
+    assert_eq!(
+        run("profile 1", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(run("profile count 3", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(run("profile 2", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(
+        run("profile combo back guide", &mut settings).0,
+        Ok(Action::Apply)
+    );
+    assert_eq!(
+        settings.profiles.switch_combo,
+        ButtonSet::EMPTY.with(Button::Back).with(Button::Guide)
+    );
+    assert_eq!(
+        run("profile", &mut settings).1,
+        "profile 2 of 3, combo back guide\r\n"
+    );
+
+    assert_eq!(run("profile count 1", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(settings.profiles.active, 0);
+}
+
+#[test]
+fn motion_is_shown_and_set() {
+    let mut settings = Settings::new();
+    assert_eq!(
+        run("motion", &mut settings).1,
+        "off roll lx pitch none heading none range 30.0 deadzone 3.0 rest 0.0 0.0 toggle none\r\n\
+         orientation 12.5 -4.0 90.0\r\n"
+    );
+
+    for line in [
+        "motion on",
+        "motion pitch rt",
+        "motion roll none",
+        "motion range 45",
+    ] {
+        assert_eq!(run(line, &mut settings).0, Ok(Action::Apply), "{}", line);
+    }
+    assert_eq!(run("motion toggle rs", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(run("motion rest", &mut settings).0, Ok(Action::Apply));
+    let motion = settings.motion;
+    assert!(motion.enabled);
+    assert_eq!(motion.roll_axis, None);
+    assert_eq!(motion.pitch_axis, Some(AnalogInput::RightTrigger));
+    assert_eq!((motion.rest_roll, motion.rest_pitch), (12.5, -4.0));
+    assert_eq!(motion.toggle, Some(Button::RightThumb));
+
+    assert_eq!(
+        run("motion deadzone 45", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(
+        run("motion range 91", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(
+        run("motion yaw lx", &mut settings).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+    assert_eq!(settings.motion, motion);
+}
+
+#[test]
+fn save_and_dfu_are_left_to_the_firmware() {
+    let mut settings = Settings::new();
+    assert_eq!(run("save", &mut settings).0, Ok(Action::Save));
+    assert_eq!(run("dfu", &mut settings).0, Ok(Action::RebootDfu));
+    assert_eq!(run("  ", &mut settings).0, Ok(Action::None));
+    assert!(run("help", &mut settings).1.contains("calibration"));
+}
+
+#[test]
+fn compass_is_shown_calibrated_and_reset() {
+    let mut settings = Settings::new();
+    settings.mag_calibration = MagCalibration {
+        offset: [0.125, -0.25, 0.5],
+        ..MagCalibration::IDENTITY
+    };
+    assert_eq!(
+        run("compass", &mut settings).1,
+        "offset 0.125 -0.250 0.500 matrix 1.000 0.000 0.000 0.000 1.000 0.000 0.000 0.000 1.000\r\n\
+         heading 90.0\r\n"
+    );
+
+    let (result, out) = run("compass calibrate", &mut settings);
+    assert_eq!(result, Ok(Action::CalibrateCompass));
+    assert_eq!(
+        out,
+        "turn the controller in a figure eight until calibrated\r\n"
+    );
+
+    assert_eq!(run("compass reset", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(settings.mag_calibration, MagCalibration::IDENTITY);
+    assert_eq!(
+        run("compass north", &mut settings).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+}
+
+#[test]
+fn led_mode_is_shown_and_selected() {
+    let mut settings = Settings::new();
+    let mut mode = LedMode::default();
+    assert_eq!(
+        run_with_leds("leds", &mut settings, &mut mode).1,
+        "status\r\n"
+    );
+
+    let (result, _) = run_with_leds("leds lt", &mut settings, &mut mode);
+    assert_eq!(result, Ok(Action::None));
+    assert_eq!(mode, LedMode::LeftTrigger);
+    assert_eq!(run_with_leds("leds", &mut settings, &mut mode).1, "lt\r\n");
+
+    assert_eq!(
+        run_with_leds("leds up", &mut settings, &mut mode).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+    assert_eq!(
+        run_with_leds("leds left right", &mut settings, &mut mode).0,
+        Err(ConsoleError::TooManyArguments)
+    );
+    assert_eq!(mode, LedMode::LeftTrigger);
+    assert_eq!(settings, Settings::new());
+}
+
+#[test]
+fn timing_shows_the_measured_periods() {
+    let mut settings = Settings::new();
+    let mut jitter = JitterStats::new(1000);
+    let timing = |settings: &mut Settings, jitter: &JitterStats| {
+        run_with("timing", settings, &mut LedMode::default(), jitter).1
+    };
+    assert_eq!(timing(&mut settings, &jitter), "period 1000us\r\n");
+
+    for period_us in [998, 1000, 1006, 1000] {
+        jitter.record(period_us);
+    }
+    assert_eq!(
+        timing(&mut settings, &jitter),
+        "period 1000us min 998 max 1006 mean 1001 jitter 6 samples 4\r\n"
+    );
+}
+
+#[test]
+fn rate_is_saved_for_the_next_boot() {
+    let mut settings = Settings::new();
+    assert_eq!(run("rate", &mut settings).1, "1000\r\n");
+
+    let (result, out) = run("rate 250", &mut settings);
+    assert_eq!(result, Ok(Action::Save));
+    assert_eq!(out, "applies on the next boot\r\n");
+    assert_eq!(settings.poll_rate, PollRate::Hz250);
+
+    assert_eq!(
+        run("rate 300", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(
+        run("rate fast", &mut settings).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+    assert_eq!(settings.poll_rate, PollRate::Hz250);
+}
+
+#[test]
+fn turbo_is_shown_and_set() {
+    let mut settings = Settings::new();
+    assert_eq!(run("turbo", &mut settings).1, "none\r\n");
+
+    assert_eq!(run("turbo a 10", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(run("turbo rb 30", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(settings.turbo.button(Button::A), 10);
+    assert_eq!(run("turbo", &mut settings).1, "a 10 rb 30\r\n");
+    assert_eq!(run("turbo a", &mut settings).1, "10\r\n");
+
+    assert_eq!(run("turbo a 0", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(settings.turbo.button(Button::A), 0);
+    assert_eq!(
+        run("turbo a 31", &mut settings).0,
+        Err(ConsoleError::InvalidValue)
+    );
+    assert_eq!(
+        run("turbo lx 10", &mut settings).0,
+        Err(ConsoleError::InvalidArgument)
+    );
+}
+
+#[test]
+fn macro_is_bound_shown_and_cleared() {
+    let mut settings = Settings::new();
+    let line = "macro 1 guide a:50 wait:80 a+lb@0,-1:50 wait@0.5,0.25:20";
+    assert_eq!(run(line, &mut settings).0, Ok(Action::Apply));
+
+    let jump = ButtonSet::EMPTY.with(Button::A);
+    let expected = Macro::new(
+        Button::Guide,
+        &[
+            MacroStep::press(jump, 50),
+            MacroStep::wait(80),
+            MacroStep {
+                buttons: jump.with(Button::LeftShoulder),
+                left_stick: Some((0.0, -1.0)),
+                duration_ms: 50,
+            },
+            MacroStep {
+                buttons: ButtonSet::EMPTY,
+                left_stick: Some((0.5, 0.25)),
+                duration_ms: 20,
+            },
+        ],
+    )
+    .unwrap();
+    assert_eq!(settings.macros.slots[1], Some(expected));
+    assert_eq!(
+        run("macro", &mut settings).1,
+        "0 none\r\n1 guide a:50 wait:80 a+lb@0,-1:50 wait@0.5,0.25:20\r\n2 none\r\n3 none\r\n"
+    );
+    // Shown as it is typed
+    let shown = run("macro 1", &mut settings).1;
+    let mut copy = Settings::new();
+    let line = format!("macro 1 {}", shown.trim_end());
+    assert_eq!(run(&line, &mut copy).0, Ok(Action::Apply));
+    assert_eq!(copy.macros, settings.macros);
+
+    assert_eq!(run("macro 1 none", &mut settings).0, Ok(Action::Apply));
+    assert_eq!(settings.macros.slots[1], None);
+}
+
+#[test]
+fn invalid_macros_are_refused() {
+    let mut settings = Settings::new();
+    let too_many = format!("macro 0 guide{}", " wait:10".repeat(MAX_MACRO_STEPS + 1));
+    let lines = [
+        ("macro 4 guide a:50", ConsoleError::InvalidValue),
+        ("macro 0 guide", ConsoleError::MissingArgument),
+        ("macro 0 lx a:50", ConsoleError::InvalidArgument),
+        ("macro 0 guide a", ConsoleError::InvalidArgument),
+        ("macro 0 guide z:50", ConsoleError::InvalidArgument),
+        ("macro 0 guide a:-5", ConsoleError::InvalidArgument),
+        ("macro 0 guide a@1:50", ConsoleError::InvalidArgument),
+        ("macro 0 guide a@0,2:50", ConsoleError::InvalidValue),
+        (too_many.as_str(), ConsoleError::TooManyArguments),
+    ];
+    for (line, error) in lines {
+        assert_eq!(run(line, &mut settings).0, Err(error), "{}", line);
+    }
+    assert_eq!(settings, Settings::new());
+}
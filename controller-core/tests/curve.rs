@@ -0,0 +1,96 @@
+use controller_core::curve::{CurveTable, ResponseCurve, MAX_CURVE_POINTS};
+
+const EPSILON: f32 = 1e-5;
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < EPSILON,
+        "{actual} != {expected}"
+    );
+}
+
+#[test]
+fn every_curve_keeps_ends_and_sign() {
+    let table = CurveTable::new(&[(0.3, 0.1), (0.7, 0.8)]).unwrap();
+    let curves = [
+        ResponseCurve::Linear,
+        ResponseCurve::EXPONENTIAL,
+        ResponseCurve::S_CURVE,
+        ResponseCurve::SCurve { strength: 1.0 },
+        ResponseCurve::Custom(table),
+    ];
+    for curve in curves {
+        assert_close(curve.apply(0.0), 0.0);
+        assert_close(curve.apply(1.0), 1.0);
+        assert_close(curve.apply(-1.0), -1.0);
+        assert_close(curve.apply(-0.4), -curve.apply(0.4));
+    }
+}
+
+#[test]
+fn exponential_is_finer_near_center() {
+    let curve = ResponseCurve::Exponential { exponent: 2.0 };
+    assert_close(curve.apply(0.5), 0.25);
+    assert_close(curve.apply(-0.1), -0.01);
+
+    let sensitive = ResponseCurve::Exponential { exponent: 0.5 };
+    assert_close(sensitive.apply(0.25), 0.5);
+}
+
+#[test]
+fn s_curve_is_slow_at_both_ends() {
+    let curve = ResponseCurve::SCurve { strength: 1.0 };
+    assert_close(curve.apply(0.5), 0.5);
+    assert!(curve.apply(0.1) < 0.1);
+    assert!(curve.apply(0.9) > 0.9);
+
+    let half = ResponseCurve::SCurve { strength: 0.5 };
+    assert_close(half.apply(0.25), (0.25 + 0.15625) / 2.0);
+}
+
+#[test]
+fn custom_table_interpolates_between_points() {
+    let table = CurveTable::new(&[(0.5, 0.2), (0.8, 0.9)]).unwrap();
+    let curve = ResponseCurve::Custom(table);
+    assert_close(curve.apply(0.25), 0.1);
+    assert_close(curve.apply(0.5), 0.2);
+    assert_close(curve.apply(0.65), 0.55);
+    assert_close(curve.apply(0.9), 0.95);
+}
+
+#[test]
+fn empty_custom_table_is_linear() {
+    let curve = ResponseCurve::Custom(CurveTable::new(&[]).unwrap());
+    assert_close(curve.apply(0.42), 0.42);
+}
+
+#[test]
+fn values_past_full_deflection_are_clamped() {
+    assert_close(ResponseCurve::EXPONENTIAL.apply(1.3), 1.0);
+}
+
+#[test]
+fn table_rejects_invalid_points() {
+    assert!(CurveTable::new(&[(0.5, 0.2), (0.5, 0.3)]).is_none());
+    assert!(CurveTable::new(&[(0.6, 0.2), (0.5, 0.3)]).is_none());
+    assert!(CurveTable::new(&[(-0.1, 0.2)]).is_none());
+    assert!(CurveTable::new(&[(0.5, 1.2)]).is_none());
+    assert!(CurveTable::new(&[(f32::NAN, 0.2)]).is_none());
+
+    let too_many: Vec<(f32, f32)> = (1..=MAX_CURVE_POINTS + 1)
+        .map(|index| (index as f32 / 10.0, index as f32 / 10.0))
+        .collect();
+    assert!(CurveTable::new(&too_many).is_none());
+    assert!(CurveTable::new(&too_many[..MAX_CURVE_POINTS]).is_some());
+}
+
+#[test]
+fn parameters_are_validated() {
+    assert!(ResponseCurve::EXPONENTIAL.is_valid());
+    assert!(!ResponseCurve::Exponential { exponent: 0.0 }.is_valid());
+    assert!(!ResponseCurve::Exponential {
+        exponent: f32::INFINITY
+    }
+    .is_valid());
+    assert!(!ResponseCurve::SCurve { strength: 1.5 }.is_valid());
+}
@@ -0,0 +1,106 @@
+use controller_core::deadzone::{DeadzoneMode, StickDeadzone};
+
+const EPSILON: f32 = 1e-5;
+
+fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+    assert!(
+        (actual.0 - expected.0).abs() < EPSILON && (actual.1 - expected.1).abs() < EPSILON,
+        "{actual:?} != {expected:?}"
+    );
+}
+
+fn deadzone(mode: DeadzoneMode, inner: f32, outer: f32, anti_deadzone: f32) -> StickDeadzone {
+    StickDeadzone {
+        mode,
+        inner,
+        outer,
+        anti_deadzone,
+    }
+}
+
+#[test]
+fn none_passes_values_through() {
+    for position in [(0.0, 0.0), (0.01, -0.02), (-0.5, 0.7), (1.0, -1.0)] {
+        assert_close(StickDeadzone::NONE.apply(position.0, position.1), position);
+    }
+}
+
+#[test]
+fn axial_zeroes_each_axis_separately() {
+    let deadzone = deadzone(DeadzoneMode::Axial, 0.2, 0.0, 0.0);
+    assert_close(deadzone.apply(0.1, 0.6), (0.0, 0.5));
+    assert_close(deadzone.apply(-0.6, -0.1), (-0.5, 0.0));
+}
+
+#[test]
+fn radial_keeps_values_outside_of_circle() {
+    let deadzone = deadzone(DeadzoneMode::Radial, 0.2, 0.0, 0.0);
+    assert_close(deadzone.apply(0.1, 0.1), (0.0, 0.0));
+    // Out of the circle although both axes are below the inner size
+    assert_close(deadzone.apply(0.15, 0.15), (0.15, 0.15));
+    assert_close(deadzone.apply(0.6, 0.0), (0.6, 0.0));
+}
+
+#[test]
+fn scaled_radial_has_no_jump_at_edge() {
+    let deadzone = deadzone(DeadzoneMode::ScaledRadial, 0.2, 0.0, 0.0);
+    assert_close(deadzone.apply(0.2, 0.0), (0.0, 0.0));
+    assert_close(deadzone.apply(0.6, 0.0), (0.5, 0.0));
+    assert_close(deadzone.apply(0.0, -1.0), (0.0, -1.0));
+}
+
+#[test]
+fn scaled_radial_keeps_direction() {
+    let deadzone = deadzone(DeadzoneMode::ScaledRadial, 0.2, 0.0, 0.0);
+    let (x, y) = deadzone.apply(0.36, 0.48);
+    assert!((x / y - 0.75).abs() < EPSILON);
+    assert!(((x * x + y * y).sqrt() - 0.5).abs() < EPSILON);
+}
+
+#[test]
+fn outer_deadzone_saturates() {
+    let scaled = deadzone(DeadzoneMode::ScaledRadial, 0.0, 0.1, 0.0);
+    assert_close(scaled.apply(0.9, 0.0), (1.0, 0.0));
+    assert_close(scaled.apply(0.45, 0.0), (0.5, 0.0));
+
+    let radial = deadzone(DeadzoneMode::Radial, 0.0, 0.1, 0.0);
+    assert_close(radial.apply(0.0, -0.95), (0.0, -1.0));
+
+    let axial = deadzone(DeadzoneMode::Axial, 0.0, 0.1, 0.0);
+    assert_close(axial.apply(0.95, -0.3), (1.0, -0.3 / 0.9));
+}
+
+#[test]
+fn square_gate_corners_are_clamped_to_the_circle() {
+    let deadzone = deadzone(DeadzoneMode::ScaledRadial, 0.0, 0.0, 0.0);
+    let half_sqrt_2 = core::f32::consts::FRAC_1_SQRT_2;
+    assert_close(deadzone.apply(1.0, 1.0), (half_sqrt_2, half_sqrt_2));
+}
+
+#[test]
+fn anti_deadzone_starts_at_minimum_output() {
+    let deadzone = deadzone(DeadzoneMode::ScaledRadial, 0.1, 0.0, 0.25);
+    assert_close(deadzone.apply(0.05, 0.0), (0.0, 0.0));
+    assert_close(
+        deadzone.apply(0.1001, 0.0),
+        (0.25 + 0.75 * 0.0001 / 0.9, 0.0),
+    );
+    assert_close(deadzone.apply(0.55, 0.0), (0.625, 0.0));
+    assert_close(deadzone.apply(1.0, 0.0), (1.0, 0.0));
+
+    let axial = StickDeadzone {
+        mode: DeadzoneMode::Axial,
+        ..deadzone
+    };
+    assert_close(axial.apply(-0.55, 0.0), (-0.625, 0.0));
+}
+
+#[test]
+fn validity_requires_remaining_travel() {
+    assert!(StickDeadzone::DEFAULT.is_valid());
+    assert!(StickDeadzone::NONE.is_valid());
+    assert!(!deadzone(DeadzoneMode::Axial, 0.6, 0.4, 0.0).is_valid());
+    assert!(!deadzone(DeadzoneMode::Axial, -0.1, 0.0, 0.0).is_valid());
+    assert!(!deadzone(DeadzoneMode::Axial, 0.1, 0.0, 1.0).is_valid());
+    assert!(!deadzone(DeadzoneMode::Axial, f32::NAN, 0.0, 0.0).is_valid());
+}
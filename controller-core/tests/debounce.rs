@@ -0,0 +1,65 @@
+use controller_core::debounce::{ButtonDebounce, DebounceMode, Debouncer};
+use controller_core::schedule::PollRate;
+
+fn run(mut debouncer: Debouncer, samples: &[u8]) -> Vec<u8> {
+    samples
+        .iter()
+        .map(|sample| debouncer.update(*sample == 1) as u8)
+        .collect()
+}
+
+#[test]
+fn eager_reports_first_edge_and_ignores_bounces() {
+    let debouncer = Debouncer::new(DebounceMode::Eager, 3);
+    let output = run(debouncer, &[0, 1, 0, 1, 0, 1, 1, 1, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(output, [0, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn eager_lockout_ends_after_debounce_time() {
+    let debouncer = Debouncer::new(DebounceMode::Eager, 2);
+    let output = run(debouncer, &[1, 0, 0, 0, 1]);
+    assert_eq!(output, [1, 1, 1, 0, 0]);
+}
+
+#[test]
+fn deferred_waits_for_a_stable_switch() {
+    let debouncer = Debouncer::new(DebounceMode::Deferred, 3);
+    let output = run(debouncer, &[1, 0, 1, 1, 0, 1, 1, 1, 1, 0, 1, 0, 0, 0]);
+    assert_eq!(output, [0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 0]);
+}
+
+#[test]
+fn deferred_ignores_glitches() {
+    let debouncer = Debouncer::new(DebounceMode::Deferred, 2);
+    let output = run(debouncer, &[0, 1, 0, 0, 1, 0]);
+    assert_eq!(output, [0; 6]);
+}
+
+#[test]
+fn zero_samples_pass_the_switch_through() {
+    for mode in [DebounceMode::Eager, DebounceMode::Deferred] {
+        let output = run(Debouncer::new(mode, 0), &[1, 0, 1, 1, 0]);
+        assert_eq!(output, [1, 0, 1, 1, 0]);
+    }
+}
+
+#[test]
+fn debounce_time_converts_to_samples() {
+    let debounce = ButtonDebounce::new(DebounceMode::Deferred, 8);
+    assert_eq!(debounce.samples(PollRate::Hz1000), 8);
+    assert_eq!(debounce.samples(PollRate::Hz250), 2);
+    assert_eq!(debounce.samples(PollRate::Hz125), 1);
+    assert_eq!(
+        ButtonDebounce::new(DebounceMode::Eager, 0).samples(PollRate::Hz1000),
+        0
+    );
+}
+
+#[test]
+fn modes_round_trip() {
+    for mode in [DebounceMode::Eager, DebounceMode::Deferred] {
+        assert_eq!(DebounceMode::from_u8(mode.as_u8()), Some(mode));
+    }
+    assert_eq!(DebounceMode::from_u8(2), None);
+}
@@ -0,0 +1,89 @@
+use controller_core::controller::ControllerState;
+use controller_core::dpad::{hat_value, SocdMode, SocdResolver, HAT_NEUTRAL};
+
+fn dpad(up: bool, down: bool, left: bool, right: bool) -> ControllerState {
+    ControllerState {
+        up,
+        down,
+        left,
+        right,
+        ..ControllerState::new()
+    }
+}
+
+fn resolve(resolver: &mut SocdResolver, up: bool, down: bool, left: bool, right: bool) -> u8 {
+    let mut state = dpad(up, down, left, right);
+    resolver.resolve(&mut state);
+    hat_value(&state)
+}
+
+#[test]
+fn hat_goes_clockwise_from_up() {
+    assert_eq!(hat_value(&dpad(false, false, false, false)), HAT_NEUTRAL);
+    assert_eq!(hat_value(&dpad(true, false, false, false)), 0);
+    assert_eq!(hat_value(&dpad(true, false, false, true)), 1);
+    assert_eq!(hat_value(&dpad(false, false, false, true)), 2);
+    assert_eq!(hat_value(&dpad(false, true, false, true)), 3);
+    assert_eq!(hat_value(&dpad(false, true, false, false)), 4);
+    assert_eq!(hat_value(&dpad(false, true, true, false)), 5);
+    assert_eq!(hat_value(&dpad(false, false, true, false)), 6);
+    assert_eq!(hat_value(&dpad(true, false, true, false)), 7);
+}
+
+#[test]
+fn neutral_mode_cancels_opposites() {
+    let mut resolver = SocdResolver::new(SocdMode::Neutral);
+    assert_eq!(
+        resolve(&mut resolver, true, true, false, false),
+        HAT_NEUTRAL
+    );
+    assert_eq!(resolve(&mut resolver, true, true, true, false), 6);
+    assert_eq!(
+        resolve(&mut resolver, false, false, true, true),
+        HAT_NEUTRAL
+    );
+}
+
+#[test]
+fn last_wins_mode_follows_latest_press() {
+    let mut resolver = SocdResolver::new(SocdMode::LastWins);
+    assert_eq!(resolve(&mut resolver, false, false, true, false), 6);
+    assert_eq!(resolve(&mut resolver, false, false, true, true), 2);
+    // Releasing the latest direction goes back to the one still held
+    assert_eq!(resolve(&mut resolver, false, false, true, false), 6);
+    assert_eq!(resolve(&mut resolver, false, false, true, true), 2);
+    assert_eq!(resolve(&mut resolver, false, false, false, true), 2);
+    assert_eq!(resolve(&mut resolver, false, false, true, true), 6);
+
+    assert_eq!(resolve(&mut resolver, false, true, false, false), 4);
+    assert_eq!(resolve(&mut resolver, true, true, false, false), 0);
+}
+
+#[test]
+fn last_wins_mode_cancels_simultaneous_presses() {
+    let mut resolver = SocdResolver::new(SocdMode::LastWins);
+    assert_eq!(
+        resolve(&mut resolver, true, true, false, false),
+        HAT_NEUTRAL
+    );
+}
+
+#[test]
+fn up_priority_mode_keeps_up_and_cancels_horizontal() {
+    let mut resolver = SocdResolver::new(SocdMode::UpPriority);
+    assert_eq!(resolve(&mut resolver, false, true, false, false), 4);
+    assert_eq!(resolve(&mut resolver, true, true, false, false), 0);
+    assert_eq!(resolve(&mut resolver, true, true, true, true), 0);
+    assert_eq!(
+        resolve(&mut resolver, false, false, true, true),
+        HAT_NEUTRAL
+    );
+}
+
+#[test]
+fn socd_mode_round_trips_through_u8() {
+    for mode in [SocdMode::Neutral, SocdMode::LastWins, SocdMode::UpPriority] {
+        assert_eq!(SocdMode::from_u8(mode.as_u8()), Some(mode));
+    }
+    assert_eq!(SocdMode::from_u8(3), None);
+}
@@ -0,0 +1,100 @@
+use controller_core::filter::{AnalogFilter, FilterState, MAX_AVERAGE_SAMPLES};
+
+const PERIOD_S: f32 = 0.001;
+
+fn run(filter: AnalogFilter, values: &[u16]) -> Vec<u16> {
+    let mut state = FilterState::new(filter);
+    values
+        .iter()
+        .map(|value| state.update(*value, PERIOD_S))
+        .collect()
+}
+
+/// Noise of a few counts around the center of a stick at rest
+fn noisy_rest(samples: usize) -> Vec<u16> {
+    (0..samples)
+        .map(|index| [2048, 2052, 2045, 2050, 2044, 2051][index % 6])
+        .collect()
+}
+
+fn spread(values: &[u16]) -> u16 {
+    values.iter().max().unwrap() - values.iter().min().unwrap()
+}
+
+#[test]
+fn no_filter_passes_values_through() {
+    assert_eq!(run(AnalogFilter::None, &[0, 4095, 12]), [0, 4095, 12]);
+}
+
+#[test]
+fn average_uses_last_samples() {
+    let output = run(
+        AnalogFilter::Average { samples: 4 },
+        &[100, 200, 300, 400, 500, 600],
+    );
+    assert_eq!(output, [100, 150, 200, 250, 350, 450]);
+}
+
+#[test]
+fn exponential_moves_by_alpha() {
+    let output = run(
+        AnalogFilter::Exponential { alpha: 0.5 },
+        &[0, 1000, 1000, 1000],
+    );
+    assert_eq!(output, [0, 500, 750, 875]);
+}
+
+#[test]
+fn hysteresis_holds_small_changes() {
+    let output = run(
+        AnalogFilter::Hysteresis { threshold: 8 },
+        &[2048, 2052, 2044, 2056, 2060, 2070],
+    );
+    assert_eq!(output, [2048, 2048, 2048, 2048, 2060, 2070]);
+}
+
+#[test]
+fn one_euro_steadies_rest_noise() {
+    let output = run(AnalogFilter::ONE_EURO, &noisy_rest(200));
+    assert!(spread(&output[100..]) <= 2, "{:?}", &output[100..]);
+}
+
+#[test]
+fn one_euro_follows_fast_flicks() {
+    // Full deflection in 10 ms, then held
+    let mut values = vec![2048; 50];
+    values.extend((1..=10).map(|step| 2048 + step * 204));
+    values.extend([4088; 20]);
+    let output = run(AnalogFilter::ONE_EURO, &values);
+
+    let exponential = run(AnalogFilter::Exponential { alpha: 0.05 }, &values);
+    assert!(output[65] > 4000, "{}", output[65]);
+    assert!(output[65] > exponential[65]);
+}
+
+#[test]
+fn reset_forgets_previous_values() {
+    let mut state = FilterState::new(AnalogFilter::Average { samples: 8 });
+    state.update(4000, PERIOD_S);
+    state.reset();
+    assert_eq!(state.update(100, PERIOD_S), 100);
+}
+
+#[test]
+fn filter_parameters_are_validated() {
+    assert!(AnalogFilter::ONE_EURO.is_valid());
+    assert!(AnalogFilter::Average {
+        samples: MAX_AVERAGE_SAMPLES as u8
+    }
+    .is_valid());
+    assert!(!AnalogFilter::Average {
+        samples: MAX_AVERAGE_SAMPLES as u8 + 1
+    }
+    .is_valid());
+    assert!(!AnalogFilter::Exponential { alpha: 1.5 }.is_valid());
+    assert!(!AnalogFilter::OneEuro {
+        min_cutoff: 0.0,
+        beta: 1.0
+    }
+    .is_valid());
+}
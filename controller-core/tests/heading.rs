@@ -0,0 +1,208 @@
+use controller_core::heading::{
+    tilt_compensated_heading, MagCalibration, MagCalibrationRoutine, MIN_CALIBRATION_SAMPLES,
+};
+
+/// Earth field in the world frame, `x` north, `y` west and `z` up, in gauss
+const FIELD: [f32; 3] = [0.21, 0.0, -0.42];
+
+/// Hard iron offset and symmetric soft iron distortion of the recorded board
+const OFFSET: [f32; 3] = [0.15, -0.22, 0.4];
+const SOFT_IRON: [[f32; 3]; 3] = [[1.2, 0.1, 0.05], [0.1, 0.9, -0.08], [0.05, -0.08, 1.05]];
+
+/// Reading of the sensors and heading of the board
+struct Sample {
+    accel: [f32; 3],
+    /// Field measured by the distorted magnetometer
+    mag: [f32; 3],
+    /// Field without distortion
+    true_mag: [f32; 3],
+    heading: f32,
+}
+
+fn multiply(matrix: [[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+fn transpose(matrix: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    [0, 1, 2].map(|row| [0, 1, 2].map(|column| matrix[column][row]))
+}
+
+fn matrix_product(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    [0, 1, 2].map(|row| [0, 1, 2].map(|column| (0..3).map(|k| a[row][k] * b[k][column]).sum()))
+}
+
+fn norm(vector: [f32; 3]) -> f32 {
+    vector.iter().map(|value| value * value).sum::<f32>().sqrt()
+}
+
+/// Board to world rotation turned by `heading` toward east, pitched then rolled, in degrees
+fn rotation(heading: f32, pitch: f32, roll: f32) -> [[f32; 3]; 3] {
+    let (sh, ch) = (-heading).to_radians().sin_cos();
+    let (sp, cp) = pitch.to_radians().sin_cos();
+    let (sr, cr) = roll.to_radians().sin_cos();
+    let yaw = [[ch, -sh, 0.0], [sh, ch, 0.0], [0.0, 0.0, 1.0]];
+    let pitch = [[cp, 0.0, sp], [0.0, 1.0, 0.0], [-sp, 0.0, cp]];
+    let roll = [[1.0, 0.0, 0.0], [0.0, cr, -sr], [0.0, sr, cr]];
+    matrix_product(yaw, matrix_product(pitch, roll))
+}
+
+fn sample(heading: f32, pitch: f32, roll: f32, noise: f32) -> Sample {
+    let to_board = transpose(rotation(heading, pitch, roll));
+    let true_mag = multiply(to_board, FIELD);
+    let distorted = multiply(SOFT_IRON, true_mag);
+    let mag = [0, 1, 2].map(|axis| distorted[axis] + OFFSET[axis] + noise);
+    // Heading of the board `x` axis projected on the horizontal plane
+    let x = multiply(rotation(heading, pitch, roll), [1.0, 0.0, 0.0]);
+    Sample {
+        accel: multiply(to_board, [0.0, 0.0, 1.0]),
+        mag,
+        true_mag,
+        heading: (-x[1]).atan2(x[0]).to_degrees(),
+    }
+}
+
+/// Readings of the board turned three times around while waving it in a figure eight,
+/// with a little deterministic noise
+fn figure_eight() -> Vec<Sample> {
+    (0..600)
+        .map(|index| {
+            let t = index as f32 / 600.0 * std::f32::consts::TAU;
+            let noise = 0.002 * ((index * 7919) % 13) as f32 / 13.0 - 0.001;
+            sample(
+                (3.0 * t).to_degrees() % 360.0 - 180.0,
+                70.0 * (2.0 * t * 5.0).sin(),
+                70.0 * (t * 5.0).sin(),
+                noise,
+            )
+        })
+        .collect()
+}
+
+fn angle_error(actual: f32, expected: f32) -> f32 {
+    let error = (actual - expected).rem_euclid(360.0);
+    error.min(360.0 - error)
+}
+
+fn calibrate(samples: &[Sample]) -> MagCalibrationRoutine {
+    let mut routine = MagCalibrationRoutine::new();
+    routine.start();
+    for sample in samples {
+        routine.add(sample.mag);
+    }
+    routine
+}
+
+#[test]
+fn identity_calibration_keeps_the_reading() {
+    let reading = [0.1, -0.2, 0.3];
+    assert_eq!(MagCalibration::IDENTITY.apply(reading), reading);
+
+    let calibration = MagCalibration {
+        offset: [0.1, 0.0, -0.1],
+        matrix: [[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.5]],
+    };
+    assert_eq!(calibration.apply(reading), [0.0, -0.2, 0.2]);
+}
+
+#[test]
+fn flat_heading_points_to_the_field() {
+    let up = [0.0, 0.0, 1.0];
+    let heading = |mag| tilt_compensated_heading(up, mag).unwrap();
+    assert_eq!(heading([0.2, 0.0, -0.4]), 0.0);
+    assert!((heading([0.0, 0.2, -0.4]) - 90.0).abs() < 0.001);
+    assert!((heading([-0.2, 0.0, -0.4]).abs() - 180.0).abs() < 0.001);
+    assert!((heading([0.0, -0.2, -0.4]) + 90.0).abs() < 0.001);
+}
+
+#[test]
+fn heading_holds_while_tilted() {
+    for sample in figure_eight() {
+        let heading = tilt_compensated_heading(sample.accel, sample.true_mag).unwrap();
+        assert!(
+            angle_error(heading, sample.heading) < 0.05,
+            "{} is not {}",
+            heading,
+            sample.heading
+        );
+    }
+}
+
+#[test]
+fn heading_needs_a_horizontal_field() {
+    assert_eq!(
+        tilt_compensated_heading([0.0, 0.0, 1.0], [0.0, 0.0, -0.4]),
+        None
+    );
+    assert_eq!(tilt_compensated_heading([0.0; 3], [0.2, 0.0, -0.4]), None);
+}
+
+#[test]
+fn calibration_recovers_hard_and_soft_iron() {
+    let samples = figure_eight();
+    let uncalibrated_error = samples
+        .iter()
+        .map(|sample| {
+            let heading = tilt_compensated_heading(sample.accel, sample.mag).unwrap();
+            angle_error(heading, sample.heading)
+        })
+        .fold(0.0, f32::max);
+    assert!(uncalibrated_error > 30.0);
+
+    let mut routine = calibrate(&samples);
+    assert!(routine.is_complete());
+    let calibration = routine.finish().unwrap();
+    assert!(!routine.is_active());
+
+    for (offset, expected) in calibration.offset.iter().zip(OFFSET) {
+        assert!((offset - expected).abs() < 0.005);
+    }
+    // The soft iron is undone up to a scale, the calibrated field keeps its strength
+    let strength = norm(calibration.apply(samples[0].mag));
+    for sample in &samples {
+        let calibrated = calibration.apply(sample.mag);
+        assert!((norm(calibrated) / strength - 1.0).abs() < 0.02);
+        let heading = tilt_compensated_heading(sample.accel, calibrated).unwrap();
+        assert!(
+            angle_error(heading, sample.heading) < 1.0,
+            "{} is not {}",
+            heading,
+            sample.heading
+        );
+    }
+    assert!((strength / norm(FIELD) - 1.0).abs() < 0.1);
+}
+
+#[test]
+fn routine_waits_for_readings_on_every_axis() {
+    let mut routine = MagCalibrationRoutine::new();
+    routine.add([0.1, 0.2, 0.3]);
+    assert_eq!(routine.samples(), 0);
+
+    // Turning the board flat leaves `z` unexplored
+    let flat: Vec<Sample> = (0..MIN_CALIBRATION_SAMPLES * 2)
+        .map(|index| sample(index as f32, 0.0, 0.0, 0.0))
+        .collect();
+    let routine = calibrate(&flat);
+    assert_eq!(routine.samples(), MIN_CALIBRATION_SAMPLES * 2);
+    assert!(routine.coverage() < 0.2);
+    assert!(!routine.is_complete());
+
+    let samples = figure_eight();
+    let routine = calibrate(&samples[..MIN_CALIBRATION_SAMPLES as usize - 1]);
+    assert!(!routine.is_complete());
+}
+
+#[test]
+fn calibration_fails_without_an_ellipsoid() {
+    let mut routine = MagCalibrationRoutine::new();
+    routine.start();
+    for _ in 0..MIN_CALIBRATION_SAMPLES {
+        routine.add([0.1, 0.2, 0.3]);
+    }
+    assert_eq!(routine.finish(), None);
+
+    let flat: Vec<Sample> = (0..360)
+        .map(|index| sample(index as f32, 0.0, 0.0, 0.0))
+        .collect();
+    assert_eq!(calibrate(&flat).finish(), None);
+}
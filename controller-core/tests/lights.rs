@@ -0,0 +1,209 @@
+use controller_core::calibration::CalibrationStep;
+use controller_core::controller::ControllerState;
+use controller_core::lights::{
+    Animation, LedEngine, LedMode, LedPriority, LedState, SoftPwm, ERROR_SHOWN_MS, LED_COUNT,
+    MAX_BRIGHTNESS, OFF, PROFILE_SHOWN_MS,
+};
+
+const FULL: u8 = MAX_BRIGHTNESS;
+
+#[test]
+fn blink_lights_the_mask_for_half_the_period() {
+    let blink = Animation::Blink {
+        mask: 0b1000_0001,
+        period_ms: 200,
+    };
+    assert_eq!(blink.frame(0), [FULL, 0, 0, 0, 0, 0, 0, FULL]);
+    assert_eq!(blink.frame(99), [FULL, 0, 0, 0, 0, 0, 0, FULL]);
+    assert_eq!(blink.frame(100), OFF);
+    assert_eq!(blink.frame(250), [FULL, 0, 0, 0, 0, 0, 0, FULL]);
+}
+
+#[test]
+fn breathe_fades_in_then_out() {
+    let breathe = Animation::Breathe {
+        mask: 0b0000_0100,
+        period_ms: 1000,
+    };
+    assert_eq!(breathe.frame(0), OFF);
+    assert_eq!(breathe.frame(500)[2], FULL);
+    let rising = breathe.frame(250)[2];
+    assert!(0 < rising && rising < FULL);
+    assert_eq!(breathe.frame(750)[2], rising);
+    assert_eq!(breathe.frame(1500)[2], FULL);
+}
+
+#[test]
+fn spin_goes_clockwise_with_a_tail() {
+    let spin = Animation::Spin { period_ms: 800 };
+    assert_eq!(spin.frame(0), [FULL, 0, 0, 0, 0, 0, 1, FULL / 3]);
+    assert_eq!(spin.frame(100), [FULL / 3, FULL, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(spin.frame(750), [0, 0, 0, 0, 0, 1, FULL / 3, FULL]);
+    assert_eq!(spin.frame(800), spin.frame(0));
+}
+
+#[test]
+fn compass_lights_the_nearest_direction() {
+    let compass = |angle, magnitude| Animation::Compass { angle, magnitude }.frame(0);
+    assert_eq!(compass(0.0, 1.0), [FULL, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(compass(100.0, 1.0), [0, 0, FULL, 0, 0, 0, 0, 0]);
+    assert_eq!(compass(-30.0, 1.0), [0, 0, 0, 0, 0, 0, 0, FULL]);
+    assert_eq!(compass(200.0, 0.4)[4], 6);
+    assert_eq!(compass(200.0, 0.0), OFF);
+    assert_eq!(compass(540.0, 2.0)[4], FULL);
+}
+
+#[test]
+fn states_show_their_animation() {
+    assert_eq!(
+        LedState::Profile(2).animation().frame(0),
+        [FULL, FULL, FULL, 0, 0, 0, 0, 0]
+    );
+    let range = LedState::Calibration {
+        step: CalibrationStep::Range,
+        calibrated_axes: 3,
+    };
+    assert_eq!(
+        range.animation().frame(0),
+        [FULL, FULL, FULL, 0, 0, 0, 0, 0]
+    );
+    let center = LedState::Calibration {
+        step: CalibrationStep::Center,
+        calibrated_axes: 0,
+    };
+    assert_eq!(
+        center.animation().frame(0),
+        [FULL, 0, FULL, 0, FULL, 0, FULL, 0]
+    );
+    assert_eq!(LedState::Error.animation().frame(0), [FULL; LED_COUNT]);
+    assert_eq!(LedState::Suspended.priority(), LedPriority::Usb);
+}
+
+#[test]
+fn highest_request_shows_until_cleared() {
+    let mut engine = LedEngine::new();
+    assert_eq!(engine.render(0), OFF);
+    assert_eq!(engine.current(), None);
+
+    engine.show(LedState::Configured);
+    let configured = engine.render(10);
+    assert_ne!(configured, OFF);
+    assert_eq!(engine.current(), Some(LedPriority::Usb));
+
+    let center = LedState::Calibration {
+        step: CalibrationStep::Center,
+        calibrated_axes: 0,
+    };
+    engine.show(center);
+    assert_eq!(engine.render(20), center.animation().frame(0));
+    assert_eq!(engine.current(), Some(LedPriority::Calibration));
+
+    // A lower request waits under the calibration
+    engine.show(LedState::Profile(1));
+    assert_eq!(engine.render(30), center.animation().frame(10));
+
+    engine.clear(LedPriority::Calibration);
+    assert_eq!(engine.render(40), LedState::Profile(1).animation().frame(0));
+    assert_eq!(engine.render(30 + PROFILE_SHOWN_MS), configured);
+    assert_eq!(engine.current(), Some(LedPriority::Usb));
+}
+
+#[test]
+fn showing_a_state_again_keeps_its_phase() {
+    let mut engine = LedEngine::new();
+    engine.render(1000);
+    engine.show(LedState::Error);
+    assert_eq!(engine.render(1150), OFF);
+
+    // Shown again, the blink carries on and ends later
+    engine.show(LedState::Error);
+    assert_eq!(
+        engine.render(1150 + ERROR_SHOWN_MS - 150),
+        [FULL; LED_COUNT]
+    );
+    assert_eq!(engine.render(1150 + ERROR_SHOWN_MS), OFF);
+    assert_eq!(engine.current(), None);
+
+    // Another animation of the same priority starts over
+    engine.show(LedState::Enumerating);
+    let enumerating = engine.render(5000);
+    engine.show(LedState::Suspended);
+    assert_eq!(engine.render(5000), OFF);
+    assert_ne!(enumerating, OFF);
+}
+
+#[test]
+fn requests_end_across_the_clock_wrap() {
+    let mut engine = LedEngine::new();
+    engine.render(u32::MAX - 100);
+    engine.show(LedState::Profile(0));
+    assert_ne!(engine.render(u32::MAX), OFF);
+    assert_ne!(engine.render(PROFILE_SHOWN_MS - 200), OFF);
+    assert_eq!(engine.render(PROFILE_SHOWN_MS), OFF);
+}
+
+#[test]
+fn pwm_duty_follows_the_brightness() {
+    let frame = [0, 1, 5, 8, 14, FULL, 3, 0];
+    let mut pwm = SoftPwm::new();
+    let mut on_ticks = [0u8; LED_COUNT];
+    for _ in 0..MAX_BRIGHTNESS * 2 {
+        for (count, on) in on_ticks.iter_mut().zip(pwm.next(&frame)) {
+            *count += on as u8;
+        }
+    }
+    assert_eq!(on_ticks, frame.map(|level| level * 2));
+}
+
+#[test]
+fn ring_fills_clockwise() {
+    let ring = |level| Animation::Ring { level }.frame(0);
+    assert_eq!(ring(0.0), OFF);
+    assert_eq!(ring(0.25), [FULL, FULL, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(ring(0.5 + 0.5 / 8.0), [FULL, FULL, FULL, FULL, 8, 0, 0, 0]);
+    assert_eq!(ring(1.2), [FULL; LED_COUNT]);
+}
+
+#[test]
+fn modes_show_the_inputs() {
+    let mut state = ControllerState::new();
+    state.left_thumb_x = 0.5;
+    state.left_thumb_y = -0.5;
+    state.right_thumb_x = -1.0;
+    state.right_trigger = 0.25;
+
+    assert_eq!(LedMode::Status.animation(&state), None);
+    // Down and right, South East
+    let left = LedMode::LeftStick.animation(&state).unwrap().frame(0);
+    assert_eq!(left, [0, 0, 0, 11, 0, 0, 0, 0]);
+    let right = LedMode::RightStick.animation(&state).unwrap().frame(0);
+    assert_eq!(right, [0, 0, 0, 0, 0, 0, FULL, 0]);
+    assert_eq!(
+        LedMode::LeftTrigger.animation(&state).unwrap().frame(0),
+        OFF
+    );
+    assert_eq!(
+        LedMode::RightTrigger.animation(&state).unwrap().frame(0),
+        [FULL, FULL, 0, 0, 0, 0, 0, 0]
+    );
+
+    for mode in LedMode::ALL {
+        assert_eq!(LedMode::from_name(mode.name()), Some(mode));
+    }
+}
+
+#[test]
+fn diagnostic_shows_over_the_usb_state() {
+    let mut engine = LedEngine::new();
+    engine.show(LedState::Configured);
+    let stick = Animation::Compass {
+        angle: 90.0,
+        magnitude: 1.0,
+    };
+    engine.request(LedPriority::Diagnostic, stick, None);
+    assert_eq!(engine.render(0), stick.frame(0));
+
+    engine.show(LedState::Profile(0));
+    assert_eq!(engine.render(10), LedState::Profile(0).animation().frame(0));
+    assert_eq!(engine.render(PROFILE_SHOWN_MS), stick.frame(0));
+}
@@ -0,0 +1,137 @@
+use controller_core::controller::ControllerState;
+use controller_core::input::Button;
+use controller_core::macros::{Macro, MacroPlayer, MacroStep, Macros, Turbo, TurboRates};
+use controller_core::remap::ButtonSet;
+
+/// Jump, wait, then jump again with the stick pulled back
+fn half_flip() -> Macro {
+    let jump = ButtonSet::EMPTY.with(Button::A);
+    let back_jump = MacroStep {
+        buttons: jump,
+        left_stick: Some((0.0, -1.0)),
+        duration_ms: 20,
+    };
+    Macro::new(
+        Button::LeftThumb,
+        &[MacroStep::press(jump, 10), MacroStep::wait(30), back_jump],
+    )
+    .unwrap()
+}
+
+fn macros() -> Macros {
+    let mut macros = Macros::new();
+    macros.slots[1] = Some(half_flip());
+    macros
+}
+
+/// Runs the player once per millisecond from `start_ms`, returns the reported states
+fn play(
+    player: &mut MacroPlayer,
+    start_ms: u32,
+    duration_ms: u32,
+    physical: ControllerState,
+) -> Vec<ControllerState> {
+    let macros = macros();
+    (0..duration_ms)
+        .map(|elapsed_ms| {
+            let now_ms = start_ms.wrapping_add(elapsed_ms);
+            let mut state = physical;
+            player.read_triggers(&macros, now_ms, &mut state);
+            player.play(&macros, now_ms, &mut state);
+            state
+        })
+        .collect()
+}
+
+#[test]
+fn macro_rejects_invalid_steps() {
+    let too_far = MacroStep {
+        buttons: ButtonSet::EMPTY,
+        left_stick: Some((1.5, 0.0)),
+        duration_ms: 10,
+    };
+    assert_eq!(Macro::new(Button::A, &[]), None);
+    assert_eq!(Macro::new(Button::A, &[MacroStep::wait(1); 7]), None);
+    assert_eq!(Macro::new(Button::A, &[too_far]), None);
+}
+
+#[test]
+fn steps_follow_their_durations() {
+    let macro_ = half_flip();
+    assert_eq!(macro_.step_at(0), Some(&macro_.steps()[0]));
+    assert_eq!(macro_.step_at(9), Some(&macro_.steps()[0]));
+    assert_eq!(macro_.step_at(10), Some(&macro_.steps()[1]));
+    assert_eq!(macro_.step_at(59), Some(&macro_.steps()[2]));
+    assert_eq!(macro_.step_at(60), None);
+}
+
+#[test]
+fn trigger_plays_macro_with_millisecond_timing() {
+    let mut player = MacroPlayer::default();
+    let mut physical = ControllerState::new();
+    physical.left_thumb = true;
+    physical.left_thumb_y = 0.5;
+
+    // Starts at an arbitrary time, the clock wraps in the middle of the macro
+    let states = play(&mut player, u32::MAX - 20, 70, physical);
+
+    assert!(states.iter().all(|state| !state.left_thumb));
+    let jumps: Vec<usize> = (0..70).filter(|ms| states[*ms].a).collect();
+    assert_eq!(jumps, (0..10).chain(40..60).collect::<Vec<_>>());
+    assert_eq!(states[39].left_thumb_y, 0.5);
+    assert_eq!(states[40].left_thumb_y, -1.0);
+    assert_eq!(states[60].left_thumb_y, 0.5);
+    assert!(!player.is_playing());
+}
+
+#[test]
+fn held_trigger_does_not_restart_macro() {
+    let mut player = MacroPlayer::default();
+    let mut physical = ControllerState::new();
+    physical.left_thumb = true;
+
+    play(&mut player, 0, 100, physical);
+    assert!(!player.is_playing());
+
+    physical.left_thumb = false;
+    play(&mut player, 100, 1, physical);
+    physical.left_thumb = true;
+    let states = play(&mut player, 101, 1, physical);
+    assert!(states[0].a);
+    assert!(player.is_playing());
+}
+
+#[test]
+fn turbo_repeats_held_buttons() {
+    let mut rates = TurboRates::new();
+    *rates.button_mut(Button::X) = 10;
+    let mut turbo = Turbo::default();
+
+    let mut presses = Vec::new();
+    for now_ms in 1000..1300 {
+        let mut state = ControllerState::new();
+        state.x = true;
+        state.y = true;
+        turbo.update(&rates, now_ms, &mut state);
+        assert!(state.y);
+        presses.push(state.x);
+    }
+
+    // 50 ms pressed then 50 ms released, three times
+    let toggles = presses.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    assert!(presses[0] && presses[49] && !presses[50] && presses[100]);
+    assert_eq!(toggles, 5);
+
+    let mut state = ControllerState::new();
+    turbo.update(&rates, 1300, &mut state);
+    assert!(!state.x);
+}
+
+#[test]
+fn turbo_rate_is_bounded() {
+    let mut rates = TurboRates::new();
+    *rates.button_mut(Button::A) = 30;
+    assert!(rates.is_valid());
+    *rates.button_mut(Button::A) = 31;
+    assert!(!rates.is_valid());
+}
@@ -0,0 +1,171 @@
+use controller_core::controller::ControllerState;
+use controller_core::input::{AnalogInput, Button};
+use controller_core::motion::{MotionInput, MotionSettings, Orientation};
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 0.01,
+        "{} is not {}",
+        actual,
+        expected
+    );
+}
+
+fn enabled() -> MotionSettings {
+    MotionSettings {
+        enabled: true,
+        ..MotionSettings::new()
+    }
+}
+
+/// Applies `settings` to a centered state after a single reading
+fn steer(settings: &MotionSettings, reading: Orientation) -> ControllerState {
+    let mut input = MotionInput::default();
+    input.update(reading);
+    let mut state = ControllerState::new();
+    input.apply(settings, &mut state);
+    state
+}
+
+#[test]
+fn orientation_follows_gravity() {
+    let flat = Orientation::from_sensors([0.0, 0.0, 1.0], None);
+    assert_close(flat.roll, 0.0);
+    assert_close(flat.pitch, 0.0);
+    assert_close(flat.heading, 0.0);
+
+    let half = core::f32::consts::FRAC_1_SQRT_2;
+    let rolled = Orientation::from_sensors([0.0, half, half], None);
+    assert_close(rolled.roll, 45.0);
+    assert_close(rolled.pitch, 0.0);
+
+    let pitched = Orientation::from_sensors([-0.5, 0.0, 0.866], Some([0.0, 1.0, 0.0]));
+    assert_close(pitched.pitch, 30.0);
+    assert_close(pitched.heading, 90.0);
+}
+
+#[test]
+fn deflection_scales_past_the_deadzone() {
+    let settings = enabled();
+    assert_eq!(settings.deflection(2.0), 0.0);
+    assert_close(settings.deflection(16.5), 0.5);
+    assert_close(settings.deflection(-16.5), -0.5);
+    assert_eq!(settings.deflection(-60.0), -1.0);
+}
+
+#[test]
+fn settings_are_validated() {
+    assert!(MotionSettings::new().is_valid());
+    let mut settings = MotionSettings::new();
+    settings.deadzone = 30.0;
+    assert!(!settings.is_valid());
+    settings.deadzone = 0.0;
+    settings.range = 120.0;
+    assert!(!settings.is_valid());
+}
+
+#[test]
+fn tilt_is_measured_from_rest() {
+    let mut settings = enabled();
+    settings.rest_roll = 10.0;
+    settings.pitch_axis = Some(AnalogInput::RightTrigger);
+    let state = steer(
+        &settings,
+        Orientation {
+            roll: 26.5,
+            pitch: -20.0,
+            heading: 0.0,
+        },
+    );
+    assert_close(state.left_thumb_x, 0.5);
+    // A trigger is only pulled by a positive tilt
+    assert_eq!(state.right_trigger, 0.0);
+}
+
+#[test]
+fn tilt_adds_to_the_stick() {
+    let mut input = MotionInput::default();
+    input.update(Orientation {
+        roll: -90.0,
+        ..Orientation::LEVEL
+    });
+    let mut state = ControllerState::new();
+    state.left_thumb_x = -0.5;
+    input.apply(&enabled(), &mut state);
+    assert_eq!(state.left_thumb_x, -1.0);
+}
+
+#[test]
+fn disabled_motion_leaves_the_state() {
+    let state = steer(
+        &MotionSettings::new(),
+        Orientation {
+            roll: 45.0,
+            ..Orientation::LEVEL
+        },
+    );
+    assert_eq!(state, ControllerState::new());
+}
+
+#[test]
+fn toggle_turns_motion_on_and_off() {
+    let mut settings = enabled();
+    settings.toggle = Some(Button::RightShoulder);
+    let mut input = MotionInput::default();
+    input.update(Orientation {
+        roll: 45.0,
+        ..Orientation::LEVEL
+    });
+
+    let mut sample = |pressed: bool| {
+        let mut state = ControllerState::new();
+        state.right_shoulder = pressed;
+        input.apply(&settings, &mut state);
+        assert!(!state.right_shoulder);
+        state.left_thumb_x
+    };
+    assert_eq!(sample(false), 0.0);
+    assert_eq!(sample(true), 1.0);
+    assert_eq!(sample(true), 1.0);
+    assert_eq!(sample(false), 1.0);
+    assert_eq!(sample(true), 0.0);
+}
+
+#[test]
+fn heading_is_centered_when_motion_turns_on() {
+    let mut settings = enabled();
+    settings.roll_axis = None;
+    settings.heading_axis = Some(AnalogInput::RightThumbX);
+    let mut input = MotionInput::default();
+    input.update(Orientation {
+        heading: 170.0,
+        ..Orientation::LEVEL
+    });
+    let mut state = ControllerState::new();
+    input.apply(&settings, &mut state);
+    assert_eq!(state.right_thumb_x, 0.0);
+
+    // Turning past south wraps around to -160
+    for _ in 0..40 {
+        input.update(Orientation {
+            heading: -160.0,
+            ..Orientation::LEVEL
+        });
+    }
+    input.apply(&settings, &mut state);
+    assert_close(input.orientation().unwrap().heading, -160.0);
+    assert_close(state.right_thumb_x, 1.0);
+}
+
+#[test]
+fn readings_are_smoothed() {
+    let mut input = MotionInput::default();
+    assert_eq!(input.orientation(), None);
+    input.update(Orientation::LEVEL);
+    input.update(Orientation {
+        roll: 40.0,
+        ..Orientation::LEVEL
+    });
+    let roll = input.orientation().unwrap().roll;
+    assert!(roll > 0.0 && roll < 40.0);
+}
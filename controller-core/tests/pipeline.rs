@@ -0,0 +1,420 @@
+mod common;
+
+use common::{MockAdc, MockGpio, MockSink};
+use controller_core::calibration::{AxisCalibration, Calibration};
+use controller_core::curve::{ResponseCurve, ResponseCurves};
+use controller_core::deadzone::{Deadzones, StickDeadzone};
+use controller_core::debounce::{ButtonDebounce, ButtonDebounces, DebounceMode};
+use controller_core::dpad::{SocdMode, HAT_NEUTRAL};
+use controller_core::filter::{AnalogFilter, AnalogFilters};
+use controller_core::heading::MagCalibration;
+use controller_core::input::{AnalogInput, Button};
+use controller_core::macros::{Macro, MacroStep, Macros};
+use controller_core::motion::{MotionSettings, Orientation};
+use controller_core::pipeline::{InputPipeline, PipelineError};
+use controller_core::press::{GuideMode, PressEvent};
+use controller_core::remap::{AxisBinding, ButtonBinding, ButtonSet, Profiles};
+use controller_core::schedule::PollRate;
+use packed_struct::prelude::*;
+
+#[test]
+fn sample_maps_raw_values_to_axes() {
+    let mut adc = MockAdc::centered();
+    adc.set(AnalogInput::LeftThumbX, 0);
+    adc.set(AnalogInput::RightThumbY, 4095);
+    let mut pipeline = InputPipeline::new();
+
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+
+    let state = pipeline.state();
+    assert_eq!(state.left_thumb_x, -1.0);
+    assert_eq!(state.right_thumb_y, 1.0);
+    assert!(state.left_thumb_y.abs() < 0.001);
+}
+
+#[test]
+fn sample_applies_calibration() {
+    let mut adc = MockAdc::centered();
+    adc.set(AnalogInput::LeftThumbX, 1800);
+    adc.set(AnalogInput::LeftThumbY, 3000);
+    let mut calibration = Calibration::new();
+    *calibration.axis_mut(AnalogInput::LeftThumbX) = AxisCalibration::new(300, 1800, 3300);
+    *calibration.axis_mut(AnalogInput::LeftThumbY) = AxisCalibration::new(300, 1800, 3000);
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_calibration(calibration);
+
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+
+    assert_eq!(pipeline.state().left_thumb_x, 0.0);
+    assert_eq!(pipeline.state().left_thumb_y, 1.0);
+    assert_eq!(pipeline.raw_values()[AnalogInput::LeftThumbX.index()], 1800);
+}
+
+#[test]
+fn sample_applies_stick_deadzones() {
+    let mut adc = MockAdc::centered();
+    adc.set(AnalogInput::LeftThumbX, 2100);
+    adc.set(AnalogInput::RightThumbX, 2100);
+    let mut deadzones = Deadzones::new();
+    deadzones.right_stick = StickDeadzone::NONE;
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_deadzones(deadzones);
+
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+
+    assert_eq!(pipeline.state().left_thumb_x, 0.0);
+    assert!(pipeline.state().right_thumb_x > 0.02);
+}
+
+#[test]
+fn sample_applies_curves_after_deadzones() {
+    let mut adc = MockAdc::centered();
+    adc.set(AnalogInput::LeftThumbX, 0);
+    adc.set(AnalogInput::LeftTrigger, 2048);
+    let mut curves = ResponseCurves::new();
+    *curves.axis_mut(AnalogInput::LeftThumbX) = ResponseCurve::EXPONENTIAL;
+    *curves.axis_mut(AnalogInput::LeftTrigger) = ResponseCurve::EXPONENTIAL;
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_curves(curves);
+
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+
+    assert_eq!(pipeline.state().left_thumb_x, -1.0);
+    assert!((pipeline.state().left_trigger - 0.25).abs() < 0.001);
+}
+
+#[test]
+fn sample_reads_buttons() {
+    let mut gpio = MockGpio::default();
+    gpio.press(Button::A);
+    gpio.press(Button::Back);
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_debounces(ButtonDebounces::disabled());
+
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert!(pipeline.state().a);
+    assert!(pipeline.state().back);
+    assert!(!pipeline.state().b);
+
+    gpio.release(Button::A);
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert!(!pipeline.state().a);
+}
+
+#[test]
+fn sample_reports_adc_errors() {
+    let mut adc = MockAdc::centered();
+    adc.failing = Some(AnalogInput::LeftTrigger);
+    let mut pipeline = InputPipeline::new();
+
+    let result = pipeline.sample(&mut adc, &mut MockGpio::default());
+    assert_eq!(result, Err(PipelineError::Analog(())));
+}
+
+#[test]
+fn sample_filters_raw_values_before_calibration() {
+    let mut adc = MockAdc::centered();
+    let mut pipeline = InputPipeline::new();
+    let mut filters = AnalogFilters::new();
+    *filters.axis_mut(AnalogInput::LeftThumbX) = AnalogFilter::Average { samples: 2 };
+    pipeline.set_filters(filters);
+
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+    adc.set(AnalogInput::LeftThumbX, 0);
+    adc.set(AnalogInput::LeftThumbY, 0);
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+
+    assert_eq!(pipeline.raw_values()[AnalogInput::LeftThumbX.index()], 1024);
+    assert_eq!(pipeline.raw_values()[AnalogInput::LeftThumbY.index()], 0);
+    let state = pipeline.state();
+    assert!(state.left_thumb_x < 0.0 && state.left_thumb_x > state.left_thumb_y);
+}
+
+#[test]
+fn sample_begins_analog_sample_once() {
+    let mut adc = MockAdc::centered();
+    let mut pipeline = InputPipeline::new();
+
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+    pipeline.sample(&mut adc, &mut MockGpio::default()).unwrap();
+    assert_eq!(adc.samples, 2);
+}
+
+#[test]
+fn sample_debounces_buttons() {
+    let mut gpio = MockGpio::default();
+    let mut pipeline = InputPipeline::new();
+    let mut debounces = ButtonDebounces::new();
+    *debounces.button_mut(Button::B) = ButtonDebounce::new(DebounceMode::Deferred, 2);
+    pipeline.set_debounces(debounces);
+    pipeline.set_poll_rate(PollRate::Hz1000);
+
+    // A bounces on press, B is deferred by 2 ms
+    let mut a_states = Vec::new();
+    let mut b_states = Vec::new();
+    for pressed in [true, false, true, false, true, true, true, true] {
+        if pressed {
+            gpio.press(Button::A);
+            gpio.press(Button::B);
+        } else {
+            gpio.release(Button::A);
+            gpio.release(Button::B);
+        }
+        pipeline
+            .sample(&mut MockAdc::centered(), &mut gpio)
+            .unwrap();
+        a_states.push(pipeline.state().a);
+        b_states.push(pipeline.state().b);
+    }
+
+    assert_eq!(a_states, [true; 8]);
+    assert_eq!(
+        b_states,
+        [false, false, false, false, false, true, true, true]
+    );
+}
+
+#[test]
+fn publish_packs_state_into_report() {
+    let mut adc = MockAdc::centered();
+    adc.set(AnalogInput::LeftThumbX, 4095);
+    adc.set(AnalogInput::RightThumbX, 0);
+    adc.set(AnalogInput::LeftTrigger, 0);
+    adc.set(AnalogInput::RightTrigger, 4095);
+    let mut gpio = MockGpio::default();
+    gpio.press(Button::A);
+    gpio.press(Button::Start);
+    let mut sink = MockSink::default();
+    let mut pipeline = InputPipeline::new();
+
+    pipeline.sample(&mut adc, &mut gpio).unwrap();
+    pipeline.publish(&mut sink).unwrap();
+
+    assert_eq!(sink.reports.len(), 1);
+    let report = sink.reports[0];
+    assert_eq!(report.x, 32767);
+    assert_eq!(report.y, 0);
+    assert_eq!(report.z, -32767);
+    assert_eq!(report.rx, 0);
+    assert_eq!(report.ry, 0);
+    assert_eq!(report.rz, 65535);
+    assert_eq!(report.buttons, 1 << 0 | 1 << 6);
+    assert_eq!(report.hat, HAT_NEUTRAL);
+
+    let packed = report.pack();
+    assert_eq!(
+        packed,
+        [0xff, 0x7f, 0, 0, 0x01, 0x80, 0, 0, 0, 0, 0xff, 0xff, 0x41, 0x00, 0x08]
+    );
+}
+
+#[test]
+fn sample_resolves_dpad_into_hat() {
+    let mut gpio = MockGpio::default();
+    gpio.press(Button::Up);
+    gpio.press(Button::Right);
+    let mut pipeline = InputPipeline::new();
+
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert_eq!(pipeline.report().hat, 1);
+    assert_eq!(pipeline.report().buttons, 0);
+
+    gpio.press(Button::Down);
+    pipeline.set_socd_mode(SocdMode::UpPriority);
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert!(!pipeline.state().down);
+    assert_eq!(pipeline.report().hat, 1);
+}
+
+#[test]
+fn guide_button_is_reported_in_guide_mode() {
+    let mut gpio = MockGpio::default();
+    gpio.press(Button::Guide);
+    let mut pipeline = InputPipeline::new();
+
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert_eq!(pipeline.report().buttons, 1 << 10);
+    assert_eq!(pipeline.press_event(), None);
+}
+
+#[test]
+fn guide_button_is_kept_from_host_in_mode_key_mode() {
+    let mut gpio = MockGpio::default();
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_guide_mode(GuideMode::ModeKey);
+
+    gpio.press(Button::Guide);
+    let mut events = Vec::new();
+    for _ in 0..200 {
+        pipeline
+            .sample(&mut MockAdc::centered(), &mut gpio)
+            .unwrap();
+        assert_eq!(pipeline.report().buttons, 0);
+        events.extend(pipeline.press_event());
+    }
+    assert_eq!(events, [PressEvent::Long]);
+}
+
+#[test]
+fn report_follows_active_profile() {
+    let mut gpio = MockGpio::default();
+    gpio.press(Button::A);
+    let mut adc = MockAdc::centered();
+    adc.set(AnalogInput::LeftThumbX, 4095);
+    let mut profiles = Profiles::new();
+    *profiles.profiles[0].button_mut(Button::A) = ButtonBinding::Unbound;
+    *profiles.profiles[0].button_mut(Button::B) = ButtonBinding::Button(Button::A);
+    *profiles.profiles[0].button_mut(Button::Right) = ButtonBinding::Axis {
+        input: AnalogInput::LeftThumbX,
+        threshold: 0.5,
+    };
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_debounces(ButtonDebounces::disabled());
+    pipeline.set_profiles(profiles);
+
+    pipeline.sample(&mut adc, &mut gpio).unwrap();
+
+    assert!(pipeline.state().a);
+    assert!(!pipeline.output_state().a);
+    assert!(pipeline.output_state().b);
+    assert!(pipeline.output_state().right);
+    assert_eq!(pipeline.report().buttons, 1 << 1);
+    assert_eq!(pipeline.report().hat, 2);
+}
+
+#[test]
+fn switch_combo_cycles_profiles() {
+    let mut gpio = MockGpio::default();
+    let mut profiles = Profiles::new();
+    profiles.count = 2;
+    *profiles.profiles[1].button_mut(Button::Y) = ButtonBinding::Button(Button::X);
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_debounces(ButtonDebounces::disabled());
+    pipeline.set_profiles(profiles);
+
+    gpio.press(Button::Back);
+    gpio.press(Button::RightShoulder);
+    gpio.press(Button::X);
+    let mut switches = 0;
+    for _ in 0..3 {
+        pipeline
+            .sample(&mut MockAdc::centered(), &mut gpio)
+            .unwrap();
+        switches += pipeline.profile_switched() as u32;
+        // Only X reaches the report, now also on Y
+        assert_eq!(pipeline.report().buttons, 1 << 2 | 1 << 3);
+    }
+    assert_eq!(switches, 1);
+    assert_eq!(pipeline.profiles().active, 1);
+
+    gpio.release(Button::Back);
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    gpio.press(Button::Back);
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert!(pipeline.profile_switched());
+    assert_eq!(pipeline.profiles().active, 0);
+}
+
+#[test]
+fn single_profile_ignores_the_switch_combo() {
+    let mut gpio = MockGpio::default();
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_debounces(ButtonDebounces::disabled());
+
+    gpio.press(Button::Back);
+    gpio.press(Button::RightShoulder);
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut gpio)
+        .unwrap();
+    assert!(!pipeline.profile_switched());
+    assert_eq!(pipeline.profiles().active, 0);
+    assert!(pipeline.output_state().back && pipeline.output_state().right_shoulder);
+}
+
+#[test]
+fn sample_plays_macros_on_the_sample_clock() {
+    let mut gpio = MockGpio::default();
+    let step = MacroStep::press(ButtonSet::EMPTY.with(Button::B), 20);
+    let mut macros = Macros::new();
+    macros.slots[0] = Some(Macro::new(Button::Guide, &[MacroStep::wait(10), step]).unwrap());
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_debounces(ButtonDebounces::disabled());
+    pipeline.set_poll_rate(PollRate::Hz500);
+    pipeline.set_macros(macros);
+
+    gpio.press(Button::Guide);
+    let mut presses = Vec::new();
+    for _ in 0..20 {
+        pipeline
+            .sample(&mut MockAdc::centered(), &mut gpio)
+            .unwrap();
+        assert!(!pipeline.output_state().guide);
+        presses.push(pipeline.output_state().b);
+    }
+
+    // A sample every 2 ms: 10 ms waiting, then 20 ms holding B
+    let expected: Vec<bool> = (0..20).map(|sample| (5..15).contains(&sample)).collect();
+    assert_eq!(presses, expected);
+    assert!(!pipeline.is_playing_macro());
+}
+
+#[test]
+fn tilt_steers_the_axis_before_the_profile() {
+    let mut profiles = Profiles::new();
+    *profiles.profiles[0].axis_mut(AnalogInput::RightThumbX) = AxisBinding::Axis {
+        input: AnalogInput::LeftThumbX,
+        inverted: false,
+    };
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_profiles(profiles);
+    pipeline.set_motion(MotionSettings {
+        enabled: true,
+        ..MotionSettings::new()
+    });
+
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut MockGpio::default())
+        .unwrap();
+    assert!(pipeline.output_state().right_thumb_x.abs() < 0.001);
+
+    pipeline.set_orientation(Orientation {
+        roll: 30.0,
+        ..Orientation::LEVEL
+    });
+    pipeline
+        .sample(&mut MockAdc::centered(), &mut MockGpio::default())
+        .unwrap();
+    assert!(pipeline.is_motion_active());
+    assert!(pipeline.state().left_thumb_x.abs() < 0.001);
+    assert!(pipeline.output_state().right_thumb_x > 0.999);
+}
+
+#[test]
+fn heading_reads_the_calibrated_field() {
+    let mut pipeline = InputPipeline::new();
+    // Level, with a hard iron offset pulling the field toward `x`
+    pipeline.read_sensors([0.0, 0.0, 1.0], Some([0.3, 0.2, -0.4]));
+    assert!((pipeline.orientation().unwrap().heading - 33.69).abs() < 0.01);
+
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_mag_calibration(MagCalibration {
+        offset: [0.3, 0.0, 0.0],
+        ..MagCalibration::IDENTITY
+    });
+    pipeline.read_sensors([0.0, 0.0, 1.0], Some([0.3, 0.2, -0.4]));
+    assert!((pipeline.orientation().unwrap().heading - 90.0).abs() < 0.01);
+}
@@ -0,0 +1,50 @@
+use controller_core::press::{PressDetector, PressEvent};
+
+const LONG: u32 = 10;
+const GAP: u32 = 3;
+
+/// Feeds `samples` identical samples, returns every event sent
+fn hold(detector: &mut PressDetector, pressed: bool, samples: u32) -> Vec<PressEvent> {
+    (0..samples)
+        .filter_map(|_| detector.update(pressed))
+        .collect()
+}
+
+#[test]
+fn short_press_is_sent_after_double_press_gap() {
+    let mut detector = PressDetector::new(LONG, GAP);
+    assert!(hold(&mut detector, true, 2).is_empty());
+    assert!(hold(&mut detector, false, GAP).is_empty());
+    assert_eq!(hold(&mut detector, false, 1), [PressEvent::Short]);
+    assert!(hold(&mut detector, false, 20).is_empty());
+}
+
+#[test]
+fn long_press_is_sent_while_held() {
+    let mut detector = PressDetector::new(LONG, GAP);
+    assert!(hold(&mut detector, true, LONG - 1).is_empty());
+    assert_eq!(hold(&mut detector, true, 1), [PressEvent::Long]);
+    assert!(hold(&mut detector, true, 20).is_empty());
+    assert!(hold(&mut detector, false, 20).is_empty());
+}
+
+#[test]
+fn double_press_replaces_short_press() {
+    let mut detector = PressDetector::new(LONG, GAP);
+    hold(&mut detector, true, 2);
+    hold(&mut detector, false, 2);
+    assert_eq!(hold(&mut detector, true, 1), [PressEvent::Double]);
+    // Neither holding nor releasing the second press sends anything else
+    assert!(hold(&mut detector, true, LONG * 2).is_empty());
+    assert!(hold(&mut detector, false, 20).is_empty());
+}
+
+#[test]
+fn presses_further_apart_than_gap_are_two_short_presses() {
+    let mut detector = PressDetector::new(LONG, GAP);
+    let mut events = hold(&mut detector, true, 2);
+    events.extend(hold(&mut detector, false, GAP + 2));
+    events.extend(hold(&mut detector, true, 2));
+    events.extend(hold(&mut detector, false, GAP + 2));
+    assert_eq!(events, [PressEvent::Short, PressEvent::Short]);
+}
@@ -0,0 +1,161 @@
+use controller_core::controller::ControllerState;
+use controller_core::input::{AnalogInput, Button};
+use controller_core::remap::{
+    AxisBinding, ButtonBinding, ButtonSet, Profile, ProfileSwitch, Profiles, DEFAULT_SWITCH_COMBO,
+};
+
+fn state() -> ControllerState {
+    let mut state = ControllerState::new();
+    state.a = true;
+    state.left = true;
+    state.left_thumb_x = -0.75;
+    state.right_thumb_y = 0.25;
+    state.left_trigger = 0.4;
+    state
+}
+
+#[test]
+fn identity_reports_inputs_as_wired() {
+    assert_eq!(Profile::IDENTITY.apply(&state()), state());
+}
+
+#[test]
+fn buttons_follow_their_source() {
+    let mut profile = Profile::IDENTITY;
+    *profile.button_mut(Button::A) = ButtonBinding::Unbound;
+    *profile.button_mut(Button::RightShoulder) = ButtonBinding::Button(Button::A);
+
+    let output = profile.apply(&state());
+    assert!(!output.a);
+    assert!(output.right_shoulder);
+    assert!(output.left);
+}
+
+#[test]
+fn axis_drives_button_past_threshold() {
+    let mut profile = Profile::IDENTITY;
+    *profile.button_mut(Button::Left) = ButtonBinding::Axis {
+        input: AnalogInput::LeftThumbX,
+        threshold: -0.5,
+    };
+    *profile.button_mut(Button::Right) = ButtonBinding::Axis {
+        input: AnalogInput::LeftThumbX,
+        threshold: 0.5,
+    };
+    *profile.button_mut(Button::LeftShoulder) = ButtonBinding::Axis {
+        input: AnalogInput::LeftTrigger,
+        threshold: 0.5,
+    };
+
+    let output = profile.apply(&state());
+    assert!(output.left);
+    assert!(!output.right);
+    assert!(!output.left_shoulder);
+}
+
+#[test]
+fn buttons_drive_axis() {
+    let mut profile = Profile::IDENTITY;
+    let dpad = AxisBinding::Buttons {
+        negative: Some(Button::Left),
+        positive: Some(Button::Right),
+    };
+    *profile.axis_mut(AnalogInput::RightThumbX) = dpad;
+    *profile.axis_mut(AnalogInput::RightTrigger) = AxisBinding::Buttons {
+        negative: None,
+        positive: Some(Button::A),
+    };
+    *profile.axis_mut(AnalogInput::LeftTrigger) = AxisBinding::Buttons {
+        negative: Some(Button::A),
+        positive: None,
+    };
+
+    let mut physical = state();
+    let output = profile.apply(&physical);
+    assert_eq!(output.right_thumb_x, -1.0);
+    assert_eq!(output.right_trigger, 1.0);
+    assert_eq!(output.left_trigger, 0.0);
+
+    physical.right = true;
+    assert_eq!(profile.apply(&physical).right_thumb_x, 0.0);
+}
+
+#[test]
+fn inverted_axes_reverse_their_travel() {
+    let mut profile = Profile::IDENTITY;
+    *profile.axis_mut(AnalogInput::RightThumbY) = AxisBinding::Axis {
+        input: AnalogInput::RightThumbY,
+        inverted: true,
+    };
+    *profile.axis_mut(AnalogInput::LeftTrigger) = AxisBinding::Axis {
+        input: AnalogInput::LeftTrigger,
+        inverted: true,
+    };
+    // A stick pushed left on a trigger only reports its positive half
+    *profile.axis_mut(AnalogInput::RightTrigger) = AxisBinding::Axis {
+        input: AnalogInput::LeftThumbX,
+        inverted: false,
+    };
+
+    let output = profile.apply(&state());
+    assert_eq!(output.right_thumb_y, -0.25);
+    assert_eq!(output.left_trigger, 0.6);
+    assert_eq!(output.right_trigger, 0.0);
+}
+
+#[test]
+fn threshold_must_be_in_range() {
+    let binding = |threshold| ButtonBinding::Axis {
+        input: AnalogInput::Other0,
+        threshold,
+    };
+    assert!(binding(-1.0).is_valid());
+    assert!(!binding(0.0).is_valid());
+    assert!(!binding(1.5).is_valid());
+    assert!(!binding(f32::NAN).is_valid());
+}
+
+#[test]
+fn profiles_cycle_through_used_ones() {
+    let mut profiles = Profiles::new();
+    profiles.next();
+    assert_eq!(profiles.active, 0);
+
+    profiles.count = 3;
+    profiles.next();
+    profiles.next();
+    assert_eq!(profiles.active, 2);
+    profiles.next();
+    assert_eq!(profiles.active, 0);
+
+    profiles.active = 3;
+    assert!(!profiles.is_valid());
+}
+
+#[test]
+fn button_set_rejects_unknown_buttons() {
+    assert_eq!(ButtonSet::from_bits(1 << 15), None);
+    let combo = ButtonSet::from_bits(DEFAULT_SWITCH_COMBO.0).unwrap();
+    assert_eq!(
+        combo.iter().collect::<Vec<_>>(),
+        [Button::RightShoulder, Button::Back]
+    );
+}
+
+#[test]
+fn switch_hides_held_combo() {
+    let mut switch = ProfileSwitch::default();
+    let mut physical = state();
+    physical.back = true;
+    assert!(!switch.update(DEFAULT_SWITCH_COMBO, &mut physical));
+    assert!(physical.back);
+
+    physical.right_shoulder = true;
+    assert!(switch.update(DEFAULT_SWITCH_COMBO, &mut physical));
+    assert!(!physical.back && !physical.right_shoulder && physical.a);
+
+    physical.back = true;
+    physical.right_shoulder = true;
+    assert!(!switch.update(DEFAULT_SWITCH_COMBO, &mut physical));
+    assert!(!switch.update(ButtonSet::EMPTY, &mut state()));
+}
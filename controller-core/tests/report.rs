@@ -0,0 +1,94 @@
+use controller_core::config;
+use controller_core::controller::ControllerState;
+use controller_core::pipeline::button_value_mut;
+use controller_core::report::{
+    get_report, stick_value, trigger_value, JOYSTICK_MAX_VALUE, REPORT_BUTTONS, TRIGGER_MAX_VALUE,
+    XBOX_JOYSTICK_DESCRIPTOR,
+};
+use packed_struct::prelude::*;
+
+#[test]
+fn sticks_use_full_16_bit_range() {
+    assert_eq!(stick_value(1.0), JOYSTICK_MAX_VALUE);
+    assert_eq!(stick_value(-1.0), -JOYSTICK_MAX_VALUE);
+    assert_eq!(stick_value(0.0), 0);
+    // One 12-bit ADC step is still visible in the report
+    assert_ne!(stick_value(1.0 / 2047.0), 0);
+    assert_eq!(stick_value(2.0), JOYSTICK_MAX_VALUE);
+}
+
+#[test]
+fn report_buttons_match_the_bits() {
+    for (bit, button) in REPORT_BUTTONS.into_iter().enumerate() {
+        let mut state = ControllerState::new();
+        *button_value_mut(&mut state, button) = true;
+        assert_eq!(get_report(&state).buttons, 1 << bit, "{:?}", button);
+    }
+}
+
+#[test]
+fn triggers_are_unsigned() {
+    assert_eq!(trigger_value(0.0), 0);
+    assert_eq!(trigger_value(1.0), TRIGGER_MAX_VALUE);
+    assert_eq!(trigger_value(0.5), 32768);
+    assert_eq!(trigger_value(-0.3), 0);
+}
+
+#[test]
+fn report_maps_triggers_on_their_own_usages() {
+    let mut state = ControllerState::new();
+    state.left_trigger = 1.0;
+    state.right_trigger = 0.25;
+    state.right_thumb_y = -0.5;
+
+    let report = get_report(&state);
+    // Down on the stick is positive on the HID axis
+    assert_eq!(report.rx, 16384);
+    assert_eq!(report.ry, TRIGGER_MAX_VALUE);
+    assert_eq!(report.rz, 16384);
+}
+
+/// Sums the report size * report count of every main item with the `item` prefix,
+/// 0x80 for inputs and 0xb0 for features
+fn descriptor_bits(descriptor: &[u8], item: u8) -> usize {
+    let mut bits = 0;
+    let mut report_size = 0;
+    let mut report_count = 0;
+    let mut index = 0;
+    while index < descriptor.len() {
+        let prefix = descriptor[index];
+        let size = match prefix & 0x03 {
+            3 => 4,
+            size => size as usize,
+        };
+        let mut data = 0usize;
+        for (shift, byte) in descriptor[index + 1..index + 1 + size].iter().enumerate() {
+            data |= (*byte as usize) << (8 * shift);
+        }
+        match prefix & 0xfc {
+            0x74 => report_size = data,
+            0x94 => report_count = data,
+            prefix if prefix == item => bits += report_size * report_count,
+            _ => {}
+        }
+        index += 1 + size;
+    }
+    bits
+}
+
+#[test]
+fn descriptor_matches_report_size() {
+    let packed = get_report(&ControllerState::new()).pack();
+    assert_eq!(
+        descriptor_bits(XBOX_JOYSTICK_DESCRIPTOR, 0x80),
+        packed.len() * 8
+    );
+}
+
+#[test]
+fn descriptor_feature_matches_config_report_size() {
+    assert_eq!(
+        descriptor_bits(XBOX_JOYSTICK_DESCRIPTOR, 0xb0),
+        config::REPORT_SIZE * 8
+    );
+}
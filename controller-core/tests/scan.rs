@@ -0,0 +1,39 @@
+use controller_core::scan::{sample_time_registers, sequence_registers, Half};
+
+#[test]
+fn remaining_transfers_select_the_half_being_written() {
+    // 3 channels, 6 transfers per loop
+    assert_eq!(Half::being_written(6, 3), Half::First);
+    assert_eq!(Half::being_written(4, 3), Half::First);
+    assert_eq!(Half::being_written(3, 3), Half::Second);
+    assert_eq!(Half::being_written(1, 3), Half::Second);
+    assert_eq!(Half::First.other(), Half::Second);
+    assert_eq!(Half::Second.other().index(), 0);
+}
+
+#[test]
+fn sequence_registers_hold_length_and_ranks() {
+    let registers = sequence_registers(&[7, 8, 10]);
+    assert_eq!(registers[0], 2 | 7 << 6 | 8 << 12 | 10 << 18);
+    assert_eq!(registers[1..], [0, 0, 0]);
+}
+
+#[test]
+fn long_sequences_continue_in_sqr2() {
+    let registers = sequence_registers(&[12, 13, 3, 11, 5, 1]);
+    assert_eq!(registers[0], 5 | 12 << 6 | 13 << 12 | 3 << 18 | 11 << 24);
+    assert_eq!(registers[1], 5 | 1 << 6);
+}
+
+#[test]
+#[should_panic]
+fn empty_sequence_is_rejected() {
+    sequence_registers(&[]);
+}
+
+#[test]
+fn sample_times_are_set_per_channel() {
+    let registers = sample_time_registers(&[3, 5, 12], 0b111);
+    assert_eq!(registers[0], 0b111 << 9 | 0b111 << 15);
+    assert_eq!(registers[1], 0b111 << 6);
+}
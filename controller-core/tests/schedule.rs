@@ -0,0 +1,61 @@
+use controller_core::schedule::{JitterStats, PollRate};
+
+#[test]
+fn poll_rates_match_endpoint_intervals() {
+    let rates = [
+        (PollRate::Hz125, 8000, 8),
+        (PollRate::Hz250, 4000, 4),
+        (PollRate::Hz500, 2000, 2),
+        (PollRate::Hz1000, 1000, 1),
+    ];
+    for (rate, period_us, interval_ms) in rates {
+        assert_eq!(rate.period_us(), period_us);
+        assert_eq!(rate.endpoint_interval_ms(), interval_ms);
+        assert_eq!(PollRate::from_u8(rate.as_u8()), Some(rate));
+        assert_eq!(PollRate::from_hz(rate.hz()), Some(rate));
+    }
+    assert_eq!(PollRate::from_u8(4), None);
+    assert_eq!(PollRate::from_hz(300), None);
+}
+
+#[test]
+fn durations_convert_to_sample_counts() {
+    assert_eq!(PollRate::Hz1000.samples(800), 800);
+    assert_eq!(PollRate::Hz125.samples(800), 100);
+    assert_eq!(PollRate::Hz125.samples(1), 1);
+}
+
+#[test]
+fn empty_stats_have_no_period() {
+    let stats = JitterStats::new(1000);
+    assert_eq!(stats.count(), 0);
+    assert_eq!(stats.min_us(), None);
+    assert_eq!(stats.max_us(), None);
+    assert_eq!(stats.mean_us(), None);
+    assert_eq!(stats.max_jitter_us(), 0);
+}
+
+#[test]
+fn stats_track_extremes_and_mean() {
+    let mut stats = JitterStats::new(1000);
+    for period in [1000, 990, 1030, 980] {
+        stats.record(period);
+    }
+    assert_eq!(stats.count(), 4);
+    assert_eq!(stats.min_us(), Some(980));
+    assert_eq!(stats.max_us(), Some(1030));
+    assert_eq!(stats.mean_us(), Some(1000));
+    assert_eq!(stats.max_jitter_us(), 30);
+
+    stats.record(940);
+    assert_eq!(stats.max_jitter_us(), 60);
+}
+
+#[test]
+fn reset_keeps_expected_period() {
+    let mut stats = JitterStats::new(4000);
+    stats.record(4100);
+    stats.reset();
+    assert_eq!(stats.count(), 0);
+    assert_eq!(stats.expected_us(), 4000);
+}
@@ -0,0 +1,380 @@
+use controller_core::calibration::AxisCalibration;
+use controller_core::codec::{crc32, CodecError};
+use controller_core::curve::{CurveTable, ResponseCurve};
+use controller_core::deadzone::{DeadzoneMode, StickDeadzone};
+use controller_core::debounce::{ButtonDebounce, DebounceMode};
+use controller_core::dpad::SocdMode;
+use controller_core::filter::{AnalogFilter, AnalogFilters};
+use controller_core::heading::MagCalibration;
+use controller_core::input::{AnalogInput, Button};
+use controller_core::macros::{Macro, MacroStep, Macros, TurboRates, MAX_MACROS};
+use controller_core::motion::MotionSettings;
+use controller_core::press::GuideMode;
+use controller_core::remap::{
+    AxisBinding, ButtonBinding, ButtonSet, Profile, Profiles, MAX_PROFILES,
+};
+use controller_core::schedule::PollRate;
+use controller_core::settings::{Settings, SettingsError, MAX_SETTINGS_SIZE, SETTINGS_VERSION};
+use controller_core::xinput::UsbMode;
+
+/// Encoded size of [`Profiles::default`]: count, active index and combo,
+/// then two bytes per button binding and three per axis binding of its profile
+const DEFAULT_PROFILES_SIZE: usize = 4 + 2 * Button::COUNT + 3 * AnalogInput::COUNT;
+
+/// Encoded size of the motion settings: the enabled flag, three axes, four angles and the toggle
+const MOTION_SIZE: usize = 1 + 3 + 4 * 4 + 1;
+
+/// Encoded size of the magnetometer calibration: the offset then the matrix
+const MAG_CALIBRATION_SIZE: usize = 4 * (3 + 9);
+
+/// Encoded size of the fields following the debounces, once reset by [`with_default_tail`]:
+/// the profiles, a turbo rate per button, a byte per empty macro slot, the motion and the
+/// magnetometer calibration
+const DEFAULT_TAIL_SIZE: usize =
+    DEFAULT_PROFILES_SIZE + Button::COUNT + MAX_MACROS + MOTION_SIZE + MAG_CALIBRATION_SIZE;
+
+fn with_default_tail(mut settings: Settings) -> Settings {
+    settings.profiles = Profiles::default();
+    settings.turbo = TurboRates::default();
+    settings.macros = Macros::default();
+    settings.motion = MotionSettings::default();
+    settings.mag_calibration = MagCalibration::default();
+    settings
+}
+
+/// Jump, wait, then jump again with the stick pulled back
+fn half_flip() -> Macro {
+    let jump = ButtonSet::EMPTY.with(Button::A);
+    let back_jump = MacroStep {
+        buttons: jump,
+        left_stick: Some((0.0, -1.0)),
+        duration_ms: 40,
+    };
+    Macro::new(
+        Button::LeftThumb,
+        &[MacroStep::press(jump, 30), MacroStep::wait(60), back_jump],
+    )
+    .unwrap()
+}
+
+/// Face buttons swapped, powerslide on the right shoulder and the d-pad on the left stick
+fn custom_profile() -> Profile {
+    let mut profile = Profile::IDENTITY;
+    *profile.button_mut(Button::A) = ButtonBinding::Button(Button::B);
+    *profile.button_mut(Button::B) = ButtonBinding::Button(Button::A);
+    *profile.button_mut(Button::X) = ButtonBinding::Button(Button::RightShoulder);
+    *profile.button_mut(Button::Guide) = ButtonBinding::Unbound;
+    *profile.button_mut(Button::Up) = ButtonBinding::Axis {
+        input: AnalogInput::LeftThumbY,
+        threshold: 0.5,
+    };
+    *profile.axis_mut(AnalogInput::LeftThumbX) = AxisBinding::Buttons {
+        negative: Some(Button::Left),
+        positive: Some(Button::Right),
+    };
+    *profile.axis_mut(AnalogInput::RightTrigger) = AxisBinding::Buttons {
+        negative: None,
+        positive: Some(Button::Y),
+    };
+    *profile.axis_mut(AnalogInput::RightThumbY) = AxisBinding::Axis {
+        input: AnalogInput::RightThumbY,
+        inverted: true,
+    };
+    *profile.axis_mut(AnalogInput::Other1) = AxisBinding::Unbound;
+    profile
+}
+
+fn calibrated() -> Settings {
+    let mut settings = Settings::new();
+    *settings.calibration.axis_mut(AnalogInput::LeftThumbX) = AxisCalibration::new(120, 1980, 3900);
+    *settings.calibration.axis_mut(AnalogInput::RightTrigger) =
+        AxisCalibration::new(310, 310, 3650);
+    settings.deadzones.right_stick = StickDeadzone {
+        mode: DeadzoneMode::Axial,
+        inner: 0.1,
+        outer: 0.05,
+        anti_deadzone: 0.2,
+    };
+    *settings.curves.axis_mut(AnalogInput::LeftThumbX) = ResponseCurve::EXPONENTIAL;
+    *settings.curves.axis_mut(AnalogInput::LeftThumbY) = ResponseCurve::S_CURVE;
+    *settings.curves.axis_mut(AnalogInput::RightTrigger) =
+        ResponseCurve::Custom(CurveTable::new(&[(0.2, 0.0), (0.6, 0.9)]).unwrap());
+    settings.socd_mode = SocdMode::LastWins;
+    settings.guide_mode = GuideMode::ModeKey;
+    settings.usb_mode = UsbMode::XInput;
+    settings.poll_rate = PollRate::Hz250;
+    *settings.filters.axis_mut(AnalogInput::LeftThumbX) = AnalogFilter::ONE_EURO;
+    *settings.filters.axis_mut(AnalogInput::LeftTrigger) = AnalogFilter::Average { samples: 4 };
+    *settings.filters.axis_mut(AnalogInput::RightThumbY) =
+        AnalogFilter::Exponential { alpha: 0.25 };
+    *settings.filters.axis_mut(AnalogInput::Other0) = AnalogFilter::Hysteresis { threshold: 8 };
+    *settings.debounces.button_mut(Button::A) = ButtonDebounce::new(DebounceMode::Deferred, 10);
+    *settings.debounces.button_mut(Button::Guide) = ButtonDebounce::new(DebounceMode::Eager, 0);
+    settings.profiles.profiles[1] = custom_profile();
+    settings.profiles.count = 2;
+    settings.profiles.active = 1;
+    settings.profiles.switch_combo = ButtonSet::EMPTY.with(Button::Back).with(Button::Guide);
+    *settings.turbo.button_mut(Button::X) = 15;
+    settings.macros.slots[2] = Some(half_flip());
+    settings.motion = MotionSettings {
+        enabled: true,
+        roll_axis: Some(AnalogInput::RightThumbX),
+        pitch_axis: None,
+        heading_axis: Some(AnalogInput::LeftThumbX),
+        range: 40.0,
+        deadzone: 2.5,
+        rest_roll: -3.0,
+        rest_pitch: 20.0,
+        toggle: Some(Button::RightThumb),
+    };
+    settings.mag_calibration = MagCalibration {
+        offset: [0.12, -0.31, 0.05],
+        matrix: [[1.1, 0.02, 0.0], [0.02, 0.95, -0.01], [0.0, -0.01, 0.97]],
+    };
+    settings
+}
+
+#[test]
+fn crc32_matches_reference_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn encoded_settings_round_trip() {
+    let settings = calibrated();
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[..2], &SETTINGS_VERSION.to_le_bytes());
+    assert_eq!(Settings::decode(&buffer[..length]), Ok(settings));
+}
+
+#[test]
+fn encode_reports_small_buffer() {
+    let mut buffer = [0u8; 8];
+    assert_eq!(
+        Settings::new().encode(&mut buffer),
+        Err(SettingsError::Codec(CodecError::BufferTooSmall))
+    );
+}
+
+#[test]
+fn decode_rejects_newer_version() {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = calibrated().encode(&mut buffer).unwrap();
+    buffer[..2].copy_from_slice(&(SETTINGS_VERSION + 1).to_le_bytes());
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::UnsupportedVersion(SETTINGS_VERSION + 1))
+    );
+}
+
+#[test]
+fn decode_rejects_truncated_record() {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = calibrated().encode(&mut buffer).unwrap();
+
+    assert_eq!(
+        Settings::decode(&buffer[..length - 1]),
+        Err(SettingsError::Codec(CodecError::UnexpectedEnd))
+    );
+}
+
+#[test]
+fn decode_rejects_inverted_calibration() {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = calibrated().encode(&mut buffer).unwrap();
+    // min of the first axis above its center
+    buffer[2..4].copy_from_slice(&4000u16.to_le_bytes());
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_version_zero() {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = calibrated().encode(&mut buffer).unwrap();
+    buffer[..2].copy_from_slice(&0u16.to_le_bytes());
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::UnsupportedVersion(0))
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_curve() {
+    let mut settings = calibrated();
+    *settings.curves.axis_mut(AnalogInput::RightThumbX) =
+        ResponseCurve::Exponential { exponent: -1.0 };
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn largest_settings_fit_in_record() {
+    let points: Vec<(f32, f32)> = (1..=8).map(|index| (index as f32 / 9.0, 0.5)).collect();
+    let mut settings = calibrated();
+    settings.curves.axes = [ResponseCurve::Custom(CurveTable::new(&points).unwrap()); 8];
+    settings.filters.axes = [AnalogFilter::ONE_EURO; 8];
+    let mut profile = custom_profile();
+    profile.buttons = [ButtonBinding::Axis {
+        input: AnalogInput::Other0,
+        threshold: -0.5,
+    }; Button::COUNT];
+    settings.profiles.profiles = [profile; MAX_PROFILES];
+    settings.profiles.count = MAX_PROFILES as u8;
+    settings.turbo.buttons = [30; Button::COUNT];
+    let step = MacroStep {
+        buttons: ButtonSet::EMPTY.with(Button::Y),
+        left_stick: Some((1.0, 1.0)),
+        duration_ms: 1000,
+    };
+    settings.macros.slots = [Some(Macro::new(Button::Guide, &[step; 6]).unwrap()); MAX_MACROS];
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+
+    assert_eq!(Settings::decode(&buffer[..length]), Ok(settings));
+}
+
+#[test]
+fn decode_rejects_invalid_deadzone() {
+    let mut settings = calibrated();
+    settings.deadzones.left_stick.inner = 0.9;
+    settings.deadzones.left_stick.outer = 0.2;
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_unknown_socd_mode() {
+    let mut settings = calibrated();
+    settings.filters = AnalogFilters::default();
+    let settings = with_default_tail(settings);
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+    // SOCD mode is followed by the guide mode, USB mode and poll rate bytes,
+    // then a single byte per disabled filter, two bytes per button debounce and the tail
+    buffer[length - 4 - AnalogInput::COUNT - 2 * Button::COUNT - DEFAULT_TAIL_SIZE] = 3;
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_filter() {
+    let mut settings = calibrated();
+    *settings.filters.axis_mut(AnalogInput::RightTrigger) = AnalogFilter::Average { samples: 0 };
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_unknown_debounce_mode() {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = with_default_tail(calibrated()).encode(&mut buffer).unwrap();
+    // The mode of the last button precedes its debounce time
+    buffer[length - 2 - DEFAULT_TAIL_SIZE] = 2;
+
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_profiles() {
+    let mut settings = calibrated();
+    *settings.profiles.profiles[1].button_mut(Button::B) = ButtonBinding::Axis {
+        input: AnalogInput::LeftTrigger,
+        threshold: 0.0,
+    };
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+
+    let length = with_default_tail(calibrated()).encode(&mut buffer).unwrap();
+    // Active profile index, past the single profile
+    buffer[length - DEFAULT_TAIL_SIZE + 1] = 1;
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_turbo_and_macros() {
+    let mut settings = with_default_tail(calibrated());
+    *settings.turbo.button_mut(Button::B) = 31;
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+
+    let length = with_default_tail(calibrated()).encode(&mut buffer).unwrap();
+    // Number of steps of the last macro slot
+    buffer[length - MAG_CALIBRATION_SIZE - MOTION_SIZE - 1] = 7;
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_motion() {
+    let mut settings = calibrated();
+    settings.motion.deadzone = 50.0;
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+
+    let length = calibrated().encode(&mut buffer).unwrap();
+    // Toggle button, last of the motion settings
+    buffer[length - MAG_CALIBRATION_SIZE - 1] = Button::COUNT as u8;
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_mag_calibration() {
+    let mut settings = calibrated();
+    settings.mag_calibration.matrix[1][2] = f32::NAN;
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings.encode(&mut buffer).unwrap();
+    assert_eq!(
+        Settings::decode(&buffer[..length]),
+        Err(SettingsError::Codec(CodecError::InvalidValue))
+    );
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{MockFlash, WriteInterrupted};
+use controller_core::calibration::AxisCalibration;
+use controller_core::input::AnalogInput;
+use controller_core::settings::{Settings, SETTINGS_VERSION};
+use controller_core::storage::{SettingsStore, StoreError, RECORD_MAGIC};
+
+const PAGE_SIZE: usize = 2048;
+
+fn settings_with_center(center: u16) -> Settings {
+    let mut settings = Settings::new();
+    *settings.calibration.axis_mut(AnalogInput::LeftThumbX) = AxisCalibration::new(0, center, 4095);
+    settings
+}
+
+/// Size of the record starting at `address`, including alignment
+fn record_size(flash: &MockFlash, address: usize) -> usize {
+    assert_eq!(flash.data[address..address + 2], RECORD_MAGIC.to_le_bytes());
+    let length = u16::from_le_bytes([flash.data[address + 2], flash.data[address + 3]]) as usize;
+    (8 + length + 4).div_ceil(4) * 4
+}
+
+fn reload(store: SettingsStore<MockFlash>) -> (SettingsStore<MockFlash>, Settings) {
+    let mut store = SettingsStore::new(store.into_inner());
+    let settings = store.load().unwrap();
+    (store, settings)
+}
+
+#[test]
+fn empty_flash_loads_defaults() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    assert_eq!(store.load(), Ok(Settings::default()));
+}
+
+#[test]
+fn saved_settings_survive_reset() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    store.save(&settings_with_center(2000)).unwrap();
+
+    let (_, settings) = reload(store);
+    assert_eq!(settings, settings_with_center(2000));
+}
+
+#[test]
+fn latest_record_wins() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    store.load().unwrap();
+    for center in 1900..1910 {
+        store.save(&settings_with_center(center)).unwrap();
+    }
+
+    let (_, settings) = reload(store);
+    assert_eq!(settings, settings_with_center(1909));
+}
+
+#[test]
+fn pages_are_erased_in_turn() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 3));
+    for center in 0..1000 {
+        store.save(&settings_with_center(center)).unwrap();
+    }
+
+    let flash = store.into_inner();
+    let min = *flash.erase_counts.iter().min().unwrap();
+    let max = *flash.erase_counts.iter().max().unwrap();
+    assert!(min > 0);
+    assert!(max - min <= 1, "uneven wear: {:?}", flash.erase_counts);
+
+    let (_, settings) = reload(SettingsStore::new(flash));
+    assert_eq!(settings, settings_with_center(999));
+}
+
+#[test]
+fn saving_continues_after_reset_without_erasing() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    store.save(&settings_with_center(1000)).unwrap();
+
+    let (mut store, _) = reload(store);
+    store.save(&settings_with_center(1001)).unwrap();
+
+    let (store, settings) = reload(store);
+    assert_eq!(settings, settings_with_center(1001));
+    assert_eq!(store.into_inner().erase_counts, vec![1, 0]);
+}
+
+#[test]
+fn corrupted_record_falls_back_to_previous() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    store.save(&settings_with_center(1500)).unwrap();
+    store.save(&settings_with_center(1600)).unwrap();
+
+    let mut flash = store.into_inner();
+    let second_record = record_size(&flash, 0);
+    flash.data[second_record + 10] ^= 0x01;
+
+    let (_, settings) = reload(SettingsStore::new(flash));
+    assert_eq!(settings, settings_with_center(1500));
+}
+
+#[test]
+fn interrupted_write_keeps_previous_settings() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    store.save(&settings_with_center(1500)).unwrap();
+
+    let (mut store, _) = reload(store);
+    let mut flash = store.into_inner();
+    flash.interrupt_write_after = Some(12);
+    store = SettingsStore::new(flash);
+    assert_eq!(
+        store.save(&settings_with_center(1600)),
+        Err(StoreError::Flash(WriteInterrupted))
+    );
+
+    let (mut store, settings) = reload(store);
+    assert_eq!(settings, settings_with_center(1500));
+
+    store.save(&settings_with_center(1700)).unwrap();
+    let (_, settings) = reload(store);
+    assert_eq!(settings, settings_with_center(1700));
+}
+
+#[test]
+fn garbage_flash_loads_defaults_and_recovers() {
+    let mut flash = MockFlash::new(PAGE_SIZE, 2);
+    flash.data.fill(0x42);
+    let mut store = SettingsStore::new(flash);
+    assert_eq!(store.load(), Ok(Settings::default()));
+
+    store.save(&settings_with_center(1234)).unwrap();
+    let (_, settings) = reload(store);
+    assert_eq!(settings, settings_with_center(1234));
+}
+
+#[test]
+fn record_from_newer_firmware_loads_defaults() {
+    let mut store = SettingsStore::new(MockFlash::new(PAGE_SIZE, 2));
+    store.save(&settings_with_center(1500)).unwrap();
+
+    // Rewrite the payload version and its CRC as a newer firmware would have
+    let mut flash = store.into_inner();
+    flash.data[8..10].copy_from_slice(&(SETTINGS_VERSION + 1).to_le_bytes());
+    let length = u16::from_le_bytes([flash.data[2], flash.data[3]]) as usize;
+    let crc = controller_core::codec::crc32(&flash.data[..8 + length]);
+    flash.data[8 + length..12 + length].copy_from_slice(&crc.to_le_bytes());
+
+    let (_, settings) = reload(SettingsStore::new(flash));
+    assert_eq!(settings, Settings::default());
+}
+
+#[test]
+fn record_must_fit_in_a_page() {
+    let mut store = SettingsStore::new(MockFlash::new(32, 2));
+    assert_eq!(store.save(&Settings::new()), Err(StoreError::TooLarge));
+}
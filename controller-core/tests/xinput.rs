@@ -0,0 +1,91 @@
+use controller_core::controller::ControllerState;
+use controller_core::report::get_report;
+use controller_core::xinput::{
+    buttons, get_xinput_report, xinput_trigger_value, UsbMode, XInputOutput,
+};
+use packed_struct::prelude::*;
+
+#[test]
+fn idle_report_has_header_and_zeroes() {
+    let packed = get_xinput_report(&ControllerState::new()).pack();
+    let mut expected = [0u8; 20];
+    expected[1] = 0x14;
+    assert_eq!(packed, expected);
+}
+
+#[test]
+fn report_packs_buttons_triggers_and_sticks() {
+    let mut state = ControllerState::new();
+    state.a = true;
+    state.up = true;
+    state.guide = true;
+    state.right_shoulder = true;
+    state.left_trigger = 1.0;
+    state.right_trigger = 0.5;
+    state.left_thumb_x = 1.0;
+    state.left_thumb_y = -1.0;
+    state.right_thumb_y = 0.5;
+
+    let report = get_xinput_report(&state);
+    assert_eq!(
+        report.buttons,
+        buttons::A | buttons::UP | buttons::GUIDE | buttons::RIGHT_SHOULDER
+    );
+    assert_eq!(
+        report.pack(),
+        [
+            0x00, 0x14, // Header
+            0x01, 0x16, // Buttons
+            0xff, 0x80, // Triggers
+            0xff, 0x7f, 0x01, 0x80, // Left stick
+            0x00, 0x00, 0x00, 0x40, // Right stick
+            0, 0, 0, 0, 0, 0,
+        ]
+    );
+}
+
+#[test]
+fn triggers_use_8_bits() {
+    assert_eq!(xinput_trigger_value(0.0), 0);
+    assert_eq!(xinput_trigger_value(1.0), 255);
+    assert_eq!(xinput_trigger_value(2.0), 255);
+    assert_eq!(xinput_trigger_value(-1.0), 0);
+}
+
+#[test]
+fn output_reports_are_parsed() {
+    assert_eq!(
+        XInputOutput::parse(&[0x00, 0x08, 0x00, 0xc0, 0x20, 0x00, 0x00, 0x00]),
+        Some(XInputOutput::Rumble {
+            left: 0xc0,
+            right: 0x20
+        })
+    );
+    assert_eq!(
+        XInputOutput::parse(&[0x01, 0x03, 0x06]),
+        Some(XInputOutput::Led(0x06))
+    );
+    assert_eq!(XInputOutput::parse(&[0x00, 0x08, 0x00, 0xc0]), None);
+    assert_eq!(XInputOutput::parse(&[0x02, 0x08]), None);
+    assert_eq!(XInputOutput::parse(&[]), None);
+}
+
+#[test]
+fn usb_mode_round_trips_through_u8() {
+    for mode in [UsbMode::Hid, UsbMode::XInput] {
+        assert_eq!(UsbMode::from_u8(mode.as_u8()), Some(mode));
+    }
+    assert_eq!(UsbMode::from_u8(2), None);
+}
+
+#[test]
+fn stick_up_is_positive_in_xinput_and_negative_in_hid() {
+    let mut state = ControllerState::new();
+    state.left_thumb_y = 1.0;
+    state.right_thumb_y = 0.5;
+
+    let xinput = get_xinput_report(&state);
+    let hid = get_report(&state);
+    assert_eq!((xinput.left_y, hid.y), (i16::MAX, -i16::MAX));
+    assert_eq!((xinput.right_y, hid.rx), (16384, -16384));
+}
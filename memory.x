@@ -2,7 +2,9 @@
 MEMORY
 {
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  /* The last 2 pages (2K each) hold the settings store, see `src/flash.rs` */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 252K
+  SETTINGS : ORIGIN = 0x0803F000, LENGTH = 4K
   RAM : ORIGIN = 0x20000000, LENGTH = 40K
 }
 
@@ -0,0 +1,197 @@
+//! Continuous scanning of the analog inputs
+//!
+//! ADC3 and ADC4 convert their channels in a loop and DMA2 copies every conversion
+//! into a buffer holding two scans, see [`controller_core::scan`]. Reading a snapshot
+//! never waits for a conversion, and all the values of a snapshot come from one scan.
+//!
+//! The HAL only drives DMA for the serial ports, so the ADC and DMA registers are
+//! accessed through their raw address like in [`crate::flash`]. The HAL types are
+//! still taken to prove the peripherals are not used elsewhere.
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use controller_core::scan::{sample_time_registers, sequence_registers, Half};
+use stm32f3xx_hal::{
+    adc::Adc,
+    dma::dma2,
+    pac::{adc1, dma1, ADC3, ADC4, DMA2},
+};
+
+/// `SMPx` value of 181.5 ADC clock cycles, long enough for the potentiometers
+/// of the sticks to charge the sampling capacitor
+const SAMPLE_TIME: u8 = 0b110;
+
+/// Bits of a channel in the DMA `ISR` and `IFCR` registers
+const TRANSFER_COMPLETE: u32 = 1 << 1;
+const HALF_TRANSFER: u32 = 1 << 2;
+const TRANSFER_ERROR: u32 = 1 << 3;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScanError {
+    /// The DMA hit a bus error and stopped, the snapshot would never change again
+    Transfer,
+}
+
+/// ADC with a DMA2 request, the DMA1 requests are taken by ADC1 and ADC2
+pub trait ScanAdc {
+    /// HAL channel reserved for the ADC requests
+    type DmaChannel;
+    /// Number of the channel, from 1
+    const DMA_CHANNEL: u8;
+
+    fn registers() -> &'static adc1::RegisterBlock;
+}
+
+impl ScanAdc for ADC3 {
+    type DmaChannel = dma2::C5;
+    const DMA_CHANNEL: u8 = 5;
+
+    fn registers() -> &'static adc1::RegisterBlock {
+        unsafe { &*ADC3::ptr() }
+    }
+}
+
+impl ScanAdc for ADC4 {
+    type DmaChannel = dma2::C2;
+    const DMA_CHANNEL: u8 = 2;
+
+    fn registers() -> &'static adc1::RegisterBlock {
+        unsafe { &*ADC4::ptr() }
+    }
+}
+
+/// `N` channels of an ADC converted continuously into a double buffer
+pub struct AdcScan<ADC: ScanAdc, const N: usize> {
+    _adc: Adc<ADC>,
+    _dma_channel: ADC::DmaChannel,
+    /// Written by the DMA, only read through volatile reads
+    buffer: &'static mut [[u16; N]; 2],
+}
+
+impl<ADC: ScanAdc, const N: usize> AdcScan<ADC, N> {
+    /// Starts converting `channels` in order, returns once a first scan filled the whole buffer.
+    ///
+    /// `adc` must be enabled and calibrated, which its constructor does.
+    pub fn new(
+        adc: Adc<ADC>,
+        dma_channel: ADC::DmaChannel,
+        channels: [u8; N],
+        buffer: &'static mut [[u16; N]; 2],
+    ) -> Self {
+        let registers = ADC::registers();
+        let channel = Self::channel();
+
+        let [sqr1, sqr2, sqr3, sqr4] = sequence_registers(&channels);
+        let [smpr1, smpr2] = sample_time_registers(&channels, SAMPLE_TIME);
+        registers.sqr1.write(|w| unsafe { w.bits(sqr1) });
+        registers.sqr2.write(|w| unsafe { w.bits(sqr2) });
+        registers.sqr3.write(|w| unsafe { w.bits(sqr3) });
+        registers.sqr4.write(|w| unsafe { w.bits(sqr4) });
+        registers.smpr1.write(|w| unsafe { w.bits(smpr1) });
+        registers.smpr2.write(|w| unsafe { w.bits(smpr2) });
+
+        // Nobody reads the data register but the DMA, a late read must not stop the scan
+        registers.cfgr.modify(|_, w| {
+            w.cont()
+                .continuous()
+                .ovrmod()
+                .overwrite()
+                .dmacfg()
+                .circular()
+                .dmaen()
+                .enabled()
+        });
+
+        channel.cr.reset();
+        Self::clear_flags(HALF_TRANSFER | TRANSFER_COMPLETE | TRANSFER_ERROR);
+        channel
+            .par
+            .write(|w| unsafe { w.pa().bits(&registers.dr as *const _ as u32) });
+        channel
+            .mar
+            .write(|w| unsafe { w.ma().bits(buffer.as_ptr() as u32) });
+        channel.ndtr.write(|w| w.ndt().bits((2 * N) as u16));
+        compiler_fence(Ordering::SeqCst);
+        channel.cr.write(|w| {
+            w.dir()
+                .from_peripheral()
+                .minc()
+                .enabled()
+                .psize()
+                .bits16()
+                .msize()
+                .bits16()
+                .circ()
+                .enabled()
+                .pl()
+                .high()
+                .en()
+                .enabled()
+        });
+
+        registers.cr.modify(|_, w| w.adstart().start());
+
+        // Until the first loop both halves are not complete yet
+        while Self::flags() & TRANSFER_COMPLETE == 0 {}
+
+        AdcScan {
+            _adc: adc,
+            _dma_channel: dma_channel,
+            buffer,
+        }
+    }
+
+    /// Last complete scan, in the order of the channels given to [`Self::new`]
+    pub fn snapshot(&self) -> Result<[u16; N], ScanError> {
+        let channel = Self::channel();
+        loop {
+            if Self::flags() & TRANSFER_ERROR != 0 {
+                return Err(ScanError::Transfer);
+            }
+
+            Self::clear_flags(HALF_TRANSFER | TRANSFER_COMPLETE);
+            let writing = Half::being_written(channel.ndtr.read().ndt().bits(), N);
+            compiler_fence(Ordering::SeqCst);
+            let values = unsafe { ptr::read_volatile(&self.buffer[writing.other().index()]) };
+            compiler_fence(Ordering::SeqCst);
+
+            // The DMA only moves on to the half we read once it completed the other one.
+            // If it did while copying, e.g. when preempted by the USB interrupt, read again.
+            let completed = match writing {
+                Half::First => HALF_TRANSFER,
+                Half::Second => TRANSFER_COMPLETE,
+            };
+            if Self::flags() & completed == 0 {
+                return Ok(values);
+            }
+        }
+    }
+
+    fn channel() -> &'static dma1::CH {
+        let dma = unsafe { &*DMA2::ptr() };
+        match ADC::DMA_CHANNEL {
+            1 => &dma.ch1,
+            2 => &dma.ch2,
+            3 => &dma.ch3,
+            4 => &dma.ch4,
+            5 => &dma.ch5,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Status bits of the channel in the DMA `ISR` register
+    fn flags() -> u32 {
+        let dma = unsafe { &*DMA2::ptr() };
+        (dma.isr.read().bits() >> Self::flags_offset()) & 0xf
+    }
+
+    fn clear_flags(flags: u32) {
+        let dma = unsafe { &*DMA2::ptr() };
+        dma.ifcr
+            .write(|w| unsafe { w.bits(flags << Self::flags_offset()) });
+    }
+
+    fn flags_offset() -> u32 {
+        4 * (ADC::DMA_CHANNEL as u32 - 1)
+    }
+}
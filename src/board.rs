@@ -0,0 +1,143 @@
+//! Pin table of the controller built on the F3 Discovery board
+//!
+//! The pins of the table are configured and read through their raw registers,
+//! so a pin can move to another port without changing any type. The ports must
+//! still be split with the HAL first, splitting a port resets it.
+use controller_core::board::{
+    ActiveLevel, Adc, AnalogPin, Board, BoardError, DigitalPin, PinId, Port, Pull,
+};
+use controller_core::input::{AnalogInput, Button};
+use stm32f3xx_hal::pac::{gpioc, GPIOA, GPIOB, GPIOC, GPIOD, GPIOE, GPIOF, RCC};
+
+const fn button(port: Port, pin: u8, pull: Pull, active: ActiveLevel, button: Button) -> DigitalPin {
+    DigitalPin {
+        pin: PinId::new(port, pin),
+        pull,
+        active,
+        button,
+    }
+}
+
+const fn analog(port: Port, pin: u8, adc: Adc, channel: u8, input: AnalogInput) -> AnalogPin {
+    AnalogPin {
+        pin: PinId::new(port, pin),
+        adc,
+        channel,
+        input,
+    }
+}
+
+pub const BOARD: Board = Board {
+    digital: &[
+        button(Port::D, 3, Pull::Up, ActiveLevel::Low, Button::A),
+        button(Port::D, 4, Pull::Up, ActiveLevel::Low, Button::B),
+        button(Port::D, 5, Pull::Up, ActiveLevel::Low, Button::X),
+        button(Port::D, 6, Pull::Up, ActiveLevel::Low, Button::Y),
+        button(Port::D, 1, Pull::Up, ActiveLevel::Low, Button::LeftShoulder),
+        // External pull-up on the shoulder board
+        button(Port::D, 7, Pull::None, ActiveLevel::Low, Button::RightShoulder),
+        button(Port::D, 0, Pull::Up, ActiveLevel::Low, Button::LeftThumb),
+        button(Port::D, 2, Pull::Up, ActiveLevel::Low, Button::RightThumb),
+        button(Port::B, 4, Pull::Up, ActiveLevel::Low, Button::Start),
+        button(Port::B, 5, Pull::Up, ActiveLevel::Low, Button::Back),
+        button(Port::C, 6, Pull::Up, ActiveLevel::Low, Button::Up),
+        button(Port::C, 7, Pull::Up, ActiveLevel::Low, Button::Down),
+        button(Port::C, 8, Pull::Up, ActiveLevel::Low, Button::Left),
+        button(Port::C, 9, Pull::Up, ActiveLevel::Low, Button::Right),
+        // User button, pulled down on the Discovery board
+        button(Port::A, 0, Pull::None, ActiveLevel::High, Button::Guide),
+    ],
+    analog: &[
+        analog(Port::D, 10, Adc::Adc3, 7, AnalogInput::RightThumbX),
+        analog(Port::D, 11, Adc::Adc3, 8, AnalogInput::RightThumbY),
+        analog(Port::D, 13, Adc::Adc3, 10, AnalogInput::RightTrigger),
+        analog(Port::D, 8, Adc::Adc4, 12, AnalogInput::LeftThumbX),
+        analog(Port::D, 9, Adc::Adc4, 13, AnalogInput::LeftThumbY),
+        analog(Port::B, 12, Adc::Adc4, 3, AnalogInput::LeftTrigger),
+        analog(Port::D, 14, Adc::Adc4, 11, AnalogInput::Other0),
+        analog(Port::B, 15, Adc::Adc4, 5, AnalogInput::Other1),
+    ],
+};
+
+/// Pins driven through the HAL by the rest of the firmware, the table must not use them
+pub const RESERVED_PINS: &[PinId] = &[
+    // USB
+    PinId::new(Port::A, 11),
+    PinId::new(Port::A, 12),
+    // I2C1 and interrupts of the LSM303DLHC
+    PinId::new(Port::B, 6),
+    PinId::new(Port::B, 7),
+    PinId::new(Port::E, 2),
+    PinId::new(Port::E, 4),
+    PinId::new(Port::E, 5),
+    // LEDs
+    PinId::new(Port::E, 8),
+    PinId::new(Port::E, 9),
+    PinId::new(Port::E, 10),
+    PinId::new(Port::E, 11),
+    PinId::new(Port::E, 12),
+    PinId::new(Port::E, 13),
+    PinId::new(Port::E, 14),
+    PinId::new(Port::E, 15),
+];
+
+/// Only ADC3 and ADC4 are scanned, see [`crate::init::get_adc3`]
+const SCANNED_ADCS: [Adc; 2] = [Adc::Adc3, Adc::Adc4];
+
+/// Sets the mode and pull of every pin of `board`, before interrupts are enabled
+pub fn configure(board: &Board) -> Result<(), BoardError> {
+    board.validate(RESERVED_PINS)?;
+    if let Some(analog) = board.analog.iter().find(|analog| !SCANNED_ADCS.contains(&analog.adc)) {
+        return Err(BoardError::UnsupportedAdc(analog.adc));
+    }
+
+    for digital in board.digital {
+        let pull = match digital.pull {
+            Pull::None => 0b00,
+            Pull::Up => 0b01,
+            Pull::Down => 0b10,
+        };
+        configure_pin(digital.pin, 0b00, pull);
+    }
+    for analog in board.analog {
+        configure_pin(analog.pin, 0b11, 0b00);
+    }
+    Ok(())
+}
+
+/// Current level of an input pin
+pub fn is_high(pin: PinId) -> bool {
+    registers(pin.port).idr.read().bits() & (1 << pin.pin) != 0
+}
+
+/// Writes the 2-bit `MODER` and `PUPDR` fields of `pin`
+fn configure_pin(pin: PinId, mode: u32, pull: u32) {
+    enable_clock(pin.port);
+    let port = registers(pin.port);
+    let shift = 2 * pin.pin as u32;
+    port.moder
+        .modify(|r, w| unsafe { w.bits(r.bits() & !(0b11 << shift) | mode << shift) });
+    port.pupdr
+        .modify(|r, w| unsafe { w.bits(r.bits() & !(0b11 << shift) | pull << shift) });
+}
+
+fn enable_clock(port: Port) {
+    // IOPAEN is bit 17, the following ports follow in order
+    let rcc = unsafe { &*RCC::ptr() };
+    rcc.ahbenr
+        .modify(|r, w| unsafe { w.bits(r.bits() | 1 << (17 + port.index())) });
+}
+
+/// GPIOA and GPIOB have their own register block types in the PAC,
+/// with the same layout as the other ports
+fn registers(port: Port) -> &'static gpioc::RegisterBlock {
+    let address = match port {
+        Port::A => GPIOA::ptr() as usize,
+        Port::B => GPIOB::ptr() as usize,
+        Port::C => GPIOC::ptr() as usize,
+        Port::D => GPIOD::ptr() as usize,
+        Port::E => GPIOE::ptr() as usize,
+        Port::F => GPIOF::ptr() as usize,
+    };
+    unsafe { &*(address as *const gpioc::RegisterBlock) }
+}
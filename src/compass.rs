@@ -1,131 +1,152 @@
 use accelerometer::vector::{F32x3, I16x3};
 use accelerometer::{Accelerometer, RawAccelerometer};
+use controller_core::compass::{
+    accel_counts, mag_counts, CompassConfig, ACCEL_ADDRESS, AUTO_INCREMENT, MAG_ADDRESS,
+    OUT_X_H_M, OUT_X_L_A,
+};
 use stm32f3xx_hal::gpio;
-use stm32f3xx_hal::gpio::{gpiob, OpenDrain};
+use stm32f3xx_hal::gpio::{gpiob, gpioe, Input, OpenDrain};
 use stm32f3xx_hal::i2c;
 use stm32f3xx_hal::pac;
 use stm32f3xx_hal::prelude::*;
-use stm32f3xx_hal::rcc;
-
-type Lsm303 = lsm303dlhc::Lsm303dlhc<
-    i2c::I2c<
-        pac::I2C1,
-        (
-            gpiob::PB6<gpio::AF4<OpenDrain>>,
-            gpiob::PB7<gpio::AF4<OpenDrain>>,
-        ),
-    >,
+
+pub type CompassI2c = i2c::I2c<
+    pac::I2C1,
+    (
+        gpiob::PB6<gpio::AF4<OpenDrain>>,
+        gpiob::PB7<gpio::AF4<OpenDrain>>,
+    ),
 >;
 
+/// Data ready lines of the Lsm303dlhc, high while a reading waits in the output registers
+pub struct DataReadyPins {
+    /// PE2, DRDY of the magnetometer
+    pub mag: gpioe::PE2<Input>,
+    /// PE4, INT1 of the accelerometer
+    pub accel: gpioe::PE4<Input>,
+}
+
+impl DataReadyPins {
+    pub fn new<Pe2Mode, Pe4Mode>(
+        pe2: gpioe::PE2<Pe2Mode>,
+        pe4: gpioe::PE4<Pe4Mode>,
+        mode: &mut gpioe::MODER,
+        pull_up_down: &mut gpioe::PUPDR,
+    ) -> Self {
+        DataReadyPins {
+            mag: pe2.into_floating_input(mode, pull_up_down),
+            accel: pe4.into_floating_input(mode, pull_up_down),
+        }
+    }
+}
+
+/// Onboard Lsm303dhlc e-Compass, measuring at the ranges and rates of its [`CompassConfig`]
 pub struct Compass {
-    lsm303dlhc: Lsm303,
+    i2c: CompassI2c,
+    ready: DataReadyPins,
+    config: CompassConfig,
 }
 
 impl Compass {
     /// Initialize the onboard Lsm303dhlc e-Compass
-    pub fn new<Pb6Mode, Pb7Mode>(
-        pb6: gpiob::PB6<Pb6Mode>,
-        pb7: gpiob::PB7<Pb7Mode>,
-        mode: &mut gpiob::MODER,
-        otype: &mut gpiob::OTYPER,
-        alternate_function_low: &mut gpiob::AFRL,
-        i2c1: pac::I2C1,
-        clocks: rcc::Clocks,
-        advanced_periph_bus: &mut rcc::APB1,
-    ) -> Result<Self, stm32f3xx_hal::i2c::Error> {
-        /*
-         * Pinout:
-         * PB6 -> SCL (clock)
-         * PB7 -> SDA (data)
-         * PE2 -> DRDY (magnometer data ready)
-         * PE4 -> INT1 (configurable interrupt 1)
-         * PE5 -> INT2 (configurable interrupt 2)
-         * lsm303hdlc driver uses continuos mode, so no need to wait for interrupts on DRDY
-         */
-        let scl = pb6.into_af4_open_drain(mode, otype, alternate_function_low);
-        let sda = pb7.into_af4_open_drain(mode, otype, alternate_function_low);
-        let i2c = i2c::I2c::new(i2c1, (scl, sda), 400_000.Hz(), clocks, advanced_periph_bus);
-
-        let lsm303dhlc = Lsm303::new(i2c)?;
-        Ok(Compass {
-            lsm303dlhc: lsm303dhlc,
-        })
+    ///
+    /// Pinout:
+    /// PB6 -> SCL (clock)
+    /// PB7 -> SDA (data)
+    /// PE2 -> DRDY (magnometer data ready)
+    /// PE4 -> INT1 (accelerometer data ready)
+    /// PE5 -> INT2 (unused)
+    pub fn new(
+        i2c: CompassI2c,
+        ready: DataReadyPins,
+        config: CompassConfig,
+    ) -> Result<Self, i2c::Error> {
+        let mut compass = Compass { i2c, ready, config };
+        compass.configure(config)?;
+        Ok(compass)
+    }
+
+    pub fn config(&self) -> &CompassConfig {
+        &self.config
+    }
+
+    /// Writes `config` to the sensor, the readings are scaled with it from now on.
+    ///
+    /// On error the sensor may be left partially configured, the previous config is kept.
+    pub fn configure(&mut self, config: CompassConfig) -> Result<(), i2c::Error> {
+        for (register, value) in config.accel_registers() {
+            self.i2c.write(ACCEL_ADDRESS, &[register, value])?;
+        }
+        for (register, value) in config.mag_registers() {
+            self.i2c.write(MAG_ADDRESS, &[register, value])?;
+        }
+        self.config = config;
+        Ok(())
     }
 
-    /// Read the raw magnetometer data
+    /// Acceleration in g, `None` until the accelerometer has a new sample
+    pub fn read_accel(&mut self) -> Result<Option<F32x3>, i2c::Error> {
+        if !self.ready.accel.is_high().unwrap_or(false) {
+            return Ok(None);
+        }
+        let [x, y, z] = self.config.accel_range.to_g(self.read_accel_counts()?);
+        Ok(Some(F32x3::new(x, y, z)))
+    }
+
+    /// Magnetic field in gauss, `None` until the magnetometer has a new sample
+    pub fn read_mag(&mut self) -> Result<Option<F32x3>, i2c::Error> {
+        if !self.ready.mag.is_high().unwrap_or(false) {
+            return Ok(None);
+        }
+        let [x, y, z] = self.config.mag_gain.to_gauss(self.read_mag_counts()?);
+        Ok(Some(F32x3::new(x, y, z)))
+    }
+
+    /// Read the raw magnetometer data, without waiting for a new sample
     pub fn mag_raw(&mut self) -> Result<I16x3, i2c::Error> {
-        let reading = self.lsm303dlhc.mag()?;
-        Ok(I16x3::new(reading.x, reading.y, reading.z))
+        let [x, y, z] = self.read_mag_counts()?;
+        Ok(I16x3::new(x, y, z))
     }
 
-    /// Consume the Compass and return the underlying Lsm303dhlc
-    pub fn into_lsm303dlhc(self) -> Lsm303 {
-        self.lsm303dlhc
+    /// Consume the Compass and return the bus and the data ready lines
+    pub fn release(self) -> (CompassI2c, DataReadyPins) {
+        (self.i2c, self.ready)
+    }
+
+    fn read_accel_counts(&mut self) -> Result<[i16; 3], i2c::Error> {
+        let mut bytes = [0; 6];
+        self.i2c
+            .write_read(ACCEL_ADDRESS, &[OUT_X_L_A | AUTO_INCREMENT], &mut bytes)?;
+        Ok(accel_counts(&bytes))
+    }
+
+    fn read_mag_counts(&mut self) -> Result<[i16; 3], i2c::Error> {
+        let mut bytes = [0; 6];
+        self.i2c.write_read(MAG_ADDRESS, &[OUT_X_H_M], &mut bytes)?;
+        Ok(mag_counts(&bytes))
     }
 }
 
 impl RawAccelerometer<I16x3> for Compass {
     type Error = i2c::Error;
 
-    /// Read the raw accelerometer data
+    /// Read the raw accelerometer data, without waiting for a new sample
     fn accel_raw(&mut self) -> Result<I16x3, accelerometer::Error<Self::Error>> {
-        let reading = self.lsm303dlhc.accel()?;
-        Ok(I16x3::new(reading.x, reading.y, reading.z))
+        let [x, y, z] = self.read_accel_counts()?;
+        Ok(I16x3::new(x, y, z))
     }
 }
 
-/// Reads Accelerometer data in G-Force
-///
-/// # Warning
-/// This is hard coded for the default settings because the driver doesn't provide a way
-/// to read the necessary registers to calculate it based on current settings.
-/// If you take control of the underlying device driver and change settings,
-/// this will not calculate the correct G-Force values.
+/// Reads Accelerometer data in G-Force, scaled for the configured range
 impl Accelerometer for Compass {
     type Error = i2c::Error;
+
     fn accel_norm(&mut self) -> Result<F32x3, accelerometer::Error<Self::Error>> {
-        let reading = self.accel_raw()?;
-        /*
-         * LA_FS (linear acceleration measurment range [full scale])
-         *  can be +/-2, +/-4, +/-8, or +/- 16
-         * LA_So (Linear acceleration sensitivity) can be 1,2,4, or 12
-         *  and is measured in milli-G / LSB
-         * The driver provides a way to set the range/sensitivy,
-         * but no way to read it, so we just hard code the default settings here (for now?).
-         *
-         * At +/-2g, we get 1mg/LSB (1 mg/bit) resolution.
-         * The device returns a 16 bit result.
-         * magnitude g / (1 mg / bit) =
-         * +/- 2g range = 4g magnitude
-         * 4g / 65535 bits = 4g/(1<<16) = 0.000061 g / bit
-         * 2g / 32768 bits = 2g/(1<<15) = 0.000061 g / bit
-         *
-         * I _think_ the general equation is:
-         * scale_factor = magnitude / #of_bits * sensitivity
-         * scale_factor = (abs(range)*2) / #of_bits * sensitivity
-         * scale_factor = abs(range) / (#of_bits/2) * sensitivity
-         * sf(+/-2g)  =  4g / 65535 bits *  1mg/LSB = 0.000061 g
-         * sf(+/-4g)  =  8g / 65535 bits *  2mg/LSB = 0.000244 g
-         * sf(+/-8g)  = 16g / 65535 bits *  4mg/LSB = 0.000976 g
-         * sf(+/-16g) = 32g / 65535 bits * 12mg/LSB = 0.005859 g
-         *
-         * NOTE: This also does not account for temperature variance.
-         */
-        const MAGNITUDE: i32 = 4;
-        const NO_OF_BITS: i32 = 1 << 16;
-        const SENSITIVITY: i32 = 1;
-        const SCALE_FACTOR: f32 = (MAGNITUDE as f32 / NO_OF_BITS as f32) * SENSITIVITY as f32;
-        Ok(F32x3::new(
-            reading.x as f32 * SCALE_FACTOR,
-            reading.y as f32 * SCALE_FACTOR,
-            reading.z as f32 * SCALE_FACTOR,
-        ))
+        let [x, y, z] = self.config.accel_range.to_g(self.read_accel_counts()?);
+        Ok(F32x3::new(x, y, z))
     }
 
     fn sample_rate(&mut self) -> Result<f32, accelerometer::Error<<Self as Accelerometer>::Error>> {
-        // we don't expose a way to change this, so hard coded to 400Hz right now
-        // it should really be read from the device in case someone snags the raw lsm303dlhc struct,
-        // but the driver does't give us a way to read it back from the device
-        Ok(400.0)
+        Ok(self.config.accel_rate.hz())
     }
 }
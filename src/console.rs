@@ -0,0 +1,76 @@
+//! Configuration console on the USB serial port, see [`controller_core::console`]
+//!
+//! The USB interrupt gathers the received bytes into lines and sends the output,
+//! the commands run in the idle loop as they may write to flash.
+use controller_core::console::{ConsoleError, Line, LineBuffer, MAX_LINE_LEN};
+use heapless::Deque;
+use usb_device::class_prelude::*;
+use usbd_serial::SerialPort;
+
+/// Bytes waiting to be sent, holds the longest command output
+pub const OUTPUT_SIZE: usize = 1024;
+
+/// Kept free for the echo of the line and, after the output, the error or save reply and the prompt
+const REPLY_SIZE: usize = MAX_LINE_LEN + 2 + 64;
+
+/// Longest output of a single command, longer outputs end in an `output truncated` error
+pub const COMMAND_OUTPUT_SIZE: usize = OUTPUT_SIZE - REPLY_SIZE;
+
+pub type ConsoleOutput = Deque<u8, OUTPUT_SIZE>;
+
+pub struct SerialConsole<'a, B: UsbBus> {
+    pub serial: SerialPort<'a, B>,
+    input: LineBuffer,
+}
+
+impl<'a, B: UsbBus> SerialConsole<'a, B> {
+    pub fn new(usb_alloc: &'a UsbBusAllocator<B>) -> Self {
+        SerialConsole {
+            serial: SerialPort::new(usb_alloc),
+            input: LineBuffer::new(),
+        }
+    }
+
+    /// Reads the received bytes until a line is complete, echoing them to `output`.
+    ///
+    /// Bytes after the line are left in the serial port until the next call.
+    pub fn read_line(&mut self, output: &mut ConsoleOutput) -> Option<Result<Line, ConsoleError>> {
+        let mut byte = [0u8];
+        while let Ok(1) = self.serial.read(&mut byte) {
+            let echo: &[u8] = match byte[0] {
+                b'\r' | b'\n' => b"\r\n",
+                0x08 | 0x7F => b"\x08 \x08",
+                b' '..=b'~' => &byte,
+                _ => &[],
+            };
+            push_output(output, echo);
+
+            if let Some(line) = self.input.push(byte[0]) {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// Sends as much of `output` as the serial port accepts
+    pub fn write_output(&mut self, output: &mut ConsoleOutput) {
+        while !output.is_empty() {
+            let written = match self.serial.write(output.as_slices().0) {
+                Ok(written) if written > 0 => written,
+                _ => break,
+            };
+            for _ in 0..written {
+                output.pop_front();
+            }
+        }
+    }
+}
+
+/// Queues `bytes` to be sent, what does not fit is dropped
+pub fn push_output(output: &mut ConsoleOutput, bytes: &[u8]) {
+    for byte in bytes {
+        if output.push_back(*byte).is_err() {
+            return;
+        }
+    }
+}
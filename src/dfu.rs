@@ -0,0 +1,37 @@
+//! Reboot into the DFU bootloader of the system memory, to update the firmware over USB
+//!
+//! The bootloader expects the chip as it is after a reset, so the request is stored
+//! in RAM that the startup code does not initialize, the chip is reset, and the
+//! firmware jumps to the bootloader first thing after the next boot.
+use core::mem::MaybeUninit;
+use core::ptr;
+
+/// Vector table of the system memory bootloader of the STM32F303xB/C
+const SYSTEM_MEMORY: usize = 0x1FFF_D800;
+
+/// Left in [`DFU_REQUEST`] to jump to the bootloader after the reset
+const DFU_MAGIC: u32 = 0xDF00_B007;
+
+#[link_section = ".uninit.DFU_REQUEST"]
+static mut DFU_REQUEST: MaybeUninit<u32> = MaybeUninit::uninit();
+
+/// Resets the chip into the bootloader
+pub fn reboot_into_bootloader() -> ! {
+    unsafe { ptr::write_volatile(ptr::addr_of_mut!(DFU_REQUEST).cast::<u32>(), DFU_MAGIC) };
+    cortex_m::peripheral::SCB::sys_reset()
+}
+
+/// Jumps to the bootloader if [`reboot_into_bootloader`] asked for it.
+///
+/// Must be called first in `main`, before any peripheral is configured.
+pub fn enter_bootloader_if_requested() {
+    let request = ptr::addr_of_mut!(DFU_REQUEST).cast::<u32>();
+    // Garbage after a power on, at worst it matches the magic and DFU mode is entered once
+    if unsafe { ptr::read_volatile(request) } != DFU_MAGIC {
+        return;
+    }
+    unsafe {
+        ptr::write_volatile(request, 0);
+        cortex_m::asm::bootload(SYSTEM_MEMORY as *const u32)
+    }
+}
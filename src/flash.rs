@@ -0,0 +1,140 @@
+//! Provides the flash pages reserved for the settings store
+//!
+//! The pages are excluded from the `FLASH` region in `memory.x`,
+//! keep both in sync when resizing the store.
+use controller_core::storage::FlashRegion;
+use stm32f3xx_hal::pac::FLASH;
+
+/// First byte of the `SETTINGS` region in `memory.x`
+pub const SETTINGS_START: usize = 0x0803_F000;
+pub const PAGE_SIZE: usize = 2048;
+pub const PAGE_COUNT: usize = 2;
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlashError {
+    /// Programming a half-word that was not erased
+    Programming,
+    /// Erasing or programming a write protected page
+    WriteProtected,
+}
+
+/// Settings pages of the on-chip flash.
+///
+/// The `FLASH` peripheral is consumed by `constrain()` to set the wait states,
+/// so the programming registers are accessed through their raw address.
+/// Only one instance should exist.
+pub struct SettingsFlash {
+    _private: (),
+}
+
+impl SettingsFlash {
+    pub fn new() -> Self {
+        SettingsFlash { _private: () }
+    }
+
+    fn registers() -> &'static stm32f3xx_hal::pac::flash::RegisterBlock {
+        unsafe { &*FLASH::ptr() }
+    }
+
+    fn unlock() {
+        let flash = Self::registers();
+        if flash.cr.read().lock().is_locked() {
+            flash.keyr.write(|w| w.fkeyr().bits(KEY1));
+            flash.keyr.write(|w| w.fkeyr().bits(KEY2));
+        }
+    }
+
+    fn lock() {
+        Self::registers().cr.modify(|_, w| w.lock().set_bit());
+    }
+
+    /// Waits for the end of the current operation and clears its status flags
+    fn wait_ready() -> Result<(), FlashError> {
+        let flash = Self::registers();
+        while flash.sr.read().bsy().is_active() {}
+
+        let status = flash.sr.read();
+        let result = if status.pgerr().is_error() {
+            Err(FlashError::Programming)
+        } else if status.wrprterr().is_error() {
+            Err(FlashError::WriteProtected)
+        } else {
+            Ok(())
+        };
+
+        flash
+            .sr
+            .write(|w| w.eop().set_bit().pgerr().set_bit().wrprterr().set_bit());
+        result
+    }
+}
+
+impl Default for SettingsFlash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlashRegion for SettingsFlash {
+    type Error = FlashError;
+
+    fn page_size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn page_count(&self) -> usize {
+        PAGE_COUNT
+    }
+
+    fn read(&mut self, address: usize, bytes: &mut [u8]) -> Result<(), FlashError> {
+        for (offset, byte) in bytes.iter_mut().enumerate() {
+            let source = (SETTINGS_START + address + offset) as *const u8;
+            *byte = unsafe { core::ptr::read_volatile(source) };
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: usize, bytes: &[u8]) -> Result<(), FlashError> {
+        let flash = Self::registers();
+        Self::unlock();
+        flash.cr.modify(|_, w| w.pg().set_bit());
+
+        // The flash is programmed one half-word at a time
+        let mut result = Ok(());
+        for (offset, half_word) in bytes.chunks_exact(2).enumerate() {
+            let destination = (SETTINGS_START + address + offset * 2) as *mut u16;
+            unsafe {
+                core::ptr::write_volatile(
+                    destination,
+                    u16::from_le_bytes([half_word[0], half_word[1]]),
+                )
+            };
+            result = Self::wait_ready();
+            if result.is_err() {
+                break;
+            }
+        }
+
+        flash.cr.modify(|_, w| w.pg().clear_bit());
+        Self::lock();
+        result
+    }
+
+    fn erase_page(&mut self, page: usize) -> Result<(), FlashError> {
+        let flash = Self::registers();
+        Self::unlock();
+        flash.cr.modify(|_, w| w.per().set_bit());
+        flash
+            .ar
+            .write(|w| w.far().bits((SETTINGS_START + page * PAGE_SIZE) as u32));
+        flash.cr.modify(|_, w| w.strt().set_bit());
+        let result = Self::wait_ready();
+
+        flash.cr.modify(|_, w| w.per().clear_bit());
+        Self::lock();
+        result
+    }
+}
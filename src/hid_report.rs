@@ -1,125 +1,200 @@
-//!HID joystick
-use crate::usb_class::prelude::*;
-use core::default::Default;
-use usb_device::bus::UsbBus;
-use usb_device::class_prelude::UsbBusAllocator;
-use fugit::ExtU32;
-use packed_struct::prelude::*;
-
-// from https://github.com/nefarius/ViGEmBus/issues/40
-// see https://github.com/dlkj/usbd-human-interface-device/blob/main/src/device/joystick.rs
-// see https://github.com/TimDeve/sunrs/blob/0d335dffa3ebe8909a0308639af4bb66dccee902/src/main.rs
-// see https://usb.org/sites/default/files/hut1_2.pdf
-// see http://who-t.blogspot.com/2018/12/understanding-hid-report-descriptors.html
-
-#[rustfmt::skip]
-pub const XBOX_JOYSTICK_DESCRIPTOR: &[u8] = &[
-    0x05, 0x01, // Usage Page (Generic Desktop)         5,   1
-    0x09, 0x04, // Usage (Joystick)                     9,   4
-    0xa1, 0x01, // Collection (Application)             161, 1
-    0x09, 0x01, //   Usage Page (Pointer)               9,   1
-    0xa1, 0x00, //   Collection (Physical)              161, 0
-    0x09, 0x30, //     Usage (X)                        9,   48
-    0x09, 0x31, //     Usage (Y)                        9,   49
-    0x09, 0x32, //     Usage (Z)                        9,   50
-    0x09, 0x33, //     Usage (Rx)                       9,   51
-    0x09, 0x34, //     Usage (Ry)                       9,   52
-    0x09, 0x35, //     Usage (Rz)                       9,   53
-    0x15, 0x81, //     Logical Minimum (-127)           21,  129
-    0x25, 0x7f, //     Logical Maximum (127)            37,  127
-    0x75, 0x08, //     Report Size (8)                  117, 8
-    0x95, 0x06, //     Report count (6)                 149, 6,
-    0x81, 0x02, //     Input (Data, Variable, Absolute) 129, 2,
-    0xc0,       //   End Collection                     192,
-    0x05, 0x09, //   Usage Page (Button)                5,   9,
-    0x19, 0x01, //   Usage Minimum (0)                  25,  1,
-    0x29, 0x10, //   Usage Maximum (8)                  41,  8,
-    0x15, 0x00, //   Logical Minimum (0)                21,  0
-    0x25, 0x01, //   Logical Maximum (1)                37,  1,
-    0x75, 0x01, //   Report Size (1)                    117, 1,
-    0x95, 0x10, //   Report Count (16)                  149, 8
-    0x81, 0x02, //   Input (Data, Variable, Absolute)   129, 2,
-    0xc0,       // End Collection                       192
-];
-
-#[derive(Clone, Copy, Debug, Default, PackedStruct)]
-#[packed_struct(endian = "lsb", size_bytes="8")]
-pub struct XboxJoystickReport {
-    #[packed_field]
-    pub x: i8,
-    #[packed_field]
-    pub y: i8,
-    #[packed_field]
-    pub z: i8,
-    #[packed_field]
-    pub rx: i8,
-    #[packed_field]
-    pub ry: i8,
-    #[packed_field]
-    pub rz: i8,
-    #[packed_field]
-    pub buttons: u16,
-}
-
-pub struct XboxJoystick<'a, B: UsbBus> {
-    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
-}
-
-impl<'a, B: UsbBus> XboxJoystick<'a, B> {
-    pub fn write_report(&mut self, report: &XboxJoystickReport) -> Result<(), UsbHidError> {
-        let data = report.pack();
-        self.interface
-            .write_report(&data)
-            .map(|_| ())
-            .map_err(UsbHidError::from)
-    }
-}
-
-impl<'a, B: UsbBus> DeviceClass<'a> for XboxJoystick<'a, B> {
-    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
-
-    fn interface(&mut self) -> &mut Self::I {
-        &mut self.interface
-    }
-
-    fn reset(&mut self) {}
-
-    fn tick(&mut self) -> Result<(), UsbHidError> {
-        Ok(())
-    }
-}
-
-pub struct XboxJoystickConfig<'a> {
-    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
-}
-
-impl<'a> Default for XboxJoystickConfig<'a> {
-    #[must_use]
-    fn default() -> Self {
-        Self::new(
-            ((InterfaceBuilder::new(XBOX_JOYSTICK_DESCRIPTOR)).unwrap()
-                .boot_device(InterfaceProtocol::None)
-                .description("Joystick")
-                .in_endpoint(10.millis())).unwrap()
-            .without_out_endpoint()
-            .build(),
-        )
-    }
-}
-
-impl<'a> XboxJoystickConfig<'a> {
-    #[must_use]
-    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>) -> Self {
-        Self { interface }
-    }
-}
-
-impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for XboxJoystickConfig<'a> {
-    type Allocated = XboxJoystick<'a, B>;
-
-    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
-        Self::Allocated {
-            interface: Interface::new(usb_alloc, self.interface),
-        }
-    }
-}
+//!HID joystick
+//!
+//! The joystick interface also carries the feature report of the configuration
+//! protocol, see [`controller_core::config`]. The HID class only hands over the report
+//! data of GET_REPORT and SET_REPORT, without the report type, so every GET_REPORT is
+//! answered with the configuration response: hosts read the input reports from the
+//! interrupt endpoint.
+use crate::usb_class::prelude::*;
+use core::default::Default;
+use controller_core::config::{Response, Status, REPORT_SIZE};
+use usb_device::bus::{StringIndex, UsbBus};
+use usb_device::class_prelude::{DescriptorWriter, InterfaceNumber, UsbBusAllocator};
+use usbd_human_interface_device::interface::InterfaceClass;
+use fugit::{ExtU32, MillisDurationU32};
+use packed_struct::prelude::*;
+use controller_core::report::{ReportSink, XboxJoystickReport, XBOX_JOYSTICK_DESCRIPTOR};
+
+type JoystickInterface<'a, B> = Interface<'a, B, InBytes16, OutNone, ReportSingle>;
+
+/// Joystick interface answering the configuration feature report itself
+pub struct ConfigInterface<'a, B: UsbBus> {
+    inner: JoystickInterface<'a, B>,
+    /// Last request written by the host, not handled yet
+    request: Option<[u8; REPORT_SIZE]>,
+    response: [u8; REPORT_SIZE],
+}
+
+impl<'a, B: UsbBus> ConfigInterface<'a, B> {
+    fn new(inner: JoystickInterface<'a, B>) -> Self {
+        ConfigInterface {
+            inner,
+            request: None,
+            response: pending_response(0),
+        }
+    }
+
+    fn write_report(&mut self, data: &[u8]) -> usb_device::Result<usize> {
+        self.inner.write_report(data)
+    }
+}
+
+/// Response to a `command` request that is not handled yet
+fn pending_response(command: u8) -> [u8; REPORT_SIZE] {
+    Response::encode(command, Err(Status::Pending)).unwrap_or([0; REPORT_SIZE])
+}
+
+impl<'a, B: UsbBus> InterfaceClass<'a> for ConfigInterface<'a, B> {
+    fn hid_descriptor_body(&self) -> [u8; 7] {
+        self.inner.hid_descriptor_body()
+    }
+
+    fn report_descriptor(&self) -> &'_ [u8] {
+        self.inner.report_descriptor()
+    }
+
+    fn id(&self) -> InterfaceNumber {
+        self.inner.id()
+    }
+
+    fn write_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
+        self.inner.write_descriptors(writer)
+    }
+
+    fn get_string(&self, index: StringIndex, lang_id: u16) -> Option<&'a str> {
+        self.inner.get_string(index, lang_id)
+    }
+
+    fn reset(&mut self) {
+        InterfaceClass::reset(&mut self.inner);
+        self.request = None;
+        self.response = pending_response(0);
+    }
+
+    fn set_report(&mut self, data: &[u8]) -> usb_device::Result<()> {
+        let mut request = [0; REPORT_SIZE];
+        let length = data.len().min(REPORT_SIZE);
+        request[..length].copy_from_slice(&data[..length]);
+        self.response = pending_response(request[1]);
+        self.request = Some(request);
+        Ok(())
+    }
+
+    fn get_report(&self, data: &mut [u8]) -> usb_device::Result<usize> {
+        let response = data
+            .get_mut(..REPORT_SIZE)
+            .ok_or(usb_device::UsbError::BufferOverflow)?;
+        response.copy_from_slice(&self.response);
+        Ok(REPORT_SIZE)
+    }
+
+    fn get_report_ack(&mut self) -> usb_device::Result<()> {
+        Ok(())
+    }
+
+    fn set_idle(&mut self, report_id: u8, value: u8) {
+        self.inner.set_idle(report_id, value)
+    }
+
+    fn get_idle(&self, report_id: u8) -> u8 {
+        self.inner.get_idle(report_id)
+    }
+
+    fn set_protocol(&mut self, protocol: HidProtocol) {
+        self.inner.set_protocol(protocol)
+    }
+
+    fn get_protocol(&self) -> HidProtocol {
+        self.inner.get_protocol()
+    }
+}
+
+pub struct XboxJoystick<'a, B: UsbBus> {
+    interface: ConfigInterface<'a, B>,
+}
+
+impl<'a, B: UsbBus> XboxJoystick<'a, B> {
+    /// Takes the configuration request written by the host, if any
+    pub fn take_config_request(&mut self) -> Option<[u8; REPORT_SIZE]> {
+        self.interface.request.take()
+    }
+
+    /// Answers the last configuration request
+    pub fn set_config_response(&mut self, response: [u8; REPORT_SIZE]) {
+        // A request written meanwhile is answered next
+        if self.interface.request.is_none() {
+            self.interface.response = response;
+        }
+    }
+
+    pub fn write_report(&mut self, report: &XboxJoystickReport) -> Result<(), UsbHidError> {
+        let data = report.pack();
+        self.interface
+            .write_report(&data)
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+}
+
+impl<'a, B: UsbBus> ReportSink for XboxJoystick<'a, B> {
+    type Error = UsbHidError;
+
+    fn write_report(&mut self, report: &XboxJoystickReport) -> Result<(), UsbHidError> {
+        XboxJoystick::write_report(self, report)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for XboxJoystick<'a, B> {
+    type I = ConfigInterface<'a, B>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+}
+
+pub struct XboxJoystickConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes16, OutNone, ReportSingle>,
+}
+
+impl<'a> Default for XboxJoystickConfig<'a> {
+    #[must_use]
+    fn default() -> Self {
+        Self::with_interval(10.millis())
+    }
+}
+
+impl<'a> XboxJoystickConfig<'a> {
+    /// Joystick interface polled by the host every `poll_interval`
+    #[must_use]
+    pub fn with_interval(poll_interval: MillisDurationU32) -> Self {
+        Self::new(
+            ((InterfaceBuilder::new(XBOX_JOYSTICK_DESCRIPTOR)).unwrap()
+                .boot_device(InterfaceProtocol::None)
+                .description("Joystick")
+                .in_endpoint(poll_interval)).unwrap()
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes16, OutNone, ReportSingle>) -> Self {
+        Self { interface }
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for XboxJoystickConfig<'a> {
+    type Allocated = XboxJoystick<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: ConfigInterface::new(Interface::new(usb_alloc, self.interface)),
+        }
+    }
+}
@@ -1,30 +1,34 @@
 use cortex_m::prelude::_embedded_hal_blocking_delay_DelayMs;
 
 use stm32f3xx_hal::{
-    adc::{self, Adc},
+    adc,
     delay::Delay,
+    dma::dma2,
     flash::Parts,
-    gpio::{self, gpioa, gpioe, Alternate, Gpioa, Output, Pin, PushPull, Ux, U},
-    pac::{ADC3, ADC4, ADC3_4, USB},
-    prelude::_embedded_hal_digital_OutputPin,
-    rcc::{Clocks, AHB, CFGR},
+    gpio::{gpioa, gpiob, gpioe, Alternate, Gpioa, Input, Pin, PushPull, U},
+    i2c::I2c,
+    pac::{ADC3, ADC4, ADC3_4, I2C1, USB},
+    prelude::{_embedded_hal_digital_OutputPin, _stm32f3xx_hal_time_rate_Extensions},
+    rcc::{Clocks, AHB, APB1, CFGR},
     time::rate::Megahertz,
     usb::Peripheral,
 };
 
-use switch_hal::{ActiveHigh, Switch};
+use controller_core::board::Adc;
 
+use crate::adc_scan::AdcScan;
+use crate::board::BOARD;
+use crate::compass::{CompassI2c, DataReadyPins};
 use crate::leds::Leds;
 
-type LedPinType = Pin<gpio::Gpioe, Ux, Output<PushPull>>;
-pub type LedArray = [Switch<LedPinType, ActiveHigh>; 8];
-
 pub type UsbDmPinType = Pin<Gpioa, U<11>, Alternate<PushPull, 14>>;
 pub type UsbDpPinType = Pin<Gpioa, U<12>, Alternate<PushPull, 14>>;
 
 pub type UsbPeriph = Peripheral<UsbDmPinType, UsbDpPinType>;
 
-pub fn get_leds(mut gpioe: gpioe::Parts) -> LedArray {
+/// Splits port E between the LEDs and the data ready lines of the e-compass
+pub fn get_leds(mut gpioe: gpioe::Parts) -> (Leds, DataReadyPins) {
+    let ready = DataReadyPins::new(gpioe.pe2, gpioe.pe4, &mut gpioe.moder, &mut gpioe.pupdr);
     let leds = Leds::new(
         gpioe.pe8,
         gpioe.pe9,
@@ -36,13 +40,44 @@ pub fn get_leds(mut gpioe: gpioe::Parts) -> LedArray {
         gpioe.pe15,
         &mut gpioe.moder,
         &mut gpioe.otyper,
-    )
-    .into_array();
+    );
+
+    (leds, ready)
+}
 
-    return leds;
+/// I2C bus of the e-compass on PB6 and PB7, at 400 kHz
+pub fn get_compass_i2c(
+    mut gpiob: gpiob::Parts,
+    i2c1: I2C1,
+    clocks: Clocks,
+    apb1: &mut APB1,
+) -> CompassI2c {
+    let scl = gpiob
+        .pb6
+        .into_af4_open_drain(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrl);
+    let sda = gpiob
+        .pb7
+        .into_af4_open_drain(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrl);
+    I2c::new(i2c1, (scl, sda), 400_000.Hz(), clocks, apb1)
 }
 
-pub fn get_adc3(adc3: ADC3, adc3_4: &mut ADC3_4, ahb: &mut AHB, clocks: Clocks) -> Adc<ADC3> {
+/// Channels converted by ADC3, see [`AdcScan::new`]
+pub const ADC3_SCAN_LEN: usize = BOARD.adc_channel_count(Adc::Adc3);
+/// Channels converted by ADC4, see [`AdcScan::new`]
+pub const ADC4_SCAN_LEN: usize = BOARD.adc_channel_count(Adc::Adc4);
+
+pub type Adc3Scan = AdcScan<ADC3, ADC3_SCAN_LEN>;
+pub type Adc4Scan = AdcScan<ADC4, ADC4_SCAN_LEN>;
+
+/// Starts scanning `channels` of ADC3, can only be called once
+pub fn get_adc3(
+    adc3: ADC3,
+    dma_channel: dma2::C5,
+    channels: [u8; ADC3_SCAN_LEN],
+    adc3_4: &mut ADC3_4,
+    ahb: &mut AHB,
+    clocks: Clocks,
+) -> Adc3Scan {
     let adc3 = adc::Adc::adc3(
         adc3, // The ADC we are going to control
         // The following is only needed to make sure the clock signal for the ADC is set up
@@ -52,11 +87,20 @@ pub fn get_adc3(adc3: ADC3, adc3_4: &mut ADC3_4, ahb: &mut AHB, clocks: Clocks)
         adc::ClockMode::default(),
         clocks,
     );
+    let buffer = cortex_m::singleton!(: [[u16; ADC3_SCAN_LEN]; 2] = [[0; ADC3_SCAN_LEN]; 2]).unwrap();
 
-    return adc3;
+    AdcScan::new(adc3, dma_channel, channels, buffer)
 }
 
-pub fn get_adc4(adc4: ADC4, adc3_4: &mut ADC3_4, ahb: &mut AHB, clocks: Clocks) -> Adc<ADC4> {
+/// Starts scanning `channels` of ADC4, can only be called once
+pub fn get_adc4(
+    adc4: ADC4,
+    dma_channel: dma2::C2,
+    channels: [u8; ADC4_SCAN_LEN],
+    adc3_4: &mut ADC3_4,
+    ahb: &mut AHB,
+    clocks: Clocks,
+) -> Adc4Scan {
     let adc4 = adc::Adc::adc4(
         adc4, // The ADC we are going to control
         // The following is only needed to make sure the clock signal for the ADC is set up
@@ -66,8 +110,9 @@ pub fn get_adc4(adc4: ADC4, adc3_4: &mut ADC3_4, ahb: &mut AHB, clocks: Clocks)
         adc::ClockMode::default(),
         clocks,
     );
+    let buffer = cortex_m::singleton!(: [[u16; ADC4_SCAN_LEN]; 2] = [[0; ADC4_SCAN_LEN]; 2]).unwrap();
 
-    return adc4;
+    AdcScan::new(adc4, dma_channel, channels, buffer)
 }
 
 pub fn get_clocks(cfgr: CFGR, flash: &mut Parts) -> Clocks {
@@ -83,27 +128,30 @@ pub fn get_clocks(cfgr: CFGR, flash: &mut Parts) -> Clocks {
     return clocks;
 }
 
-pub fn get_usb_init(mut gpioa: gpioa::Parts, delay: &mut Delay, usb: USB) -> UsbPeriph {
+/// Takes the USB pins rather than the whole port, so PA0 stays available for the user button
+pub fn get_usb_init(
+    pa11: gpioa::PA11<Input>,
+    pa12: gpioa::PA12<Input>,
+    moder: &mut gpioa::MODER,
+    otyper: &mut gpioa::OTYPER,
+    afrh: &mut gpioa::AFRH,
+    delay: &mut Delay,
+    usb: USB,
+) -> UsbPeriph {
     // F3 Discovery board has a pull-up resistor on the D+ line.
     // Pull the D+ pin down to send a RESET condition to the USB bus.
     // This forced reset is needed only for development, without it host
     // will not reset your device when you upload new firmware.
 
-    let mut usb_dp = gpioa
-        .pa12
-        .into_push_pull_output(&mut gpioa.moder, &mut gpioa.otyper);
+    let mut usb_dp = pa12.into_push_pull_output(moder, otyper);
 
     usb_dp.set_low().ok();
 
     delay.delay_ms(10_u16);
 
-    let usb_dm =
-        gpioa
-            .pa11
-            .into_af14_push_pull(&mut gpioa.moder, &mut gpioa.otyper, &mut gpioa.afrh);
+    let usb_dm = pa11.into_af14_push_pull(moder, otyper, afrh);
 
-    let usb_dp = 
-        usb_dp.into_af14_push_pull(&mut gpioa.moder, &mut gpioa.otyper, &mut gpioa.afrh);
+    let usb_dp = usb_dp.into_af14_push_pull(moder, otyper, afrh);
 
     let usb_periph = Peripheral {
         usb: usb,
@@ -0,0 +1,73 @@
+//! Board pins backing the `controller_core` input traits, as described by [`source::board::BOARD`]
+use controller_core::board::{Adc, Board};
+use controller_core::input::{AnalogInput, AnalogSource, Button, DigitalSource};
+use controller_core::pipeline::ADC_MAX_VALUE;
+use source::adc_scan::ScanError;
+use source::board;
+use source::init::{Adc3Scan, Adc4Scan, ADC3_SCAN_LEN, ADC4_SCAN_LEN};
+
+pub struct AnalogInputs {
+    pub adc3: Adc3Scan,
+    pub adc4: Adc4Scan,
+    /// Inputs of each scan, in scan order
+    pub adc3_inputs: [AnalogInput; ADC3_SCAN_LEN],
+    pub adc4_inputs: [AnalogInput; ADC4_SCAN_LEN],
+    /// Snapshot of the current sample, indexed by [`AnalogInput::index`]
+    pub values: [u16; AnalogInput::COUNT],
+}
+
+impl AnalogInputs {
+    /// Inputs missing from `board` stay at rest: triggers released, sticks centered
+    pub fn new(adc3: Adc3Scan, adc4: Adc4Scan, board: &Board) -> Self {
+        let rest = |input: AnalogInput| {
+            if input.is_trigger() {
+                0
+            } else {
+                ADC_MAX_VALUE / 2
+            }
+        };
+        AnalogInputs {
+            adc3,
+            adc4,
+            adc3_inputs: board.adc_channels(Adc::Adc3).map(|(input, _)| input),
+            adc4_inputs: board.adc_channels(Adc::Adc4).map(|(input, _)| input),
+            values: AnalogInput::ALL.map(rest),
+        }
+    }
+}
+
+impl AnalogSource for AnalogInputs {
+    type Error = ScanError;
+
+    fn begin_sample(&mut self) -> Result<(), Self::Error> {
+        let adc3 = self.adc3.snapshot()?;
+        let adc4 = self.adc4.snapshot()?;
+        for (input, value) in self.adc3_inputs.iter().zip(adc3) {
+            self.values[input.index()] = value;
+        }
+        for (input, value) in self.adc4_inputs.iter().zip(adc4) {
+            self.values[input.index()] = value;
+        }
+        Ok(())
+    }
+
+    fn read_raw(&mut self, input: AnalogInput) -> Result<u16, Self::Error> {
+        Ok(self.values[input.index()])
+    }
+}
+
+pub struct DigitalInputs {
+    pub board: Board,
+}
+
+impl DigitalSource for DigitalInputs {
+    type Error = core::convert::Infallible;
+
+    /// Buttons missing from the board are never pressed
+    fn is_pressed(&mut self, button: Button) -> Result<bool, Self::Error> {
+        Ok(self
+            .board
+            .digital_pin(button)
+            .is_some_and(|digital| digital.active.is_active(board::is_high(digital.pin))))
+    }
+}
@@ -1,14 +1,18 @@
 #![no_std]
 
 //pub use accelerometer;
-pub use lsm303dlhc;
 pub use stm32f3xx_hal;
 pub use switch_hal;
 
+pub mod adc_scan;
+pub mod board;
 pub mod button;
 pub mod compass;
+pub mod dfu;
+pub mod flash;
 pub mod init;
 pub mod leds;
+pub mod lights;
 
 /// Signals the process to go into low power mode until an interrupt occurs
 pub fn wait_for_interrupt() {
@@ -0,0 +1,58 @@
+//! Shows the requests of the LED engine on the compass LEDs, through software PWM
+use controller_core::lights::{LedEngine, SoftPwm};
+use stm32f3xx_hal::pac::TIM3;
+use stm32f3xx_hal::prelude::_embedded_hal_timer_CountDown;
+use stm32f3xx_hal::time::duration::Microseconds;
+use stm32f3xx_hal::timer::{Event, Timer};
+use switch_hal::OutputSwitch;
+
+use crate::leds::{Direction, Leds};
+
+/// Period of the LED timer. A PWM cycle takes `MAX_BRIGHTNESS` ticks, 133 Hz, without flicker.
+pub const LED_TICK_US: u32 = 500;
+const TICKS_PER_MS: u32 = 1000 / LED_TICK_US;
+
+pub struct Lights {
+    leds: Leds,
+    timer: Timer<TIM3>,
+    pub engine: LedEngine,
+    pwm: SoftPwm,
+    ticks: u32,
+    now_ms: u32,
+}
+
+impl Lights {
+    /// Starts the LED timer, its update interrupt must call [`Self::run`]
+    pub fn new(leds: Leds, mut timer: Timer<TIM3>) -> Self {
+        timer.enable_interrupt(Event::Update);
+        timer.start(Microseconds(LED_TICK_US));
+        Lights {
+            leds,
+            timer,
+            engine: LedEngine::new(),
+            pwm: SoftPwm::new(),
+            ticks: 0,
+            now_ms: 0,
+        }
+    }
+
+    /// Renders the engine and lights the LEDs for this PWM tick, on every timer update
+    pub fn run(&mut self) {
+        self.timer.clear_event(Event::Update);
+        self.ticks += 1;
+        if self.ticks == TICKS_PER_MS {
+            self.ticks = 0;
+            self.now_ms = self.now_ms.wrapping_add(1);
+        }
+
+        let frame = self.engine.render(self.now_ms);
+        for (direction, on) in Direction::iter().zip(self.pwm.next(&frame)) {
+            let led = self.leds.for_direction(*direction);
+            if on {
+                led.on().ok();
+            } else {
+                led.off().ok();
+            }
+        }
+    }
+}
@@ -2,42 +2,70 @@
 #![no_main]
 #![no_std]
 extern crate packed_struct;
-#[macro_use]
-extern crate packed_struct_codegen;
 
-const JOYSTICK_MAX_VALUE : f32 = 65535f32;
-const TRIGGER_MAX_VALUE : f32 = 65535f32;
-
-use controller::ControllerState;
-use hid_report::{XboxJoystick, XboxJoystickConfig, XboxJoystickReport};
+use core::cell::{Cell, RefCell};
+use core::fmt::Write;
+
+use controller_core::compass::CompassConfig;
+use controller_core::calibration::{CalibrationRoutine, CalibrationStep, CALIBRATION_HOLD_MS};
+use controller_core::board::Adc;
+use controller_core::config::{self, ConfigServer, REPORT_SIZE};
+use controller_core::console::{self as commands, Action, ConsoleContext, ConsoleError, Line};
+use controller_core::input::{Button, DigitalSource};
+use controller_core::heading::MagCalibrationRoutine;
+use controller_core::lights::{LedEngine, LedMode, LedPriority, LedState};
+use controller_core::pipeline::InputPipeline;
+use controller_core::report::{HID_PID, HID_VID};
+use controller_core::press::{GuideMode, PressDetector, DOUBLE_PRESS_GAP_MS, LONG_PRESS_MS};
+use controller_core::schedule::JitterStats;
+use controller_core::settings::Settings;
+use controller_core::storage::SettingsStore;
+use controller_core::xinput::{UsbMode, XINPUT_PID, XINPUT_VID};
+use cortex_m::interrupt::{free, Mutex};
+use cortex_m::prelude::_embedded_hal_blocking_delay_DelayMs;
+use console::{ConsoleOutput, SerialConsole, COMMAND_OUTPUT_SIZE, OUTPUT_SIZE};
+use cortex_m::peripheral::NVIC;
+use hid_report::{XboxJoystick, XboxJoystickConfig};
+use inputs::{AnalogInputs, DigitalInputs};
+use sampler::{GamepadReport, Sampler, Status};
+use xinput::XInput;
 pub use panic_itm; // panic handler
 
 pub use cortex_m_rt::entry;
 
-use cortex_m::prelude::_embedded_hal_adc_OneShot;
-
 use stm32_usbd::UsbBus;
 
 use stm32f3xx_hal::{
-    adc::Adc,
     delay::Delay,
-    gpio::{Alternate, Analog, Gpioa, Gpiob, Gpiod, Input, Pin, PushPull, U},
-    pac::{self, ADC3, ADC4},
+    dma::DmaExt,
+    gpio::{Alternate, Gpioa, Pin, PushPull, U},
+    pac::{self, interrupt, Interrupt},
     prelude::{
-        _embedded_hal_blocking_delay_DelayUs, _embedded_hal_digital_InputPin,
-        _stm32f3xx_hal_flash_FlashExt, _stm32f3xx_hal_gpio_GpioExt,
+        _embedded_hal_timer_CountDown, _stm32f3xx_hal_flash_FlashExt,
+        _stm32f3xx_hal_gpio_GpioExt,
     },
     rcc::RccExt,
+    time::duration::Microseconds,
+    timer::{Event, MonoTimer, Timer},
     usb::Peripheral,
 };
 
+use fugit::ExtU32;
+use source::board::{self, BOARD};
+use source::compass::Compass;
+use source::dfu;
+use source::flash::SettingsFlash;
 use source::init::*;
-use switch_hal::OutputSwitch;
+use source::lights::Lights;
+use source::wait_for_interrupt;
 use usb_device::{class_prelude::*, prelude::*};
 use usbd_human_interface_device::{usb_class::*, *};
 
-mod controller;
+mod console;
 mod hid_report;
+mod inputs;
+mod sampler;
+mod xinput;
 
 type UsbDevType<'a> = UsbDevice<'a, UsbBus<UsbPeriph>>;
 
@@ -45,105 +73,162 @@ type DmPin = Pin<Gpioa, U<11>, Alternate<PushPull, 14>>;
 type DpPin = Pin<Gpioa, U<12>, Alternate<PushPull, 14>>;
 type UsbBusType = stm32_usbd::UsbBus<Peripheral<DmPin, DpPin>>;
 
-struct App<'a> {
-    button_d3: Pin<Gpiod, U<3>, Input>,
-    button_d4: Pin<Gpiod, U<4>, Input>,
-    button_d5: Pin<Gpiod, U<5>, Input>,
-    button_d6: Pin<Gpiod, U<6>, Input>,
-    button_d7: Pin<Gpiod, U<7>, Input>,
-    button_d1: Pin<Gpiod, U<1>, Input>,
-    button_d0: Pin<Gpiod, U<0>, Input>,
-    button_d2: Pin<Gpiod, U<2>, Input>,
-
-    button_b4: Pin<Gpiob, U<4>, Input>,
-    button_b5: Pin<Gpiob, U<5>, Input>,
-
-    pb15_pin: Pin<Gpiob, U<15>, Analog>,
-    pd8_pin: Pin<Gpiod, U<8>, Analog>,
-    pd9_pin: Pin<Gpiod, U<9>, Analog>,
-    pd10_pin: Pin<Gpiod, U<10>, Analog>,
-    pd11_pin: Pin<Gpiod, U<11>, Analog>,
-    pb12_pin: Pin<Gpiob, U<12>, Analog>,
-    pd13_pin: Pin<Gpiod, U<13>, Analog>,
-    pd14_pin: Pin<Gpiod, U<14>, Analog>,
-
-    leds: LedArray,
-    delay: Delay,
-    adc3: Adc<ADC3>,
-    adc4: Adc<ADC4>,
-    usb_device: UsbDevType<'a>,
-    usb_joy: UsbHidClass<
-        'a,
-        UsbBusType,
-        frunk_core::hlist::HCons<XboxJoystick<'a, UsbBusType>, frunk_core::hlist::HNil>,
-    >,
+/// Interrupt priorities, lower values preempt higher ones. Only the upper 4 bits are used.
+const USB_PRIORITY: u8 = 1 << 4;
+const SAMPLE_PRIORITY: u8 = 2 << 4;
+const LIGHTS_PRIORITY: u8 = 3 << 4;
+
+/// USB device and class, only used from the USB interrupts once initialized
+struct UsbContext {
+    usb_device: UsbDevType<'static>,
+    gamepad: Gamepad<'static>,
+    /// Only in HID mode, an XInput controller has no other interface
+    console: Option<SerialConsole<'static, UsbBusType>>,
+}
+
+// The bus allocator is shared by reference between the device and the class, it is not `Sync`.
+// Both are moved together into `USB` and only ever used from there, so never from two contexts.
+#[allow(unsafe_code)]
+unsafe impl Send for UsbContext {}
+
+static USB: Mutex<RefCell<Option<UsbContext>>> = Mutex::new(RefCell::new(None));
+/// Rendered by the LED timer interrupt, the idle loop posts the states to show
+static LIGHTS: Mutex<RefCell<Option<Lights>>> = Mutex::new(RefCell::new(None));
+/// Only used by the sample timer interrupt once initialized
+static SAMPLER: Mutex<RefCell<Option<Sampler>>> = Mutex::new(RefCell::new(None));
+/// Latest report not yet handed to the USB class
+static REPORT: Mutex<Cell<Option<GamepadReport>>> = Mutex::new(Cell::new(None));
+static STATUS: Mutex<Cell<Option<Status>>> = Mutex::new(Cell::new(None));
+/// Settings changed by the sampler, saved by the idle loop as flash writes are slow
+static SETTINGS_TO_SAVE: Mutex<Cell<Option<Settings>>> = Mutex::new(Cell::new(None));
+/// Console line received by the USB interrupt, run by the idle loop.
+/// No other line is read until it is taken.
+static CONSOLE_LINE: Mutex<Cell<Option<Result<Line, ConsoleError>>>> = Mutex::new(Cell::new(None));
+/// Latest reading of the motion sensors, taken by the next sample
+static SENSOR_READING: Mutex<Cell<Option<SensorReading>>> = Mutex::new(Cell::new(None));
+/// Console output not yet sent
+static CONSOLE_OUTPUT: Mutex<RefCell<ConsoleOutput>> = Mutex::new(RefCell::new(ConsoleOutput::new()));
+
+/// Acceleration in g and raw magnetic field in gauss, `None` until the magnetometer
+/// gave a reading
+#[derive(Clone, Copy)]
+struct SensorReading {
+    accel: [f32; 3],
+    mag: Option<[f32; 3]>,
+}
+
+/// USB class selected at boot by [`UsbMode`]
+// A single instance, kept in `USB` for the whole run
+#[allow(clippy::large_enum_variant)]
+enum Gamepad<'a> {
+    Hid(
+        UsbHidClass<
+            'a,
+            UsbBusType,
+            frunk_core::hlist::HCons<XboxJoystick<'a, UsbBusType>, frunk_core::hlist::HNil>,
+        >,
+    ),
+    XInput(XInput<'a, UsbBusType>),
+}
+
+impl<'a> Gamepad<'a> {
+    fn write_report(&mut self, report: &GamepadReport) {
+        match (self, report) {
+            (Gamepad::Hid(usb_joy), GamepadReport::Hid(report)) => {
+                usb_joy.device().write_report(report).ok();
+            }
+            (Gamepad::XInput(xinput), GamepadReport::XInput(report)) => {
+                xinput.write_report(report).ok();
+            }
+            _ => {}
+        }
+    }
+
+    /// Configuration request written by the host, only in HID mode
+    fn take_config_request(&mut self) -> Option<[u8; REPORT_SIZE]> {
+        match self {
+            Gamepad::Hid(usb_joy) => usb_joy.device().take_config_request(),
+            Gamepad::XInput(_) => None,
+        }
+    }
+
+    fn set_config_response(&mut self, response: [u8; REPORT_SIZE]) {
+        if let Gamepad::Hid(usb_joy) = self {
+            usb_joy.device().set_config_response(response);
+        }
+    }
+
+    fn poll(
+        &mut self,
+        usb_device: &mut UsbDevType<'a>,
+        console: Option<&mut SerialConsole<'a, UsbBusType>>,
+    ) -> bool {
+        match (self, console) {
+            (Gamepad::Hid(usb_joy), Some(console)) => {
+                usb_device.poll(&mut [usb_joy, &mut console.serial])
+            }
+            (Gamepad::Hid(usb_joy), None) => usb_device.poll(&mut [usb_joy]),
+            (Gamepad::XInput(xinput), _) => usb_device.poll(&mut [xinput]),
+        }
+    }
+}
+
+/// Version reported to the configuration tools
+fn firmware_version() -> [u8; 3] {
+    [
+        env!("CARGO_PKG_VERSION_MAJOR"),
+        env!("CARGO_PKG_VERSION_MINOR"),
+        env!("CARGO_PKG_VERSION_PATCH"),
+    ]
+    .map(|part| part.parse().unwrap_or(0))
+}
+
+/// Reads a button once, before the main loop starts
+fn is_held_at_boot(digital_inputs: &mut DigitalInputs, button: Button) -> bool {
+    digital_inputs.is_pressed(button).unwrap_or(false)
 }
 
 #[entry]
 fn main() -> ! {
+    dfu::enter_bootloader_if_requested();
+
     let mut device_periphs = pac::Peripherals::take().unwrap();
     let core_periphs = cortex_m::Peripherals::take().unwrap();
     let mut reset_and_clock_control = device_periphs.RCC.constrain();
     let mut flash = device_periphs.FLASH.constrain();
     let clocks = get_clocks(reset_and_clock_control.cfgr, &mut flash);
     let mut delay = Delay::new(core_periphs.SYST, clocks);
-    let gpioa = device_periphs.GPIOA.split(&mut reset_and_clock_control.ahb);
-    let mut gpiod = device_periphs.GPIOD.split(&mut reset_and_clock_control.ahb);
+    let mut nvic = core_periphs.NVIC;
+    let mut dcb = core_periphs.DCB;
+    let mono_timer = MonoTimer::new(core_periphs.DWT, clocks, &mut dcb);
+    let mut gpioa = device_periphs.GPIOA.split(&mut reset_and_clock_control.ahb);
     let gpioe = device_periphs.GPIOE.split(&mut reset_and_clock_control.ahb);
-    let mut gpiob = device_periphs.GPIOB.split(&mut reset_and_clock_control.ahb);
-    let mut leds = get_leds(gpioe);
-
-    let button_d3 = gpiod
-        .pd3
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d4 = gpiod
-        .pd4
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d5 = gpiod
-        .pd5
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d6 = gpiod
-        .pd6
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d7 = gpiod
-        .pd7
-        .into_floating_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d1 = gpiod
-        .pd1
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d0 = gpiod
-        .pd0
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_d2 = gpiod
-        .pd2
-        .into_pull_up_input(&mut gpiod.moder, &mut gpiod.pupdr);
-
-    let button_b4 = gpiob
-        .pb4
-        .into_pull_up_input(&mut gpiob.moder, &mut gpiob.pupdr);
-
-    let button_b5 = gpiob
-        .pb5
-        .into_pull_up_input(&mut gpiob.moder, &mut gpiob.pupdr);
-
-    let pd8_pin = gpiod.pd8.into_analog(&mut gpiod.moder, &mut gpiod.pupdr);
-    let pd9_pin = gpiod.pd9.into_analog(&mut gpiod.moder, &mut gpiod.pupdr);
-    let pd10_pin = gpiod.pd10.into_analog(&mut gpiod.moder, &mut gpiod.pupdr);
-    let pd11_pin = gpiod.pd11.into_analog(&mut gpiod.moder, &mut gpiod.pupdr);
-    let pb12_pin = gpiob.pb12.into_analog(&mut gpiob.moder, &mut gpiob.pupdr);
-    let pd13_pin = gpiod.pd13.into_analog(&mut gpiod.moder, &mut gpiod.pupdr);
-    let pd14_pin = gpiod.pd14.into_analog(&mut gpiod.moder, &mut gpiod.pupdr);
-    let pb15_pin = gpiob.pb15.into_analog(&mut gpiob.moder, &mut gpiob.pupdr);
+    let gpiob = device_periphs.GPIOB.split(&mut reset_and_clock_control.ahb);
+    // Only used through the board table for now
+    let _gpioc = device_periphs.GPIOC.split(&mut reset_and_clock_control.ahb);
+    let _gpiod = device_periphs.GPIOD.split(&mut reset_and_clock_control.ahb);
+    let (leds, compass_ready) = get_leds(gpioe);
+
+    // After every port is split, splitting a port resets it
+    board::configure(&BOARD).expect("invalid board pin table");
+
+    // The motion input is optional, the controller works without the sensor
+    let compass_i2c = get_compass_i2c(
+        gpiob,
+        device_periphs.I2C1,
+        clocks,
+        &mut reset_and_clock_control.apb1,
+    );
+    let mut compass = Compass::new(compass_i2c, compass_ready, CompassConfig::new()).ok();
+    let mut last_mag = None;
+    let mut mag_calibration = MagCalibrationRoutine::new();
+
+    let dma2 = device_periphs.DMA2.split(&mut reset_and_clock_control.ahb);
 
     let adc3 = get_adc3(
         device_periphs.ADC3,
+        dma2.ch5,
+        BOARD.adc_channels(Adc::Adc3).map(|(_, channel)| channel),
         &mut device_periphs.ADC3_4,
         &mut reset_and_clock_control.ahb,
         clocks,
@@ -151,263 +236,459 @@ fn main() -> ! {
 
     let adc4 = get_adc4(
         device_periphs.ADC4,
+        dma2.ch2,
+        BOARD.adc_channels(Adc::Adc4).map(|(_, channel)| channel),
         &mut device_periphs.ADC3_4,
         &mut reset_and_clock_control.ahb,
         clocks,
     );
 
-    let usb_peripheral: UsbPeriph = get_usb_init(gpioa, &mut delay, device_periphs.USB);
-    let usb_bus: UsbBusAllocator<_> = UsbBus::new(usb_peripheral);
-
-    let usb_joy = UsbHidClassBuilder::new()
-        .add_device(XboxJoystickConfig::default())
-        .build(&usb_bus);
-
-    let usb_device = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
-        .manufacturer("Fake company")
-        .product("Codec usb device")
-        .serial_number("TEST")
-        //.device_class(USB_CLASS_CDC)
-        .build();
-
-    //leds[0].off().ok();
-
-    let mut controller_state = ControllerState::new();
-    let mut nb_iter = 0u64;
-
-    let mut app = App {
-        button_d3,
-        button_d4,
-        button_d5,
-        button_d6,
-        button_d7,
-        button_d1,
-        button_d0,
-        button_d2,
-        button_b4,
-        button_b5,
-        pb15_pin,
-        pd8_pin,
-        pd9_pin,
-        pd10_pin,
-        pd11_pin,
-        pb12_pin,
-        pd13_pin,
-        pd14_pin,
-        leds,
-        delay,
-        adc3,
-        adc4,
-        usb_device,
-        usb_joy,
-    };
+    let usb_peripheral: UsbPeriph = get_usb_init(
+        gpioa.pa11,
+        gpioa.pa12,
+        &mut gpioa.moder,
+        &mut gpioa.otyper,
+        &mut gpioa.afrh,
+        &mut delay,
+        device_periphs.USB,
+    );
+    let usb_bus: &'static UsbBusAllocator<_> =
+        cortex_m::singleton!(: UsbBusAllocator<UsbBusType> = UsbBus::new(usb_peripheral)).unwrap();
 
-    loop {
-        run_main_loop_iter(&mut nb_iter, &mut controller_state, &mut app);
-    }
-}
+    let mut digital_inputs = DigitalInputs { board: BOARD };
 
-fn run_main_loop_iter(nb_iter: &mut u64, controller_state: &mut ControllerState, app: &mut App) {
-    *nb_iter += 1;
-    // TODO : use clock
-
-    if *nb_iter % 1000 == 0 {
-        read_buttons_states(app, controller_state);
-        read_joystick_states(app, controller_state);
-
-        match app
-            .usb_joy
-            .device()
-            .write_report(&get_report(&controller_state))
-        {
-            // Ok(_) => {
-            //     app.leds[1].on().ok();
-            // }
-            // Err(UsbHidError::WouldBlock) => {
-            //     app.leds[2].on().ok();
-            // }
-            // Err(UsbHidError::SerializationError) => {
-            //     app.leds[3].on().ok();
-            // }
-            // Err(UsbHidError::Duplicate) => {
-            //     app.leds[3].on().ok();
-            // }
-            // Err(UsbHidError::UsbError(a)) => {
-            //     match a {
-            //         UsbError::BufferOverflow => {
-            //             app.leds[6].on().ok(); 
-            //         }
-            //         _ => {
-            //             app.leds[5].on().ok(); 
-            //         }
-            //     }
-            //     app.leds[6].on().ok();
-            // }
-            _ => { }
-        }
+    let mut settings_store = SettingsStore::new(SettingsFlash::new());
+    let mut settings = settings_store.load().unwrap_or_default();
 
-        app.delay.delay_us(100u32);
+    // Holding the user button while plugging in swaps it between guide and mode key
+    if is_held_at_boot(&mut digital_inputs, Button::Guide) {
+        settings.guide_mode = match settings.guide_mode {
+            GuideMode::Guide => GuideMode::ModeKey,
+            GuideMode::ModeKey => GuideMode::Guide,
+        };
+        settings_store.save(&settings).ok();
     }
 
-    // To debug ADC
-    let value = controller_state.left_thumb_x / 2f32 + 0.5f32;
-    let leds_max_index = 7;
-    for curr in 0..=leds_max_index {
-        let current = (curr as f32) / leds_max_index as f32;
-        if value > current + 0.01f32 {
-            app.leds[curr].on().ok();
-        } else {
-            app.leds[curr].off().ok();
-        }
+    // Holding BACK while plugging in swaps between HID and XInput
+    if is_held_at_boot(&mut digital_inputs, Button::Back) {
+        settings.usb_mode = match settings.usb_mode {
+            UsbMode::Hid => UsbMode::XInput,
+            UsbMode::XInput => UsbMode::Hid,
+        };
+        settings_store.save(&settings).ok();
     }
 
-    if !app.usb_device.poll(&mut [&mut app.usb_joy]) {
-        //app.leds[0].on().ok();
-        return;
-    } else {
-        //app.leds[0].on().ok();
-    }
-}
+    let (gamepad, console, usb_device) = match settings.usb_mode {
+        UsbMode::Hid => {
+            let usb_joy = UsbHidClassBuilder::new()
+                .add_device(XboxJoystickConfig::with_interval(
+                    (settings.poll_rate.endpoint_interval_ms() as u32).millis(),
+                ))
+                .build(usb_bus);
+            let console = SerialConsole::new(usb_bus);
+
+            let usb_device = UsbDeviceBuilder::new(usb_bus, UsbVidPid(HID_VID, HID_PID))
+                .manufacturer("Fake company")
+                .product("Codec usb device")
+                .serial_number("TEST")
+                .composite_with_iads()
+                .build();
+
+            (Gamepad::Hid(usb_joy), Some(console), usb_device)
+        }
+        UsbMode::XInput => {
+            let xinput = XInput::new(usb_bus, settings.poll_rate.endpoint_interval_ms());
+
+            let usb_device = UsbDeviceBuilder::new(usb_bus, UsbVidPid(XINPUT_VID, XINPUT_PID))
+                .manufacturer("Fake company")
+                .product("Controller")
+                .serial_number("TEST")
+                .device_class(0xff)
+                .device_sub_class(0xff)
+                .device_protocol(0xff)
+                .build();
+
+            (Gamepad::XInput(xinput), None, usb_device)
+        }
+    };
 
-fn read_adc_value(result: Result<u16, stm32f3xx_hal::nb::Error<()>>) -> f32 {
-    let adc_value: u16 = result.expect("Error reading adc.");
-    let adc_val_32 = adc_value as f32;
-    let scaled_adb_value = adc_val_32 / 4095_f32;
-    scaled_adb_value
-}
+    let poll_rate = settings.poll_rate;
+    let mut pipeline = InputPipeline::new();
+    pipeline.set_poll_rate(poll_rate);
+    pipeline.apply_settings(&settings);
+    pipeline.set_guide_press(PressDetector::new(
+        poll_rate.samples(LONG_PRESS_MS),
+        poll_rate.samples(DOUBLE_PRESS_GAP_MS),
+    ));
+    let calibration = CalibrationRoutine::new(poll_rate.samples(CALIBRATION_HOLD_MS));
+
+    let mut sample_timer = Timer::new(device_periphs.TIM2, clocks, &mut reset_and_clock_control.apb1);
+    sample_timer.enable_interrupt(Event::Update);
+    sample_timer.start(Microseconds(poll_rate.period_us()));
+
+    let sampler = Sampler {
+        analog_inputs: AnalogInputs::new(adc3, adc4, &BOARD),
+        digital_inputs,
+        sample_timer,
+        mono_timer,
+        last_sample: None,
+        jitter: JitterStats::new(poll_rate.period_us()),
+        pipeline,
+        calibration,
+        settings,
+    };
+    let mut active_profile = sampler.settings.profiles.active;
+    let mut led_mode = LedMode::default();
+
+    let mut lights = Lights::new(
+        leds,
+        Timer::new(device_periphs.TIM3, clocks, &mut reset_and_clock_control.apb1),
+    );
+    lights.engine.show(LedState::Enumerating);
+
+    free(|cs| {
+        USB.borrow(cs).replace(Some(UsbContext {
+            usb_device,
+            gamepad,
+            console,
+        }));
+        SAMPLER.borrow(cs).replace(Some(sampler));
+        LIGHTS.borrow(cs).replace(Some(lights));
+    });
+
+    #[allow(unsafe_code)]
+    unsafe {
+        nvic.set_priority(Interrupt::USB_LP_CAN_RX0, USB_PRIORITY);
+        nvic.set_priority(Interrupt::USB_HP_CAN_TX, USB_PRIORITY);
+        nvic.set_priority(Interrupt::TIM2, SAMPLE_PRIORITY);
+        nvic.set_priority(Interrupt::TIM3, LIGHTS_PRIORITY);
+        NVIC::unmask(Interrupt::USB_LP_CAN_RX0);
+        NVIC::unmask(Interrupt::USB_HP_CAN_TX);
+        NVIC::unmask(Interrupt::TIM2);
+        NVIC::unmask(Interrupt::TIM3);
+    }
 
-fn read_joystick_states(app: &mut App, controller_state: &mut ControllerState) {
-    let val = app.adc4.read(&mut app.pd8_pin);
-    controller_state.left_thumb_x = lerp(-1f32, 1f32, read_adc_value(val));
+    let mut config_server = ConfigServer::new();
 
-    let val = app.adc4.read(&mut app.pd9_pin);
-    controller_state.left_thumb_y = lerp(-1f32, 1f32, read_adc_value(val));
+    // Idle task: everything too slow or not urgent enough for the interrupts
+    loop {
+        let (status, settings_to_save) =
+            free(|cs| (STATUS.borrow(cs).get(), SETTINGS_TO_SAVE.borrow(cs).take()));
 
-    let val = app.adc3.read(&mut app.pd10_pin);
-    controller_state.right_thumb_x = lerp(-1f32, 1f32, read_adc_value(val));
+        if let Some(settings) = settings_to_save {
+            if settings_store.save(&settings).is_err() {
+                with_lights(|engine| engine.show(LedState::Error));
+            }
+        }
 
-    let val = app.adc3.read(&mut app.pd11_pin);
-    controller_state.right_thumb_y = lerp(-1f32, 1f32, read_adc_value(val));
+        if let Some(status) = status {
+            show_status(&status, &mut active_profile, led_mode);
+        }
 
-    let val = app.adc4.read(&mut app.pb12_pin);
-    controller_state.left_trigger = lerp(-1f32, 1f32, read_adc_value(val));
+        if let Some(line) = free(|cs| CONSOLE_LINE.borrow(cs).take()) {
+            run_console_line(
+                line,
+                &mut settings_store,
+                &mut delay,
+                &mut mag_calibration,
+                &mut led_mode,
+            );
+        }
 
-    let val = app.adc3.read(&mut app.pd13_pin);
-    controller_state.right_trigger = lerp(-1f32, 1f32, read_adc_value(val));
+        run_config_request(&mut config_server, &mut settings_store);
 
-    let val = app.adc4.read(&mut app.pd14_pin);
-    controller_state.other_value_0 = lerp(-1f32, 1f32, read_adc_value(val));
+        if let Some(compass) = compass.as_mut() {
+            read_sensors(compass, &mut last_mag, &mut mag_calibration);
+        }
+        if mag_calibration.is_complete() {
+            finish_compass_calibration(&mut mag_calibration);
+        }
 
-    let val = app.adc4.read(&mut app.pb15_pin);
-    controller_state.other_value_1 = lerp(-1f32, 1f32, read_adc_value(val));
+        wait_for_interrupt();
+    }
 }
 
-fn read_buttons_states(app: &mut App, controller_state: &mut ControllerState) {
-    controller_state.a = app.button_d3.is_low().unwrap();
-    controller_state.b = app.button_d4.is_low().unwrap();
-    controller_state.x = app.button_d5.is_low().unwrap();
-    controller_state.y = app.button_d6.is_low().unwrap();
-    controller_state.left_shoulder = app.button_d1.is_low().unwrap();
-    controller_state.right_shoulder = app.button_d7.is_low().unwrap();
-    controller_state.left_thumb = app.button_d0.is_low().unwrap();
-    controller_state.right_thumb = app.button_d2.is_low().unwrap();
-    controller_state.start = app.button_b4.is_low().unwrap();
-    controller_state.back = app.button_b5.is_low().unwrap();
+/// Sample task, at the poll rate
+#[interrupt]
+fn TIM2() {
+    // Taken out of the mutex so sampling does not hold off the USB interrupts
+    let mut sampler = match free(|cs| SAMPLER.borrow(cs).take()) {
+        Some(sampler) => sampler,
+        None => return,
+    };
+    if let Some(reading) = free(|cs| SENSOR_READING.borrow(cs).take()) {
+        sampler.pipeline.read_sensors(reading.accel, reading.mag);
+    }
+    let output = sampler.run();
+
+    free(|cs| {
+        if let Some(report) = output.report {
+            REPORT.borrow(cs).set(Some(report));
+        }
+        if let Some(settings) = output.settings_changed {
+            SETTINGS_TO_SAVE.borrow(cs).set(Some(settings));
+        }
+        STATUS.borrow(cs).set(Some(output.status));
+        SAMPLER.borrow(cs).replace(Some(sampler));
+    });
+
+    // Hands the report over right away rather than waiting for the next USB event
+    if output.report.is_some() {
+        NVIC::pend(Interrupt::USB_LP_CAN_RX0);
+    }
 }
 
-fn lerp(from: f32, to: f32, value: f32) -> f32 {
-    return from * (1.0f32 - value) + to * value;
+/// LED task, a software PWM tick
+#[interrupt]
+fn TIM3() {
+    free(|cs| {
+        if let Some(lights) = LIGHTS.borrow(cs).borrow_mut().as_mut() {
+            lights.run();
+        }
+    });
 }
 
-fn get_report(controller_state: &ControllerState) -> XboxJoystickReport {
-    let mut buttons = 0;
+#[interrupt]
+fn USB_LP_CAN_RX0() {
+    service_usb();
+}
 
-    let mut button_index = 0;
+#[interrupt]
+fn USB_HP_CAN_TX() {
+    service_usb();
+}
 
-    if controller_state.a {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+fn service_usb() {
+    free(|cs| {
+        if let Some(usb) = USB.borrow(cs).borrow_mut().as_mut() {
+            usb.gamepad.poll(&mut usb.usb_device, usb.console.as_mut());
+            if let Some(report) = REPORT.borrow(cs).take() {
+                usb.gamepad.write_report(&report);
+            }
+            if let Some(console) = usb.console.as_mut() {
+                let output = &mut *CONSOLE_OUTPUT.borrow(cs).borrow_mut();
+                let line = CONSOLE_LINE.borrow(cs);
+                if line.get().is_none() {
+                    line.set(console.read_line(output));
+                }
+                console.write_output(output);
+            }
+        }
+    });
+}
 
-    if controller_state.b {
-        buttons |= 1 << button_index;
+/// Hands a new accelerometer sample to the sampler, with the latest magnetometer one,
+/// on the idle loop. Never waits: the data ready lines tell when a sample is there.
+fn read_sensors(
+    compass: &mut Compass,
+    last_mag: &mut Option<[f32; 3]>,
+    mag_calibration: &mut MagCalibrationRoutine,
+) {
+    // A failed read is skipped, the next sample keeps the last orientation
+    if let Ok(Some(mag)) = compass.read_mag() {
+        let mag = [mag.x, mag.y, mag.z];
+        mag_calibration.add(mag);
+        *last_mag = Some(mag);
     }
-    button_index += 1;
-
-    if controller_state.x {
-        buttons |= 1 << button_index;
+    if let Ok(Some(accel)) = compass.read_accel() {
+        let reading = SensorReading {
+            accel: [accel.x, accel.y, accel.z],
+            mag: *last_mag,
+        };
+        free(|cs| SENSOR_READING.borrow(cs).set(Some(reading)));
     }
-    button_index += 1;
+}
 
-    if controller_state.y {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+/// Fits the magnetometer calibration started from the console and applies it, on the idle
+/// loop. Like the other console changes it is only written to flash on `save`.
+fn finish_compass_calibration(mag_calibration: &mut MagCalibrationRoutine) {
+    let calibration = mag_calibration.finish();
+    // The sample interrupt always puts the sampler back before returning
+    free(|cs| {
+        let mut sampler = SAMPLER.borrow(cs).borrow_mut();
+        if let (Some(sampler), Some(calibration)) = (sampler.as_mut(), calibration) {
+            sampler.settings.mag_calibration = calibration;
+            sampler.pipeline.set_mag_calibration(calibration);
+        }
+    });
 
-    if controller_state.left_shoulder {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+    let reply: &[u8] = match calibration {
+        Some(_) => b"\r\ncompass calibrated\r\n> ",
+        None => b"\r\ncompass calibration failed\r\n> ",
+    };
+    free(|cs| console::push_output(&mut CONSOLE_OUTPUT.borrow(cs).borrow_mut(), reply));
+    NVIC::pend(Interrupt::USB_LP_CAN_RX0);
+}
 
-    if controller_state.right_shoulder {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+/// Handles the configuration request of the HID feature report, on the idle loop
+fn run_config_request(server: &mut ConfigServer, settings_store: &mut SettingsStore<SettingsFlash>) {
+    let request = free(|cs| {
+        USB.borrow(cs)
+            .borrow_mut()
+            .as_mut()
+            .and_then(|usb| usb.gamepad.take_config_request())
+    });
+    let Some(request) = request else { return };
+
+    // The sample interrupt always puts the sampler back before returning
+    let handled = free(|cs| {
+        let mut sampler = SAMPLER.borrow(cs).borrow_mut();
+        let sampler = sampler.as_mut()?;
+        let (response, action) = server.handle(&request, &mut sampler.settings, firmware_version());
+        let mut settings_to_save = None;
+        match action {
+            config::Action::Apply { save } => {
+                sampler.pipeline.apply_settings(&sampler.settings);
+                settings_to_save = save.then_some(sampler.settings);
+            }
+            config::Action::StartCalibration => sampler.calibration.start(),
+            config::Action::None => {}
+        }
+        Some((response, settings_to_save))
+    });
+
+    if let Some((mut response, settings_to_save)) = handled {
+        // Out of the critical section: the code runs from flash so every erase and write still
+        // stalls the CPU, but the interrupts are served between them instead of after the save
+        if let Some(settings) = settings_to_save {
+            if settings_store.save(&settings).is_err() {
+                let failed = Err(config::Status::SaveFailed);
+                response = config::Response::encode(request[1], failed).unwrap_or(response);
+            }
+        }
 
-    if controller_state.up {
-        buttons |= 1 << button_index;
+        free(|cs| {
+            if let Some(usb) = USB.borrow(cs).borrow_mut().as_mut() {
+                usb.gamepad.set_config_response(response);
+            }
+        });
     }
-    button_index += 1;
+}
 
-    if controller_state.down {
-        buttons |= 1 << button_index;
+/// Runs a console command and queues its output, on the idle loop
+fn run_console_line(
+    line: Result<Line, ConsoleError>,
+    settings_store: &mut SettingsStore<SettingsFlash>,
+    delay: &mut Delay,
+    mag_calibration: &mut MagCalibrationRoutine,
+    led_mode: &mut LedMode,
+) {
+    // The command runs on copies, the interrupts stay enabled while it formats its output
+    let snapshot = free(|cs| {
+        SAMPLER.borrow(cs).borrow().as_ref().map(|sampler| {
+            let pipeline = &sampler.pipeline;
+            let values = (*pipeline.state(), *pipeline.raw_values(), pipeline.orientation());
+            (sampler.settings, values, sampler.jitter)
+        })
+    });
+    let mut command_output = heapless::String::<COMMAND_OUTPUT_SIZE>::new();
+    let handled = snapshot.map(|(mut settings, (state, raw_values, orientation), jitter)| {
+        let mut context = ConsoleContext {
+            settings: &mut settings,
+            state: &state,
+            raw_values: &raw_values,
+            orientation,
+            jitter: &jitter,
+            led_mode,
+        };
+        let action = line.and_then(|line| commands::execute(line.as_str(), &mut context, &mut command_output));
+        (action, settings)
+    });
+
+    // The command output leaves room for the replies below
+    let mut output = heapless::String::<OUTPUT_SIZE>::new();
+    output.push_str(&command_output).ok();
+    let action = handled.map(|(action, settings)| {
+        match action {
+            Ok(Action::Apply) => free(|cs| {
+                if let Some(sampler) = SAMPLER.borrow(cs).borrow_mut().as_mut() {
+                    sampler.settings = settings;
+                    sampler.pipeline.apply_settings(&sampler.settings);
+                }
+            }),
+            Ok(Action::Save) => {
+                // Out of the critical section, as for the config requests: the flash still stalls
+                // the CPU, but only while a page is erased or a half-word written
+                let reply = match settings_store.save(&settings) {
+                    Ok(()) => "saved\r\n",
+                    Err(_) => "save failed\r\n",
+                };
+                output.push_str(reply).ok();
+            }
+            Ok(Action::CalibrateCompass) => mag_calibration.start(),
+            Err(error) => {
+                write!(output, "error: {}\r\n", error).ok();
+            }
+            _ => {}
+        }
+        action
+    });
+    output.push_str("> ").ok();
+
+    free(|cs| console::push_output(&mut CONSOLE_OUTPUT.borrow(cs).borrow_mut(), output.as_bytes()));
+    NVIC::pend(Interrupt::USB_LP_CAN_RX0);
+
+    if let Some(Ok(Action::RebootDfu)) = action {
+        // Gives the host time to read the reply
+        for _ in 0..100 {
+            if free(|cs| CONSOLE_OUTPUT.borrow(cs).borrow().is_empty()) {
+                break;
+            }
+            delay.delay_ms(1u32);
+        }
+        dfu::reboot_into_bootloader();
     }
-    button_index += 1;
+}
 
-    if controller_state.right {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+/// Posts the states of the controller to the LEDs, on the idle loop
+fn show_status(status: &Status, active_profile: &mut u8, led_mode: LedMode) {
+    let usb_state = free(|cs| {
+        USB.borrow(cs)
+            .borrow()
+            .as_ref()
+            .map(|usb| usb.usb_device.state())
+    });
+
+    with_lights(|engine| {
+        if let Some(usb_state) = usb_state {
+            engine.show(match usb_state {
+                UsbDeviceState::Default | UsbDeviceState::Addressed => LedState::Enumerating,
+                UsbDeviceState::Configured => LedState::Configured,
+                UsbDeviceState::Suspend => LedState::Suspended,
+            });
+        }
 
-    if controller_state.left {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+        match led_mode.animation(&status.state) {
+            Some(animation) => engine.request(LedPriority::Diagnostic, animation, None),
+            None => engine.clear(LedPriority::Diagnostic),
+        }
 
-    if controller_state.start {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+        if status.calibration_step == CalibrationStep::Idle {
+            engine.clear(LedPriority::Calibration);
+        } else {
+            engine.show(LedState::Calibration {
+                step: status.calibration_step,
+                calibrated_axes: status.calibrated_axes,
+            });
+        }
 
-    if controller_state.back {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+        // Shown for a while on every switch
+        if status.active_profile != *active_profile {
+            *active_profile = status.active_profile;
+            engine.show(LedState::Profile(status.active_profile));
+        }
 
-    if controller_state.left_thumb {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
+        if status.sample_failed {
+            engine.show(LedState::Error);
+        }
+    });
+}
 
-    if controller_state.right_thumb {
-        buttons |= 1 << button_index;
-    }
-    button_index += 1;
-
-    let x = (controller_state.left_thumb_x * 127f32) as i8;
-    let y = (controller_state.left_thumb_y * 127f32) as i8;
-    let z = (controller_state.right_thumb_x * 127f32) as i8;
-    let rx = (controller_state.right_thumb_y * 127f32) as i8;
-    let ry = (controller_state.left_trigger * 127f32) as i8;
-    let rz = (controller_state.right_trigger * 127f32) as i8;
-
-    XboxJoystickReport {
-        buttons,
-        x,
-        y,
-        z,
-        rx,
-        ry,
-        rz,
-    }
+/// Runs `f` on the LED engine, in a critical section as the LED timer renders it
+fn with_lights(f: impl FnOnce(&mut LedEngine)) {
+    free(|cs| {
+        if let Some(lights) = LIGHTS.borrow(cs).borrow_mut().as_mut() {
+            f(&mut lights.engine);
+        }
+    });
 }
@@ -0,0 +1,140 @@
+//! Sampling task, run from the sample timer interrupt
+use controller_core::calibration::{CalibrationRoutine, CalibrationStep};
+use controller_core::controller::ControllerState;
+use controller_core::dpad::SocdMode;
+use controller_core::pipeline::InputPipeline;
+use controller_core::press::PressEvent;
+use controller_core::report::XboxJoystickReport;
+use controller_core::schedule::JitterStats;
+use controller_core::settings::Settings;
+use controller_core::xinput::{UsbMode, XInputReport};
+
+use stm32f3xx_hal::{
+    pac::TIM2,
+    time::fixed_point::FixedPoint,
+    timer::{Event, Instant, MonoTimer, Timer},
+};
+
+use crate::inputs::{AnalogInputs, DigitalInputs};
+
+/// Report handed to the USB interrupt, matching the class selected at boot
+#[derive(Clone, Copy)]
+pub enum GamepadReport {
+    Hid(XboxJoystickReport),
+    XInput(XInputReport),
+}
+
+/// Snapshot of the sampler shown on the LEDs by the idle loop
+#[derive(Clone, Copy)]
+pub struct Status {
+    pub calibration_step: CalibrationStep,
+    pub calibrated_axes: usize,
+    pub active_profile: u8,
+    /// Reading the inputs failed
+    pub sample_failed: bool,
+    /// Output of the pipeline, for the diagnostic LED modes
+    pub state: ControllerState,
+}
+
+/// What a sample produced, for the other tasks
+pub struct SampleOutput {
+    /// `None` when the sample failed or while calibrating
+    pub report: Option<GamepadReport>,
+    /// Settings changed by this sample, to be saved out of the interrupt
+    pub settings_changed: Option<Settings>,
+    pub status: Status,
+}
+
+pub struct Sampler {
+    pub analog_inputs: AnalogInputs,
+    pub digital_inputs: DigitalInputs,
+    pub sample_timer: Timer<TIM2>,
+    pub mono_timer: MonoTimer,
+    pub last_sample: Option<Instant>,
+    /// Measured sampling periods, for diagnostics
+    pub jitter: JitterStats,
+    pub pipeline: InputPipeline,
+    pub calibration: CalibrationRoutine,
+    pub settings: Settings,
+}
+
+impl Sampler {
+    /// Samples the inputs once, called on every sample timer update
+    pub fn run(&mut self) -> SampleOutput {
+        self.sample_timer.clear_event(Event::Update);
+        self.record_sample_period();
+
+        let mut settings_changed = None;
+        let sampled = self
+            .pipeline
+            .sample(&mut self.analog_inputs, &mut self.digital_inputs)
+            .is_ok();
+
+        if sampled {
+            match self.pipeline.press_event() {
+                Some(PressEvent::Short) => {
+                    self.settings.socd_mode = next_socd_mode(self.settings.socd_mode);
+                    self.pipeline.set_socd_mode(self.settings.socd_mode);
+                    settings_changed = Some(self.settings);
+                }
+                Some(PressEvent::Long) => self.calibration.start(),
+                Some(PressEvent::Double) => self.pipeline.next_profile(),
+                None => {}
+            }
+
+            // Switched by the pipeline combo or the double press above
+            if self.pipeline.profiles().active != self.settings.profiles.active {
+                self.settings.profiles.active = self.pipeline.profiles().active;
+                settings_changed = Some(self.settings);
+            }
+
+            if let Some(new_calibration) = self.calibration.update(
+                self.pipeline.raw_values(),
+                self.pipeline.state(),
+                self.pipeline.calibration(),
+            ) {
+                self.pipeline.set_calibration(new_calibration);
+                self.settings.calibration = new_calibration;
+                settings_changed = Some(self.settings);
+            }
+        }
+
+        // A failed sample keeps the previous report rather than sending a half updated one,
+        // and the buttons driving the calibration must not reach the game
+        let report = (sampled && !self.calibration.is_active()).then(|| match self.settings.usb_mode {
+            UsbMode::Hid => GamepadReport::Hid(self.pipeline.report()),
+            UsbMode::XInput => GamepadReport::XInput(self.pipeline.xinput_report()),
+        });
+
+        SampleOutput {
+            report,
+            settings_changed,
+            status: Status {
+                calibration_step: self.calibration.step(),
+                calibrated_axes: self.calibration.calibrated_axes(),
+                active_profile: self.settings.profiles.active,
+                sample_failed: !sampled,
+                state: *self.pipeline.state(),
+            },
+        }
+    }
+
+    /// Adds the time since the previous sample to the jitter statistics
+    fn record_sample_period(&mut self) {
+        let now = self.mono_timer.now();
+        if let Some(last_sample) = self.last_sample {
+            let cycles_per_us = self.mono_timer.frequency().integer() / 1_000_000;
+            self.jitter.record(last_sample.elapsed() / cycles_per_us);
+        }
+        self.last_sample = Some(now);
+    }
+}
+
+/// Mode key short press cycles through the d-pad SOCD modes
+fn next_socd_mode(mode: SocdMode) -> SocdMode {
+    match mode {
+        SocdMode::Neutral => SocdMode::LastWins,
+        SocdMode::LastWins => SocdMode::UpPriority,
+        SocdMode::UpPriority => SocdMode::Neutral,
+    }
+}
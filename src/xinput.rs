@@ -0,0 +1,85 @@
+//!XInput vendor specific USB class
+use controller_core::xinput::{
+    XInputOutput, XInputReport, XINPUT_INTERFACE_CLASS, XINPUT_INTERFACE_PROTOCOL,
+    XINPUT_INTERFACE_SUBCLASS, XINPUT_UNKNOWN_DESCRIPTOR, XINPUT_UNKNOWN_DESCRIPTOR_TYPE,
+};
+use packed_struct::prelude::*;
+use usb_device::class_prelude::*;
+
+/// Largest packet on both endpoints, as declared by genuine controllers
+const MAX_PACKET_SIZE: u16 = 32;
+
+pub struct XInput<'a, B: UsbBus> {
+    interface: InterfaceNumber,
+    ep_in: EndpointIn<'a, B>,
+    ep_out: EndpointOut<'a, B>,
+    rumble: (u8, u8),
+    led: u8,
+}
+
+impl<'a, B: UsbBus> XInput<'a, B> {
+    /// Must be the first class allocated, the unknown descriptor hardcodes endpoints 1 IN and OUT.
+    /// The host reads reports every `interval_ms`.
+    pub fn new(usb_alloc: &'a UsbBusAllocator<B>, interval_ms: u8) -> Self {
+        XInput {
+            interface: usb_alloc.interface(),
+            ep_in: usb_alloc.interrupt(MAX_PACKET_SIZE, interval_ms),
+            ep_out: usb_alloc.interrupt(MAX_PACKET_SIZE, 8),
+            rumble: (0, 0),
+            led: 0,
+        }
+    }
+
+    pub fn write_report(&mut self, report: &XInputReport) -> Result<(), UsbError> {
+        let data = report.pack();
+        self.ep_in.write(&data).map(|_| ())
+    }
+
+    /// Speed of the large and small motors last requested by the host
+    #[allow(dead_code)] // The board has no motors
+    pub fn rumble(&self) -> (u8, u8) {
+        self.rumble
+    }
+
+    /// Ring of light animation last requested by the host
+    #[allow(dead_code)]
+    pub fn led(&self) -> u8 {
+        self.led
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for XInput<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
+        writer.interface(
+            self.interface,
+            XINPUT_INTERFACE_CLASS,
+            XINPUT_INTERFACE_SUBCLASS,
+            XINPUT_INTERFACE_PROTOCOL,
+        )?;
+        writer.write(XINPUT_UNKNOWN_DESCRIPTOR_TYPE, XINPUT_UNKNOWN_DESCRIPTOR)?;
+        writer.endpoint(&self.ep_in)?;
+        writer.endpoint(&self.ep_out)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.rumble = (0, 0);
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        if addr != self.ep_out.address() {
+            return;
+        }
+
+        let mut data = [0u8; MAX_PACKET_SIZE as usize];
+        let length = match self.ep_out.read(&mut data) {
+            Ok(length) => length,
+            Err(_) => return,
+        };
+        match XInputOutput::parse(&data[..length]) {
+            Some(XInputOutput::Rumble { left, right }) => self.rumble = (left, right),
+            Some(XInputOutput::Led(pattern)) => self.led = pattern,
+            None => {}
+        }
+    }
+}